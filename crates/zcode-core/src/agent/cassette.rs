@@ -0,0 +1,182 @@
+//! Record and replay of provider traffic. With `--record <dir>` every API request and its raw
+//! response go to numbered fixture files in `dir`; with `--replay <dir>` those responses are
+//! served back instead of calling the API, so runs of the agent loop are repeatable and need
+//! no network or key.
+//!
+//! A replayed request gets the unused recording of the identical request if there is one,
+//! otherwise the next unused recording for the same endpoint, so small prompt changes (a
+//! different workspace path, a new tool) don't break a fixture set.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// One recorded exchange.
+#[derive(Serialize, Deserialize)]
+struct Fixture {
+    endpoint: String,
+    /// SHA-256 of the request body, to find the recording of the same request.
+    hash: String,
+    request: serde_json::Value,
+    status: u16,
+    /// The raw response: JSON, or the server-sent events of a streamed reply.
+    body: String,
+}
+
+enum Cassette {
+    Record { dir: PathBuf, next: usize },
+    Replay { fixtures: Vec<(Fixture, bool)> },
+}
+
+static CASSETTE: Mutex<Option<Cassette>> = Mutex::new(None);
+
+/// Record provider traffic to `dir`, numbering after any fixtures already there.
+pub fn record(dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let next = fixture_files(dir)?.len() + 1;
+    *CASSETTE.lock().unwrap() = Some(Cassette::Record {
+        dir: dir.to_path_buf(),
+        next,
+    });
+    Ok(())
+}
+
+/// Serve provider traffic from the fixtures in `dir`.
+pub fn replay(dir: &Path) -> Result<(), String> {
+    let mut fixtures = Vec::new();
+    for path in fixture_files(dir)? {
+        let text = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let fixture: Fixture =
+            serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        fixtures.push((fixture, false));
+    }
+    if fixtures.is_empty() {
+        return Err(format!("No fixtures in {}", dir.display()));
+    }
+    *CASSETTE.lock().unwrap() = Some(Cassette::Replay { fixtures });
+    Ok(())
+}
+
+/// Stop recording or replaying; requests go to the API again.
+pub fn stop() {
+    *CASSETTE.lock().unwrap() = None;
+}
+
+/// Whether responses come from fixtures, so no API key is needed.
+pub fn is_replaying() -> bool {
+    matches!(*CASSETTE.lock().unwrap(), Some(Cassette::Replay { .. }))
+}
+
+/// Whether traffic is being recorded or replayed.
+pub(super) fn is_active() -> bool {
+    CASSETTE.lock().unwrap().is_some()
+}
+
+/// `NNNN.json` files in `dir`, in order.
+fn fixture_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "json"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn hash(body: &serde_json::Value) -> String {
+    let digest = Sha256::digest(body.to_string().as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The recorded status and body for a request, when replaying; None when calls go to the API.
+pub(super) fn lookup(
+    endpoint: &str,
+    body: &serde_json::Value,
+) -> Option<Result<(u16, String), String>> {
+    let mut cassette = CASSETTE.lock().unwrap();
+    let Some(Cassette::Replay { fixtures }) = cassette.as_mut() else {
+        return None;
+    };
+    let hash = hash(body);
+    let unused = |f: &(Fixture, bool)| !f.1 && f.0.endpoint == endpoint;
+    let found = fixtures
+        .iter()
+        .position(|f| unused(f) && f.0.hash == hash)
+        .or_else(|| {
+            let position = fixtures.iter().position(unused);
+            if position.is_some() {
+                warn!(
+                    endpoint,
+                    "no recording of this exact request; replaying the next one"
+                );
+            }
+            position
+        });
+    let Some(i) = found else {
+        return Some(Err(format!("No recorded response left for {}", endpoint)));
+    };
+    fixtures[i].1 = true;
+    info!(endpoint, fixture = i + 1, "replayed response");
+    Some(Ok((fixtures[i].0.status, fixtures[i].0.body.clone())))
+}
+
+/// Collects a streamed response as it is read and saves it, once the stream has ended, when
+/// dropped.
+pub(super) struct Recorder {
+    endpoint: String,
+    request: serde_json::Value,
+    body: Vec<u8>,
+}
+
+impl Recorder {
+    pub(super) fn push(&mut self, chunk: &[u8]) {
+        self.body.extend_from_slice(chunk);
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let body = String::from_utf8_lossy(&self.body).to_string();
+        // A reply cut short (interrupted, or a dropped connection) would replay as a broken one.
+        if body.contains("data: [DONE]") {
+            save(&self.endpoint, &self.request, 200, body);
+        }
+    }
+}
+
+/// A recorder for a streamed response, when recording.
+pub(super) fn recorder(endpoint: &str, request: &serde_json::Value) -> Option<Recorder> {
+    matches!(*CASSETTE.lock().unwrap(), Some(Cassette::Record { .. })).then(|| Recorder {
+        endpoint: endpoint.to_string(),
+        request: request.clone(),
+        body: Vec::new(),
+    })
+}
+
+/// Save an exchange, when recording.
+pub(super) fn save(endpoint: &str, request: &serde_json::Value, status: u16, body: String) {
+    let mut cassette = CASSETTE.lock().unwrap();
+    let Some(Cassette::Record { dir, next }) = cassette.as_mut() else {
+        return;
+    };
+    let path = dir.join(format!("{:04}.json", next));
+    *next += 1;
+    let fixture = Fixture {
+        endpoint: endpoint.to_string(),
+        hash: hash(request),
+        request: request.clone(),
+        status,
+        body,
+    };
+    let written = serde_json::to_string_pretty(&fixture)
+        .map_err(|e| e.to_string())
+        .and_then(|text| fs::write(&path, text).map_err(|e| e.to_string()));
+    match written {
+        Ok(()) => info!(endpoint, path = %path.display(), "recorded response"),
+        Err(e) => warn!(path = %path.display(), error = %e, "could not record the response"),
+    }
+}
@@ -0,0 +1,285 @@
+//! An agent that replies from a script instead of a model, for tests and demos. Replies are
+//! used in order, whichever call asks for the next one; clones share the script, so one
+//! script can drive both the planner and the executor:
+//!
+//! ```yaml
+//! - content: '{"summary": "Add a greeting", "todos": ["Write hello.txt"]}'
+//! - tool_calls:
+//!     - name: write_file
+//!       arguments: { path: hello.txt, content: "Hello\n" }
+//! - content: Wrote hello.txt.
+//! - error: rate limited   # the call fails with this message
+//! ```
+
+use super::{Agent, AgentResponse, FunctionCall, Message, ToolCall};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// One scripted reply.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScriptedReply {
+    pub content: Option<String>,
+    pub tool_calls: Vec<ScriptedCall>,
+    /// Fail the call with this message instead of replying.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptedCall {
+    pub name: String,
+    /// A mapping, or a string of raw JSON.
+    #[serde(default)]
+    pub arguments: serde_yaml::Value,
+}
+
+#[derive(Default)]
+struct Script {
+    replies: Vec<ScriptedReply>,
+    next: usize,
+    /// The input each call answered: the user text, or the newest message.
+    inputs: Vec<String>,
+}
+
+#[derive(Clone, Default)]
+pub struct FakeAgent {
+    script: Arc<Mutex<Script>>,
+    streamed: Arc<AtomicUsize>,
+}
+
+impl FakeAgent {
+    pub fn new(replies: Vec<ScriptedReply>) -> Self {
+        Self {
+            script: Arc::new(Mutex::new(Script {
+                replies,
+                ..Default::default()
+            })),
+            streamed: Arc::default(),
+        }
+    }
+
+    /// Parse a script: a YAML list of replies.
+    pub fn from_yaml(text: &str) -> Result<Self, String> {
+        let replies: Vec<ScriptedReply> = serde_yaml::from_str(text).map_err(|e| e.to_string())?;
+        Ok(Self::new(replies))
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::from_yaml(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// What each call so far was answering, in order.
+    pub fn inputs(&self) -> Vec<String> {
+        self.script.lock().unwrap().inputs.clone()
+    }
+
+    /// How many replies are left.
+    pub fn remaining(&self) -> usize {
+        let script = self.script.lock().unwrap();
+        script.replies.len() - script.next
+    }
+
+    fn next(&self, input: String) -> Result<ScriptedReply, String> {
+        let mut script = self.script.lock().unwrap();
+        script.inputs.push(input);
+        let n = script.next;
+        let reply = script
+            .replies
+            .get(n)
+            .cloned()
+            .ok_or_else(|| format!("Script ran out after {} replies", n))?;
+        script.next += 1;
+        match reply.error {
+            Some(error) => Err(error),
+            None => Ok(reply),
+        }
+    }
+
+    /// Take the next reply for a chat call and add it to `messages`.
+    fn answer(
+        &self,
+        messages: &mut Vec<Message>,
+        user_input: Option<&str>,
+    ) -> Result<AgentResponse, String> {
+        if let Some(input) = user_input {
+            messages.push(Message::Role {
+                role: "user".into(),
+                content: input.into(),
+            });
+        }
+        let input = match messages.last() {
+            Some(Message::Role { content, .. } | Message::ToolResult { content, .. }) => {
+                content.clone()
+            }
+            Some(Message::Assistant { content, .. }) => content.clone().unwrap_or_default(),
+            None => String::new(),
+        };
+        let reply = self.next(input)?;
+        let tool_calls = (!reply.tool_calls.is_empty()).then(|| {
+            reply
+                .tool_calls
+                .iter()
+                .enumerate()
+                .map(|(i, call)| ToolCall {
+                    id: format!("call_{}", i),
+                    type_: "function".into(),
+                    function: FunctionCall {
+                        name: call.name.clone(),
+                        arguments: match &call.arguments {
+                            serde_yaml::Value::String(raw) => raw.clone(),
+                            serde_yaml::Value::Null => "{}".into(),
+                            value => serde_json::to_string(value).unwrap_or_default(),
+                        },
+                    },
+                })
+                .collect::<Vec<_>>()
+        });
+        messages.push(Message::Assistant {
+            role: "assistant".into(),
+            content: reply.content.clone(),
+            tool_calls: tool_calls.clone(),
+        });
+        Ok(AgentResponse {
+            content: reply.content,
+            tool_calls,
+            usage: None,
+        })
+    }
+}
+
+#[async_trait]
+impl Agent for FakeAgent {
+    async fn chat(
+        &self,
+        messages: &mut Vec<Message>,
+        user_input: Option<&str>,
+    ) -> Result<AgentResponse, String> {
+        self.answer(messages, user_input)
+    }
+
+    async fn chat_stream<F>(
+        &self,
+        messages: &mut Vec<Message>,
+        user_input: Option<&str>,
+        on_chunk: &mut F,
+    ) -> Result<AgentResponse, String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        let response = self.answer(messages, user_input)?;
+        self.streamed.store(0, Ordering::Relaxed);
+        // Word by word, like a model's stream.
+        for chunk in response.content.iter().flat_map(|c| c.split_inclusive(' ')) {
+            self.streamed.fetch_add(1, Ordering::Relaxed);
+            on_chunk(chunk);
+        }
+        Ok(response)
+    }
+
+    async fn completion(&self, _system: &str, user: &str) -> Result<String, String> {
+        let reply = self.next(user.to_string())?;
+        if !reply.tool_calls.is_empty() {
+            return Err("Scripted reply has tool calls, but a completion was requested".into());
+        }
+        Ok(reply.content.unwrap_or_default())
+    }
+
+    fn stream_counter(&self) -> Arc<AtomicUsize> {
+        self.streamed.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = r#"
+- content: planned
+- tool_calls:
+    - name: write_file
+      arguments: { path: a.txt, content: "A" }
+    - name: read_file
+      arguments: '{"path": "a.txt"}'
+    - name: list_dir
+- error: rate limited
+"#;
+
+    #[tokio::test]
+    async fn replies_come_in_script_order() {
+        let agent = FakeAgent::from_yaml(SCRIPT).unwrap();
+        assert_eq!(
+            agent.completion("system", "plan it").await.unwrap(),
+            "planned"
+        );
+
+        let mut messages = Vec::new();
+        let reply = agent.chat(&mut messages, Some("do it")).await.unwrap();
+        let calls = reply.tool_calls.unwrap();
+        let arguments: Vec<&str> = calls
+            .iter()
+            .map(|c| c.function.arguments.as_str())
+            .collect();
+        assert_eq!(
+            arguments,
+            [
+                r#"{"path":"a.txt","content":"A"}"#,
+                r#"{"path": "a.txt"}"#,
+                "{}"
+            ]
+        );
+        assert_eq!(calls[2].id, "call_2");
+        assert_eq!(messages.len(), 2);
+
+        assert_eq!(
+            agent.chat(&mut messages, None).await.unwrap_err(),
+            "rate limited"
+        );
+        assert_eq!(agent.remaining(), 0);
+        assert_eq!(
+            agent.chat(&mut messages, None).await.unwrap_err(),
+            "Script ran out after 3 replies"
+        );
+        assert_eq!(agent.inputs(), ["plan it", "do it", "", ""]);
+    }
+
+    #[tokio::test]
+    async fn clones_share_the_script() {
+        let planner = FakeAgent::from_yaml("- content: one\n- content: two\n").unwrap();
+        let executor = planner.clone();
+        assert_eq!(planner.completion("", "a").await.unwrap(), "one");
+        assert_eq!(executor.completion("", "b").await.unwrap(), "two");
+        assert_eq!(planner.remaining(), 0);
+    }
+
+    #[tokio::test]
+    async fn streamed_replies_arrive_word_by_word() {
+        let agent = FakeAgent::from_yaml("- content: Wrote the file.\n").unwrap();
+        let mut chunks = Vec::new();
+        let reply = agent
+            .chat_stream(&mut Vec::new(), Some("go"), &mut |chunk: &str| {
+                chunks.push(chunk.to_string())
+            })
+            .await
+            .unwrap();
+        assert_eq!(chunks, ["Wrote ", "the ", "file."]);
+        assert_eq!(reply.content.as_deref(), Some("Wrote the file."));
+        assert_eq!(agent.stream_counter().load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn completions_reject_tool_calls() {
+        let agent = FakeAgent::from_yaml("- tool_calls: [{ name: list_dir }]\n").unwrap();
+        assert!(agent.completion("", "plan").await.is_err());
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        assert!(FakeAgent::from_yaml("- contents: typo\n").is_err());
+    }
+}
@@ -0,0 +1,289 @@
+pub mod cassette;
+mod fake;
+mod openai;
+pub mod response_cache;
+
+pub use fake::{FakeAgent, ScriptedCall, ScriptedReply};
+pub use openai::{list_models, KeyError, OpenAiAgent};
+
+use crate::config::{self, Provider};
+use crate::http;
+use crate::usage::Usage;
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
+
+/// Tools that cannot modify the workspace or run commands.
+pub const READ_ONLY_TOOLS: &[&str] = &["read_file", "list_dir", "recall_memory"];
+
+/// Default endpoint for `ollama:<model>` specs.
+const OLLAMA_BASE_URL: &str = "http://localhost:11434/v1";
+
+/// An OpenAI-compatible provider: `[providers.<name>]` in the config (`base_url`, optional
+/// `api_key`, headers, and TLS files), with a built-in default for `ollama`. None without a
+/// base URL.
+fn provider_endpoint(provider: &str) -> Option<Provider> {
+    let mut profile = config::load()
+        .providers
+        .remove(provider)
+        .unwrap_or_default();
+    if profile.base_url.is_none() && provider == "ollama" {
+        profile.base_url = Some(OLLAMA_BASE_URL.to_string());
+    }
+    profile.base_url.is_some().then_some(profile)
+}
+
+/// Extra headers and the TLS client for a profile.
+fn connection(profile: &Provider) -> Result<(HeaderMap, Option<reqwest::Client>), String> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &profile.headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("invalid header name {:?}", name))?;
+        let mut value = HeaderValue::from_str(value)
+            .map_err(|_| format!("invalid value for header {}", name))?;
+        value.set_sensitive(true);
+        headers.insert(name, value);
+    }
+    Ok((headers, http::provider_client(profile)?))
+}
+
+/// Check that every `[providers.<name>]` has valid headers and readable TLS files.
+pub fn check_providers() -> Result<(), String> {
+    for (name, profile) in &config::load().providers {
+        connection(profile).map_err(|e| format!("providers.{}: {}", name, e))?;
+    }
+    Ok(())
+}
+
+/// The endpoint and model of a `<provider>:<model>` spec, or None for an OpenAI model (`gpt-4o`,
+/// `openai:gpt-4o`, or a fine-tune's `ft:` id). A provider without a base URL is an error
+/// rather than a request to OpenAI with the provider's model name.
+fn endpoint(spec: &str) -> Result<Option<(Provider, &str)>, String> {
+    match spec.split_once(':') {
+        None | Some(("openai" | "ft", _)) => Ok(None),
+        Some((provider, model)) => match provider_endpoint(provider) {
+            Some(profile) => Ok(Some((profile, model))),
+            None => Err(format!(
+                "Model {}: no base_url in [providers.{}] (zcode talks to OpenAI-compatible endpoints only)",
+                spec, provider
+            )),
+        },
+    }
+}
+
+/// Check that a model spec (or `[models]` alias) names OpenAI or a configured provider.
+pub fn check_spec(spec: &str) -> Result<(), String> {
+    endpoint(&config::resolve_model(spec)).map(|_| ())
+}
+
+/// Whether a model spec runs on OpenAI rather than a `<provider>:` endpoint.
+pub fn is_openai(spec: &str) -> bool {
+    matches!(endpoint(spec), Ok(None))
+}
+
+/// Agent for a model spec: an OpenAI model id (`gpt-4o`, optionally `openai:gpt-4o`), or
+/// `<provider>:<model>` for an OpenAI-compatible provider (`ollama:llama3.1:8b`). OpenAI
+/// models take headers and TLS files from `[providers.openai]`. `[models]` aliases are resolved.
+pub fn from_spec(spec: &str, openai_key: &str) -> Result<OpenAiAgent, String> {
+    let spec = &config::resolve_model(spec);
+    let (agent, profile) = match endpoint(spec)? {
+        Some((profile, model)) => (
+            OpenAiAgent::new(profile.api_key.clone().unwrap_or_default())
+                .with_base_url(profile.base_url.as_deref().unwrap_or_default())
+                .with_model(model),
+            profile,
+        ),
+        None => (
+            OpenAiAgent::new(openai_key.to_string())
+                .with_model(spec.strip_prefix("openai:").unwrap_or(spec)),
+            config::load()
+                .providers
+                .remove("openai")
+                .unwrap_or_default(),
+        ),
+    };
+    match connection(&profile) {
+        Ok((headers, client)) => {
+            let agent = agent.with_headers(headers);
+            Ok(match client {
+                Some(client) => agent.with_client(client),
+                None => agent,
+            })
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "ignoring the provider's headers and TLS settings");
+            Ok(agent)
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged, from = "SavedMessage")]
+pub enum Message {
+    Role {
+        role: String,
+        content: String,
+    },
+    Assistant {
+        role: String,
+        content: Option<String>,
+        tool_calls: Option<Vec<ToolCall>>,
+    },
+    ToolResult {
+        role: String,
+        tool_call_id: String,
+        function_name: String,
+        content: String,
+    },
+}
+
+/// A saved message. Read untagged, tool results and assistant replies would come back as
+/// `Role` (whose fields they all have), so the variant is picked by the fields present.
+#[derive(Deserialize)]
+struct SavedMessage {
+    role: String,
+    content: Option<String>,
+    tool_calls: Option<Vec<ToolCall>>,
+    tool_call_id: Option<String>,
+    function_name: Option<String>,
+}
+
+impl From<SavedMessage> for Message {
+    fn from(m: SavedMessage) -> Self {
+        match (m.tool_call_id, m.role.as_str()) {
+            (Some(tool_call_id), _) => Message::ToolResult {
+                role: m.role,
+                tool_call_id,
+                function_name: m.function_name.unwrap_or_default(),
+                content: m.content.unwrap_or_default(),
+            },
+            (None, "assistant") => Message::Assistant {
+                role: m.role,
+                content: m.content,
+                tool_calls: m.tool_calls,
+            },
+            (None, _) => Message::Role {
+                role: m.role,
+                content: m.content.unwrap_or_default(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type", default = "default_tool_type")]
+    pub type_: String,
+    pub function: FunctionCall,
+}
+
+fn default_tool_type() -> String {
+    "function".into()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FunctionCall {
+    pub name: String,
+    pub arguments: String,
+}
+
+#[derive(Debug)]
+pub struct AgentResponse {
+    pub content: Option<String>,
+    pub tool_calls: Option<Vec<ToolCall>>,
+    /// Token usage for this call, if the provider reported it.
+    pub usage: Option<Usage>,
+}
+
+#[async_trait]
+pub trait Agent: Send + Sync {
+    async fn chat(
+        &self,
+        messages: &mut Vec<Message>,
+        user_input: Option<&str>,
+    ) -> Result<AgentResponse, String>;
+
+    /// Same as chat but streams content to `on_chunk` as it arrives (e.g. for live terminal output).
+    async fn chat_stream<F>(
+        &self,
+        messages: &mut Vec<Message>,
+        user_input: Option<&str>,
+        on_chunk: &mut F,
+    ) -> Result<AgentResponse, String>
+    where
+        F: FnMut(&str) + Send;
+
+    /// Single completion with no tools (e.g. for planning). Returns assistant content text.
+    async fn completion(&self, system: &str, user: &str) -> Result<String, String>;
+
+    /// Counter of tokens streamed by the in-flight `chat_stream` call, for progress display.
+    /// Agents that don't count get one that stays at zero.
+    fn stream_counter(&self) -> Arc<AtomicUsize> {
+        Arc::default()
+    }
+
+    /// Whether only `READ_ONLY_TOOLS` are offered.
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
+
+#[async_trait]
+impl Agent for OpenAiAgent {
+    async fn chat(
+        &self,
+        messages: &mut Vec<Message>,
+        user_input: Option<&str>,
+    ) -> Result<AgentResponse, String> {
+        OpenAiAgent::chat(self, messages, user_input).await
+    }
+
+    async fn chat_stream<F>(
+        &self,
+        messages: &mut Vec<Message>,
+        user_input: Option<&str>,
+        on_chunk: &mut F,
+    ) -> Result<AgentResponse, String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        OpenAiAgent::chat_stream(self, messages, user_input, on_chunk).await
+    }
+
+    async fn completion(&self, system: &str, user: &str) -> Result<String, String> {
+        OpenAiAgent::completion(self, system, user).await
+    }
+
+    fn stream_counter(&self) -> Arc<AtomicUsize> {
+        OpenAiAgent::stream_counter(self)
+    }
+
+    fn is_read_only(&self) -> bool {
+        OpenAiAgent::is_read_only(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_unconfigured_provider_is_not_sent_to_openai() {
+        let e = check_spec("nonesuch:some-model").unwrap_err();
+        assert!(e.contains("[providers.nonesuch]"), "{}", e);
+        assert!(from_spec("nonesuch:some-model", "sk-test").is_err());
+        for spec in [
+            "gpt-4o",
+            "openai:gpt-4o",
+            "ft:gpt-4o-mini:acme::abc123",
+            "ollama:llama3.1:8b",
+        ] {
+            assert!(check_spec(spec).is_ok(), "{}", spec);
+        }
+        assert!(is_openai("ft:gpt-4o-mini:acme::abc123"));
+        assert!(!is_openai("ollama:llama3.1:8b"));
+    }
+}
@@ -0,0 +1,896 @@
+use super::{cassette, response_cache, AgentResponse, Message, ToolCall, READ_ONLY_TOOLS};
+use crate::http;
+use crate::plugins;
+use crate::routing::{self, Turn};
+use crate::tokens;
+use crate::usage::{self, Usage};
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio_stream::{Stream, StreamExt};
+use tracing::{debug, info, warn};
+
+const BASE_URL: &str = "https://api.openai.com/v1";
+const MODELS_URL: &str = "https://api.openai.com/v1/models";
+
+/// Why an API key could not be used, as reported by the models endpoint.
+#[derive(Debug)]
+pub enum KeyError {
+    /// The request never got an answer (DNS, TLS, proxy, timeout).
+    Network(String),
+    /// 401: the key is malformed, revoked, or belongs to another provider.
+    Invalid,
+    /// 403 (or 404 for an unknown organization): the key works but lacks access.
+    Permission(String),
+    /// Anything else, e.g. rate limits or server errors.
+    Other(String),
+}
+
+impl std::fmt::Display for KeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyError::Network(e) => write!(
+                f,
+                "could not reach OpenAI ({}); check your network or proxy",
+                e
+            ),
+            KeyError::Invalid => write!(
+                f,
+                "the API key is invalid or revoked; create a new one at platform.openai.com"
+            ),
+            KeyError::Permission(e) => write!(
+                f,
+                "the key has no access ({}); check its organization, project, and permissions",
+                e
+            ),
+            KeyError::Other(e) => write!(f, "unexpected response from OpenAI: {}", e),
+        }
+    }
+}
+
+/// List the model ids available to `api_key`. A cheap call, used to validate keys.
+pub async fn list_models(api_key: &str) -> Result<Vec<String>, KeyError> {
+    let res = http::client()
+        .get(MODELS_URL)
+        .bearer_auth(api_key)
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| KeyError::Network(e.to_string()))?;
+    let status = res.status();
+    let body: serde_json::Value = res.json().await.unwrap_or_default();
+    let message = body["error"]["message"].as_str().unwrap_or("").to_string();
+    match status.as_u16() {
+        200..=299 => Ok(body["data"]
+            .as_array()
+            .map(|a| {
+                a.iter()
+                    .filter_map(|m| m["id"].as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default()),
+        401 => Err(KeyError::Invalid),
+        403 | 404 => Err(KeyError::Permission(
+            format!("{} {}", status, message).trim().to_string(),
+        )),
+        _ => Err(KeyError::Other(
+            format!("{} {}", status, message).trim().to_string(),
+        )),
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Tool {
+    r#type: String,
+    function: FunctionDef,
+}
+
+#[derive(Debug, Serialize)]
+struct FunctionDef {
+    name: String,
+    description: String,
+    parameters: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatResponse {
+    choices: Vec<Choice>,
+    usage: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Choice {
+    message: AssistantMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct AssistantMessage {
+    content: Option<String>,
+    tool_calls: Option<Vec<ToolCall>>,
+}
+
+// Streaming chunk (SSE delta)
+#[derive(Debug, Deserialize)]
+struct StreamChunk {
+    choices: Option<Vec<StreamChoice>>,
+    usage: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamChoice {
+    delta: StreamDelta,
+    #[allow(dead_code)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamDelta {
+    content: Option<String>,
+    tool_calls: Option<Vec<StreamToolCallDelta>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamToolCallDelta {
+    index: usize,
+    id: Option<String>,
+    function: Option<StreamFunctionDelta>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamFunctionDelta {
+    name: Option<String>,
+    arguments: Option<String>,
+}
+
+fn tool_defs(read_only: bool) -> Vec<Tool> {
+    let mut tools = vec![
+        Tool {
+            r#type: "function".into(),
+            function: FunctionDef {
+                name: "create_file".into(),
+                description: "Create a new file with the given path and content".into(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string", "description": "File path" },
+                        "content": { "type": "string", "description": "File content" }
+                    },
+                    "required": ["path", "content"]
+                }),
+            },
+        },
+        Tool {
+            r#type: "function".into(),
+            function: FunctionDef {
+                name: "read_file".into(),
+                description: "Read contents of a file".into(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string", "description": "File path" }
+                    },
+                    "required": ["path"]
+                }),
+            },
+        },
+        Tool {
+            r#type: "function".into(),
+            function: FunctionDef {
+                name: "write_file".into(),
+                description: "Write or overwrite file content".into(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string", "description": "File path" },
+                        "content": { "type": "string", "description": "File content" }
+                    },
+                    "required": ["path", "content"]
+                }),
+            },
+        },
+        Tool {
+            r#type: "function".into(),
+            function: FunctionDef {
+                name: "list_dir".into(),
+                description: "List directory contents".into(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string", "description": "Directory path" }
+                    },
+                    "required": ["path"]
+                }),
+            },
+        },
+        Tool {
+            r#type: "function".into(),
+            function: FunctionDef {
+                name: "run_command".into(),
+                description: "Run a shell command".into(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "command": { "type": "string", "description": "Shell command to run" }
+                    },
+                    "required": ["command"]
+                }),
+            },
+        },
+        Tool {
+            r#type: "function".into(),
+            function: FunctionDef {
+                name: "create_directory".into(),
+                description: "Create a directory (and parent directories if needed)".into(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string", "description": "Directory path" }
+                    },
+                    "required": ["path"]
+                }),
+            },
+        },
+        Tool {
+            r#type: "function".into(),
+            function: FunctionDef {
+                name: "spawn_subagent".into(),
+                description: "Delegate a self-contained subtask (e.g. \"write tests for src/config.rs\") to a fresh sub-agent with its own small context and budget. Returns its summary and the diff it made".into(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "task": { "type": "string", "description": "What the sub-agent should do, with everything it needs to know" },
+                        "files": { "type": "array", "items": { "type": "string" }, "description": "Files to give the sub-agent as context" }
+                    },
+                    "required": ["task"]
+                }),
+            },
+        },
+        Tool {
+            r#type: "function".into(),
+            function: FunctionDef {
+                name: "save_memory".into(),
+                description: "Save a note about this project for future sessions (e.g. how to run its tests, a convention, a pitfall). Saving under an existing key replaces that note".into(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "key": { "type": "string", "description": "Short topic, e.g. \"test command\"" },
+                        "text": { "type": "string", "description": "The note" }
+                    },
+                    "required": ["key", "text"]
+                }),
+            },
+        },
+        Tool {
+            r#type: "function".into(),
+            function: FunctionDef {
+                name: "recall_memory".into(),
+                description: "Look up notes saved about this project in earlier sessions".into(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "query": { "type": "string", "description": "Words to match against saved notes; empty for the most recent" }
+                    },
+                    "required": ["query"]
+                }),
+            },
+        },
+    ];
+    for plugin in plugins::all() {
+        // Built-in tools win over a plugin with the same name.
+        if !tools.iter().any(|t| t.function.name == plugin.name) {
+            tools.push(Tool {
+                r#type: "function".into(),
+                function: FunctionDef {
+                    name: plugin.name.clone(),
+                    description: plugin.description.clone(),
+                    parameters: plugin.parameters.clone(),
+                },
+            });
+        }
+    }
+    tools
+        .into_iter()
+        .filter(|t| !read_only || READ_ONLY_TOOLS.contains(&t.function.name.as_str()))
+        .collect()
+}
+
+/// A call right after tool results continues a tool loop; anything else starts from a prompt.
+fn turn(messages: &[Message], user_input: Option<&str>) -> Turn {
+    match messages.last() {
+        Some(Message::ToolResult { .. }) if user_input.is_none() => Turn::Tools,
+        _ => Turn::Prose,
+    }
+}
+
+const SYSTEM_PROMPT: &str = r#"You are a CLI coding agent that helps developers. You can create files, read files, write files, list directories, run commands, create directories, hand self-contained subtasks to a sub-agent, and save or recall notes about the project that persist across sessions. Save what you had to work out and would want to know next time. Work in the current directory unless told otherwise. Be concise. When creating or editing code, write complete implementations."#;
+
+const READ_ONLY_SYSTEM_PROMPT: &str = r#"You are a CLI coding assistant that explains code. You can read files, list directories, and recall notes saved about the project, but cannot change anything. Read what you need to answer accurately, cite files and line numbers, and be concise."#;
+
+pub struct OpenAiAgent {
+    client: reqwest::Client,
+    api_key: String,
+    /// Extra headers for every request (e.g. an enterprise gateway's token).
+    headers: HeaderMap,
+    /// API root; another OpenAI-compatible server (e.g. Ollama) when set with `with_base_url`.
+    base_url: String,
+    model: String,
+    /// Deltas (≈ tokens) received by the current `chat_stream` call, for progress display.
+    streamed: Arc<AtomicUsize>,
+    /// Offer only `READ_ONLY_TOOLS` (e.g. for `zcode explain`).
+    read_only: bool,
+    /// Appended to every system prompt (e.g. a role's instructions).
+    instructions: Option<String>,
+    /// Key for models picked by `[routing]`, and the agents built for them by spec.
+    routing: Option<(String, Arc<Mutex<RoutedAgents>>)>,
+}
+
+type RoutedAgents = BTreeMap<String, Arc<OpenAiAgent>>;
+
+/// Body of a streamed response, chunk by chunk.
+type ByteStream = Pin<Box<dyn Stream<Item = Result<Vec<u8>, String>> + Send>>;
+
+impl OpenAiAgent {
+    pub fn new(api_key: String) -> Self {
+        Self {
+            client: http::client(),
+            api_key,
+            headers: HeaderMap::new(),
+            base_url: BASE_URL.into(),
+            model: "gpt-4o-mini".into(),
+            streamed: Arc::new(AtomicUsize::new(0)),
+            read_only: false,
+            instructions: None,
+            routing: None,
+        }
+    }
+
+    /// Counter of tokens streamed by the in-flight `chat_stream` call (reset on each call).
+    pub fn stream_counter(&self) -> Arc<AtomicUsize> {
+        self.streamed.clone()
+    }
+
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+
+    /// Talk to an OpenAI-compatible API at `base_url` (e.g. `http://localhost:11434/v1`).
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Send `headers` with every request.
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Use `client` (e.g. one with a client certificate) for requests.
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    fn url(&self, endpoint: &str) -> String {
+        format!("{}/{}", self.base_url, endpoint)
+    }
+
+    /// Only offer tools that cannot modify the workspace.
+    pub fn with_read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Add `instructions` to every system prompt this agent sends.
+    pub fn with_instructions(mut self, instructions: &str) -> Self {
+        self.instructions = Some(instructions.to_string());
+        self
+    }
+
+    /// Send calls to the models `[routing]` picks for the phase and turn (see `routing`);
+    /// `api_key` is for OpenAI models among them.
+    pub fn with_routing(mut self, api_key: &str) -> Self {
+        self.routing = Some((api_key.to_string(), Arc::default()));
+        self
+    }
+
+    /// The agent to make a call with, when routing picks a model other than this one.
+    fn routed(&self, turn: Turn) -> Option<Arc<OpenAiAgent>> {
+        let (api_key, built) = self.routing.as_ref()?;
+        let phase = routing::current();
+        let phase_name = phase.map_or("none", |p| p.name());
+        let Some(spec) = routing::model_for(phase, turn) else {
+            info!(phase = phase_name, turn = turn.name(), model = %self.model, "model kept");
+            return None;
+        };
+        let mut built = built.lock().unwrap();
+        if !built.contains_key(&spec) {
+            let mut agent = match super::from_spec(&spec, api_key) {
+                Ok(agent) => agent,
+                Err(e) => {
+                    warn!(phase = phase_name, turn = turn.name(), model = %self.model, error = %e, "model kept");
+                    return None;
+                }
+            };
+            agent.streamed = self.streamed.clone();
+            agent.read_only = self.read_only;
+            agent.instructions = self.instructions.clone();
+            built.insert(spec.clone(), Arc::new(agent));
+        }
+        let agent = built[&spec].clone();
+        drop(built);
+        if agent.model == self.model && agent.base_url == self.base_url {
+            info!(phase = phase_name, turn = turn.name(), model = %self.model, "model kept");
+            return None;
+        }
+        info!(phase = phase_name, turn = turn.name(), model = %agent.model, instead_of = %self.model, "model routed");
+        Some(agent)
+    }
+
+    /// Elide old tool outputs if the request would overflow the model's context window.
+    fn fit<T: Serialize>(&self, messages: &mut [serde_json::Value], tools: &T) {
+        let tools = serde_json::to_string(tools).unwrap_or_default();
+        let elided = tokens::fit(&self.model, messages, tokens::count(&self.model, &tools));
+        if elided > 0 {
+            warn!(model = %self.model, elided, "request too large for the context window; elided old tool outputs");
+        }
+    }
+
+    fn system_prompt(&self) -> String {
+        self.with_extra(if self.read_only {
+            READ_ONLY_SYSTEM_PROMPT
+        } else {
+            SYSTEM_PROMPT
+        })
+    }
+
+    fn with_extra(&self, system: &str) -> String {
+        match &self.instructions {
+            Some(extra) => format!("{}\n\n{}", system, extra),
+            None => system.to_string(),
+        }
+    }
+
+    /// POST `body` to `endpoint` and return the response text, or Err with the API's error.
+    /// Goes through the cassette when recording or replaying, and the response cache when on.
+    async fn post(&self, endpoint: &str, body: &serde_json::Value) -> Result<String, String> {
+        let (status, text) = match cassette::lookup(endpoint, body) {
+            Some(recorded) => recorded?,
+            None => {
+                let url = self.url(endpoint);
+                if let Some(cached) = response_cache::lookup(&url, body) {
+                    return Ok(cached);
+                }
+                let resp = self
+                    .client
+                    .post(&url)
+                    .bearer_auth(&self.api_key)
+                    .headers(self.headers.clone())
+                    .json(body)
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let status = resp.status().as_u16();
+                let text = resp.text().await.map_err(|e| e.to_string())?;
+                cassette::save(endpoint, body, status, text.clone());
+                if (200..300).contains(&status) {
+                    response_cache::save(&url, body, &text);
+                }
+                (status, text)
+            }
+        };
+        if !(200..300).contains(&status) {
+            warn!(model = %self.model, status, "API request failed");
+            return Err(format!("API error: {}", text));
+        }
+        Ok(text)
+    }
+
+    /// Like `post`, for a streamed response: the body as it arrives.
+    async fn post_stream(
+        &self,
+        endpoint: &str,
+        body: &serde_json::Value,
+    ) -> Result<ByteStream, String> {
+        if let Some(recorded) = cassette::lookup(endpoint, body) {
+            let (status, text) = recorded?;
+            if !(200..300).contains(&status) {
+                return Err(format!("API error: {}", text));
+            }
+            return Ok(Box::pin(tokio_stream::once(Ok(text.into_bytes()))));
+        }
+        let url = self.url(endpoint);
+        if let Some(cached) = response_cache::lookup(&url, body) {
+            return Ok(Box::pin(tokio_stream::once(Ok(cached.into_bytes()))));
+        }
+        let resp = self
+            .client
+            .post(&url)
+            .bearer_auth(&self.api_key)
+            .headers(self.headers.clone())
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let err_text = resp.text().await.unwrap_or_default();
+            cassette::save(endpoint, body, status.as_u16(), err_text.clone());
+            warn!(model = %self.model, %status, "API request failed");
+            return Err(format!("API error: {}", err_text));
+        }
+        let mut recorder = cassette::recorder(endpoint, body);
+        let mut collector = response_cache::collector(&url, body);
+        Ok(Box::pin(resp.bytes_stream().map(move |chunk| {
+            let chunk = chunk.map_err(|e| e.to_string())?.to_vec();
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.push(&chunk);
+            }
+            if let Some(collector) = collector.as_mut() {
+                collector.push(&chunk);
+            }
+            Ok(chunk)
+        })))
+    }
+
+    /// Embedding vectors for `inputs`, in order, using this agent's model.
+    pub async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        let body = serde_json::json!({ "model": self.model, "input": inputs });
+        info!(model = %self.model, inputs = inputs.len(), "embeddings request");
+        let started = Instant::now();
+        let text = self.post("embeddings", &body).await?;
+        let v: serde_json::Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+        usage::record(Usage::from_json(
+            &self.model,
+            &v["usage"],
+            started.elapsed(),
+        ));
+        let mut data: Vec<(u64, Vec<f32>)> = v["data"]
+            .as_array()
+            .ok_or("No embeddings in response")?
+            .iter()
+            .map(|d| {
+                let vector = d["embedding"]
+                    .as_array()
+                    .map(|a| {
+                        a.iter()
+                            .filter_map(|x| x.as_f64())
+                            .map(|x| x as f32)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                (d["index"].as_u64().unwrap_or(0), vector)
+            })
+            .collect();
+        data.sort_by_key(|(i, _)| *i);
+        Ok(data.into_iter().map(|(_, v)| v).collect())
+    }
+
+    /// Single completion with no tools (e.g. for planning). Returns assistant content text.
+    pub async fn completion(&self, system: &str, user: &str) -> Result<String, String> {
+        if let Some(agent) = self.routed(Turn::Prose) {
+            return Box::pin(agent.completion(system, user)).await;
+        }
+        let body = serde_json::json!({
+            "model": self.model,
+            "messages": [
+                { "role": "system", "content": self.with_extra(system) },
+                { "role": "user", "content": user }
+            ]
+        });
+
+        info!(model = %self.model, messages = body["messages"].as_array().map_or(0, |m| m.len()), "request");
+        let started = Instant::now();
+        let text = self.post("chat/completions", &body).await?;
+        let chat_resp: ChatResponse = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+        debug!(model = %self.model, latency_ms = started.elapsed().as_millis() as u64, "completion done");
+        if let Some(u) = &chat_resp.usage {
+            usage::record(Usage::from_json(&self.model, u, started.elapsed()));
+        }
+        let choice = chat_resp.choices.into_iter().next().ok_or("No response")?;
+        Ok(choice.message.content.unwrap_or_default())
+    }
+
+    pub async fn chat(
+        &self,
+        messages: &mut Vec<Message>,
+        user_input: Option<&str>,
+    ) -> Result<AgentResponse, String> {
+        if let Some(agent) = self.routed(turn(messages, user_input)) {
+            return Box::pin(agent.chat(messages, user_input)).await;
+        }
+        if let Some(input) = user_input {
+            messages.push(Message::Role {
+                role: "user".into(),
+                content: input.into(),
+            });
+        }
+
+        let mut request_messages: Vec<serde_json::Value> = vec![serde_json::json!({
+            "role": "system",
+            "content": self.system_prompt()
+        })];
+
+        for m in messages.iter() {
+            match m {
+                Message::Role { role, content } => {
+                    request_messages.push(serde_json::json!({
+                        "role": role,
+                        "content": content
+                    }));
+                }
+                Message::Assistant {
+                    role,
+                    content,
+                    tool_calls,
+                } => {
+                    let mut msg = serde_json::json!({
+                        "role": role,
+                        "content": content
+                    });
+                    if let Some(tc) = tool_calls {
+                        msg["tool_calls"] = serde_json::to_value(tc).unwrap();
+                    }
+                    request_messages.push(msg);
+                }
+                Message::ToolResult {
+                    role,
+                    tool_call_id,
+                    function_name: _,
+                    content,
+                } => {
+                    request_messages.push(serde_json::json!({
+                        "role": role,
+                        "tool_call_id": tool_call_id,
+                        "content": content
+                    }));
+                }
+            }
+        }
+
+        let tools = tool_defs(self.read_only);
+        self.fit(&mut request_messages, &tools);
+        let body = serde_json::json!({
+            "model": self.model,
+            "messages": request_messages,
+            "tools": tools,
+            "tool_choice": "auto"
+        });
+
+        info!(model = %self.model, messages = body["messages"].as_array().map_or(0, |m| m.len()), "request");
+        let started = Instant::now();
+        let text = self.post("chat/completions", &body).await?;
+        let chat_resp: ChatResponse = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+        debug!(model = %self.model, latency_ms = started.elapsed().as_millis() as u64, "chat done");
+        let usage = chat_resp
+            .usage
+            .as_ref()
+            .map(|u| Usage::from_json(&self.model, u, started.elapsed()));
+        if let Some(u) = &usage {
+            usage::record(u.clone());
+        }
+        let choice = chat_resp.choices.into_iter().next().ok_or("No response")?;
+        let msg = choice.message;
+
+        messages.push(Message::Assistant {
+            role: "assistant".into(),
+            content: msg.content.clone(),
+            tool_calls: msg.tool_calls.clone(),
+        });
+
+        Ok(AgentResponse {
+            content: msg.content,
+            tool_calls: msg.tool_calls,
+            usage,
+        })
+    }
+
+    pub async fn chat_stream<F>(
+        &self,
+        messages: &mut Vec<Message>,
+        user_input: Option<&str>,
+        on_chunk: &mut F,
+    ) -> Result<AgentResponse, String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        if let Some(agent) = self.routed(turn(messages, user_input)) {
+            return Box::pin(agent.chat_stream(messages, user_input, on_chunk)).await;
+        }
+        if let Some(input) = user_input {
+            messages.push(Message::Role {
+                role: "user".into(),
+                content: input.into(),
+            });
+        }
+
+        let mut request_messages: Vec<serde_json::Value> = vec![serde_json::json!({
+            "role": "system",
+            "content": self.system_prompt()
+        })];
+
+        for m in messages.iter() {
+            match m {
+                Message::Role { role, content } => {
+                    request_messages.push(serde_json::json!({
+                        "role": role,
+                        "content": content
+                    }));
+                }
+                Message::Assistant {
+                    role,
+                    content,
+                    tool_calls,
+                } => {
+                    let mut msg = serde_json::json!({
+                        "role": role,
+                        "content": content
+                    });
+                    if let Some(tc) = tool_calls {
+                        msg["tool_calls"] = serde_json::to_value(tc).unwrap();
+                    }
+                    request_messages.push(msg);
+                }
+                Message::ToolResult {
+                    role,
+                    tool_call_id,
+                    function_name: _,
+                    content,
+                } => {
+                    request_messages.push(serde_json::json!({
+                        "role": role,
+                        "tool_call_id": tool_call_id,
+                        "content": content
+                    }));
+                }
+            }
+        }
+
+        let tools = tool_defs(self.read_only);
+        self.fit(&mut request_messages, &tools);
+        let body = serde_json::json!({
+            "model": self.model,
+            "messages": request_messages,
+            "tools": tools,
+            "tool_choice": "auto",
+            "stream": true,
+            "stream_options": { "include_usage": true }
+        });
+
+        info!(model = %self.model, messages = body["messages"].as_array().map_or(0, |m| m.len()), "request");
+        let started = Instant::now();
+        let mut stream = self.post_stream("chat/completions", &body).await?;
+        self.streamed.store(0, Ordering::Relaxed);
+        let mut buffer = Vec::<u8>::new();
+        let mut content_acc = String::new();
+        let mut usage_json: Option<serde_json::Value> = None;
+        // Accumulate tool calls by index: id, name, arguments (append for arguments)
+        let mut tool_calls_acc: Vec<(String, String, String)> = Vec::new();
+
+        'stream: while let Some(chunk_result) = stream.next().await {
+            let chunk = chunk_result?;
+            buffer.extend_from_slice(&chunk);
+
+            // Process complete lines (SSE: "data: {...}\n" or "data: [DONE]\n")
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line = std::mem::take(&mut buffer);
+                let (full_line, rest) = line.split_at(pos + 1);
+                buffer.extend_from_slice(rest);
+
+                let line_str = match std::str::from_utf8(full_line) {
+                    Ok(s) => s.trim(),
+                    Err(_) => continue,
+                };
+                let Some(data) = line_str.strip_prefix("data: ") else {
+                    continue;
+                };
+                if data == "[DONE]" {
+                    break 'stream;
+                }
+                let Ok(stream_chunk) = serde_json::from_str::<StreamChunk>(data) else {
+                    continue;
+                };
+                if stream_chunk.usage.is_some() {
+                    usage_json = stream_chunk.usage;
+                }
+                let Some(choices) = stream_chunk.choices else {
+                    continue;
+                };
+                let Some(choice) = choices.into_iter().next() else {
+                    continue;
+                };
+                let delta = choice.delta;
+                self.streamed.fetch_add(1, Ordering::Relaxed);
+
+                if let Some(ref text) = delta.content {
+                    if !text.is_empty() {
+                        on_chunk(text);
+                        content_acc.push_str(text);
+                    }
+                }
+                if let Some(deltas) = delta.tool_calls {
+                    for d in deltas {
+                        let idx = d.index;
+                        if idx >= tool_calls_acc.len() {
+                            tool_calls_acc
+                                .resize(idx + 1, (String::new(), String::new(), String::new()));
+                        }
+                        let acc = &mut tool_calls_acc[idx];
+                        if let Some(id) = d.id {
+                            acc.0 = id;
+                        }
+                        if let Some(f) = d.function {
+                            if let Some(n) = f.name {
+                                acc.1 = n;
+                            }
+                            if let Some(a) = f.arguments {
+                                acc.2.push_str(&a);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Build final tool_calls from accumulator
+        let tool_calls: Option<Vec<ToolCall>> = if tool_calls_acc.is_empty() {
+            None
+        } else {
+            Some(
+                tool_calls_acc
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, (id, name, arguments))| ToolCall {
+                        id: if id.is_empty() {
+                            format!("call_{}", i)
+                        } else {
+                            id
+                        },
+                        type_: "function".into(),
+                        function: super::FunctionCall { name, arguments },
+                    })
+                    .collect(),
+            )
+        };
+
+        let content = if content_acc.is_empty() {
+            None
+        } else {
+            Some(content_acc)
+        };
+
+        messages.push(Message::Assistant {
+            role: "assistant".into(),
+            content: content.clone(),
+            tool_calls: tool_calls.clone(),
+        });
+
+        let usage = usage_json.map(|u| Usage::from_json(&self.model, &u, started.elapsed()));
+        debug!(
+            model = %self.model,
+            latency_ms = started.elapsed().as_millis() as u64,
+            tool_calls = tool_calls.as_ref().map_or(0, |t| t.len()),
+            prompt_tokens = usage.as_ref().map_or(0, |u| u.prompt_tokens),
+            completion_tokens = usage.as_ref().map_or(0, |u| u.completion_tokens),
+            "stream done"
+        );
+        if let Some(u) = &usage {
+            usage::record(u.clone());
+        }
+
+        Ok(AgentResponse {
+            content,
+            tool_calls,
+            usage,
+        })
+    }
+}
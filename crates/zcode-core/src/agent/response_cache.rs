@@ -0,0 +1,111 @@
+//! Model responses cached on disk by request: a request with the same provider URL and body
+//! (model, messages, tools) is answered from the cache without calling the API. Off unless
+//! `--response-cache` or `response_cache = true` in the config; cached replies carry no usage,
+//! so they cost nothing. Bypassed while recording or replaying.
+
+use super::cassette;
+use crate::config;
+use crate::options;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+fn active() -> bool {
+    options::get().response_cache && !cassette::is_active()
+}
+
+fn entry_path(url: &str, body: &serde_json::Value) -> Option<PathBuf> {
+    let mut hasher = Sha256::new();
+    hasher.update(url.as_bytes());
+    hasher.update([0]);
+    hasher.update(body.to_string().as_bytes());
+    let key: String = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    config::data_dir().map(|d| d.join("responses").join(format!("{}.txt", key)))
+}
+
+/// The cached response to a request, without its usage.
+pub(super) fn lookup(url: &str, body: &serde_json::Value) -> Option<String> {
+    if !active() {
+        return None;
+    }
+    let text = fs::read_to_string(entry_path(url, body)?).ok()?;
+    info!(url, "cached response");
+    Some(strip_usage(&text))
+}
+
+/// Save a successful response: JSON, or the server-sent events of a complete streamed reply.
+pub(super) fn save(url: &str, body: &serde_json::Value, response: &str) {
+    if !active() {
+        return;
+    }
+    let Some(path) = entry_path(url, body) else {
+        return;
+    };
+    let written = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, response));
+    if let Err(e) = written {
+        warn!(path = %path.display(), error = %e, "could not cache the response");
+    }
+}
+
+/// Collects a streamed response and caches it once the stream has ended.
+pub(super) struct Collector {
+    url: String,
+    request: serde_json::Value,
+    body: Vec<u8>,
+}
+
+impl Collector {
+    pub(super) fn push(&mut self, chunk: &[u8]) {
+        self.body.extend_from_slice(chunk);
+    }
+}
+
+impl Drop for Collector {
+    fn drop(&mut self) {
+        let body = String::from_utf8_lossy(&self.body);
+        // A reply cut short (interrupted, or a dropped connection) is not worth keeping.
+        if body.contains("data: [DONE]") {
+            save(&self.url, &self.request, &body);
+        }
+    }
+}
+
+/// A collector for a streamed response, when caching.
+pub(super) fn collector(url: &str, request: &serde_json::Value) -> Option<Collector> {
+    active().then(|| Collector {
+        url: url.to_string(),
+        request: request.clone(),
+        body: Vec::new(),
+    })
+}
+
+/// `text` with the `usage` objects removed, for a JSON body or each event of a stream.
+fn strip_usage(text: &str) -> String {
+    let strip = |json: &str| -> Option<String> {
+        let mut value: serde_json::Value = serde_json::from_str(json).ok()?;
+        value.as_object_mut()?.remove("usage")?;
+        Some(value.to_string())
+    };
+    if let Some(stripped) = strip(text) {
+        return stripped;
+    }
+    text.split_inclusive('\n')
+        .map(|line| {
+            match line
+                .strip_prefix("data: ")
+                .and_then(|data| strip(data.trim_end()))
+            {
+                Some(stripped) => format!("data: {}\n", stripped),
+                None => line.to_string(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,50 @@
+//! Tool calls held until a client approves or rejects them (`zcode serve`). Without the gate,
+//! and for read-only tools, every call runs straight away.
+
+use crate::agent::READ_ONLY_TOOLS;
+use crate::events::{self, Event};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tokio::sync::oneshot;
+
+static REQUIRED: AtomicBool = AtomicBool::new(false);
+/// Calls waiting for a decision, by tool call id.
+static PENDING: Mutex<BTreeMap<String, oneshot::Sender<bool>>> = Mutex::new(BTreeMap::new());
+
+/// Hold mutating tool calls for approval.
+pub fn set_required(on: bool) {
+    REQUIRED.store(on, Ordering::Relaxed);
+}
+
+pub fn required() -> bool {
+    REQUIRED.load(Ordering::Relaxed)
+}
+
+/// Wait for a decision on a tool call. Returns false if it was rejected or abandoned.
+pub async fn request(id: &str, name: &str, arguments: &str) -> bool {
+    if !REQUIRED.load(Ordering::Relaxed) || READ_ONLY_TOOLS.contains(&name) {
+        return true;
+    }
+    let (tx, rx) = oneshot::channel();
+    PENDING.lock().unwrap().insert(id.to_string(), tx);
+    events::emit(Event::NeedsApproval {
+        id,
+        name,
+        arguments,
+    });
+    rx.await.unwrap_or(false)
+}
+
+/// Ids of the tool calls waiting for a decision.
+pub fn pending() -> Vec<String> {
+    PENDING.lock().unwrap().keys().cloned().collect()
+}
+
+/// Approve or reject a waiting call. Returns false if no call with that id is waiting.
+pub fn decide(id: &str, approve: bool) -> bool {
+    match PENDING.lock().unwrap().remove(id) {
+        Some(tx) => tx.send(approve).is_ok(),
+        None => false,
+    }
+}
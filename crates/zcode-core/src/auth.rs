@@ -0,0 +1,33 @@
+//! `zcode auth <provider>`: prompt for an API key, validate it, and store it in the config.
+
+use crate::agent::list_models;
+use crate::config;
+use crate::ui;
+
+/// Providers `zcode auth` knows how to validate.
+pub const PROVIDERS: &[&str] = &["openai"];
+
+pub async fn run(provider: &str) -> Result<(), String> {
+    let key = ui::password(&format!("{} API key", provider))?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err("No key entered".into());
+    }
+
+    let models = ui::with_spinner("Checking key", list_models(key))
+        .await
+        .map_err(|e| e.to_string())?;
+    ui::info(&format!("Key accepted ({} models available)", models.len()));
+
+    config::set("api_key", key)?;
+    if let Some(path) = config::config_path() {
+        config::restrict_permissions(&path);
+        ui::info(&format!("Saved to {}", path.display()));
+    }
+    if std::env::var("OPENAI_API_KEY").is_ok() {
+        ui::note(
+            "OPENAI_API_KEY is set in the environment and takes precedence over the saved key.",
+        );
+    }
+    Ok(())
+}
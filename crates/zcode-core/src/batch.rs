@@ -0,0 +1,124 @@
+//! `zcode batch tasks.yaml`: run a list of prompts one after another, each as an isolated
+//! pipeline run, and write a JSON report with per-task results and diffs.
+//!
+//! ```yaml
+//! tasks:
+//!   - Add doc comments to src/config.rs
+//!   - name: errors
+//!     prompt: Replace unwrap() in src/tools with proper errors
+//! ```
+
+use crate::agent::Agent;
+use crate::run::{self, Agents};
+use crate::tools::Executor;
+use crate::ui;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Instant;
+
+#[derive(Deserialize)]
+struct BatchFile {
+    tasks: Vec<TaskSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TaskSpec {
+    Prompt(String),
+    Named {
+        name: Option<String>,
+        prompt: String,
+    },
+}
+
+#[derive(Serialize)]
+pub struct FileReport {
+    pub path: String,
+    pub added: usize,
+    pub removed: usize,
+}
+
+#[derive(Serialize)]
+pub struct TaskReport {
+    pub name: String,
+    pub prompt: String,
+    pub status: &'static str,
+    pub reply: Option<String>,
+    pub duration_secs: f64,
+    pub files: Vec<FileReport>,
+    /// Unified diff of everything the task changed.
+    pub diff: String,
+}
+
+#[derive(Serialize)]
+pub struct BatchReport {
+    pub tasks: Vec<TaskReport>,
+    pub completed: usize,
+    pub failed: usize,
+}
+
+pub async fn run_batch<P: Agent, E: Agent>(
+    agents: &Agents<P, E>,
+    executor: &Executor,
+    tasks_file: &Path,
+    report_path: &Path,
+) -> Result<BatchReport, String> {
+    let content = std::fs::read_to_string(tasks_file)
+        .map_err(|e| format!("{}: {}", tasks_file.display(), e))?;
+    let file: BatchFile =
+        serde_yaml::from_str(&content).map_err(|e| format!("{}: {}", tasks_file.display(), e))?;
+
+    let total = file.tasks.len();
+    let mut reports = Vec::new();
+    for (i, spec) in file.tasks.into_iter().enumerate() {
+        let (name, prompt) = match spec {
+            TaskSpec::Prompt(p) => (format!("task-{}", i + 1), p),
+            TaskSpec::Named { name, prompt } => {
+                (name.unwrap_or_else(|| format!("task-{}", i + 1)), prompt)
+            }
+        };
+        ui::phase(&format!("Batch task {}/{}: {}", i + 1, total, name));
+        executor.checkpoint();
+        let started = Instant::now();
+        let reply = run::run_once(agents, executor, &prompt).await;
+        let changes = executor.changes();
+        reports.push(TaskReport {
+            name,
+            prompt,
+            status: if reply.is_some() {
+                "completed"
+            } else {
+                "failed"
+            },
+            reply,
+            duration_secs: started.elapsed().as_secs_f64(),
+            files: changes
+                .iter()
+                .map(|c| {
+                    let (added, removed) = ui::line_counts(c.before.as_deref(), c.after.as_deref());
+                    FileReport {
+                        path: c.path.clone(),
+                        added,
+                        removed,
+                    }
+                })
+                .collect(),
+            diff: changes
+                .iter()
+                .map(ui::plain_diff)
+                .collect::<Vec<_>>()
+                .join(""),
+        });
+        outln!();
+    }
+
+    let completed = reports.iter().filter(|r| r.status == "completed").count();
+    let report = BatchReport {
+        failed: reports.len() - completed,
+        completed,
+        tasks: reports,
+    };
+    let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    std::fs::write(report_path, json).map_err(|e| format!("{}: {}", report_path.display(), e))?;
+    Ok(report)
+}
@@ -0,0 +1,156 @@
+//! `zcode bench`: time models on the same small requests to compare providers. For each
+//! model it measures time to first token and streaming throughput on a plain answer, and
+//! the round trip of a tool call (request, run `list_dir`, answer), over several runs.
+
+use crate::agent::{self, Agent, Message};
+use crate::tools::Executor;
+use crate::ui;
+use std::time::{Duration, Instant};
+
+const STREAM_PROMPT: &str =
+    "Without using any tools, write the numbers from 1 to 60 as English words, one per line.";
+const TOOL_PROMPT: &str =
+    "Call list_dir on the current directory once, then reply with only the number of entries.";
+
+/// One model's measurements, one entry per successful run.
+pub struct ModelBench {
+    pub model: String,
+    pub first_token: Vec<Duration>,
+    /// Output tokens per second after the first token.
+    pub tokens_per_sec: Vec<f64>,
+    pub tool_round_trip: Vec<Duration>,
+    pub failures: Vec<String>,
+}
+
+/// Benchmark each model spec `runs` times.
+pub async fn run(
+    api_key: &str,
+    executor: &Executor,
+    models: &[String],
+    runs: usize,
+) -> Vec<ModelBench> {
+    let mut results = Vec::new();
+    for model in models {
+        ui::phase(&format!("Benchmarking {}", model));
+        let mut bench = ModelBench {
+            model: model.clone(),
+            first_token: Vec::new(),
+            tokens_per_sec: Vec::new(),
+            tool_round_trip: Vec::new(),
+            failures: Vec::new(),
+        };
+        let agents = agent::from_spec(model, api_key)
+            .and_then(|agent| Ok((agent, agent::from_spec(model, api_key)?.with_read_only())));
+        let (agent, tool_agent) = match agents {
+            Ok(agents) => agents,
+            Err(e) => {
+                bench.failures.push(e);
+                results.push(bench);
+                continue;
+            }
+        };
+        for n in 1..=runs {
+            match stream_once(&agent).await {
+                Ok((first, rate)) => {
+                    bench.first_token.push(first);
+                    bench.tokens_per_sec.push(rate);
+                }
+                Err(e) => bench.failures.push(format!("stream: {}", e)),
+            }
+            match tool_round_trip(&tool_agent, executor).await {
+                Ok(elapsed) => bench.tool_round_trip.push(elapsed),
+                Err(e) => bench.failures.push(format!("tool: {}", e)),
+            }
+            ui::note(&format!("run {}/{} done", n, runs));
+        }
+        ui::phase_done(&format!("Benchmarking {}", model));
+        results.push(bench);
+    }
+    results
+}
+
+/// Time to the first streamed token, and output tokens per second after it.
+async fn stream_once<A: Agent>(agent: &A) -> Result<(Duration, f64), String> {
+    let mut messages = Vec::new();
+    let started = Instant::now();
+    let mut first: Option<Duration> = None;
+    let mut chunks = 0usize;
+    let response = agent
+        .chat_stream(&mut messages, Some(STREAM_PROMPT), &mut |_: &str| {
+            first.get_or_insert_with(|| started.elapsed());
+            chunks += 1;
+        })
+        .await?;
+    let total = started.elapsed();
+    let first = first.ok_or("No content was streamed")?;
+    // Providers that don't report usage get one token per chunk, which is close for most.
+    let tokens = response
+        .usage
+        .map_or(chunks as u64, |u| u.completion_tokens);
+    let streaming = (total - first).as_secs_f64().max(0.001);
+    Ok((first, tokens as f64 / streaming))
+}
+
+/// Time from the request until the answer that follows one tool call and its result.
+async fn tool_round_trip<A: Agent>(agent: &A, executor: &Executor) -> Result<Duration, String> {
+    let mut messages = Vec::new();
+    let started = Instant::now();
+    let response = agent.chat(&mut messages, Some(TOOL_PROMPT)).await?;
+    let calls = response
+        .tool_calls
+        .filter(|c| !c.is_empty())
+        .ok_or("The model did not call a tool")?;
+    for call in &calls {
+        let content = executor.execute(call).unwrap_or_else(|e| e);
+        messages.push(Message::ToolResult {
+            role: "tool".into(),
+            tool_call_id: call.id.clone(),
+            function_name: call.function.name.clone(),
+            content,
+        });
+    }
+    agent.chat(&mut messages, None).await?;
+    Ok(started.elapsed())
+}
+
+fn median_secs(values: &[Duration]) -> Option<f64> {
+    median(&values.iter().map(Duration::as_secs_f64).collect::<Vec<_>>())
+}
+
+fn median(values: &[f64]) -> Option<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    sorted.get(sorted.len() / 2).copied()
+}
+
+/// Print medians per model, then any failures.
+pub fn print_table(results: &[ModelBench]) {
+    let secs = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.2}s", v));
+    let rows: Vec<Vec<String>> = results
+        .iter()
+        .map(|b| {
+            vec![
+                b.model.clone(),
+                secs(median_secs(&b.first_token)),
+                median(&b.tokens_per_sec).map_or("-".to_string(), |v| format!("{:.0}", v)),
+                secs(median_secs(&b.tool_round_trip)),
+                b.failures.len().to_string(),
+            ]
+        })
+        .collect();
+    ui::table(
+        &[
+            "model",
+            "first token",
+            "tokens/s",
+            "tool round trip",
+            "failures",
+        ],
+        &rows,
+    );
+    for bench in results {
+        for failure in &bench.failures {
+            ui::error_msg(&format!("{}: {}", bench.model, failure));
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! `zcode changelog`: draft a CHANGELOG entry from git history since a tag.
+
+use crate::agent;
+use crate::context;
+use crate::git::git;
+use crate::interrupt;
+use crate::run;
+use crate::tools::Executor;
+use crate::ui;
+use std::fs;
+
+const CHANGELOG_FILE: &str = "CHANGELOG.md";
+/// The combined diff beyond this many bytes is cut before it goes into the prompt.
+const DIFF_CAP: usize = 24 * 1024;
+
+const CHANGELOG_SYSTEM: &str = r#"You write release notes in the Keep a Changelog format. Given commit messages, a diff stat, and a (possibly truncated) diff, output only a markdown entry: a `## <version> - <date>` heading followed by `### Added`, `### Changed`, `### Fixed`, and `### Removed` sections (omit empty ones). One bullet per user-visible change, written for users, not developers; merge related commits and skip internal refactors, CI, and formatting."#;
+
+/// Draft an entry for changes since `since` (default: the latest tag) and, after
+/// confirmation, prepend it to CHANGELOG.md.
+pub async fn run(
+    api_key: &str,
+    executor: &Executor,
+    since: Option<&str>,
+    version: &str,
+    yes: bool,
+) -> Result<(), String> {
+    let since = match since {
+        Some(s) => Some(s.to_string()),
+        None => git(executor.workspace(), &["describe", "--tags", "--abbrev=0"])
+            .ok()
+            .map(|t| t.trim().to_string()),
+    };
+    let range = since
+        .as_ref()
+        .map(|s| format!("{}..HEAD", s))
+        .unwrap_or_else(|| "HEAD".into());
+
+    let log = git(
+        executor.workspace(),
+        &["log", "--no-merges", "--format=- %h %s%n%b", &range],
+    )?;
+    if log.trim().is_empty() {
+        ui::info(&format!("No commits in {}.", range));
+        return Ok(());
+    }
+    let (stat, diff) = match &since {
+        Some(s) => (
+            git(executor.workspace(), &["diff", "--stat", s, "HEAD"])?,
+            git(executor.workspace(), &["diff", s, "HEAD"])?,
+        ),
+        None => (String::new(), String::new()),
+    };
+    ui::phase_done(&format!(
+        "{} commits in {}",
+        log.lines().filter(|l| l.starts_with("- ")).count(),
+        range
+    ));
+
+    let date = git(executor.workspace(), &["log", "-1", "--format=%cs"]).unwrap_or_default();
+    let user = format!(
+        "Version: {}\nDate: {}\n\nCommits:\n{}\n\nDiff stat:\n{}\n\nDiff:\n{}",
+        version,
+        date.trim(),
+        log,
+        stat,
+        context::truncate(&diff, DIFF_CAP)
+    );
+    let agent = agent::from_spec(&run::executor_model(), api_key)?;
+    let drafting = ui::with_spinner(
+        "Drafting changelog",
+        agent.completion(CHANGELOG_SYSTEM, &user),
+    );
+    let entry = match interrupt::cancellable(drafting).await {
+        Some(result) => result?,
+        None => return Err("Interrupted".into()),
+    };
+    let entry = entry
+        .trim()
+        .trim_start_matches("```markdown")
+        .trim_matches('`')
+        .trim()
+        .to_string();
+    outln!("\n{}\n", entry);
+
+    if !yes {
+        let write = ui::confirm(&format!("Add to {}?", CHANGELOG_FILE), true)?;
+        if !write {
+            return Ok(());
+        }
+    }
+    let path = executor.workspace().join(CHANGELOG_FILE);
+    let existing = fs::read_to_string(&path).unwrap_or_else(|_| "# Changelog\n".into());
+    executor.track(CHANGELOG_FILE);
+    fs::write(&path, insert_entry(&existing, &entry))
+        .map_err(|e| format!("{}: {}", CHANGELOG_FILE, e))?;
+    ui::info(&format!("Updated {}", CHANGELOG_FILE));
+    Ok(())
+}
+
+/// Put `entry` above the newest release, keeping the title and intro at the top.
+fn insert_entry(existing: &str, entry: &str) -> String {
+    let at = existing
+        .find("\n## ")
+        .map(|i| i + 1)
+        .unwrap_or(existing.len());
+    let (head, rest) = existing.split_at(at);
+    format!("{}\n\n{}\n\n{}", head.trim_end(), entry, rest)
+        .trim_end()
+        .to_string()
+        + "\n"
+}
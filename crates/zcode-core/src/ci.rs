@@ -0,0 +1,86 @@
+//! Headless runs (`--ci`): run limits, the tool policy, and an exit code that says how the
+//! run ended. Limits and the policy also apply outside CI when set.
+
+use crate::options;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Process exit codes for `--ci`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    Success = 0,
+    /// Some todos were not completed.
+    Partial = 2,
+    /// The model asked for a tool the policy does not allow.
+    PolicyBlocked = 3,
+    /// `--max-cost` or `--max-turns` was reached.
+    BudgetExceeded = 4,
+    /// A model request failed.
+    ApiFailure = 5,
+}
+
+/// Limits and tool policy for a run.
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    /// Stop once the estimated cost (USD) reaches this.
+    pub max_cost: Option<f64>,
+    /// Most model requests per todo.
+    pub max_turns: Option<usize>,
+    /// Tools the model may use; `None` allows all.
+    pub allow_tools: Option<Vec<String>>,
+}
+
+static OUTCOME: Mutex<Option<Exit>> = Mutex::new(None);
+static TURNS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn enabled() -> bool {
+    options::get().ci
+}
+
+/// Note how the run went wrong. The first failure wins: later ones are usually consequences.
+pub fn record(exit: Exit) {
+    let mut outcome = OUTCOME.lock().unwrap();
+    if outcome.is_none() && exit != Exit::Success {
+        *outcome = Some(exit);
+    }
+}
+
+pub fn exit_code() -> i32 {
+    OUTCOME.lock().unwrap().unwrap_or(Exit::Success) as i32
+}
+
+/// Whether the policy allows `tool`; records a policy block if not.
+pub fn tool_allowed(tool: &str) -> bool {
+    let allowed = options::get()
+        .limits
+        .allow_tools
+        .is_none_or(|tools| tools.iter().any(|t| t == tool));
+    if !allowed {
+        record(Exit::PolicyBlocked);
+    }
+    allowed
+}
+
+/// Start counting model requests for a new todo.
+pub fn reset_turns() {
+    TURNS.store(0, Ordering::Relaxed);
+}
+
+/// Check the budget before a model request. Returns an error (and records it) once a
+/// limit is reached.
+pub fn before_request(spent: f64) -> Result<(), String> {
+    let limits = options::get().limits;
+    if let Some(max) = limits.max_cost.filter(|max| spent >= *max) {
+        record(Exit::BudgetExceeded);
+        return Err(format!("Cost limit reached (${:.4} of ${:.4})", spent, max));
+    }
+    let turns = TURNS.fetch_add(1, Ordering::Relaxed) + 1;
+    if let Some(max) = limits.max_turns.filter(|max| turns > *max) {
+        record(Exit::BudgetExceeded);
+        return Err(format!(
+            "Turn limit reached ({} model requests for this step)",
+            max
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,39 @@
+//! System clipboard access.
+
+pub fn copy(text: &str) -> Result<(), String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| e.to_string())?;
+    clipboard
+        .set_text(text.to_string())
+        .map_err(|e| e.to_string())
+}
+
+pub fn paste() -> Result<String, String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| e.to_string())?;
+    clipboard.get_text().map_err(|e| e.to_string())
+}
+
+/// Attach pasted text to a prompt as a context block.
+pub fn attach(prompt: &str, pasted: &str) -> String {
+    format!(
+        "{}\n\nPasted from clipboard:\n```\n{}\n```",
+        prompt,
+        pasted.trim_end()
+    )
+}
+
+/// Contents of the last fenced code block in markdown text.
+pub fn last_code_block(text: &str) -> Option<String> {
+    let mut blocks = Vec::new();
+    let mut current: Option<Vec<&str>> = None;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            match current.take() {
+                Some(lines) => blocks.push(lines.join("\n")),
+                None => current = Some(Vec::new()),
+            }
+        } else if let Some(lines) = current.as_mut() {
+            lines.push(line);
+        }
+    }
+    blocks.pop()
+}
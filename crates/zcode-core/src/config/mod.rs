@@ -0,0 +1,392 @@
+//! The config file (`~/.config/zcode/config.toml`) with the project's `.zcode.toml` on top
+//! and the team config from `config_url` underneath: typed settings via `load`, checked by
+//! `validate`, and dotted-key access for `zcode config` and free-form tables.
+
+pub mod remote;
+mod schema;
+pub mod trust;
+
+pub use schema::{
+    Chunking, Colors, Config, Hook, HttpConfig, IndexConfig, LanguageChunking, NotifyMode,
+    Provider, ProxyConfig, RoleConfig, Route, StringList, Tools, Ui,
+};
+
+use serde_path_to_error::Segment;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use toml_edit::{DocumentMut, ImDocument, Item, Table, Value};
+
+pub fn config_path() -> Option<PathBuf> {
+    directories::ProjectDirs::from("dev", "zcode", "zcode")
+        .map(|d| d.config_dir().join("config.toml"))
+}
+
+/// The `.zcode.toml` that applies to the workspace, found by `set_workspace`.
+static PROJECT: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Layer the workspace's `.zcode.toml` over the global config from now on.
+pub fn set_workspace(workspace: &Path) {
+    let _ = PROJECT.set(project_path(workspace));
+}
+
+/// The nearest `.zcode.toml` walking up from `workspace`, stopping at the repository root.
+pub fn project_path(workspace: &Path) -> Option<PathBuf> {
+    for dir in workspace.ancestors() {
+        let path = dir.join(PROJECT_FILE);
+        if path.is_file() {
+            return Some(path);
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    None
+}
+
+/// Config files in the order they apply; later ones override earlier ones.
+fn sources() -> Vec<PathBuf> {
+    remote::active()
+        .into_iter()
+        .chain(config_path())
+        .chain(PROJECT.get().cloned().flatten())
+        .collect()
+}
+
+fn read_document(path: &Path) -> Option<DocumentMut> {
+    fs::read_to_string(path).ok()?.parse().ok()
+}
+
+/// Split a dotted key into segments; quoted segments may contain dots (`price."gpt-4.1"`).
+fn split_key(key: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in key.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '.' if !quoted => segments.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    segments.push(current);
+    segments
+}
+
+/// Find `key` under `item`, trying the whole key before splitting at each dot,
+/// so unquoted keys like `price.gpt-4.1` still resolve.
+fn lookup<'a>(item: &'a Item, key: &str) -> Option<&'a Item> {
+    let table = item.as_table_like()?;
+    if let Some(found) = table.get(key.trim_matches('"')) {
+        return Some(found);
+    }
+    key.match_indices('.').find_map(|(i, _)| {
+        let head = key[..i].trim_matches('"');
+        table.get(head).and_then(|sub| lookup(sub, &key[i + 1..]))
+    })
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.value().clone(),
+        other => other.to_string().trim().to_string(),
+    }
+}
+
+/// Look up a `key = "value"` setting in the config file; dotted keys reach into tables.
+/// Settings that moved into `[ui]` are found under either name.
+/// The project's `.zcode.toml` wins over the global config.
+pub fn get(key: &str) -> Option<String> {
+    let keys = aliases(key);
+    sources()
+        .iter()
+        .rev()
+        .filter_map(|path| read_document(path))
+        .find_map(|doc| {
+            let value = keys
+                .iter()
+                .find_map(|k| lookup(doc.as_item(), k))?
+                .as_value()?;
+            let v = display_value(value);
+            (!v.is_empty()).then_some(v)
+        })
+}
+
+/// `key` where it lives now first, then as written, then under its old top-level name.
+fn aliases(key: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    keys.extend(schema::moved(key).map(|path| schema::dotted(&path)));
+    keys.push(key.to_string());
+    if let Some(rest) = key.strip_prefix("ui.") {
+        keys.push(
+            rest.strip_prefix("colors.")
+                .map_or_else(|| rest.to_string(), |c| format!("theme_{}", c)),
+        );
+    }
+    keys
+}
+
+/// Parse `content` (the config at `path`) into typed settings, with a warning per unknown key.
+/// Errors and warnings point at the line of the offending key.
+pub fn parse(path: &Path, content: &str) -> Result<(Config, Vec<String>), String> {
+    let (json, warnings) = check(path, content)?;
+    let config = serde_json::from_value(json).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok((config, warnings))
+}
+
+/// The file as JSON (with top-level settings moved into their tables), once it deserializes
+/// into `Config`, and its warnings.
+fn check(path: &Path, content: &str) -> Result<(serde_json::Value, Vec<String>), String> {
+    let doc = ImDocument::parse(content)
+        .map_err(|e| format!("{}: {}", path.display(), e.to_string().trim_end()))?;
+    let json = schema::to_json(&doc);
+    let located = |key: &[String]| match schema::line_of(&doc, key) {
+        Some(line) => format!("{}:{}", path.display(), line),
+        None => path.display().to_string(),
+    };
+    let config: Config = serde_path_to_error::deserialize(&json).map_err(|e| {
+        let key: Vec<String> = e
+            .path()
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Map { key } => Some(key.clone()),
+                Segment::Seq { index } => Some(index.to_string()),
+                _ => None,
+            })
+            .collect();
+        format!(
+            "{}: `{}`: {}",
+            located(&key),
+            schema::dotted(&key),
+            e.inner()
+        )
+    })?;
+    let typed = serde_json::to_value(&config).unwrap_or_default();
+    let mut unknown = schema::unknown_keys(&json, &typed);
+    unknown.sort_by_key(|key| schema::line_of(&doc, key));
+    let warnings = unknown
+        .iter()
+        .map(|key| {
+            format!(
+                "{}: unknown setting `{}`",
+                located(key),
+                schema::dotted(key)
+            )
+        })
+        .collect();
+    Ok((json, warnings))
+}
+
+/// Check the team, config, and project files: the unknown-key warnings, or the first error.
+pub fn validate() -> Result<Vec<String>, String> {
+    let mut warnings = Vec::new();
+    for path in sources() {
+        if let Ok(content) = fs::read_to_string(&path) {
+            warnings.extend(check(&path, &content)?.1);
+        }
+    }
+    Ok(warnings)
+}
+
+/// Typed settings, with the project's `.zcode.toml` over the global config over the team config
+/// (tables merge key by key). A missing or invalid file contributes nothing (see `validate`),
+/// and the project's commands, endpoints, and keys, and the team's commands, only once that
+/// file is trusted (see `trust`).
+pub fn load() -> Config {
+    let mut merged = serde_json::Value::Object(Default::default());
+    for path in sources() {
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        if let Ok((mut json, _)) = check(&path, &content) {
+            trust::apply(&path, &mut json);
+            schema::merge(&mut merged, json);
+        }
+    }
+    serde_json::from_value(merged).unwrap_or_default()
+}
+
+/// Hex SHA-256 of `body`.
+fn sha256_hex(body: &str) -> String {
+    Sha256::digest(body.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The model spec a `[models]` alias names, else `spec` itself.
+pub fn resolve_model(spec: &str) -> String {
+    load()
+        .models
+        .remove(spec)
+        .unwrap_or_else(|| spec.to_string())
+}
+
+/// Project settings file in the workspace root (see `zcode init`).
+pub const PROJECT_FILE: &str = ".zcode.toml";
+
+/// Set `key` (dotted for tables) and write the config back, keeping comments and layout.
+/// Values that look like booleans or numbers are stored unquoted.
+pub fn set(key: &str, value: &str) -> Result<(), String> {
+    let path = config_path().ok_or("No config directory")?;
+    let content = fs::read_to_string(&path).unwrap_or_default();
+    let mut doc: DocumentMut = content
+        .parse()
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let segments = split_key(key);
+    let (last, parents) = segments.split_last().ok_or("Empty key")?;
+    if segments.iter().any(|s| s.is_empty()) {
+        return Err(format!("Invalid key: {}", key));
+    }
+    let mut table = doc.as_table_mut() as &mut dyn toml_edit::TableLike;
+    for segment in parents {
+        let entry = table.entry(segment).or_insert_with(|| {
+            let mut t = Table::new();
+            t.set_implicit(true);
+            Item::Table(t)
+        });
+        table = entry
+            .as_table_like_mut()
+            .ok_or_else(|| format!("{} is not a table", segment))?;
+    }
+    table.insert(last, Item::Value(parse_value(value)));
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    fs::write(&path, doc.to_string()).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn parse_value(raw: &str) -> Value {
+    if let Ok(b) = raw.parse::<bool>() {
+        return b.into();
+    }
+    if let Ok(i) = raw.parse::<i64>() {
+        return i.into();
+    }
+    if let Ok(f) = raw.parse::<f64>() {
+        return f.into();
+    }
+    raw.into()
+}
+
+/// Every leaf setting as `(dotted key, value)`, in file order.
+pub fn entries() -> Result<Vec<(String, String)>, String> {
+    let Some(path) = config_path() else {
+        return Ok(Vec::new());
+    };
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Ok(Vec::new());
+    };
+    let doc: DocumentMut = content
+        .parse()
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut out = Vec::new();
+    collect(doc.as_table(), "", &mut out);
+    Ok(out)
+}
+
+fn collect(table: &dyn toml_edit::TableLike, prefix: &str, out: &mut Vec<(String, String)>) {
+    for (k, item) in table.iter() {
+        let key = if k.contains('.') {
+            format!("\"{}\"", k)
+        } else {
+            k.to_string()
+        };
+        let key = if prefix.is_empty() {
+            key
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match item {
+            Item::Value(v) => match v.as_inline_table() {
+                Some(t) => collect(t, &key, out),
+                None => out.push((key, display_value(v))),
+            },
+            Item::Table(t) => collect(t, &key, out),
+            _ => {}
+        }
+    }
+}
+
+/// The config may hold an API key: make it readable only by the owner.
+pub fn restrict_permissions(path: &Path) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o600));
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+pub fn load_api_key() -> Option<String> {
+    const ENV_VAR: &str = "OPENAI_API_KEY";
+
+    let config = load();
+    std::env::var(ENV_VAR)
+        .ok()
+        .or(config.openai_api_key)
+        .or(config.api_key)
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    directories::ProjectDirs::from("dev", "zcode", "zcode").map(|d| d.config_dir().to_path_buf())
+}
+
+pub fn data_dir() -> Option<PathBuf> {
+    directories::ProjectDirs::from("dev", "zcode", "zcode").map(|d| d.data_dir().to_path_buf())
+}
+
+/// Replace the file at `path` with `contents` so a crash leaves either the old file or the new
+/// one: write a sibling temporary file, sync it, and rename it over `path`.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+    let write = || {
+        let mut file = fs::File::create(&tmp)?;
+        std::io::Write::write_all(&mut file, contents.as_ref())?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    };
+    write().inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_atomic_replaces_the_file_and_leaves_no_temporary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        fs::write(&path, "old").unwrap();
+        write_atomic(&path, "new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn parse_reads_legacy_keys_and_warns_about_unknown_ones() {
+        let path = Path::new(".zcode.toml");
+        let content = "show_usage = true\nmodle = \"gpt-4o\"\n[ui]\ntheme = \"light\"\n";
+        let (config, warnings) = parse(path, content).unwrap();
+        assert_eq!(config.ui.show_usage, Some(true));
+        assert_eq!(config.ui.theme.as_deref(), Some("light"));
+        assert_eq!(warnings, vec![".zcode.toml:2: unknown setting `modle`"]);
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_key() {
+        let err = parse(
+            Path::new("config.toml"),
+            "planner_model = \"a\"\nmax_turns = \"many\"\n",
+        )
+        .unwrap_err();
+        assert!(err.starts_with("config.toml:2: `max_turns`:"), "{}", err);
+    }
+}
@@ -0,0 +1,128 @@
+//! Shared team settings at `config_url`: fetched over HTTPS at startup (a conditional request
+//! with the cached ETag), checked against `config_sha256` when it is set, and cached so runs
+//! keep working offline. They apply under the local config and `.zcode.toml`, their commands once
+//! trusted (see `trust`). `config_url` and `config_sha256` are read from the global config only,
+//! so a workspace can't choose its own team config.
+
+use super::check;
+use crate::http;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::warn;
+
+/// The cached team config and its URL, once `refresh` has accepted it.
+static ACTIVE: OnceLock<(PathBuf, String)> = OnceLock::new();
+
+pub(super) fn active() -> Option<PathBuf> {
+    ACTIVE.get().map(|(path, _)| path.clone())
+}
+
+pub(super) fn url() -> Option<String> {
+    ACTIVE.get().map(|(_, url)| url.clone())
+}
+
+/// The global config alone, where `config_url` and `config_sha256` are read from.
+fn global() -> super::Config {
+    super::config_path()
+        .and_then(|path| {
+            let content = fs::read_to_string(&path).ok()?;
+            super::parse(&path, &content).ok()
+        })
+        .map(|(config, _)| config)
+        .unwrap_or_default()
+}
+
+fn cache_path() -> Option<PathBuf> {
+    super::data_dir().map(|d| d.join("team-config.toml"))
+}
+
+/// Fetch the team config named by `config_url`, if any, and layer it under the local files.
+/// An unreachable server falls back to the cached copy; an invalid or mismatching file is an
+/// error.
+pub async fn refresh() -> Result<(), String> {
+    let local = global();
+    let Some(url) = local.config_url else {
+        return Ok(());
+    };
+    let parsed = reqwest::Url::parse(&url).map_err(|e| format!("config_url: {}: {}", url, e))?;
+    let loopback = matches!(parsed.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"));
+    if parsed.scheme() != "https" && !(parsed.scheme() == "http" && loopback) {
+        return Err(format!("config_url: {}: must be an https:// URL", url));
+    }
+    let cache = cache_path().ok_or("No data directory for the team config")?;
+    let etag_path = cache.with_extension("etag");
+    let etag = cache
+        .is_file()
+        .then(|| fs::read_to_string(&etag_path).ok())
+        .flatten();
+    match fetch(&url, etag.as_deref()).await {
+        Ok(None) => {}
+        Ok(Some((body, etag))) => {
+            verify(&url, &body, local.config_sha256.as_deref())?;
+            check(Path::new(&url), &body)?;
+            if let Some(dir) = cache.parent() {
+                fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+            }
+            fs::write(&cache, &body).map_err(|e| format!("{}: {}", cache.display(), e))?;
+            match etag {
+                Some(etag) => fs::write(&etag_path, etag).map_err(|e| e.to_string())?,
+                None => {
+                    let _ = fs::remove_file(&etag_path);
+                }
+            }
+        }
+        Err(e) if cache.is_file() => warn!(%url, error = %e, "using the cached team config"),
+        Err(e) => return Err(format!("config_url: {}: {}", url, e)),
+    }
+    // The pin may have changed since the cached copy was fetched.
+    let body = fs::read_to_string(&cache).map_err(|e| format!("{}: {}", cache.display(), e))?;
+    verify(&url, &body, local.config_sha256.as_deref())?;
+    let _ = ACTIVE.set((cache, url));
+    Ok(())
+}
+
+/// The new body and its ETag, or None when the server says the cached copy is current.
+async fn fetch(url: &str, etag: Option<&str>) -> Result<Option<(String, Option<String>)>, String> {
+    // Not the shared client: that one is built once the team's `[http]` and `[proxy]` apply.
+    let client = http::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut request = client.get(url);
+    if let Some(etag) = etag {
+        request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+    }
+    let res = request.send().await.map_err(|e| e.to_string())?;
+    if res.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    if !res.status().is_success() {
+        return Err(res.status().to_string());
+    }
+    let etag = res
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    let body = res.text().await.map_err(|e| e.to_string())?;
+    Ok(Some((body, etag)))
+}
+
+/// Check `body` against the pinned SHA-256 (hex), if any.
+fn verify(url: &str, body: &str, pinned: Option<&str>) -> Result<(), String> {
+    let Some(pinned) = pinned else {
+        return Ok(());
+    };
+    let digest = super::sha256_hex(body);
+    if !digest.eq_ignore_ascii_case(pinned.trim()) {
+        return Err(format!(
+            "config_url: {}: SHA-256 is {}, expected {} (config_sha256)",
+            url,
+            digest,
+            pinned.trim()
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,447 @@
+//! The typed shape of the config file, and the checks behind `config::validate`: type errors
+//! and unknown keys, each with the line it comes from.
+
+use crate::tokens::Tokenizer;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as Json};
+use std::collections::BTreeMap;
+use toml_edit::{ImDocument, Item, Value};
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    pub api_key: Option<String>,
+    #[serde(rename = "OPENAI_API_KEY")]
+    pub openai_api_key: Option<String>,
+    pub github_token: Option<String>,
+    pub planner_model: Option<String>,
+    pub executor_model: Option<String>,
+    /// `single` or `multi-agent`.
+    pub strategy: Option<String>,
+    pub auto_commit: Option<bool>,
+    pub critic: Option<bool>,
+    pub parallel: Option<bool>,
+    pub review_plan: Option<bool>,
+    pub save_report: Option<bool>,
+    pub plan_cache: Option<bool>,
+    pub response_cache: Option<bool>,
+    pub dataset_log: Option<bool>,
+    pub context_tokens: Option<usize>,
+    pub repo_map_tokens: Option<usize>,
+    pub verify: Option<StringList>,
+    pub exclude: Option<StringList>,
+    pub webhook_url: Option<String>,
+    pub slack_webhook_url: Option<String>,
+    pub discord_webhook_url: Option<String>,
+    /// Team settings to fetch and apply under the local ones (see `config::remote`).
+    pub config_url: Option<String>,
+    /// Hex SHA-256 the file at `config_url` must have.
+    pub config_sha256: Option<String>,
+    /// Default for `--max-cost`.
+    pub max_cost: Option<f64>,
+    /// Default for `--max-turns`.
+    pub max_turns: Option<usize>,
+    /// Model specs that may be used; `*` matches any text.
+    pub allowed_models: Option<Vec<String>>,
+    /// Short names for model specs (`fast = "ollama:llama3.1"`), usable wherever a model is given.
+    pub models: BTreeMap<String, String>,
+    pub providers: BTreeMap<String, Provider>,
+    /// `"input,cached,output"` USD per million tokens, by model.
+    pub price: BTreeMap<String, String>,
+    /// `o200k`, `cl100k`, or `bytes`, by model or provider pattern (`*` matches any text); the
+    /// longest matching pattern wins.
+    pub tokenizers: BTreeMap<String, Tokenizer>,
+    pub routing: BTreeMap<String, Route>,
+    pub roles: BTreeMap<String, RoleConfig>,
+    pub hooks: BTreeMap<String, Hook>,
+    pub ui: Ui,
+    pub tools: Tools,
+    pub proxy: ProxyConfig,
+    pub http: HttpConfig,
+    pub index: IndexConfig,
+}
+
+/// A command or list of commands; a single string is a one-item list.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum StringList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl StringList {
+    pub fn to_vec(&self) -> Vec<String> {
+        match self {
+            StringList::One(s) => vec![s.clone()],
+            StringList::Many(items) => items.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Provider {
+    pub base_url: Option<String>,
+    pub api_key: Option<String>,
+    /// Sent with every request, on top of the bearer key.
+    pub headers: BTreeMap<String, String>,
+    /// PEM client certificate for mTLS; may hold the private key too.
+    pub client_cert: Option<String>,
+    /// PEM private key, when it is not in `client_cert`.
+    pub client_key: Option<String>,
+    /// PEM CA certificate to trust in addition to the built-in roots.
+    pub ca_cert: Option<String>,
+}
+
+/// A model for a whole phase, or per kind of turn (`tools`, `prose`).
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Route {
+    Model(String),
+    ByTurn(BTreeMap<String, String>),
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RoleConfig {
+    pub model: Option<String>,
+    pub prompt: Option<String>,
+}
+
+/// Lifecycle hooks are commands; tool hooks map tool names (or `*`) to commands.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Hook {
+    Commands(StringList),
+    ByTool(BTreeMap<String, StringList>),
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Ui {
+    /// `dark` or `light`.
+    pub theme: Option<String>,
+    pub colors: Colors,
+    pub show_usage: Option<bool>,
+    pub notify: Option<NotifyMode>,
+    pub notify_after: Option<u64>,
+    pub editor_url: Option<String>,
+}
+
+/// Color overrides for the theme; each is a color name or `#rrggbb`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Colors {
+    pub accent: Option<String>,
+    pub success: Option<String>,
+    pub tool: Option<String>,
+    pub error: Option<String>,
+    pub text: Option<String>,
+    pub dim: Option<String>,
+    pub diff_add: Option<String>,
+    pub diff_remove: Option<String>,
+    pub diff_hunk: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyMode {
+    Bell,
+    Desktop,
+    Both,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Tools {
+    /// Tools the model may use (the default for `--allow-tools`).
+    pub allow: Option<Vec<String>>,
+    /// Commands `run_command` may run; `*` matches any text.
+    pub allow_commands: Option<Vec<String>>,
+}
+
+/// Explicit proxies; without any, `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY`, and `NO_PROXY` apply.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ProxyConfig {
+    /// For all requests: `http://`, `https://`, `socks5://`, or `socks5h://` (DNS via the proxy).
+    pub url: Option<String>,
+    pub http: Option<String>,
+    pub https: Option<String>,
+    /// Hosts, domains (`.corp.example`), and CIDR ranges to reach directly.
+    pub no_proxy: Option<StringList>,
+}
+
+/// Connection pool for the shared client; times are in seconds.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Most idle connections kept per host.
+    pub pool_max_idle_per_host: Option<usize>,
+    /// How long an idle connection is kept.
+    pub pool_idle_timeout: Option<u64>,
+    /// TCP keepalive interval for open connections.
+    pub tcp_keepalive: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct IndexConfig {
+    /// Embedder spec: a model id, `<provider>:<model>`, `gemini:<model>`, `local[:<model>]`, or `hashed`.
+    pub model: Option<String>,
+    pub chunking: Option<Chunking>,
+    pub chunk_lines: Option<usize>,
+    pub chunk_overlap: Option<usize>,
+    /// Chunking by language name (`rust`) or file extension.
+    pub languages: BTreeMap<String, LanguageChunking>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LanguageChunking {
+    pub chunking: Option<Chunking>,
+    pub chunk_lines: Option<usize>,
+    pub chunk_overlap: Option<usize>,
+}
+
+/// How files are split for the semantic index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Chunking {
+    /// At function, type, and statement boundaries.
+    Syntax,
+    /// Fixed windows of lines.
+    Lines,
+}
+
+/// Where a top-level setting from before the `[ui]` table lives now.
+pub(super) fn moved(key: &str) -> Option<Vec<String>> {
+    if let Some(color) = key.strip_prefix("theme_") {
+        return Some(vec!["ui".into(), "colors".into(), color.into()]);
+    }
+    matches!(
+        key,
+        "theme" | "show_usage" | "notify" | "notify_after" | "editor_url"
+    )
+    .then(|| vec!["ui".into(), key.into()])
+}
+
+/// The top-level key a path under `[ui]` had before the table existed.
+fn legacy(path: &[String]) -> Option<Vec<String>> {
+    match path {
+        [ui, colors, color, rest @ ..] if ui == "ui" && colors == "colors" => {
+            Some([vec![format!("theme_{}", color)], rest.to_vec()].concat())
+        }
+        [ui, rest @ ..] if ui == "ui" && !rest.is_empty() => Some(rest.to_vec()),
+        _ => None,
+    }
+}
+
+/// The document as JSON, with top-level settings moved to their tables (a value already in
+/// the table wins).
+pub(super) fn to_json(doc: &ImDocument<&str>) -> Json {
+    let mut json = item_json(doc.as_item());
+    let Json::Object(root) = &mut json else {
+        return json;
+    };
+    let keys: Vec<String> = root.keys().cloned().collect();
+    for key in keys {
+        let Some(path) = moved(&key) else { continue };
+        let value = root.remove(&key).unwrap_or_default();
+        insert_missing(root, &path, value);
+    }
+    json
+}
+
+fn insert_missing(table: &mut Map<String, Json>, path: &[String], value: Json) {
+    match path {
+        [] => {}
+        [last] => {
+            table.entry(last.clone()).or_insert(value);
+        }
+        [first, rest @ ..] => {
+            if let Json::Object(sub) = table
+                .entry(first.clone())
+                .or_insert_with(|| Json::Object(Map::new()))
+            {
+                insert_missing(sub, rest, value);
+            }
+        }
+    }
+}
+
+/// Lay `over` on top of `base`: tables merge key by key, anything else is replaced.
+pub(super) fn merge(base: &mut Json, over: Json) {
+    match (base, over) {
+        (Json::Object(base), Json::Object(over)) => {
+            for (key, value) in over {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, over) => *base = over,
+    }
+}
+
+fn item_json(item: &Item) -> Json {
+    match item {
+        Item::Value(v) => value_json(v),
+        Item::Table(t) => Json::Object(
+            t.iter()
+                .map(|(k, v)| (k.to_string(), item_json(v)))
+                .collect(),
+        ),
+        Item::ArrayOfTables(a) => Json::Array(
+            a.iter()
+                .map(|t| {
+                    Json::Object(
+                        t.iter()
+                            .map(|(k, v)| (k.to_string(), item_json(v)))
+                            .collect(),
+                    )
+                })
+                .collect(),
+        ),
+        Item::None => Json::Null,
+    }
+}
+
+fn value_json(value: &Value) -> Json {
+    match value {
+        Value::String(s) => Json::String(s.value().clone()),
+        Value::Integer(i) => Json::from(*i.value()),
+        Value::Float(f) => {
+            serde_json::Number::from_f64(*f.value()).map_or(Json::Null, Json::Number)
+        }
+        Value::Boolean(b) => Json::Bool(*b.value()),
+        Value::Datetime(d) => Json::String(d.value().to_string()),
+        Value::Array(items) => Json::Array(items.iter().map(value_json).collect()),
+        Value::InlineTable(t) => Json::Object(
+            t.iter()
+                .map(|(k, v)| (k.to_string(), value_json(v)))
+                .collect(),
+        ),
+    }
+}
+
+/// Paths present in `input` that the typed config dropped.
+pub(super) fn unknown_keys(input: &Json, typed: &Json) -> Vec<Vec<String>> {
+    let mut out = Vec::new();
+    collect_unknown(input, typed, &mut Vec::new(), &mut out);
+    out
+}
+
+fn collect_unknown(input: &Json, typed: &Json, path: &mut Vec<String>, out: &mut Vec<Vec<String>>) {
+    let (Json::Object(input), Json::Object(typed)) = (input, typed) else {
+        return;
+    };
+    for (key, value) in input {
+        path.push(key.clone());
+        match typed.get(key) {
+            Some(known) => collect_unknown(value, known, path, out),
+            None => out.push(path.clone()),
+        }
+        path.pop();
+    }
+}
+
+/// 1-based line of the key at `path`, looking under its old top-level name too.
+pub(super) fn line_of(doc: &ImDocument<&str>, path: &[String]) -> Option<usize> {
+    let span = key_span(doc.as_item(), path).or_else(|| key_span(doc.as_item(), &legacy(path)?))?;
+    Some(doc.raw()[..span].matches('\n').count() + 1)
+}
+
+fn key_span(item: &Item, path: &[String]) -> Option<usize> {
+    let (first, rest) = path.split_first()?;
+    let (key, value) = item.as_table_like()?.get_key_value(first)?;
+    if rest.is_empty() {
+        return key.span().map(|s| s.start);
+    }
+    key_span(value, rest).or_else(|| key.span().map(|s| s.start))
+}
+
+/// `a.b."c.d"` for messages.
+pub(super) fn dotted(path: &[String]) -> String {
+    path.iter()
+        .map(|s| {
+            if s.contains('.') {
+                format!("\"{}\"", s)
+            } else {
+                s.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn path(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|k| k.to_string()).collect()
+    }
+
+    #[test]
+    fn merge_overlays_tables_key_by_key() {
+        let mut base =
+            json!({"model": "a", "ui": {"theme": "dark", "notify": true}, "verify": ["make"]});
+        merge(
+            &mut base,
+            json!({"ui": {"theme": "light"}, "verify": ["cargo test"], "max_turns": 5}),
+        );
+        assert_eq!(
+            base,
+            json!({"model": "a", "ui": {"theme": "light", "notify": true}, "verify": ["cargo test"], "max_turns": 5})
+        );
+    }
+
+    #[test]
+    fn merge_replaces_values_of_another_kind() {
+        let mut base = json!({"hooks": {"after_edit": ["fmt"]}});
+        merge(&mut base, json!({"hooks": "off"}));
+        assert_eq!(base, json!({"hooks": "off"}));
+    }
+
+    #[test]
+    fn unknown_keys_are_reported_with_their_path() {
+        let input = json!({"model": "a", "typo": 1, "ui": {"theme": "dark", "colour": "red"}});
+        let typed = json!({"model": "a", "ui": {"theme": "dark"}});
+        assert_eq!(
+            unknown_keys(&input, &typed),
+            vec![path(&["typo"]), path(&["ui", "colour"])]
+        );
+        assert!(unknown_keys(&typed, &typed).is_empty());
+    }
+
+    #[test]
+    fn legacy_keys_move_under_ui() {
+        let doc =
+            ImDocument::parse("theme = \"light\"\nshow_usage = true\ntheme_error = \"red\"\n")
+                .unwrap();
+        assert_eq!(
+            to_json(&doc),
+            json!({"ui": {"theme": "light", "show_usage": true, "colors": {"error": "red"}}})
+        );
+        assert_eq!(moved("model"), None);
+    }
+
+    #[test]
+    fn the_table_wins_over_a_legacy_key() {
+        let doc = ImDocument::parse("theme = \"light\"\n[ui]\ntheme = \"dark\"\n").unwrap();
+        assert_eq!(to_json(&doc), json!({"ui": {"theme": "dark"}}));
+    }
+
+    #[test]
+    fn moved_keys_are_found_on_their_old_line() {
+        let doc = ImDocument::parse("model = \"a\"\ntheme_error = \"red\"\n").unwrap();
+        assert_eq!(line_of(&doc, &path(&["ui", "colors", "error"])), Some(2));
+        assert_eq!(line_of(&doc, &path(&["model"])), Some(1));
+        assert_eq!(line_of(&doc, &path(&["missing"])), None);
+    }
+}
@@ -0,0 +1,169 @@
+//! Context blocks attached to prompts (e.g. `@path` mentions).
+
+use crate::events::{self, Event};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+/// Max bytes attached per mentioned file.
+pub(crate) const MENTION_FILE_CAP: usize = 32 * 1024;
+/// Max files attached from one mentioned directory.
+const MENTION_DIR_FILES: usize = 20;
+
+/// Paths mentioned as `@path` tokens in the input.
+pub fn mentions(input: &str) -> Vec<String> {
+    input
+        .split_whitespace()
+        .filter_map(|w| w.strip_prefix('@'))
+        .map(|w| w.trim_end_matches([',', '.', ';', ':', ')']))
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_string())
+        .collect()
+}
+
+/// Read a file as a context block, truncated to `MENTION_FILE_CAP` bytes.
+pub fn file_block(workspace: &Path, path: &str) -> Result<String, String> {
+    let content = fs::read_to_string(workspace.join(path)).map_err(|e| e.to_string())?;
+    Ok(format!(
+        "--- {} ---\n{}",
+        path,
+        truncate(&content, MENTION_FILE_CAP)
+    ))
+}
+
+pub(crate) fn truncate(content: &str, cap: usize) -> String {
+    if content.len() <= cap {
+        return content.to_string();
+    }
+    let mut end = cap;
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "{}\n… (truncated, {} bytes total)",
+        &content[..end],
+        content.len()
+    )
+}
+
+fn dir_blocks(workspace: &Path, dir: &str) -> Result<Vec<String>, String> {
+    let entries = fs::read_dir(workspace.join(dir)).map_err(|e| e.to_string())?;
+    let mut files: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().map(|t| t.is_file()).unwrap_or(false))
+        .map(|e| {
+            Path::new(dir)
+                .join(e.file_name())
+                .to_string_lossy()
+                .to_string()
+        })
+        .collect();
+    files.sort();
+    Ok(files
+        .iter()
+        .take(MENTION_DIR_FILES)
+        .filter_map(|f| file_block(workspace, f).ok())
+        .collect())
+}
+
+/// Project instructions file included with every task (see `zcode init`).
+pub const INSTRUCTIONS_FILE: &str = "ZCODE.md";
+
+/// Contents of the workspace's instructions file, if present.
+pub fn project_instructions(workspace: &Path) -> Option<String> {
+    let content = fs::read_to_string(workspace.join(INSTRUCTIONS_FILE)).ok()?;
+    (!content.trim().is_empty()).then(|| truncate(&content, MENTION_FILE_CAP))
+}
+
+/// Workspace files (relative paths), respecting .gitignore.
+pub fn workspace_files(workspace: &Path) -> Vec<String> {
+    let mut files: Vec<String> = ignore::WalkBuilder::new(workspace)
+        .build()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().map(|t| t.is_file()).unwrap_or(false))
+        .filter_map(|e| {
+            e.path()
+                .strip_prefix(workspace)
+                .ok()
+                .map(|p| p.to_string_lossy().to_string())
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Context blocks for pinned files, re-read on every call. Files whose contents differ from
+/// the hash in `seen` (the last version sent) are flagged as changed; `seen` is updated.
+pub fn pinned_blocks(
+    workspace: &Path,
+    pinned: &[String],
+    seen: &mut HashMap<String, u64>,
+) -> Vec<String> {
+    pinned
+        .iter()
+        .map(|p| match fs::read_to_string(workspace.join(p)) {
+            Ok(content) => {
+                let hash = content_hash(&content);
+                let changed = seen.insert(p.clone(), hash).is_some_and(|old| old != hash);
+                let mark = if changed {
+                    " (changed since the last prompt)"
+                } else {
+                    ""
+                };
+                format!(
+                    "--- {}{} ---\n{}",
+                    p,
+                    mark,
+                    truncate(&content, MENTION_FILE_CAP)
+                )
+            }
+            Err(e) => format!("--- {} ---\n(unreadable: {})", p, e),
+        })
+        .collect()
+}
+
+/// Record the current contents of `paths` as sent, without building blocks.
+pub fn mark_seen(workspace: &Path, paths: &[String], seen: &mut HashMap<String, u64>) {
+    for p in paths {
+        if let Ok(content) = fs::read_to_string(workspace.join(p)) {
+            seen.insert(p.clone(), content_hash(&content));
+        }
+    }
+}
+
+fn content_hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Mentioned paths that are files, which the REPL pins for later prompts.
+pub fn mentioned_files(input: &str, workspace: &Path) -> Vec<String> {
+    mentions(input)
+        .into_iter()
+        .filter(|p| workspace.join(p).is_file())
+        .collect()
+}
+
+/// Append the contents of every `@path` mentioned in `input` as context blocks.
+pub fn expand_mentions(input: &str, workspace: &Path) -> String {
+    let mut blocks = Vec::new();
+    for path in mentions(input) {
+        let result = if workspace.join(&path).is_dir() {
+            dir_blocks(workspace, &path)
+        } else {
+            file_block(workspace, &path).map(|b| vec![b])
+        };
+        events::emit(Event::FileRead {
+            path: &path,
+            error: result.as_ref().err().map(String::as_str),
+        });
+        blocks.extend(result.unwrap_or_default());
+    }
+    if blocks.is_empty() {
+        return input.to_string();
+    }
+    format!("{}\n\nAttached files:\n{}", input, blocks.join("\n\n"))
+}
@@ -0,0 +1,163 @@
+//! `zcode doctor`: check the environment and print a fix for each problem found.
+
+use crate::agent::{self, list_models, KeyError};
+use crate::config;
+use crate::plugins;
+use crate::run;
+use crate::ui;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Run every check. Returns false if any failed.
+pub async fn run(workspace: &Path) -> bool {
+    let mut ok = check_config();
+    let key = config::load_api_key();
+    ok &= report(
+        key.is_some(),
+        "API key",
+        "set OPENAI_API_KEY or run `zcode init`",
+    );
+    if let Some(key) = &key {
+        ok &= check_provider(key).await;
+    }
+    ok &= check_git(workspace);
+    ok &= report(
+        Command::new("sh")
+            .arg("-c")
+            .arg("true")
+            .status()
+            .is_ok_and(|s| s.success()),
+        "Shell (sh) for run_command",
+        "install a POSIX shell and make sure `sh` is on PATH",
+    );
+    ok &= report(
+        is_writable(workspace),
+        &format!("Workspace writable ({})", workspace.display()),
+        "check directory permissions or pass -C <dir>",
+    );
+    check_plugins();
+    ok
+}
+
+fn report(passed: bool, label: &str, fix: &str) -> bool {
+    ui::check(passed, label, (!passed).then_some(fix));
+    passed
+}
+
+/// Plugin tools are optional, so a broken one is listed in the log rather than failing the run.
+fn check_plugins() {
+    let Some(dir) = plugins::dir().filter(|d| d.is_dir()) else {
+        return;
+    };
+    let names: Vec<&str> = plugins::all().iter().map(|p| p.name.as_str()).collect();
+    let label = if names.is_empty() {
+        format!(
+            "Plugin tools in {} (none loaded; see the log for why)",
+            dir.display()
+        )
+    } else {
+        format!("Plugin tools in {}: {}", dir.display(), names.join(", "))
+    };
+    ui::check(true, &label, None);
+}
+
+fn check_config() -> bool {
+    let Some(path) = config::config_path() else {
+        return report(
+            false,
+            "Config directory",
+            "set HOME so the config directory can be found",
+        );
+    };
+    if !path.is_file() {
+        ui::check(
+            true,
+            &format!("Config ({} not found, using defaults)", path.display()),
+            None,
+        );
+        return true;
+    }
+    match config::validate() {
+        Ok(warnings) if warnings.is_empty() => {
+            report(true, &format!("Config parses ({})", path.display()), "")
+        }
+        Ok(warnings) => report(
+            false,
+            &format!("Config has no unknown settings ({})", path.display()),
+            &format!("remove or rename: {}", warnings.join("; ")),
+        ),
+        Err(e) => report(
+            false,
+            &format!("Config parses ({})", path.display()),
+            &format!("fix: {}", e),
+        ),
+    }
+}
+
+async fn check_provider(key: &str) -> bool {
+    let models = match list_models(key).await {
+        Ok(models) => models,
+        Err(e) => {
+            let label = match e {
+                KeyError::Network(_) => "OpenAI reachable",
+                _ => "OpenAI API key accepted",
+            };
+            return report(false, label, &e.to_string());
+        }
+    };
+    report(true, "OpenAI reachable, key accepted", "");
+
+    let mut ok = true;
+    for spec in [run::planner_model(), run::executor_model()] {
+        if !agent::is_openai(&spec) {
+            ui::check(
+                true,
+                &format!("Model {} is not served by OpenAI (not checked)", spec),
+                None,
+            );
+            continue;
+        }
+        let model = spec.strip_prefix("openai:").unwrap_or(&spec);
+        ok &= report(
+            models.iter().any(|m| m == model),
+            &format!("Model {} available", model),
+            "this key's project has no access to the model; enable it in the OpenAI dashboard",
+        );
+    }
+    ok
+}
+
+fn check_git(workspace: &Path) -> bool {
+    let installed = Command::new("git")
+        .arg("--version")
+        .output()
+        .is_ok_and(|o| o.status.success());
+    if !report(
+        installed,
+        "git installed",
+        "install git to enable diffs against the repository",
+    ) {
+        return false;
+    }
+    let in_repo = Command::new("git")
+        .args(["rev-parse", "--is-inside-work-tree"])
+        .current_dir(workspace)
+        .output()
+        .is_ok_and(|o| o.status.success());
+    if !in_repo {
+        ui::check(
+            true,
+            "Workspace is not a git repository (run `git init` to track changes)",
+            None,
+        );
+    }
+    true
+}
+
+fn is_writable(dir: &Path) -> bool {
+    let probe = dir.join(".zcode-doctor");
+    let ok = fs::write(&probe, b"").is_ok();
+    let _ = fs::remove_file(&probe);
+    ok
+}
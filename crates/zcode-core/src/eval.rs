@@ -0,0 +1,388 @@
+//! `zcode eval suite.yaml`: run task prompts against throwaway copies of fixture repos, check
+//! the results, and compare pass rates across model configurations.
+//!
+//! ```yaml
+//! configs:                     # optional; default: the configured models
+//!   - name: mini
+//!     executor_model: gpt-4o-mini
+//!   - name: local
+//!     planner_model: ollama:qwen2.5-coder
+//!     executor_model: ollama:qwen2.5-coder
+//! runs: 3                      # optional; runs of each task per config
+//! tasks:
+//!   - name: greet
+//!     repo: fixtures/hello     # copied for each run; relative to the suite file
+//!     setup: [git init -q]     # optional commands run in the copy first
+//!     prompt: Add a greet(name) function to src/lib.rs with a test
+//!     checks:
+//!       - exists: src/lib.rs
+//!       - command: cargo test
+//!       - diff: "+*fn greet("
+//!       - no_diff: "*unwrap()*"
+//! ```
+
+use crate::options;
+use crate::run::{self, Agents};
+use crate::tools::Executor;
+use crate::ui;
+use crate::usage;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Suite {
+    #[serde(default)]
+    configs: Vec<ConfigSpec>,
+    #[serde(default = "one")]
+    runs: usize,
+    tasks: Vec<TaskSpec>,
+}
+
+fn one() -> usize {
+    1
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigSpec {
+    name: Option<String>,
+    planner_model: Option<String>,
+    executor_model: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskSpec {
+    name: Option<String>,
+    prompt: String,
+    repo: Option<PathBuf>,
+    #[serde(default)]
+    setup: Vec<String>,
+    #[serde(default)]
+    checks: Vec<Check>,
+}
+
+/// What a finished run must satisfy. Diff patterns match any part of a changed line
+/// (including its leading `+` or `-`), with `*` standing for any text.
+#[derive(Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+enum Check {
+    /// The file exists afterwards.
+    Exists { exists: String },
+    /// The file does not exist afterwards.
+    Missing { missing: String },
+    /// The shell command exits 0 in the workspace.
+    Command { command: String },
+    /// Some changed line matches.
+    Diff { diff: String },
+    /// No changed line matches.
+    NoDiff { no_diff: String },
+}
+
+impl Check {
+    fn label(&self) -> String {
+        match self {
+            Check::Exists { exists } => format!("exists: {}", exists),
+            Check::Missing { missing } => format!("missing: {}", missing),
+            Check::Command { command } => format!("command: {}", command),
+            Check::Diff { diff } => format!("diff: {}", diff),
+            Check::NoDiff { no_diff } => format!("no_diff: {}", no_diff),
+        }
+    }
+
+    /// Err with why the check failed.
+    fn run(&self, executor: &Executor, changed_lines: &[String]) -> Result<(), String> {
+        let workspace = executor.workspace();
+        match self {
+            Check::Exists { exists } if !workspace.join(exists).exists() => Err("not found".into()),
+            Check::Missing { missing } if workspace.join(missing).exists() => {
+                Err("still exists".into())
+            }
+            Check::Command { command } => {
+                let mut process = Command::new("sh");
+                process.args(["-c", command]);
+                let (status, stdout, stderr) = executor.run_process(process, Some(""))?;
+                if status.is_some_and(|s| s.success()) {
+                    return Ok(());
+                }
+                let output = format!("{}{}", stdout, stderr);
+                let tail: Vec<&str> = output.lines().rev().take(5).collect();
+                Err(tail.into_iter().rev().collect::<Vec<_>>().join("\n"))
+            }
+            Check::Diff { diff } if !changed_lines.iter().any(|l| wildcard_match(l, diff)) => {
+                Err("no changed line matches".into())
+            }
+            Check::NoDiff { no_diff } => {
+                match changed_lines.iter().find(|l| wildcard_match(l, no_diff)) {
+                    Some(line) => Err(format!("changed line matches: {}", line)),
+                    None => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Whether `pattern` occurs in `line`, `*` matching any text.
+fn wildcard_match(line: &str, pattern: &str) -> bool {
+    let mut rest = line;
+    for part in pattern.split('*') {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+#[derive(Serialize)]
+pub struct CheckResult {
+    pub check: String,
+    pub passed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RunResult {
+    pub config: String,
+    pub task: String,
+    pub run: usize,
+    /// The run finished and every check passed.
+    pub passed: bool,
+    /// The pipeline finished with a reply.
+    pub completed: bool,
+    pub checks: Vec<CheckResult>,
+    pub duration_secs: f64,
+    pub cost: f64,
+}
+
+#[derive(Serialize)]
+pub struct ConfigSummary {
+    pub name: String,
+    pub planner_model: String,
+    pub executor_model: String,
+    pub passed: usize,
+    pub total: usize,
+    pub pass_rate: f64,
+    pub cost: f64,
+    pub duration_secs: f64,
+}
+
+#[derive(Serialize)]
+pub struct EvalReport {
+    pub configs: Vec<ConfigSummary>,
+    pub runs: Vec<RunResult>,
+}
+
+/// Run the suite at `suite_path` and write the JSON report to `report_path`.
+pub async fn run(
+    api_key: &str,
+    suite_path: &Path,
+    report_path: &Path,
+    keep: bool,
+) -> Result<EvalReport, String> {
+    let text =
+        fs::read_to_string(suite_path).map_err(|e| format!("{}: {}", suite_path.display(), e))?;
+    let suite: Suite =
+        serde_yaml::from_str(&text).map_err(|e| format!("{}: {}", suite_path.display(), e))?;
+    if suite.tasks.is_empty() {
+        return Err(format!("{}: no tasks", suite_path.display()));
+    }
+    let base = suite_path.parent().unwrap_or(Path::new("."));
+    let configs = if suite.configs.is_empty() {
+        vec![ConfigSpec {
+            name: Some("default".into()),
+            planner_model: None,
+            executor_model: None,
+        }]
+    } else {
+        suite.configs
+    };
+    // Every run plans from scratch, so cached plans from another config don't skew results.
+    options::update(|o| o.plan_cache = false);
+
+    let mut summaries = Vec::new();
+    let mut results = Vec::new();
+    for (c, config) in configs.iter().enumerate() {
+        let name = config
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("config-{}", c + 1));
+        let planner = config
+            .planner_model
+            .clone()
+            .unwrap_or_else(run::planner_model);
+        let executor_model = config
+            .executor_model
+            .clone()
+            .unwrap_or_else(run::executor_model);
+        let agents = Agents::for_models(api_key, &planner, &executor_model)?;
+        let first = results.len();
+        for (t, task) in suite.tasks.iter().enumerate() {
+            let task_name = task
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("task-{}", t + 1));
+            for n in 1..=suite.runs {
+                ui::phase(&format!(
+                    "Eval {} — {} (run {}/{})",
+                    name, task_name, n, suite.runs
+                ));
+                let dir = std::env::temp_dir().join(format!(
+                    "zcode-eval-{}-{}-{}-{}",
+                    std::process::id(),
+                    c,
+                    t,
+                    n
+                ));
+                let result = run_task(&agents, task, base, &dir).await;
+                if !keep {
+                    let _ = fs::remove_dir_all(&dir);
+                }
+                let (completed, checks, duration_secs, cost) = result.unwrap_or_else(|e| {
+                    ui::error_msg(&e);
+                    (false, Vec::new(), 0.0, 0.0)
+                });
+                let passed = completed && checks.iter().all(|c| c.passed);
+                for check in &checks {
+                    ui::check(check.passed, &check.check, None);
+                    for line in check.detail.iter().flat_map(|d| d.lines()) {
+                        ui::note(&format!("    {}", line));
+                    }
+                }
+                results.push(RunResult {
+                    config: name.clone(),
+                    task: task_name.clone(),
+                    run: n,
+                    passed,
+                    completed,
+                    checks,
+                    duration_secs,
+                    cost,
+                });
+                outln!();
+            }
+        }
+        let runs = &results[first..];
+        let passed = runs.iter().filter(|r| r.passed).count();
+        summaries.push(ConfigSummary {
+            name,
+            planner_model: planner,
+            executor_model,
+            passed,
+            total: runs.len(),
+            pass_rate: passed as f64 / runs.len().max(1) as f64,
+            cost: runs.iter().map(|r| r.cost).sum(),
+            duration_secs: runs.iter().map(|r| r.duration_secs).sum(),
+        });
+    }
+
+    let report = EvalReport {
+        configs: summaries,
+        runs: results,
+    };
+    let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    fs::write(report_path, json).map_err(|e| format!("{}: {}", report_path.display(), e))?;
+    Ok(report)
+}
+
+/// Set up a copy of the task's repo in `dir`, run the prompt there, and check the result.
+/// Returns whether the run completed, the check results, its duration, and its cost.
+async fn run_task(
+    agents: &Agents,
+    task: &TaskSpec,
+    base: &Path,
+    dir: &Path,
+) -> Result<(bool, Vec<CheckResult>, f64, f64), String> {
+    let _ = fs::remove_dir_all(dir);
+    match &task.repo {
+        Some(repo) => copy_dir(&base.join(repo), dir)?,
+        None => fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?,
+    }
+    let executor = Executor::new(dir.to_path_buf());
+    for command in &task.setup {
+        let mut process = Command::new("sh");
+        process.args(["-c", command]);
+        let (status, _, stderr) = executor.run_process(process, Some(""))?;
+        if !status.is_some_and(|s| s.success()) {
+            return Err(format!(
+                "Setup command {} failed: {}",
+                command,
+                stderr.trim()
+            ));
+        }
+    }
+
+    let mark = usage::mark();
+    let started = Instant::now();
+    let reply = run::run_once(agents, &executor, &task.prompt).await;
+    let duration = started.elapsed().as_secs_f64();
+    let cost = usage::total_cost(usage::all().get(mark..).unwrap_or_default());
+
+    let changed_lines: Vec<String> = executor
+        .changes()
+        .iter()
+        .flat_map(|c| {
+            ui::plain_diff(c)
+                .lines()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .filter(|l| {
+            (l.starts_with('+') || l.starts_with('-'))
+                && !l.starts_with("+++")
+                && !l.starts_with("---")
+        })
+        .collect();
+    let checks = task
+        .checks
+        .iter()
+        .map(|check| {
+            let outcome = check.run(&executor, &changed_lines);
+            CheckResult {
+                check: check.label(),
+                passed: outcome.is_ok(),
+                detail: outcome.err(),
+            }
+        })
+        .collect();
+    Ok((reply.is_some(), checks, duration, cost))
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), String> {
+    fs::create_dir_all(to).map_err(|e| format!("{}: {}", to.display(), e))?;
+    for entry in fs::read_dir(from).map_err(|e| format!("{}: {}", from.display(), e))? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let target = to.join(entry.file_name());
+        if entry.path().is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)
+                .map_err(|e| format!("{}: {}", entry.path().display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Print pass rates per config.
+pub fn print_summary(report: &EvalReport) {
+    let rows: Vec<Vec<String>> = report
+        .configs
+        .iter()
+        .map(|c| {
+            vec![
+                c.name.clone(),
+                format!("{}/{}", c.passed, c.total),
+                format!("{:.0}%", c.pass_rate * 100.0),
+                format!("${:.4}", c.cost),
+                format!("{:.0}s", c.duration_secs),
+            ]
+        })
+        .collect();
+    ui::table(&["config", "passed", "rate", "cost", "time"], &rows);
+}
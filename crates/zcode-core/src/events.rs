@@ -0,0 +1,290 @@
+//! What the agent loop and pipeline report as they run. Every event goes to the observers
+//! added with `subscribe` (a terminal front end subscribes `ui::Terminal`) and, as JSON, to
+//! stdout with `--output-format json` or to the channel of the server session whose prompt is
+//! running. Events only a display needs (spinners, the todo list) are not sent as JSON.
+
+use crate::report::Report;
+use crate::tools::FileChange;
+use crate::ui::{self, TodoStatus};
+use crate::usage::Usage;
+use serde::Serialize;
+use std::future::Future;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// Consumes events, e.g. a library user's own front end.
+pub trait Observer: Send + Sync {
+    fn on_event(&self, event: &Event);
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// Receives events instead of stdout while set (the server session whose prompt is running).
+static SINK: Mutex<Option<broadcast::Sender<String>>> = Mutex::new(None);
+static OBSERVERS: Mutex<Vec<Arc<dyn Observer>>> = Mutex::new(Vec::new());
+
+/// Switch to JSON events; the human UI is silenced.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+    ui::set_quiet(true);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Send events to `sink` (one JSON string each) instead of stdout; `None` restores stdout.
+pub fn set_sink(sink: Option<broadcast::Sender<String>>) {
+    *SINK.lock().unwrap() = sink;
+}
+
+/// Pass every event from now on to `observer` as well.
+pub fn subscribe(observer: Arc<dyn Observer>) {
+    OBSERVERS.lock().unwrap().push(observer);
+}
+
+tokio::task_local! {
+    static HIDDEN: bool;
+}
+
+/// Run `future` with its events marked hidden, for work done out of the user's sight
+/// (sub-agents, concurrent branches); `ui::Terminal` skips them.
+pub async fn hidden<F: Future>(future: F) -> F::Output {
+    HIDDEN.scope(true, future).await
+}
+
+/// Whether the event being emitted comes from `hidden` work.
+pub fn is_hidden() -> bool {
+    HIDDEN.try_with(|hidden| *hidden).unwrap_or(false)
+}
+
+#[derive(Serialize)]
+pub struct ChangedFile {
+    pub path: String,
+    pub added: usize,
+    pub removed: usize,
+}
+
+impl From<&FileChange> for ChangedFile {
+    fn from(c: &FileChange) -> Self {
+        let (added, removed) = ui::line_counts(c.before.as_deref(), c.after.as_deref());
+        Self {
+            path: c.path.clone(),
+            added,
+            removed,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// A pipeline phase started, or finished with `phase` as its outcome.
+    PhaseChanged {
+        phase: &'a str,
+        done: bool,
+    },
+    Plan {
+        summary: &'a str,
+        todos: &'a [String],
+        paths_to_read: &'a [String],
+    },
+    TodoStarted {
+        index: usize,
+        todo: &'a str,
+    },
+    TodoFinished {
+        index: usize,
+        ok: bool,
+    },
+    #[serde(rename = "tool_call")]
+    ToolStarted {
+        id: &'a str,
+        name: &'a str,
+        arguments: &'a str,
+    },
+    #[serde(rename = "tool_result")]
+    ToolFinished {
+        id: &'a str,
+        name: &'a str,
+        ok: bool,
+        output: &'a str,
+        /// The file a write changed, for showing its diff.
+        #[serde(skip)]
+        change: Option<&'a FileChange>,
+    },
+    /// A mutating tool call is waiting for a decision (`zcode serve`).
+    #[serde(rename = "approval_required")]
+    NeedsApproval {
+        id: &'a str,
+        name: &'a str,
+        arguments: &'a str,
+    },
+    /// The reviewer's findings on the run's diff (empty when it found nothing to fix).
+    Review {
+        findings: &'a [String],
+    },
+    /// A verification command finished (after any fix rounds).
+    Verification {
+        command: &'a str,
+        passed: bool,
+    },
+    /// Guidance the user sent during the run was handed to the model.
+    Steering {
+        text: &'a str,
+    },
+    AssistantDelta {
+        text: &'a str,
+    },
+    Usage {
+        #[serde(flatten)]
+        usage: &'a Usage,
+        cost: f64,
+    },
+    Error {
+        message: &'a str,
+    },
+    /// A remark on how the run is going (e.g. the review's findings, a cancelled plan).
+    Note {
+        text: &'a str,
+    },
+    /// The plan's todos with their current status.
+    #[serde(skip)]
+    Todos {
+        todos: &'a [String],
+        statuses: &'a [TodoStatus],
+    },
+    /// A model call or other wait started, or finished after `elapsed`.
+    #[serde(skip)]
+    Progress {
+        label: &'a str,
+        elapsed: Option<Duration>,
+    },
+    /// The executor is waiting for its reply; `tokens` counts what has streamed so far.
+    #[serde(skip)]
+    Thinking {
+        tokens: &'a Arc<AtomicUsize>,
+    },
+    /// The executor's turn ended; `text` when its text (if any) is over.
+    #[serde(skip)]
+    TurnEnded {
+        text: bool,
+        usage: Option<&'a Usage>,
+    },
+    /// Ctrl-C stopped the current step.
+    #[serde(skip)]
+    Interrupted,
+    /// A file was read for context, or could not be (`error`).
+    #[serde(skip)]
+    FileRead {
+        path: &'a str,
+        error: Option<&'a str>,
+    },
+    /// Files changed by a batch of tool calls.
+    #[serde(skip)]
+    Changes {
+        title: &'a str,
+        changes: &'a [FileChange],
+    },
+    /// The run's report, once it is finished.
+    #[serde(skip)]
+    Report {
+        report: &'a Report,
+    },
+    Done {
+        reply: Option<&'a str>,
+        changes: Vec<ChangedFile>,
+        cost: f64,
+    },
+}
+
+/// A pipeline phase started.
+pub fn phase(label: &str) {
+    emit(Event::PhaseChanged {
+        phase: label,
+        done: false,
+    });
+}
+
+/// A pipeline phase finished; `label` says how.
+pub fn phase_done(label: &str) {
+    emit(Event::PhaseChanged {
+        phase: label,
+        done: true,
+    });
+}
+
+pub fn note(text: &str) {
+    emit(Event::Note { text });
+}
+
+pub fn error(message: &str) {
+    emit(Event::Error { message });
+}
+
+pub fn todos(todos: &[String], statuses: &[TodoStatus]) {
+    emit(Event::Todos { todos, statuses });
+}
+
+/// Await `future` between a `Progress` event for `label` and one with the time it took.
+pub async fn progress<F: Future>(label: &str, future: F) -> F::Output {
+    let started = Instant::now();
+    emit(Event::Progress {
+        label,
+        elapsed: None,
+    });
+    let output = future.await;
+    emit(Event::Progress {
+        label,
+        elapsed: Some(started.elapsed()),
+    });
+    output
+}
+
+/// Pass `event` to observers, and print it as one JSON line if JSON output is on, or pass it
+/// to the sink. Events without a JSON form only go to observers.
+pub fn emit(event: Event) {
+    let observers = OBSERVERS.lock().unwrap().clone();
+    for observer in observers {
+        observer.on_event(&event);
+    }
+    let sink = SINK.lock().unwrap();
+    if !enabled() && sink.is_none() {
+        return;
+    }
+    let Ok(line) = serde_json::to_string(&event) else {
+        return;
+    };
+    match sink.as_ref() {
+        Some(tx) => {
+            // No subscribers is fine: the client may connect to the stream later.
+            let _ = tx.send(line);
+        }
+        None => {
+            let mut out = std::io::stdout().lock();
+            let _ = writeln!(out, "{}", line);
+            let _ = out.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn hidden_work_does_not_hide_what_runs_beside_it() {
+        let branch = hidden(async {
+            tokio::task::yield_now().await;
+            is_hidden()
+        });
+        let beside = async {
+            tokio::task::yield_now().await;
+            is_hidden()
+        };
+        assert_eq!(tokio::join!(branch, beside), (true, false));
+        assert!(!is_hidden());
+    }
+}
@@ -0,0 +1,102 @@
+//! Files that waste the context budget: lockfiles, minified bundles, generated protobuf code,
+//! and vendored directories. They are left out of the repository map and the semantic index,
+//! and only enter a run's context when the plan names them.
+//!
+//! Extra gitignore-style patterns go in `exclude` (in `.zcode.toml` or the config); a `!`
+//! pattern brings a default back, e.g. `exclude = ["!Cargo.lock"]`.
+
+use crate::config;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::Path;
+
+const DEFAULT_PATTERNS: &[&str] = &[
+    // Lockfiles
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+    "poetry.lock",
+    "Pipfile.lock",
+    "uv.lock",
+    "Gemfile.lock",
+    "composer.lock",
+    "go.sum",
+    "flake.lock",
+    // Minified bundles and source maps
+    "*.min.js",
+    "*.min.css",
+    "*.map",
+    // Generated protobuf code
+    "*.pb.go",
+    "*.pb.cc",
+    "*.pb.h",
+    "*_pb2.py",
+    "*_pb2.pyi",
+    "*_pb2_grpc.py",
+    "*_pb.js",
+    "*_pb.d.ts",
+    "*_grpc_pb.js",
+    // Vendored dependencies
+    "vendor/",
+    "third_party/",
+    "node_modules/",
+];
+/// Markers that generated files put near the top.
+const GENERATED_MARKERS: &[&str] = &[
+    "@generated",
+    "DO NOT EDIT",
+    "Code generated by",
+    "autogenerated",
+];
+/// Lines scanned for a generated marker.
+const HEADER_LINES: usize = 5;
+/// A file whose average line is longer than this is treated as minified.
+const MINIFIED_LINE_BYTES: usize = 500;
+
+pub struct Filter {
+    matcher: Gitignore,
+}
+
+impl Filter {
+    /// The default patterns plus `exclude` from the workspace's `.zcode.toml` or the config.
+    pub fn load(workspace: &Path) -> Self {
+        let mut builder = GitignoreBuilder::new(workspace);
+        let extra = config::load()
+            .exclude
+            .map(|list| list.to_vec())
+            .unwrap_or_default();
+        for pattern in DEFAULT_PATTERNS
+            .iter()
+            .copied()
+            .chain(extra.iter().map(String::as_str))
+        {
+            // A bad user pattern shouldn't take the defaults down with it.
+            let _ = builder.add_line(None, pattern);
+        }
+        Self {
+            matcher: builder.build().unwrap_or_else(|_| Gitignore::empty()),
+        }
+    }
+
+    /// Whether the workspace-relative `path` is excluded.
+    pub fn excludes(&self, path: &str) -> bool {
+        self.matcher
+            .matched_path_or_any_parents(path, false)
+            .is_ignore()
+    }
+}
+
+/// Whether `content` looks machine-written: a generated marker in its header, or minified.
+pub fn looks_generated(content: &str) -> bool {
+    if content
+        .lines()
+        .take(HEADER_LINES)
+        .any(|l| GENERATED_MARKERS.iter().any(|m| l.contains(m)))
+    {
+        return true;
+    }
+    let lines = content.lines().count().max(1);
+    content.len() / lines > MINIFIED_LINE_BYTES
+}
@@ -0,0 +1,154 @@
+//! `zcode explain <path>[:START-END]`: walk through code using read-only tools.
+
+use crate::agent::{self, Message};
+use crate::context;
+use crate::run::{self, execute_step, StepOutcome};
+use crate::templates;
+use crate::tools::Executor;
+use crate::ui;
+use std::fs;
+
+/// Most definitions searched for callers.
+const MAX_SYMBOLS: usize = 10;
+/// Most caller lines included in the prompt.
+const MAX_CALLERS: usize = 30;
+
+/// 1-based inclusive line range.
+type LineRange = (usize, usize);
+
+/// Split `path[:START-END]` into the path and optional 1-based inclusive range.
+fn parse_target(target: &str) -> Result<(&str, Option<LineRange>), String> {
+    match target.rsplit_once(':') {
+        Some((path, range)) if range.contains('-') => {
+            Ok((path, Some(templates::parse_lines(range)?)))
+        }
+        _ => Ok((target, None)),
+    }
+}
+
+/// Names defined in `code` (functions, types, classes) in common languages.
+fn defined_symbols(code: &str) -> Vec<String> {
+    const KEYWORDS: &[&str] = &[
+        "fn",
+        "struct",
+        "enum",
+        "trait",
+        "type",
+        "def",
+        "class",
+        "function",
+        "func",
+        "interface",
+    ];
+    let mut symbols = Vec::new();
+    for line in code.lines() {
+        let mut words = line
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .filter(|w| !w.is_empty());
+        while let Some(word) = words.next() {
+            if KEYWORDS.contains(&word) {
+                if let Some(name) = words.next() {
+                    if name.len() > 2 && !symbols.iter().any(|s| s == name) {
+                        symbols.push(name.to_string());
+                    }
+                }
+                break;
+            }
+        }
+    }
+    symbols.truncate(MAX_SYMBOLS);
+    symbols
+}
+
+/// `path:line: text` for lines in other workspace files that mention one of `symbols`.
+fn find_callers(executor: &Executor, path: &str, symbols: &[String]) -> Vec<String> {
+    let mut hits = Vec::new();
+    if symbols.is_empty() {
+        return hits;
+    }
+    for file in context::workspace_files(executor.workspace()) {
+        if file == path {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(executor.workspace().join(&file)) else {
+            continue;
+        };
+        for (i, line) in content.lines().enumerate() {
+            let mentions = symbols.iter().any(|s| {
+                line.match_indices(s.as_str()).any(|(at, _)| {
+                    let before = line[..at].chars().next_back();
+                    let after = line[at + s.len()..].chars().next();
+                    !before.is_some_and(|c| c.is_alphanumeric() || c == '_')
+                        && !after.is_some_and(|c| c.is_alphanumeric() || c == '_')
+                })
+            });
+            if mentions {
+                hits.push(format!("{}:{}: {}", file, i + 1, line.trim()));
+                if hits.len() == MAX_CALLERS {
+                    return hits;
+                }
+            }
+        }
+    }
+    hits
+}
+
+pub async fn run(
+    api_key: &str,
+    executor: &Executor,
+    target: &str,
+) -> Result<Option<String>, String> {
+    let (path, range) = parse_target(target)?;
+    let content = fs::read_to_string(executor.workspace().join(path))
+        .map_err(|e| format!("{}: {}", path, e))?;
+    let lines: Vec<&str> = content.lines().collect();
+    let (start, end) = range.unwrap_or((1, lines.len().max(1)));
+    if start > lines.len() {
+        return Err(format!("{} has only {} lines", path, lines.len()));
+    }
+    let end = end.min(lines.len());
+    let selection: String = lines[start - 1..end]
+        .iter()
+        .enumerate()
+        .map(|(i, l)| format!("{:>5} {}\n", start + i, l))
+        .collect();
+
+    ui::phase("Finding callers");
+    let symbols = defined_symbols(&selection);
+    let callers = find_callers(executor, path, &symbols);
+    ui::phase_done(&format!(
+        "{} references to {} definitions",
+        callers.len(),
+        symbols.len()
+    ));
+
+    let mut prompt = format!(
+        "Explain {}{} to a developer new to this codebase: what it does, how it works step by step, and how it fits into the rest of the project. Read other files if needed.\n\n--- {} (lines {}-{}) ---\n{}",
+        path,
+        range.map(|(s, e)| format!(" lines {}-{}", s, e)).unwrap_or_default(),
+        path,
+        start,
+        end,
+        context::truncate(&selection, context::MENTION_FILE_CAP),
+    );
+    if range.is_some() && content.len() <= context::MENTION_FILE_CAP {
+        prompt.push_str(&format!("\n\nWhole file for reference:\n{}", content));
+    }
+    if !callers.is_empty() {
+        prompt.push_str(&format!(
+            "\n\nReferences elsewhere in the project:\n{}",
+            callers.join("\n")
+        ));
+    }
+
+    let agent = agent::from_spec(&run::executor_model(), api_key)?.with_read_only();
+    let mut messages = vec![Message::Role {
+        role: "user".into(),
+        content: prompt,
+    }];
+    match execute_step(&agent, executor, &mut messages).await {
+        StepOutcome::Done(reply) => Ok(reply),
+        StepOutcome::Interrupted(reply) => Ok(reply),
+        StepOutcome::Failed(e) | StepOutcome::Stalled(e) => Err(e),
+    }
+}
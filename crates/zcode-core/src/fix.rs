@@ -0,0 +1,172 @@
+//! `zcode fix`: feed compiler or test diagnostics to the agent and rebuild until they are gone.
+
+use crate::agent::{self, Agent, Message};
+use crate::context;
+use crate::run::{self, execute_step, StepOutcome};
+use crate::tools::Executor;
+use crate::ui;
+use std::path::Path;
+
+/// Most files whose contents are attached per iteration.
+const MAX_FILES: usize = 8;
+/// Build output beyond this many bytes is cut before it goes into the prompt.
+const OUTPUT_CAP: usize = 16 * 1024;
+
+/// A diagnostic that points at a file in the workspace.
+#[derive(Debug, PartialEq)]
+struct Diagnostic {
+    path: String,
+    line: usize,
+}
+
+/// `path:line[:col]` at the start of `s`, if `path` exists in the workspace.
+fn location(workspace: &Path, s: &str) -> Option<Diagnostic> {
+    let mut parts = s.trim().splitn(3, ':');
+    let path = parts.next()?.trim();
+    let line = parts.next()?.trim().parse().ok()?;
+    let path = path.strip_prefix("./").unwrap_or(path);
+    workspace.join(path).is_file().then(|| Diagnostic {
+        path: path.to_string(),
+        line,
+    })
+}
+
+/// Locations of errors in compiler or test output. Understands rustc (` --> path:line:col`),
+/// panics (`panicked at path:line:col`), and the common `path:line:col: error` form.
+fn diagnostics(workspace: &Path, output: &str) -> Vec<Diagnostic> {
+    let mut found: Vec<Diagnostic> = Vec::new();
+    for line in output.lines() {
+        let trimmed = line.trim_start();
+        let candidate = if let Some(rest) = trimmed.strip_prefix("--> ") {
+            location(workspace, rest)
+        } else if let Some((_, rest)) = trimmed.split_once("panicked at ") {
+            location(workspace, rest)
+        } else if trimmed.contains(": error") || trimmed.contains(": warning") {
+            location(workspace, trimmed)
+        } else {
+            None
+        };
+        if let Some(d) = candidate {
+            if !found.contains(&d) {
+                found.push(d);
+            }
+        }
+    }
+    found
+}
+
+/// Run the check command; returns whether it passed and its combined output.
+pub(crate) fn check(executor: &Executor, command: &str) -> Result<(bool, String), String> {
+    ui::phase(&format!("Running {}", command));
+    let (status, stdout, stderr) = executor.run_shell(command)?;
+    let Some(status) = status else {
+        return Err("Interrupted".into());
+    };
+    ui::phase_done(&format!(
+        "{} ({})",
+        command,
+        if status.success() { "passed" } else { "failed" }
+    ));
+    Ok((status.success(), format!("{}{}", stdout, stderr)))
+}
+
+/// Fix the diagnostics in `initial` (piped output), or in the check command's output.
+/// `background` explains how the errors came about (e.g. a dependency upgrade).
+/// Returns whether the check command passes at the end.
+pub async fn run(
+    api_key: &str,
+    executor: &Executor,
+    command: &str,
+    initial: Option<String>,
+    background: Option<&str>,
+    max_iterations: usize,
+) -> Result<bool, String> {
+    let output = match initial {
+        Some(output) => output,
+        None => match check(executor, command)? {
+            (true, _) => {
+                ui::info("Nothing to fix.");
+                return Ok(true);
+            }
+            (false, output) => output,
+        },
+    };
+
+    let agent = agent::from_spec(&run::executor_model(), api_key)?;
+    repair(
+        &agent,
+        executor,
+        command,
+        output,
+        background,
+        max_iterations,
+    )
+    .await
+}
+
+/// Edit-and-check rounds on the failing `output` of `command` until it passes.
+/// Returns whether it passes at the end.
+pub(crate) async fn repair<A: Agent>(
+    agent: &A,
+    executor: &Executor,
+    command: &str,
+    mut output: String,
+    background: Option<&str>,
+    max_iterations: usize,
+) -> Result<bool, String> {
+    for iteration in 1..=max_iterations {
+        let found = diagnostics(executor.workspace(), &output);
+        let mut files: Vec<&str> = Vec::new();
+        for d in &found {
+            if !files.contains(&d.path.as_str()) {
+                files.push(&d.path);
+            }
+        }
+        ui::phase_done(&format!(
+            "Round {}/{}: {} error locations in {} files",
+            iteration,
+            max_iterations,
+            found.len(),
+            files.len()
+        ));
+        files.truncate(MAX_FILES);
+        let blocks: Vec<String> = files
+            .iter()
+            .filter_map(|p| context::file_block(executor.workspace(), p).ok())
+            .collect();
+        let locations: Vec<String> = found
+            .iter()
+            .map(|d| format!("{}:{}", d.path, d.line))
+            .collect();
+        let mut prompt = background.map(|b| format!("{}\n\n", b)).unwrap_or_default();
+        prompt += &format!(
+            "`{}` fails with the output below. Fix the root causes with minimal edits; do not silence errors or delete tests. Run the command to check your work if useful.\n\nOutput:\n{}\n\nLocations: {}\n\n{}",
+            command,
+            context::truncate(&output, OUTPUT_CAP),
+            if locations.is_empty() { "(none recognized)".to_string() } else { locations.join(", ") },
+            blocks.join("\n\n"),
+        );
+        let mut messages = vec![Message::Role {
+            role: "user".into(),
+            content: prompt,
+        }];
+        match execute_step(agent, executor, &mut messages).await {
+            // The check command decides whether the round worked.
+            StepOutcome::Done(_) | StepOutcome::Stalled(_) => {}
+            StepOutcome::Interrupted(_) => return Err("Interrupted".into()),
+            StepOutcome::Failed(e) => return Err(e),
+        }
+
+        let (passed, next) = check(executor, command)?;
+        if passed {
+            ui::info(&format!("Fixed after {} iteration(s).", iteration));
+            return Ok(true);
+        }
+        output = next;
+    }
+    ui::note(&format!(
+        "Still failing after {} iterations.",
+        max_iterations
+    ));
+    Ok(false)
+}
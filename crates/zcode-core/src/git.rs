@@ -0,0 +1,136 @@
+//! Thin wrapper over the `git` command line.
+
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Snapshots taken so far, so concurrent ones use their own scratch index.
+static SNAPSHOTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Run `git <args>` in `workspace` and return stdout; stderr becomes the error.
+pub fn git(workspace: &Path, args: &[&str]) -> Result<String, String> {
+    let out = Command::new("git")
+        .args(args)
+        .current_dir(workspace)
+        .output()
+        .map_err(|e| format!("git: {}", e))?;
+    if !out.status.success() {
+        return Err(format!(
+            "git {}: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).to_string())
+}
+
+/// Whether the working tree has uncommitted changes (untracked files included).
+pub fn is_dirty(workspace: &Path) -> Result<bool, String> {
+    Ok(!git(workspace, &["status", "--porcelain"])?
+        .trim()
+        .is_empty())
+}
+
+/// Uncommitted work: `git diff HEAD` (staged and unstaged) plus the names of untracked
+/// files. None when the tree is clean or not a git repository.
+pub fn working_diff(workspace: &Path) -> Option<String> {
+    // A repository without commits has no HEAD to diff against.
+    let diff = git(workspace, &["diff", "HEAD"])
+        .or_else(|_| git(workspace, &["diff"]))
+        .ok()?;
+    let untracked =
+        git(workspace, &["ls-files", "--others", "--exclude-standard"]).unwrap_or_default();
+    let mut out = diff.trim_end().to_string();
+    if !untracked.trim().is_empty() {
+        if !out.is_empty() {
+            out.push_str("\n\n");
+        }
+        out.push_str(&format!("Untracked files:\n{}", untracked.trim_end()));
+    }
+    (!out.is_empty()).then_some(out)
+}
+
+/// Stage `paths` (including deletions) and commit them. Returns false if nothing was staged.
+pub fn commit_paths(workspace: &Path, paths: &[&str], message: &str) -> Result<bool, String> {
+    if paths.is_empty() {
+        return Ok(false);
+    }
+    let mut add = vec!["add", "-A", "--"];
+    add.extend(paths);
+    git(workspace, &add)?;
+    if git(workspace, &["diff", "--cached", "--quiet"]).is_ok() {
+        return Ok(false);
+    }
+    git(workspace, &["commit", "-m", message])?;
+    Ok(true)
+}
+
+/// Tree id of the working tree as it is now, untracked files included (ignored ones not).
+/// Written through a scratch index, so the real index is left alone.
+pub fn snapshot(workspace: &Path) -> Result<String, String> {
+    let n = SNAPSHOTS.fetch_add(1, Ordering::Relaxed);
+    let index =
+        std::env::temp_dir().join(format!("zcode-snapshot-{}-{}.index", std::process::id(), n));
+    let run = |args: &[&str]| -> Result<String, String> {
+        let out = Command::new("git")
+            .args(args)
+            .env("GIT_INDEX_FILE", &index)
+            .current_dir(workspace)
+            .output()
+            .map_err(|e| format!("git: {}", e))?;
+        if !out.status.success() {
+            return Err(format!(
+                "git {}: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&out.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+    };
+    // Starting from the real index (or HEAD) spares hashing unchanged files; a repo without
+    // commits starts empty.
+    let real_index = git(
+        workspace,
+        &["rev-parse", "--path-format=absolute", "--git-path", "index"],
+    );
+    if !real_index.is_ok_and(|path| std::fs::copy(path.trim(), &index).is_ok()) {
+        let _ = run(&["read-tree", "HEAD"]);
+    }
+    let tree = run(&["add", "-A"]).and_then(|_| run(&["write-tree"]));
+    let _ = std::fs::remove_file(&index);
+    tree
+}
+
+/// Paths that differ between two trees from `snapshot`.
+pub fn changed_paths(workspace: &Path, from: &str, to: &str) -> Result<Vec<String>, String> {
+    let out = git(
+        workspace,
+        &[
+            "diff-tree",
+            "-r",
+            "-z",
+            "--no-renames",
+            "--name-only",
+            from,
+            to,
+        ],
+    )?;
+    Ok(out
+        .split('\0')
+        .filter(|p| !p.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Content of `path` in `tree`, or None if it is not there (or not text).
+pub fn file_in_tree(workspace: &Path, tree: &str, path: &str) -> Option<String> {
+    let out = Command::new("git")
+        .args(["cat-file", "blob", &format!("{}:{}", tree, path)])
+        .current_dir(workspace)
+        .output()
+        .ok()?;
+    out.status
+        .success()
+        .then(|| String::from_utf8(out.stdout).ok())
+        .flatten()
+}
@@ -0,0 +1,119 @@
+//! GitHub REST API access for `zcode issue` and `--create-pr`.
+
+use crate::config;
+use crate::git::git;
+use crate::http;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+const API: &str = "https://api.github.com";
+
+/// `owner/repo` of a GitHub remote URL (https or ssh).
+fn parse_repo(url: &str) -> Option<String> {
+    let rest = url
+        .trim()
+        .strip_prefix("https://github.com/")
+        .or_else(|| url.trim().strip_prefix("git@github.com:"))
+        .or_else(|| url.trim().strip_prefix("ssh://git@github.com/"))?;
+    let rest = rest.trim_end_matches('/').trim_end_matches(".git");
+    let mut parts = rest.splitn(3, '/');
+    let (owner, repo) = (parts.next()?, parts.next()?);
+    Some(format!("{}/{}", owner, repo))
+}
+
+/// `owner/repo` of the workspace's `origin` remote.
+pub fn origin_repo(workspace: &Path) -> Result<String, String> {
+    let url = git(workspace, &["remote", "get-url", "origin"])?;
+    parse_repo(&url).ok_or_else(|| format!("origin is not a GitHub remote: {}", url.trim()))
+}
+
+/// An issue reference: a number (repo from `origin`) or an issue URL.
+pub fn parse_issue(workspace: &Path, reference: &str) -> Result<(String, u64), String> {
+    if let Ok(n) = reference.trim_start_matches('#').parse() {
+        return Ok((origin_repo(workspace)?, n));
+    }
+    let repo = parse_repo(reference)
+        .ok_or_else(|| format!("Not an issue number or GitHub URL: {}", reference))?;
+    let number = reference
+        .trim_end_matches('/')
+        .rsplit_once("/issues/")
+        .and_then(|(_, n)| n.split(['#', '?']).next()?.parse().ok())
+        .ok_or_else(|| format!("No issue number in {}", reference))?;
+    Ok((repo, number))
+}
+
+/// Token from `GITHUB_TOKEN`/`GH_TOKEN`, `github_token` in config, or `gh auth token`.
+pub fn token() -> Option<String> {
+    std::env::var("GITHUB_TOKEN")
+        .ok()
+        .or_else(|| std::env::var("GH_TOKEN").ok())
+        .or_else(|| config::load().github_token)
+        .or_else(|| {
+            let out = Command::new("gh").args(["auth", "token"]).output().ok()?;
+            let t = String::from_utf8_lossy(&out.stdout).trim().to_string();
+            (out.status.success() && !t.is_empty()).then_some(t)
+        })
+        .filter(|t| !t.is_empty())
+}
+
+pub struct Client {
+    http: reqwest::Client,
+    token: Option<String>,
+}
+
+impl Client {
+    pub fn new() -> Result<Self, String> {
+        let http = http::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            http,
+            token: token(),
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let req = self
+            .http
+            .request(method, format!("{}{}", API, path))
+            .header("Accept", "application/vnd.github+json");
+        match &self.token {
+            Some(t) => req.bearer_auth(t),
+            None => req,
+        }
+    }
+
+    async fn send(&self, req: reqwest::RequestBuilder) -> Result<serde_json::Value, String> {
+        let res = req.send().await.map_err(|e| format!("GitHub: {}", e))?;
+        let status = res.status();
+        let body: serde_json::Value = res.json().await.unwrap_or_default();
+        if !status.is_success() {
+            let message = body["message"].as_str().unwrap_or("");
+            let hint = if self.token.is_none() {
+                " (set GITHUB_TOKEN or log in with `gh auth login`)"
+            } else {
+                ""
+            };
+            return Err(format!("GitHub: {} {}{}", status, message, hint));
+        }
+        Ok(body)
+    }
+
+    pub async fn get(&self, path: &str) -> Result<serde_json::Value, String> {
+        self.send(self.request(reqwest::Method::GET, path)).await
+    }
+
+    pub async fn post(
+        &self,
+        path: &str,
+        body: &serde_json::Value,
+    ) -> Result<serde_json::Value, String> {
+        if self.token.is_none() {
+            return Err("GitHub: a token is needed to write (set GITHUB_TOKEN or log in with `gh auth login`)".into());
+        }
+        self.send(self.request(reqwest::Method::POST, path).json(body))
+            .await
+    }
+}
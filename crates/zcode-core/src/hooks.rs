@@ -0,0 +1,205 @@
+//! Commands from `[hooks]` in `.zcode.toml` (or the config) run around tool calls:
+//!
+//! ```toml
+//! [hooks.pre_tool]
+//! run_command = "scripts/zcode-policy.sh"
+//!
+//! [hooks.post_tool]
+//! "*" = "scripts/zcode-notify.sh"
+//! write_file = ["scripts/format-written.sh"]
+//! ```
+//!
+//! Each runs through `sh` in the workspace with the call as JSON on stdin. A pre-tool hook that
+//! exits non-zero blocks the call, and its output becomes the error the model sees. A post-tool
+//! hook can replace the result by printing `{"result": "..."}` or `{"error": "..."}`.
+//!
+//! Lifecycle hooks (`session_start`, `session_end`, `prompt_submit`, `prompt_finish`) are
+//! commands or lists of commands directly under `[hooks]`; a failing `prompt_submit` hook
+//! cancels the prompt.
+
+use crate::agent::ToolCall;
+use crate::config::{self, Hook};
+use crate::events;
+use crate::tools::Executor;
+use crate::usage;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Whether `session_start` ran and `session_end` has not yet.
+static IN_SESSION: AtomicBool = AtomicBool::new(false);
+
+/// Hook commands for `event` and `tool`: those for every tool (`"*"`) first.
+fn commands(event: &str, tool: &str) -> Vec<String> {
+    match config::load().hooks.remove(event) {
+        Some(Hook::ByTool(mut by_tool)) => ["*", tool]
+            .into_iter()
+            .filter_map(|key| by_tool.remove(key))
+            .flat_map(|list| list.to_vec())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Run one hook with `input` on stdin. Returns whether it succeeded, and its stdout and stderr.
+fn run(
+    executor: &Executor,
+    command: &str,
+    input: &serde_json::Value,
+) -> Result<(bool, String, String), String> {
+    let mut process = Command::new("sh");
+    process.args(["-c", command]);
+    let (status, stdout, stderr) = executor.run_process(process, Some(&input.to_string()))?;
+    let ok = status.is_some_and(|s| s.success());
+    info!(hook = %command, ok, "hook ran");
+    Ok((ok, stdout, stderr))
+}
+
+fn arguments(tool_call: &ToolCall) -> serde_json::Value {
+    serde_json::from_str(&tool_call.function.arguments).unwrap_or(serde_json::Value::Null)
+}
+
+/// Run the pre-tool hooks for a call. Err with the reason if one blocked it.
+pub fn pre_tool(executor: &Executor, tool_call: &ToolCall) -> Result<(), String> {
+    let tool = tool_call.function.name.as_str();
+    let commands = commands("pre_tool", tool);
+    if commands.is_empty() {
+        return Ok(());
+    }
+    let input = serde_json::json!({
+        "event": "pre_tool",
+        "tool": tool,
+        "arguments": arguments(tool_call),
+    });
+    for command in commands {
+        let (ok, stdout, stderr) = run(executor, &command, &input)
+            .map_err(|e| format!("Hook {} failed to start: {}", command, e))?;
+        if !ok {
+            let reason = if stderr.trim().is_empty() {
+                stdout
+            } else {
+                stderr
+            };
+            return Err(format!("Blocked by hook {}: {}", command, reason.trim()));
+        }
+    }
+    Ok(())
+}
+
+/// Run the post-tool hooks for a call, each seeing the result so far and able to replace it.
+pub fn post_tool(
+    executor: &Executor,
+    tool_call: &ToolCall,
+    mut result: Result<String, String>,
+) -> Result<String, String> {
+    let tool = tool_call.function.name.as_str();
+    for command in commands("post_tool", tool) {
+        let input = serde_json::json!({
+            "event": "post_tool",
+            "tool": tool,
+            "arguments": arguments(tool_call),
+            "ok": result.is_ok(),
+            "result": match &result {
+                Ok(output) | Err(output) => output,
+            },
+        });
+        let stdout = match run(executor, &command, &input) {
+            Ok((true, stdout, _)) => stdout,
+            Ok((false, _, stderr)) => {
+                warn!(hook = %command, stderr = %stderr.trim(), "post-tool hook failed");
+                continue;
+            }
+            Err(e) => {
+                warn!(hook = %command, error = %e, "post-tool hook failed to start");
+                continue;
+            }
+        };
+        // Anything other than a replacement (e.g. a formatter's own output) leaves the result alone.
+        let Ok(serde_json::Value::Object(reply)) = serde_json::from_str(stdout.trim()) else {
+            continue;
+        };
+        if let Some(error) = reply.get("error").and_then(|e| e.as_str()) {
+            result = Err(error.to_string());
+        } else if let Some(output) = reply.get("result").and_then(|r| r.as_str()) {
+            result = Ok(output.to_string());
+        }
+    }
+    result
+}
+
+/// Run the `hooks.<event>` commands with `input`, stopping at the first that fails.
+fn fire(executor: &Executor, event: &str, mut input: serde_json::Value) -> Result<(), String> {
+    let commands = match config::load().hooks.remove(event) {
+        Some(Hook::Commands(list)) => list.to_vec(),
+        _ => Vec::new(),
+    };
+    input["event"] = event.into();
+    for command in commands {
+        let (ok, stdout, stderr) = run(executor, &command, &input)
+            .map_err(|e| format!("Hook {} failed to start: {}", command, e))?;
+        if !ok {
+            let output = if stderr.trim().is_empty() {
+                stdout
+            } else {
+                stderr
+            };
+            return Err(format!("Hook {} failed: {}", command, output.trim()));
+        }
+    }
+    Ok(())
+}
+
+/// Run the `session_start` hooks, before the first prompt of a one-shot run or the REPL.
+pub fn session_start(executor: &Executor) {
+    IN_SESSION.store(true, Ordering::Relaxed);
+    if let Err(e) = fire(executor, "session_start", serde_json::json!({})) {
+        warn!(error = %e, "session_start hook failed");
+        events::error(&e);
+    }
+}
+
+/// Run the `session_end` hooks once, if `session_start` ran.
+pub fn session_end(executor: &Executor) {
+    if !IN_SESSION.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    if let Err(e) = fire(executor, "session_end", serde_json::json!({})) {
+        warn!(error = %e, "session_end hook failed");
+        events::error(&e);
+    }
+}
+
+/// Run the `prompt_submit` hooks. Err if one failed, in which case the prompt is not run.
+pub fn prompt_submit(executor: &Executor, prompt: &str) -> Result<(), String> {
+    fire(
+        executor,
+        "prompt_submit",
+        serde_json::json!({ "prompt": prompt }),
+    )
+}
+
+/// Run the `prompt_finish` hooks with how the prompt went. `mark` is the `usage::mark` taken
+/// when it started.
+pub fn prompt_finish(
+    executor: &Executor,
+    prompt: &str,
+    reply: Option<&str>,
+    elapsed: Duration,
+    mark: usize,
+) {
+    let files: Vec<String> = executor.changes().into_iter().map(|c| c.path).collect();
+    let usage = usage::all();
+    let input = serde_json::json!({
+        "prompt": prompt,
+        "ok": reply.is_some(),
+        "reply": reply,
+        "files": files,
+        "duration_secs": elapsed.as_secs_f64(),
+        "cost": usage::total_cost(usage.get(mark..).unwrap_or_default()),
+    });
+    if let Err(e) = fire(executor, "prompt_finish", input) {
+        warn!(error = %e, "prompt_finish hook failed");
+        events::error(&e);
+    }
+}
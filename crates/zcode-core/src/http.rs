@@ -0,0 +1,130 @@
+//! Outgoing HTTP: every client zcode builds gets its user agent, proxy, and pool settings here,
+//! and agents share one client (per TLS identity) so connections are reused across phases.
+//! `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY`, and `NO_PROXY` apply unless `[proxy]` in the
+//! config names a proxy.
+
+use crate::config::{self, Provider};
+use reqwest::{Certificate, Identity, NoProxy, Proxy};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tracing::warn;
+
+const USER_AGENT: &str = concat!("zcode/", env!("CARGO_PKG_VERSION"));
+
+/// Idle connections are kept this long unless `http.pool_idle_timeout` is set.
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+/// TCP keepalive interval unless `http.tcp_keepalive` is set.
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+
+static SHARED: OnceLock<reqwest::Client> = OnceLock::new();
+/// Clients for profiles with TLS files, by (client cert, key, CA).
+static PROVIDER_CLIENTS: Mutex<Option<HashMap<TlsFiles, reqwest::Client>>> = Mutex::new(None);
+
+type TlsFiles = (Option<String>, Option<String>, Option<String>);
+
+/// Client builder with the user agent, proxies, and pool settings applied; add timeouts as
+/// needed.
+pub fn builder() -> reqwest::ClientBuilder {
+    let pool = config::load().http;
+    let mut builder = reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .pool_idle_timeout(
+            pool.pool_idle_timeout
+                .map_or(POOL_IDLE_TIMEOUT, Duration::from_secs),
+        )
+        .tcp_keepalive(
+            pool.tcp_keepalive
+                .map_or(TCP_KEEPALIVE, Duration::from_secs),
+        );
+    if let Some(max) = pool.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max);
+    }
+    match proxies() {
+        Ok(proxies) => {
+            for proxy in proxies {
+                builder = builder.proxy(proxy);
+            }
+        }
+        Err(e) => warn!(error = %e, "ignoring the proxy settings"),
+    }
+    builder
+}
+
+/// The shared client. Clones share its connection pool.
+pub fn client() -> reqwest::Client {
+    SHARED
+        .get_or_init(|| {
+            builder()
+                .build()
+                .expect("TLS backend cannot be initialized")
+        })
+        .clone()
+}
+
+/// Client for a provider profile: the shared settings plus its client certificate and CA, or
+/// None when it has no TLS files. Profiles with the same files share a client.
+pub fn provider_client(profile: &Provider) -> Result<Option<reqwest::Client>, String> {
+    if profile.client_cert.is_none() && profile.ca_cert.is_none() {
+        return Ok(None);
+    }
+    let files = (
+        profile.client_cert.clone(),
+        profile.client_key.clone(),
+        profile.ca_cert.clone(),
+    );
+    let mut clients = PROVIDER_CLIENTS.lock().unwrap();
+    if let Some(client) = clients.as_ref().and_then(|c| c.get(&files)) {
+        return Ok(Some(client.clone()));
+    }
+    let client = tls_client(profile)?;
+    clients
+        .get_or_insert_with(HashMap::new)
+        .insert(files, client.clone());
+    Ok(Some(client))
+}
+
+fn tls_client(profile: &Provider) -> Result<reqwest::Client, String> {
+    let read = |path: &str| fs::read(path).map_err(|e| format!("{}: {}", path, e));
+    let mut builder = builder();
+    if let Some(cert) = &profile.client_cert {
+        let mut pem = read(cert)?;
+        if let Some(key) = &profile.client_key {
+            pem.push(b'\n');
+            pem.extend(read(key)?);
+        }
+        let identity = Identity::from_pem(&pem)
+            .map_err(|e| format!("{}: not a PEM certificate and key ({})", cert, e))?;
+        builder = builder.identity(identity);
+    }
+    if let Some(ca) = &profile.ca_cert {
+        let cert = Certificate::from_pem(&read(ca)?)
+            .map_err(|e| format!("{}: not a PEM certificate ({})", ca, e))?;
+        builder = builder.add_root_certificate(cert);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// Proxies from `[proxy]`, most specific first; empty to use the environment's.
+pub fn proxies() -> Result<Vec<Proxy>, String> {
+    let settings = config::load().proxy;
+    let no_proxy = settings
+        .no_proxy
+        .and_then(|hosts| NoProxy::from_string(&hosts.to_vec().join(",")));
+    let invalid = |key: &str, url: &str| format!("proxy.{}: not a proxy URL: {}", key, url);
+    let mut proxies = Vec::new();
+    if let Some(url) = settings.http {
+        proxies.push(Proxy::http(&url).map_err(|_| invalid("http", &url))?);
+    }
+    if let Some(url) = settings.https {
+        proxies.push(Proxy::https(&url).map_err(|_| invalid("https", &url))?);
+    }
+    if let Some(url) = settings.url {
+        proxies.push(Proxy::all(&url).map_err(|_| invalid("url", &url))?);
+    }
+    Ok(proxies
+        .into_iter()
+        .map(|p| p.no_proxy(no_proxy.clone()))
+        .collect())
+}
@@ -0,0 +1,312 @@
+//! How files are split into chunks for embedding. `syntax` (the default) cuts source files at
+//! function, type, and statement boundaries with tree-sitter and packs neighbouring items into
+//! chunks of up to `chunk_lines`; `lines` uses fixed windows sharing `chunk_overlap` lines.
+//! Files without a grammar, and items too long for one chunk, fall back to windows. Set them
+//! under `[index]`, or per language under `[index.languages.<name>]` (`rust`, `python`,
+//! `javascript`, `typescript`, `go`, or a file extension).
+
+use crate::config::{Chunking, IndexConfig};
+use crate::repomap;
+use std::path::Path;
+use tree_sitter::{Node, Parser};
+
+/// Lines per chunk, and lines shared by neighbouring windows.
+const CHUNK_LINES: usize = 60;
+const CHUNK_OVERLAP: usize = 10;
+
+/// Chunking settings for one file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    pub chunking: Chunking,
+    pub lines: usize,
+    pub overlap: usize,
+}
+
+impl Settings {
+    /// Settings for `path`: its language's table, then `[index]`, then the defaults.
+    pub fn for_path(path: &str, config: &IndexConfig) -> Self {
+        let ext = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        let language = config
+            .languages
+            .get(language_name(ext))
+            .or_else(|| config.languages.get(ext));
+        let lines = language
+            .and_then(|l| l.chunk_lines)
+            .or(config.chunk_lines)
+            .unwrap_or(CHUNK_LINES)
+            .max(1);
+        let overlap = language
+            .and_then(|l| l.chunk_overlap)
+            .or(config.chunk_overlap)
+            .unwrap_or(CHUNK_OVERLAP)
+            .min(lines - 1);
+        Self {
+            chunking: language
+                .and_then(|l| l.chunking)
+                .or(config.chunking)
+                .unwrap_or(Chunking::Syntax),
+            lines,
+            overlap,
+        }
+    }
+
+    /// Stored with each indexed file, so a settings change re-chunks it.
+    pub fn fingerprint(&self) -> String {
+        let chunking = match self.chunking {
+            Chunking::Syntax => "syntax",
+            Chunking::Lines => "lines",
+        };
+        format!("{}:{}:{}", chunking, self.lines, self.overlap)
+    }
+}
+
+/// Config name of the language for a file extension; the extension itself when there is none.
+fn language_name(ext: &str) -> &str {
+    match ext {
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "jsx" | "mjs" | "cjs" => "javascript",
+        "ts" | "tsx" => "typescript",
+        "go" => "go",
+        other => other,
+    }
+}
+
+/// `content` of `path` as `(start, end, text)` chunks, 1-based inclusive lines.
+pub fn chunks(path: &str, content: &str, settings: Settings) -> Vec<(usize, usize, String)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let ranges = match settings.chunking {
+        Chunking::Syntax => syntax_ranges(path, content, lines.len(), settings),
+        Chunking::Lines => None,
+    };
+    let ranges = ranges.unwrap_or_else(|| windows(0, lines.len().saturating_sub(1), settings));
+    ranges
+        .into_iter()
+        .filter(|(start, _)| *start < lines.len())
+        .map(|(start, end)| (start + 1, end + 1, lines[start..=end].join("\n")))
+        .collect()
+}
+
+/// Rows `first..=last` as windows of `settings.lines` sharing `settings.overlap` rows.
+fn windows(first: usize, last: usize, settings: Settings) -> Vec<(usize, usize)> {
+    let mut out = Vec::new();
+    let mut start = first;
+    loop {
+        let end = (start + settings.lines - 1).min(last);
+        out.push((start, end));
+        if end == last {
+            return out;
+        }
+        start = end + 1 - settings.overlap;
+    }
+}
+
+/// Row ranges cut at syntax boundaries, or None when the file has no grammar.
+fn syntax_ranges(
+    path: &str,
+    content: &str,
+    line_count: usize,
+    settings: Settings,
+) -> Option<Vec<(usize, usize)>> {
+    if line_count == 0 {
+        return Some(Vec::new());
+    }
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    let (language, _) = repomap::language(ext)?;
+    let mut parser = Parser::new();
+    parser.set_language(&language).ok()?;
+    let tree = parser.parse(content, None)?;
+    let last = line_count - 1;
+    let mut pieces = Vec::new();
+    split(tree.root_node(), 0, last, settings.lines, &mut pieces);
+
+    // Pack neighbouring pieces into chunks; a piece still too long is cut into windows.
+    let mut out: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in pieces {
+        if end + 1 - start > settings.lines {
+            out.extend(windows(start, end, settings));
+            continue;
+        }
+        match out.last_mut() {
+            Some(prev) if end + 1 - prev.0 <= settings.lines => prev.1 = end,
+            _ => out.push((start, end)),
+        }
+    }
+    Some(out)
+}
+
+/// Cover rows `first..=last` of `node` with pieces no longer than `max` where its children
+/// allow: a node that fits is one piece, a longer one is split among its named children, each
+/// taking the rows (comments, attributes, closing braces) up to the next.
+fn split(node: Node, first: usize, last: usize, max: usize, out: &mut Vec<(usize, usize)>) {
+    let children = items(node);
+    if last + 1 - first <= max || children.is_empty() {
+        out.push((first, last));
+        return;
+    }
+    let mut start = first;
+    for (i, (_, child)) in children.iter().enumerate() {
+        let end = match children.get(i + 1) {
+            Some((lead, _)) => lead.saturating_sub(1).min(last),
+            None => last,
+        };
+        // The next child starts on a line this piece still needs; it continues there.
+        if end < start || end < child.end_position().row.min(last) {
+            continue;
+        }
+        split(*child, start, end, max, out);
+        start = end + 1;
+    }
+    if start <= last {
+        out.push((start, last));
+    }
+}
+
+/// Named children of `node` that can start a piece, each with the row its leading comments and
+/// attributes start on (comments trailing the previous item on its last line are not leading).
+fn items(node: Node) -> Vec<(usize, Node)> {
+    let mut cursor = node.walk();
+    let mut out = Vec::new();
+    let mut lead = None;
+    let mut prev_end = None;
+    for child in node.named_children(&mut cursor) {
+        let row = child.start_position().row;
+        if child.kind().contains("comment") || child.kind() == "attribute_item" {
+            if prev_end.is_none_or(|end| row > end) {
+                lead.get_or_insert(row);
+            }
+            continue;
+        }
+        out.push((lead.take().unwrap_or(row), child));
+        prev_end = Some(child.end_position().row);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LanguageChunking;
+
+    const SOURCE: &str = "/// First.\nfn a() {\n    1;\n}\n\n/// Second.\nfn b() {\n    2;\n}\n\nfn c() {\n    3;\n}\n";
+
+    fn settings(chunking: Chunking, lines: usize, overlap: usize) -> Settings {
+        Settings {
+            chunking,
+            lines,
+            overlap,
+        }
+    }
+
+    fn ranges(path: &str, content: &str, settings: Settings) -> Vec<(usize, usize)> {
+        chunks(path, content, settings)
+            .into_iter()
+            .map(|(start, end, _)| (start, end))
+            .collect()
+    }
+
+    #[test]
+    fn line_windows_share_the_overlap() {
+        let content: String = (1..=25).map(|n| format!("line {}\n", n)).collect();
+        let chunks = chunks("notes.txt", &content, settings(Chunking::Lines, 10, 2));
+        let bounds: Vec<(usize, usize)> = chunks
+            .iter()
+            .map(|(start, end, _)| (*start, *end))
+            .collect();
+        assert_eq!(bounds, [(1, 10), (9, 18), (17, 25)]);
+        assert_eq!(chunks[1].2.lines().next(), Some("line 9"));
+    }
+
+    #[test]
+    fn empty_files_have_no_chunks() {
+        assert!(chunks("a.rs", "", settings(Chunking::Syntax, 10, 2)).is_empty());
+        assert!(chunks("a.txt", "", settings(Chunking::Lines, 10, 2)).is_empty());
+    }
+
+    #[test]
+    fn syntax_chunks_start_at_items_with_their_doc_comments() {
+        let chunks = chunks("lib.rs", SOURCE, settings(Chunking::Syntax, 5, 0));
+        let bounds: Vec<(usize, usize)> = chunks
+            .iter()
+            .map(|(start, end, _)| (*start, *end))
+            .collect();
+        assert_eq!(bounds, [(1, 5), (6, 10), (11, 13)]);
+        assert!(chunks[1].2.starts_with("/// Second.\nfn b()"));
+    }
+
+    #[test]
+    fn neighbouring_items_are_packed_up_to_the_limit() {
+        assert_eq!(
+            ranges("lib.rs", SOURCE, settings(Chunking::Syntax, 10, 0)),
+            [(1, 10), (11, 13)]
+        );
+        assert_eq!(
+            ranges("lib.rs", SOURCE, settings(Chunking::Syntax, 60, 0)),
+            [(1, 13)]
+        );
+    }
+
+    #[test]
+    fn long_items_are_split_within_the_limit() {
+        let body: String = (0..30)
+            .map(|n| format!("    let x{} = {};\n", n, n))
+            .collect();
+        let content = format!("fn long() {{\n{}}}\n\nfn short() {{}}\n", body);
+        let ranges = ranges("lib.rs", &content, settings(Chunking::Syntax, 8, 2));
+        assert!(
+            ranges.iter().all(|(start, end)| end + 1 - start <= 8),
+            "{:?}",
+            ranges
+        );
+        assert_eq!(ranges.first().map(|r| r.0), Some(1));
+        assert_eq!(ranges.last().map(|r| r.1), Some(content.lines().count()));
+        assert!(
+            ranges.windows(2).all(|w| w[1].0 <= w[0].1 + 1),
+            "no lines left out: {:?}",
+            ranges
+        );
+    }
+
+    #[test]
+    fn files_without_a_grammar_fall_back_to_windows() {
+        let content: String = (1..=12).map(|n| format!("{}\n", n)).collect();
+        assert_eq!(
+            ranges("notes.txt", &content, settings(Chunking::Syntax, 5, 1)),
+            [(1, 5), (5, 9), (9, 12)]
+        );
+    }
+
+    #[test]
+    fn settings_come_from_the_language_then_the_index_table() {
+        let mut config = IndexConfig {
+            chunk_lines: Some(40),
+            ..Default::default()
+        };
+        config.languages.insert(
+            "rust".into(),
+            LanguageChunking {
+                chunking: Some(Chunking::Lines),
+                chunk_overlap: Some(100),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            Settings::for_path("src/main.rs", &config),
+            settings(Chunking::Lines, 40, 39)
+        );
+        assert_eq!(
+            Settings::for_path("app.py", &config),
+            settings(Chunking::Syntax, 40, CHUNK_OVERLAP)
+        );
+        assert_eq!(
+            Settings::for_path("app.py", &config).fingerprint(),
+            "syntax:40:10"
+        );
+    }
+}
@@ -0,0 +1,248 @@
+//! Where embeddings come from. `[index] model` in the config picks one by spec:
+//!
+//! - a model id or `<provider>:<model>` — the OpenAI embeddings API, or any OpenAI-compatible
+//!   provider (`ollama:nomic-embed-text`)
+//! - `gemini:<model>` — Google's embeddings API, with `GEMINI_API_KEY` or `providers.gemini.api_key`
+//! - `local` or `local:<model>` — a fastembed ONNX model run on the machine (BGE small by
+//!   default), downloaded once into the data directory; needs the ONNX Runtime library
+//! - `hashed` — hashed identifiers and words: no model, network, or key, and lexical rather than
+//!   semantic matching
+
+use crate::agent::{self, OpenAiAgent};
+use crate::{config, http};
+use async_trait::async_trait;
+use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use serde_json::json;
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub const DEFAULT_MODEL: &str = "text-embedding-3-small";
+const GEMINI_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+/// Dimensions of `hashed` vectors.
+const HASHED_DIMS: usize = 512;
+/// The fastembed model loaded for `local`, kept for the rest of the process.
+static LOCAL_MODEL: Mutex<Option<(EmbeddingModel, TextEmbedding)>> = Mutex::new(None);
+
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    /// The spec stored with an index, so searches embed queries the same way.
+    fn spec(&self) -> &str;
+
+    /// One vector per input, in order.
+    async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, String>;
+}
+
+/// The embedder for `[index] model`, else the default OpenAI model.
+pub fn from_config(api_key: &str) -> Result<Box<dyn Embedder>, String> {
+    let spec = config::load()
+        .index
+        .model
+        .unwrap_or_else(|| DEFAULT_MODEL.to_string());
+    for_spec(&spec, api_key)
+}
+
+/// Whether `spec` embeds on the machine, needing no key.
+pub fn is_local(spec: &str) -> bool {
+    spec == "hashed" || spec == "local" || spec.starts_with("local:")
+}
+
+/// The embedder for `spec` (see the module docs); `api_key` is the OpenAI key.
+pub fn for_spec(spec: &str, api_key: &str) -> Result<Box<dyn Embedder>, String> {
+    if spec == "hashed" {
+        return Ok(Box::new(Hashed));
+    }
+    if is_local(spec) {
+        let model = match spec.strip_prefix("local:") {
+            Some(name) => name.parse::<EmbeddingModel>()?,
+            None => EmbeddingModel::default(),
+        };
+        return Ok(Box::new(Local {
+            spec: format!("local:{}", model),
+            model,
+        }));
+    }
+    if let Some(model) = spec.strip_prefix("gemini:") {
+        return Ok(Box::new(Gemini {
+            spec: spec.to_string(),
+            model: model.to_string(),
+        }));
+    }
+    Ok(Box::new(OpenAi {
+        spec: spec.to_string(),
+        agent: agent::from_spec(spec, api_key)?,
+    }))
+}
+
+struct OpenAi {
+    spec: String,
+    agent: OpenAiAgent,
+}
+
+#[async_trait]
+impl Embedder for OpenAi {
+    fn spec(&self) -> &str {
+        &self.spec
+    }
+
+    async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        self.agent.embed(inputs).await
+    }
+}
+
+struct Gemini {
+    spec: String,
+    model: String,
+}
+
+#[async_trait]
+impl Embedder for Gemini {
+    fn spec(&self) -> &str {
+        &self.spec
+    }
+
+    async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        let key = std::env::var("GEMINI_API_KEY")
+            .ok()
+            .or_else(|| {
+                config::load()
+                    .providers
+                    .remove("gemini")
+                    .and_then(|p| p.api_key)
+            })
+            .ok_or("No Gemini key: set GEMINI_API_KEY or providers.gemini.api_key")?;
+        let name = format!("models/{}", self.model);
+        let requests: Vec<_> = inputs
+            .iter()
+            .map(|text| json!({ "model": name, "content": { "parts": [{ "text": text }] } }))
+            .collect();
+        let res = http::client()
+            .post(format!("{}/{}:batchEmbedContents", GEMINI_URL, name))
+            .header("x-goog-api-key", key)
+            .timeout(Duration::from_secs(60))
+            .json(&json!({ "requests": requests }))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let status = res.status();
+        let body: serde_json::Value = res.json().await.map_err(|e| e.to_string())?;
+        if !status.is_success() {
+            return Err(format!(
+                "Gemini API error: {}",
+                body["error"]["message"].as_str().unwrap_or(status.as_str())
+            ));
+        }
+        body["embeddings"]
+            .as_array()
+            .ok_or("No embeddings in response")?
+            .iter()
+            .map(|e| {
+                e["values"]
+                    .as_array()
+                    .map(|a| {
+                        a.iter()
+                            .filter_map(|x| x.as_f64())
+                            .map(|x| x as f32)
+                            .collect()
+                    })
+                    .ok_or_else(|| "Malformed embedding in response".to_string())
+            })
+            .collect()
+    }
+}
+
+struct Local {
+    /// `local:<model>`, so an index records which model it was built with.
+    spec: String,
+    model: EmbeddingModel,
+}
+
+#[async_trait]
+impl Embedder for Local {
+    fn spec(&self) -> &str {
+        &self.spec
+    }
+
+    async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        let model = self.model.clone();
+        let inputs = inputs.to_vec();
+        // Inference is CPU-bound; a missing ONNX Runtime library panics inside `ort`.
+        tokio::task::spawn_blocking(move || {
+            let mut loaded = LOCAL_MODEL.lock().unwrap_or_else(|e| e.into_inner());
+            if loaded.as_ref().is_none_or(|(m, _)| *m != model) {
+                let mut options = InitOptions::new(model.clone()).with_show_download_progress(false);
+                if let Some(dir) = config::data_dir() {
+                    options = options.with_cache_dir(dir.join("models"));
+                }
+                let embedding = TextEmbedding::try_new(options).map_err(|e| format!("Local embedding model {}: {}", model, e))?;
+                *loaded = Some((model, embedding));
+            }
+            let (_, embedding) = loaded.as_mut().expect("model loaded above");
+            embedding.embed(&inputs, None).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|_| "Local embeddings need the ONNX Runtime library: install onnxruntime or set ORT_DYLIB_PATH to it".to_string())?
+    }
+}
+
+struct Hashed;
+
+#[async_trait]
+impl Embedder for Hashed {
+    fn spec(&self) -> &str {
+        "hashed"
+    }
+
+    async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        Ok(inputs.iter().map(|text| hashed(text)).collect())
+    }
+}
+
+/// Feature-hashed counts of the words in `text`, with identifiers also split into their parts
+/// (`parseConfig`, `parse_config` → `parse`, `config`), normalized to unit length.
+fn hashed(text: &str) -> Vec<f32> {
+    let mut vector = vec![0f32; HASHED_DIMS];
+    let mut add = |token: &str| {
+        let hash = fnv1a(token.to_lowercase().as_bytes());
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        vector[(hash % HASHED_DIMS as u64) as usize] += sign;
+    };
+    for word in text
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|w| w.len() > 1)
+    {
+        add(word);
+        let parts = word_parts(word);
+        if parts.len() > 1 {
+            parts.iter().filter(|p| p.len() > 1).for_each(|p| add(p));
+        }
+    }
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+/// `word` split at underscores and lower-to-upper case changes.
+fn word_parts(word: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut prev_lower = false;
+    for (i, c) in word.char_indices() {
+        if c == '_' || (c.is_uppercase() && prev_lower) {
+            parts.push(&word[start..i]);
+            start = if c == '_' { i + 1 } else { i };
+        }
+        prev_lower = c.is_lowercase() || c.is_ascii_digit();
+    }
+    parts.push(&word[start..]);
+    parts.retain(|p| !p.is_empty());
+    parts
+}
+
+/// 64-bit FNV-1a: stable across builds, unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
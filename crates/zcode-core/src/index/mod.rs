@@ -0,0 +1,372 @@
+//! Local semantic index of the workspace (`zcode index`): source files are split into line
+//! chunks, embedded (see `embedder`), and kept in `.zcode/index/` (see `store`) for semantic
+//! search and context ranking. Lockfiles, vendored code, and generated files are skipped (see
+//! `exclude`).
+
+pub mod chunking;
+pub mod embedder;
+pub mod store;
+
+pub use embedder::Embedder;
+
+use crate::config;
+use crate::context;
+use crate::exclude::{self, Filter};
+use crate::ui;
+use chunking::Settings;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// Files larger than this are skipped (generated code, data, bundles).
+const MAX_FILE_BYTES: u64 = 256 * 1024;
+/// Chunks sent per embeddings request.
+const BATCH: usize = 64;
+/// Characters of a chunk sent for embedding (keeps requests under the model's input limit).
+const MAX_CHUNK_CHARS: usize = 6000;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Chunk {
+    pub path: String,
+    /// 1-based inclusive line range.
+    pub start: usize,
+    pub end: usize,
+    /// SHA-256 of the embedded text, to reuse the vector while the text is unchanged.
+    #[serde(default)]
+    pub hash: String,
+    /// Stored as a blob (see `store`).
+    #[serde(skip)]
+    pub vector: Vec<f32>,
+}
+
+#[derive(Debug)]
+pub struct Index {
+    pub workspace: String,
+    /// The embedder spec the chunks were embedded with.
+    pub model: String,
+    /// Unix seconds when the index was built.
+    pub built: u64,
+    pub files: usize,
+    /// Indexed files by path, to tell which changed since.
+    pub sources: BTreeMap<String, Source>,
+    pub chunks: Vec<Chunk>,
+}
+
+/// An indexed file as it was when embedded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Source {
+    /// Unix milliseconds.
+    pub modified: u64,
+    pub size: u64,
+    /// SHA-256 of the content.
+    pub hash: String,
+    /// The chunking settings it was split with (see `chunking::Settings::fingerprint`).
+    #[serde(default)]
+    pub chunking: String,
+}
+
+/// Whether `content` looks like text worth indexing.
+fn is_text(content: &str) -> bool {
+    !content.contains('\0')
+}
+
+pub fn load(workspace: &Path) -> Option<Index> {
+    store::load(workspace)
+}
+
+/// What bringing an index up to date did.
+#[derive(Debug, Default)]
+pub struct Update {
+    /// Chunks sent to the embedder.
+    pub embedded: usize,
+    /// Chunks whose text had not changed, kept with their vectors.
+    pub reused: usize,
+    /// Files that are gone or no longer indexed.
+    pub removed: usize,
+}
+
+/// Walk the workspace and store an up-to-date index. Only chunks whose text changed since the
+/// last build are embedded again, unless the configured embedder differs from the stored one.
+pub async fn build(api_key: &str, workspace: &Path) -> Result<(Index, Update), String> {
+    let embedder = embedder::from_config(api_key)?;
+    let previous = load(workspace).filter(|index| index.model == embedder.spec());
+    let (index, update) = sync(embedder.as_ref(), workspace, previous).await?;
+    store::save(workspace, &index)?;
+    Ok((index, update))
+}
+
+/// Bring an existing index up to date (files the agent or the user changed), quietly. Does
+/// nothing without an index, or when it was built with another embedder than the configured one.
+pub async fn refresh(api_key: &str, workspace: &Path) {
+    let Some(previous) = load(workspace) else {
+        return;
+    };
+    let embedder = match embedder::from_config(api_key) {
+        Ok(embedder) => embedder,
+        Err(e) => {
+            warn!(error = %e, "could not update the index");
+            return;
+        }
+    };
+    if previous.model != embedder.spec() {
+        info!(index = %previous.model, configured = %embedder.spec(), "index built with another embedder; not updating");
+        return;
+    }
+    let result = sync(embedder.as_ref(), workspace, Some(previous)).await;
+    match result.and_then(|(index, update)| store::save(workspace, &index).map(|_| update)) {
+        Ok(update) => info!(
+            embedded = update.embedded,
+            reused = update.reused,
+            removed = update.removed,
+            "index updated"
+        ),
+        Err(e) => warn!(error = %e, "could not update the index"),
+    }
+}
+
+/// The index for the workspace as it is now, reusing `previous` for unchanged files and chunks.
+async fn sync(
+    embedder: &dyn Embedder,
+    workspace: &Path,
+    previous: Option<Index>,
+) -> Result<(Index, Update), String> {
+    let (mut old_sources, old_chunks) = previous.map(|i| (i.sources, i.chunks)).unwrap_or_default();
+    let mut old_by_path: HashMap<String, Vec<Chunk>> = HashMap::new();
+    let mut old_vectors: HashMap<String, Vec<f32>> = HashMap::new();
+    for chunk in old_chunks {
+        old_vectors
+            .entry(chunk.hash.clone())
+            .or_insert_with(|| chunk.vector.clone());
+        old_by_path
+            .entry(chunk.path.clone())
+            .or_default()
+            .push(chunk);
+    }
+
+    let mut update = Update::default();
+    let mut sources = BTreeMap::new();
+    let mut chunks = Vec::new();
+    // Chunks still to embed: their place in `chunks` and their text.
+    let mut pending: Vec<(usize, String)> = Vec::new();
+    let filter = Filter::load(workspace);
+    let config = config::load().index;
+    for file in context::workspace_files(workspace) {
+        if filter.excludes(&file) {
+            continue;
+        }
+        let full = workspace.join(&file);
+        let Ok(meta) = fs::metadata(&full) else {
+            continue;
+        };
+        if meta.len() > MAX_FILE_BYTES {
+            continue;
+        }
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_millis() as u64);
+        let settings = Settings::for_path(&file, &config);
+        let fingerprint = settings.fingerprint();
+        let old = old_sources
+            .remove(&file)
+            .filter(|o| o.chunking == fingerprint);
+        let stat_unchanged = old
+            .as_ref()
+            .is_some_and(|o| o.modified == modified && o.size == meta.len());
+        if stat_unchanged {
+            if let (Some(source), Some(kept)) = (old.clone(), old_by_path.remove(&file)) {
+                update.reused += kept.len();
+                chunks.extend(kept);
+                sources.insert(file, source);
+                continue;
+            }
+        }
+        let Ok(content) = fs::read_to_string(&full) else {
+            continue;
+        };
+        if !is_text(&content) || content.trim().is_empty() || exclude::looks_generated(&content) {
+            continue;
+        }
+        let source = Source {
+            modified,
+            size: meta.len(),
+            hash: digest(&content),
+            chunking: fingerprint,
+        };
+        if let Some(kept) = old
+            .filter(|o| o.hash == source.hash)
+            .and_then(|_| old_by_path.remove(&file))
+        {
+            update.reused += kept.len();
+            chunks.extend(kept);
+            sources.insert(file, source);
+            continue;
+        }
+        for (start, end, text) in chunking::chunks(&file, &content, settings) {
+            let text: String = format!("{}\n{}", file, text)
+                .chars()
+                .take(MAX_CHUNK_CHARS)
+                .collect();
+            let hash = digest(&text);
+            let vector = match old_vectors.get(&hash) {
+                Some(vector) => {
+                    update.reused += 1;
+                    vector.clone()
+                }
+                None => {
+                    pending.push((chunks.len(), text));
+                    Vec::new()
+                }
+            };
+            chunks.push(Chunk {
+                path: file.clone(),
+                start,
+                end,
+                hash,
+                vector,
+            });
+        }
+        sources.insert(file, source);
+    }
+    update.removed = old_sources.len();
+    update.embedded = pending.len();
+
+    for (i, batch) in pending.chunks(BATCH).enumerate() {
+        let inputs: Vec<String> = batch.iter().map(|(_, text)| text.clone()).collect();
+        let label = format!("Embedding {}/{}", (i * BATCH + batch.len()), pending.len());
+        let vectors = ui::with_spinner(&label, embedder.embed(&inputs)).await?;
+        if vectors.len() != batch.len() {
+            return Err(format!(
+                "Expected {} embeddings, got {}",
+                batch.len(),
+                vectors.len()
+            ));
+        }
+        for ((at, _), vector) in batch.iter().zip(vectors) {
+            chunks[*at].vector = vector;
+        }
+    }
+
+    let index = Index {
+        workspace: workspace.display().to_string(),
+        model: embedder.spec().to_string(),
+        built: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        files: sources.len(),
+        sources,
+        chunks,
+    };
+    Ok((index, update))
+}
+
+/// Hex SHA-256 of `text`.
+fn digest(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denom = norm(a) * norm(b);
+    if denom == 0.0 {
+        0.0
+    } else {
+        dot / denom
+    }
+}
+
+/// The `k` chunks most similar to `query`, best first, with their scores.
+pub async fn search<'a>(
+    api_key: &str,
+    index: &'a Index,
+    query: &str,
+    k: usize,
+) -> Result<Vec<(&'a Chunk, f32)>, String> {
+    let query = embedder::for_spec(&index.model, api_key)?
+        .embed(&[query.to_string()])
+        .await?
+        .pop()
+        .ok_or("No embedding returned")?;
+    if index
+        .chunks
+        .first()
+        .is_some_and(|c| c.vector.len() != query.len())
+    {
+        return Err(format!(
+            "The index was built with another embedder than {}; run `zcode index` again",
+            index.model
+        ));
+    }
+    let mut scored: Vec<(&Chunk, f32)> = index
+        .chunks
+        .iter()
+        .map(|c| (c, cosine(&c.vector, &query)))
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.truncate(k);
+    Ok(scored)
+}
+
+/// A chunk's current lines as a context block, or None if the file changed too much.
+pub fn chunk_block(workspace: &Path, chunk: &Chunk) -> Option<String> {
+    let content = fs::read_to_string(workspace.join(&chunk.path)).ok()?;
+    let lines: Vec<&str> = content.lines().collect();
+    let end = chunk.end.min(lines.len());
+    if chunk.start > end {
+        return None;
+    }
+    Some(format!(
+        "--- {}:{}-{} ---\n{}",
+        chunk.path,
+        chunk.start,
+        end,
+        lines[chunk.start - 1..end].join("\n")
+    ))
+}
+
+/// Print what is indexed for `workspace`.
+pub fn status(workspace: &Path) {
+    match load(workspace) {
+        Some(index) => {
+            let size = store::size(workspace);
+            let age = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs().saturating_sub(index.built))
+                .unwrap_or(0);
+            ui::info(&format!(
+                "{} files, {} chunks ({}), built {} ago, {:.1} MB at {}",
+                index.files,
+                index.chunks.len(),
+                index.model,
+                format_age(age),
+                size as f64 / 1_048_576.0,
+                store::dir(workspace).display()
+            ));
+        }
+        None => ui::info("No index for this workspace. Run `zcode index` to build one."),
+    }
+}
+
+fn format_age(secs: u64) -> String {
+    match secs {
+        s if s < 120 => format!("{}s", s),
+        s if s < 7200 => format!("{}m", s / 60),
+        s if s < 172_800 => format!("{}h", s / 3600),
+        s => format!("{}d", s / 86_400),
+    }
+}
+
+/// Delete the index for `workspace`. Returns whether there was one.
+pub fn clear(workspace: &Path) -> Result<bool, String> {
+    store::clear(workspace)
+}
@@ -0,0 +1,186 @@
+//! On-disk vector store in the workspace's `.zcode/index/`: a SQLite database with the index
+//! settings, the indexed files, and the chunks with their vectors as little-endian `f32` blobs.
+
+use super::{Chunk, Index, Source};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const INDEX_DIR: &str = ".zcode/index";
+const DB_FILE: &str = "index.sqlite";
+/// Files of the earlier JSON-and-blob format, removed when the index is next saved.
+const LEGACY_FILES: [&str; 2] = ["chunks.json", "vectors.bin"];
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS sources (
+        path TEXT PRIMARY KEY,
+        modified INTEGER NOT NULL,
+        size INTEGER NOT NULL,
+        hash TEXT NOT NULL,
+        chunking TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS chunks (
+        path TEXT NOT NULL,
+        start INTEGER NOT NULL,
+        end INTEGER NOT NULL,
+        hash TEXT NOT NULL,
+        vector BLOB NOT NULL
+    );
+";
+
+pub fn dir(workspace: &Path) -> PathBuf {
+    workspace.join(INDEX_DIR)
+}
+
+fn open(workspace: &Path) -> Result<Connection, String> {
+    let path = dir(workspace).join(DB_FILE);
+    let db = Connection::open(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    db.execute_batch(SCHEMA)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(db)
+}
+
+pub fn load(workspace: &Path) -> Option<Index> {
+    if !dir(workspace).join(DB_FILE).is_file() {
+        return None;
+    }
+    read(&open(workspace).ok()?).ok().flatten()
+}
+
+fn read(db: &Connection) -> rusqlite::Result<Option<Index>> {
+    let meta = |key: &str| -> rusqlite::Result<Option<String>> {
+        db.query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| {
+            row.get(0)
+        })
+        .optional()
+    };
+    let (Some(workspace), Some(model)) = (meta("workspace")?, meta("model")?) else {
+        return Ok(None);
+    };
+    let number = |value: Option<String>| value.and_then(|v| v.parse().ok()).unwrap_or(0);
+    let built = number(meta("built")?) as u64;
+    let files = number(meta("files")?);
+
+    let mut sources = BTreeMap::new();
+    let mut query = db.prepare("SELECT path, modified, size, hash, chunking FROM sources")?;
+    let rows = query.query_map([], |row| {
+        let source = Source {
+            modified: row.get::<_, i64>(1)? as u64,
+            size: row.get::<_, i64>(2)? as u64,
+            hash: row.get(3)?,
+            chunking: row.get(4)?,
+        };
+        Ok((row.get::<_, String>(0)?, source))
+    })?;
+    for row in rows {
+        let (path, source) = row?;
+        sources.insert(path, source);
+    }
+
+    let mut query =
+        db.prepare("SELECT path, start, end, hash, vector FROM chunks ORDER BY rowid")?;
+    let chunks = query
+        .query_map([], |row| {
+            let bytes: Vec<u8> = row.get(4)?;
+            Ok(Chunk {
+                path: row.get(0)?,
+                start: row.get::<_, i64>(1)? as usize,
+                end: row.get::<_, i64>(2)? as usize,
+                hash: row.get(3)?,
+                vector: bytes
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect(),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(Some(Index {
+        workspace,
+        model,
+        built,
+        files,
+        sources,
+        chunks,
+    }))
+}
+
+/// Replace the stored index. The directory gets a `.gitignore` so the index is not committed.
+pub fn save(workspace: &Path, index: &Index) -> Result<(), String> {
+    let dir = dir(workspace);
+    fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let _ = fs::write(dir.join(".gitignore"), "*\n");
+    let dims = index.chunks.first().map_or(0, |c| c.vector.len());
+    if let Some(chunk) = index.chunks.iter().find(|c| c.vector.len() != dims) {
+        return Err(format!(
+            "{}:{}: embedding has {} dimensions, expected {}",
+            chunk.path,
+            chunk.start,
+            chunk.vector.len(),
+            dims
+        ));
+    }
+    let mut db = open(workspace)?;
+    write(&mut db, index).map_err(|e| format!("{}: {}", dir.join(DB_FILE).display(), e))?;
+    for file in LEGACY_FILES {
+        let _ = fs::remove_file(dir.join(file));
+    }
+    Ok(())
+}
+
+fn write(db: &mut Connection, index: &Index) -> rusqlite::Result<()> {
+    let tx = db.transaction()?;
+    tx.execute_batch("DELETE FROM meta; DELETE FROM sources; DELETE FROM chunks;")?;
+    {
+        let mut insert = tx.prepare("INSERT INTO meta (key, value) VALUES (?1, ?2)")?;
+        let meta = [
+            ("workspace", index.workspace.clone()),
+            ("model", index.model.clone()),
+            ("built", index.built.to_string()),
+            ("files", index.files.to_string()),
+        ];
+        for (key, value) in meta {
+            insert.execute(params![key, value])?;
+        }
+        let mut insert = tx.prepare("INSERT INTO sources (path, modified, size, hash, chunking) VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        for (path, source) in &index.sources {
+            insert.execute(params![
+                path,
+                source.modified as i64,
+                source.size as i64,
+                source.hash,
+                source.chunking
+            ])?;
+        }
+        let mut insert = tx.prepare(
+            "INSERT INTO chunks (path, start, end, hash, vector) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for chunk in &index.chunks {
+            let vector: Vec<u8> = chunk.vector.iter().flat_map(|v| v.to_le_bytes()).collect();
+            insert.execute(params![
+                chunk.path,
+                chunk.start as i64,
+                chunk.end as i64,
+                chunk.hash,
+                vector
+            ])?;
+        }
+    }
+    tx.commit()
+}
+
+/// Bytes the stored index takes on disk.
+pub fn size(workspace: &Path) -> u64 {
+    fs::metadata(dir(workspace).join(DB_FILE)).map_or(0, |m| m.len())
+}
+
+/// Delete the stored index. Returns whether there was one.
+pub fn clear(workspace: &Path) -> Result<bool, String> {
+    let dir = dir(workspace);
+    if !dir.exists() {
+        return Ok(false);
+    }
+    fs::remove_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    Ok(true)
+}
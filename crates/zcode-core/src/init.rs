@@ -0,0 +1,73 @@
+//! `zcode init`: scaffold the global config, a project `.zcode.toml`, and a starter `ZCODE.md`.
+
+use crate::auth::PROVIDERS;
+use crate::config;
+use crate::ui;
+use std::fs;
+use std::path::Path;
+
+const PROJECT_CONFIG: &str = r#"# Project settings for zcode; these override ~/.config/zcode/config.toml.
+# planner_model = "gpt-4o-mini"
+# executor_model = "gpt-4o"
+# Commands run after each task to verify it (detected from the build files by default).
+# verify = ["cargo build", "cargo test"]
+# Paths kept out of context, gitignore-style.
+# exclude = ["fixtures/", "*.snap"]
+# [tools]
+# allow = ["read_file", "list_dir", "write_file", "run_command"]
+# allow_commands = ["cargo *", "git status", "git diff*"]
+# Commands run before or after tool calls, with the call as JSON on stdin.
+# [hooks.pre_tool]
+# run_command = "scripts/zcode-policy.sh"
+"#;
+
+const INSTRUCTIONS: &str = r#"# Project instructions for zcode
+
+zcode includes this file with every task. Describe what an agent needs to know here:
+
+- How to build and test (e.g. `cargo test`)
+- Code style and conventions
+- Directories or files that must not be modified
+"#;
+
+pub fn run(workspace: &Path, force: bool) -> Result<(), String> {
+    let config_dir = config::config_dir().ok_or("No config directory")?;
+    let config_path = config_dir.join("config.toml");
+
+    if config_path.exists() && !force {
+        ui::info(&format!("Keeping existing {}", config_path.display()));
+    } else {
+        let provider = ui::select("Provider", PROVIDERS, 0)?;
+        let storage = ui::select(
+            "Where should the API key live?",
+            &[
+                "In the config file",
+                "In an environment variable (OPENAI_API_KEY), not stored",
+            ],
+            0,
+        )?;
+        let mut content = format!("provider = \"{}\"\n", PROVIDERS[provider]);
+        if storage == 0 {
+            let key = ui::password("API key")?;
+            content.push_str(&format!("api_key = \"{}\"\n", key.trim()));
+        }
+        fs::create_dir_all(&config_dir).map_err(|e| e.to_string())?;
+        fs::write(&config_path, content).map_err(|e| e.to_string())?;
+        config::restrict_permissions(&config_path);
+        ui::info(&format!("Wrote {}", config_path.display()));
+    }
+
+    write_if_missing(&workspace.join(".zcode.toml"), PROJECT_CONFIG, force)?;
+    write_if_missing(&workspace.join("ZCODE.md"), INSTRUCTIONS, force)?;
+    Ok(())
+}
+
+fn write_if_missing(path: &Path, content: &str, force: bool) -> Result<(), String> {
+    if path.exists() && !force {
+        ui::info(&format!("Keeping existing {}", path.display()));
+        return Ok(());
+    }
+    fs::write(path, content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    ui::info(&format!("Wrote {}", path.display()));
+    Ok(())
+}
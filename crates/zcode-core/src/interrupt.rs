@@ -0,0 +1,52 @@
+//! Ctrl-C handling: the first press cancels the current model stream or tool, a second press
+//! within `DOUBLE_PRESS` asks the frontend to exit.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+const DOUBLE_PRESS: Duration = Duration::from_millis(1500);
+
+/// Incremented on every Ctrl-C; long-running work compares it to notice interrupts.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+static NOTIFY: Notify = Notify::const_new();
+static LAST_PRESS: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Replace the default SIGINT behavior (kill the process) with cancellation; a double press
+/// calls `exit` with 130.
+pub fn install(exit: fn(i32)) {
+    tokio::spawn(async move {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            let now = Instant::now();
+            let mut last = LAST_PRESS.lock().unwrap();
+            if last.is_some_and(|t| now.duration_since(t) < DOUBLE_PRESS) {
+                exit(130);
+            }
+            *last = Some(now);
+            trigger();
+        }
+    });
+}
+
+/// Cancel the current model stream or tool as Ctrl-C would (e.g. on a client's request).
+pub fn trigger() {
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    NOTIFY.notify_waiters();
+}
+
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Run `future` until it completes (Some) or Ctrl-C is pressed (None).
+pub async fn cancellable<F, T>(future: F) -> Option<T>
+where
+    F: Future<Output = T>,
+{
+    tokio::select! {
+        r = future => Some(r),
+        _ = NOTIFY.notified() => None,
+    }
+}
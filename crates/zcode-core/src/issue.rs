@@ -0,0 +1,93 @@
+//! `zcode issue <number|url>`: work on a GitHub issue on a fresh branch and report back on it.
+
+use crate::agent::Agent;
+use crate::context;
+use crate::git::{self, git};
+use crate::github::{self, Client};
+use crate::run::{self, Agents};
+use crate::tools::Executor;
+use crate::ui;
+
+/// GitHub rejects comments longer than 65536 characters; leave room for the text around the diff.
+const DIFF_CAP: usize = 60_000;
+
+pub async fn run<P: Agent, E: Agent>(
+    agents: &Agents<P, E>,
+    executor: &Executor,
+    reference: &str,
+    comment: bool,
+) -> Result<bool, String> {
+    let workspace = executor.workspace();
+    let (repo, number) = github::parse_issue(workspace, reference)?;
+    if git::is_dirty(workspace)? {
+        return Err("The working tree has uncommitted changes; commit or stash them first".into());
+    }
+
+    let client = Client::new()?;
+    let issue = ui::with_spinner(
+        &format!("Fetching {}#{}", repo, number),
+        client.get(&format!("/repos/{}/issues/{}", repo, number)),
+    )
+    .await?;
+    let comments = client
+        .get(&format!(
+            "/repos/{}/issues/{}/comments?per_page=100",
+            repo, number
+        ))
+        .await?;
+    let title = issue["title"].as_str().unwrap_or_default();
+    let mut prompt = format!(
+        "Resolve GitHub issue {}#{}: {}\n\n{}",
+        repo,
+        number,
+        title,
+        issue["body"].as_str().unwrap_or("(no description)")
+    );
+    for c in comments.as_array().into_iter().flatten() {
+        prompt.push_str(&format!(
+            "\n\nComment by {}:\n{}",
+            c["user"]["login"].as_str().unwrap_or("someone"),
+            c["body"].as_str().unwrap_or_default()
+        ));
+    }
+
+    let branch = format!("zcode/issue-{}", number);
+    git(workspace, &["checkout", "-b", &branch])?;
+    ui::phase_done(&format!("On new branch {}", branch));
+
+    let reply = run::run_once(agents, executor, &prompt).await;
+    let changes = executor.changes();
+    ui::changes_summary("Changed", &changes);
+    if changes.is_empty() {
+        ui::note("No files were changed; nothing to report on the issue.");
+        return Ok(false);
+    }
+    if !comment {
+        return Ok(true);
+    }
+
+    let diff: String = changes
+        .iter()
+        .map(ui::plain_diff)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let diff = context::truncate(&diff, DIFF_CAP);
+    let body = format!(
+        "zcode worked on this issue on branch `{}` ({} files changed, not yet pushed).\n\n{}\n\n<details><summary>Diff</summary>\n\n```diff\n{}\n```\n</details>",
+        branch,
+        changes.len(),
+        reply.as_deref().unwrap_or("").trim(),
+        diff
+    );
+    let posted = client
+        .post(
+            &format!("/repos/{}/issues/{}/comments", repo, number),
+            &serde_json::json!({ "body": body }),
+        )
+        .await?;
+    ui::info(&format!(
+        "Commented: {}",
+        posted["html_url"].as_str().unwrap_or_default()
+    ));
+    Ok(true)
+}
@@ -0,0 +1,74 @@
+/// Terminal output goes through these so it stays silent until a frontend calls
+/// `ui::attach_terminal`.
+macro_rules! out {
+    ($($arg:tt)*) => { $crate::ui::write_out(format_args!($($arg)*)) };
+}
+macro_rules! outln {
+    () => { out!("\n") };
+    ($($arg:tt)*) => { out!("{}\n", format_args!($($arg)*)) };
+}
+macro_rules! eout {
+    ($($arg:tt)*) => { $crate::ui::write_err(format_args!($($arg)*)) };
+}
+macro_rules! eoutln {
+    ($($arg:tt)*) => { eout!("{}\n", format_args!($($arg)*)) };
+}
+
+pub mod agent;
+pub mod approval;
+pub mod auth;
+pub mod batch;
+pub mod bench;
+pub mod changelog;
+pub mod ci;
+pub mod clipboard;
+pub mod config;
+pub mod context;
+pub mod dataset;
+pub mod doctor;
+pub mod eval;
+pub mod events;
+pub mod exclude;
+pub mod explain;
+pub mod fix;
+pub mod git;
+pub mod github;
+pub mod hooks;
+pub mod http;
+pub mod index;
+pub mod init;
+pub mod interrupt;
+pub mod issue;
+pub mod logging;
+pub mod memory;
+pub mod notify;
+pub mod options;
+pub mod plan_cache;
+pub mod plan_review;
+pub mod plugins;
+pub mod pr;
+pub mod queue;
+pub mod ranking;
+pub mod rename;
+pub mod repomap;
+pub mod report;
+pub mod roles;
+pub mod routing;
+pub mod rpc;
+pub mod run;
+pub mod run_state;
+pub mod session;
+pub mod shutdown;
+pub mod slicing;
+pub mod stats;
+pub mod steering;
+pub mod templates;
+pub mod testgen;
+pub mod tokens;
+pub mod tools;
+pub mod ui;
+pub mod update;
+pub mod upgrade;
+pub mod usage;
+pub mod verify;
+pub mod webhook;
@@ -0,0 +1,44 @@
+//! Structured logging via `tracing`: a daily-rolling log file under the data dir, plus stderr
+//! output with `-v` (info), `-vv` (debug), `-vvv` (trace). Request bodies are never logged.
+
+use crate::config;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+
+/// Install the subscriber. Keep the returned guard alive so the file writer flushes on exit.
+pub fn init(verbose: u8) -> Option<WorkerGuard> {
+    let level = match verbose {
+        0 | 1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    // Keep dependencies (hyper, rustls) quiet unless explicitly asked for via RUST_LOG.
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(format!("zcode={}", level)));
+
+    let stderr = (verbose > 0).then(|| {
+        tracing_subscriber::fmt::layer()
+            .with_writer(std::io::stderr)
+            .with_target(false)
+    });
+
+    let (file, guard) = match config::data_dir().map(|d| d.join("logs")) {
+        Some(dir) if std::fs::create_dir_all(&dir).is_ok() => {
+            let appender = tracing_appender::rolling::daily(dir, "zcode.log");
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let layer = tracing_subscriber::fmt::layer()
+                .with_writer(writer)
+                .with_ansi(false);
+            (Some(layer), Some(guard))
+        }
+        _ => (None, None),
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(stderr)
+        .with(file)
+        .init();
+    guard
+}
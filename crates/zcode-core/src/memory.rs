@@ -0,0 +1,128 @@
+//! Project memory: notes the model saves with `save_memory` and looks up with
+//! `recall_memory`, stored in the data dir per workspace so they carry across sessions.
+
+use crate::config;
+use crate::slicing;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Longest note kept.
+const MAX_TEXT: usize = 4 * 1024;
+/// Notes kept per workspace; the least recently updated go first.
+const MAX_ENTRIES: usize = 200;
+/// Notes returned by one recall.
+const RECALL_LIMIT: usize = 8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub text: String,
+    /// Seconds since the epoch.
+    pub updated: u64,
+}
+
+fn memory_path(workspace: &Path) -> Option<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    workspace.hash(&mut hasher);
+    let name = workspace
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    config::data_dir().map(|d| {
+        d.join("memory")
+            .join(format!("{}-{:016x}.json", name, hasher.finish()))
+    })
+}
+
+/// Every note for `workspace`, by key.
+pub fn load(workspace: &Path) -> BTreeMap<String, Entry> {
+    memory_path(workspace)
+        .and_then(|p| fs::read_to_string(p).ok())
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default()
+}
+
+/// Save (or replace) the note under `key`.
+pub fn save(workspace: &Path, key: &str, text: &str) -> Result<(), String> {
+    let key = key.trim();
+    if key.is_empty() || text.trim().is_empty() {
+        return Err("key and text must not be empty".into());
+    }
+    if text.len() > MAX_TEXT {
+        return Err(format!(
+            "text is {} bytes; keep notes under {}",
+            text.len(),
+            MAX_TEXT
+        ));
+    }
+    let path = memory_path(workspace).ok_or("No data directory")?;
+    let mut entries = load(workspace);
+    let updated = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    entries.insert(
+        key.to_string(),
+        Entry {
+            text: text.trim().to_string(),
+            updated,
+        },
+    );
+    while entries.len() > MAX_ENTRIES {
+        let oldest = entries
+            .iter()
+            .min_by_key(|(_, e)| e.updated)
+            .map(|(k, _)| k.clone());
+        entries.remove(&oldest.unwrap_or_default());
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let content = serde_json::to_string_pretty(&entries).map_err(|e| e.to_string())?;
+    fs::write(&path, content).map_err(|e| e.to_string())
+}
+
+/// Notes matching the words of `query`, best first; the most recent notes when nothing
+/// matches or the query is empty.
+pub fn recall(workspace: &Path, query: &str) -> Vec<(String, Entry)> {
+    let terms = slicing::terms(query);
+    let mut scored: Vec<(usize, String, Entry)> = load(workspace)
+        .into_iter()
+        .map(|(key, entry)| {
+            let (k, t) = (key.to_lowercase(), entry.text.to_lowercase());
+            let score = terms
+                .iter()
+                .map(|w| {
+                    2 * usize::from(k.contains(w.as_str())) + usize::from(t.contains(w.as_str()))
+                })
+                .sum();
+            (score, key, entry)
+        })
+        .collect();
+    if scored.iter().any(|(score, ..)| *score > 0) {
+        scored.retain(|(score, ..)| *score > 0);
+    }
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(b.2.updated.cmp(&a.2.updated)));
+    scored
+        .into_iter()
+        .take(RECALL_LIMIT)
+        .map(|(_, key, entry)| (key, entry))
+        .collect()
+}
+
+/// `recall` as tool output.
+pub fn recall_text(workspace: &Path, query: &str) -> String {
+    let notes = recall(workspace, query);
+    if notes.is_empty() {
+        return "No saved memories for this project.".into();
+    }
+    notes
+        .iter()
+        .map(|(key, entry)| format!("## {}\n{}", key, entry.text))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
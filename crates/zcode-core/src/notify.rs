@@ -0,0 +1,43 @@
+//! Opt-in completion notifications (terminal bell and/or desktop notification) for runs that
+//! take longer than `notify_after` seconds. Configured with `notify = "bell" | "desktop" | "both"`
+//! in `[ui]`.
+
+use crate::config::{self, NotifyMode};
+use notify_rust::Notification;
+use std::time::Duration;
+use tracing::debug;
+
+const DEFAULT_THRESHOLD_SECS: u64 = 30;
+
+/// Notify that a run finished, if enabled and it took long enough to warrant it.
+pub fn run_finished(title: &str, body: &str, elapsed: Duration) {
+    if crate::ci::enabled() {
+        return;
+    }
+    let ui = config::load().ui;
+    let Some(mode) = ui.notify else {
+        return;
+    };
+    let threshold = ui.notify_after.unwrap_or(DEFAULT_THRESHOLD_SECS);
+    if elapsed.as_secs() < threshold {
+        return;
+    }
+    if matches!(mode, NotifyMode::Bell | NotifyMode::Both) {
+        eout!("\x07");
+    }
+    if matches!(mode, NotifyMode::Desktop | NotifyMode::Both) {
+        desktop(title, body);
+    }
+}
+
+/// Best-effort desktop notification through the platform's notification service.
+fn desktop(title: &str, body: &str) {
+    if let Err(e) = Notification::new()
+        .appname("zcode")
+        .summary(title)
+        .body(body)
+        .show()
+    {
+        debug!(error = %e, "desktop notification failed");
+    }
+}
@@ -0,0 +1,78 @@
+//! How runs behave beyond the prompt: the front end's flags merged with the config, passed
+//! once to `init` at startup. `update` changes a setting later in the process, e.g. `/stats on`
+//! in the REPL or auto-commit turning itself off after a failed commit.
+
+use crate::ci::Limits;
+use std::sync::RwLock;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// `--planner-model`, which wins over `planner_model` in the config.
+    pub planner_model: Option<String>,
+    /// `--executor-model`, which wins over `executor_model` in the config.
+    pub executor_model: Option<String>,
+    /// Run the project's build and test commands after execution (off with `--no-verify`).
+    pub verify: bool,
+    /// Let the user review and edit the plan before executing it.
+    pub review_plan: bool,
+    /// Have the planner review the diff and the executor fix its findings (`--critic`).
+    pub critic: bool,
+    /// Use the role agents in `roles` (`--strategy multi-agent`).
+    pub multi_agent: bool,
+    /// Run independent todos from the plan concurrently (`--parallel`).
+    pub parallel: bool,
+    /// Commit after each completed todo (`--auto-commit`).
+    pub auto_commit: bool,
+    /// Write each run's report to `.zcode/reports/` (`--save-report`).
+    pub save_report: bool,
+    /// Append each run to the dataset log (`--dataset-log`).
+    pub dataset_log: bool,
+    /// Reuse cached plans (off with `--no-plan-cache`).
+    pub plan_cache: bool,
+    /// Answer repeated model requests from the response cache (`--response-cache`).
+    pub response_cache: bool,
+    /// Print token usage after each response.
+    pub show_usage: bool,
+    /// A headless run (`--ci`).
+    pub ci: bool,
+    /// Run limits and tool policy; they apply outside CI too.
+    pub limits: Limits,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            planner_model: None,
+            executor_model: None,
+            verify: true,
+            review_plan: false,
+            critic: false,
+            multi_agent: false,
+            parallel: false,
+            auto_commit: false,
+            save_report: false,
+            dataset_log: false,
+            plan_cache: true,
+            response_cache: false,
+            show_usage: false,
+            ci: false,
+            limits: Limits::default(),
+        }
+    }
+}
+
+static OPTIONS: RwLock<Option<Options>> = RwLock::new(None);
+
+pub fn init(options: Options) {
+    *OPTIONS.write().unwrap() = Some(options);
+}
+
+/// The current options; the defaults until `init`.
+pub fn get() -> Options {
+    OPTIONS.read().unwrap().clone().unwrap_or_default()
+}
+
+pub fn update(change: impl FnOnce(&mut Options)) {
+    let mut options = OPTIONS.write().unwrap();
+    change(options.get_or_insert_with(Options::default));
+}
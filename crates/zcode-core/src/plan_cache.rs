@@ -0,0 +1,66 @@
+//! Plans cached by request: the same prompt (ignoring case and spacing) against the same
+//! repository map and planner model reuses the saved plan instead of planning again.
+//! `--no-plan-cache` or `plan_cache = false` in the config turns it off.
+
+use crate::config;
+use crate::options;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Cached plans older than this are planned again.
+const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// Cache key for a request.
+pub fn key(model: &str, prompt: &str, repo_map: &str) -> String {
+    let prompt = prompt
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    let mut hasher = Sha256::new();
+    for part in [model, &prompt, repo_map] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn plan_path(key: &str) -> Option<PathBuf> {
+    config::data_dir().map(|d| d.join("plans").join(format!("{}.json", key)))
+}
+
+/// The planner's raw reply saved under `key`, unless caching is off or it has expired.
+pub fn load(key: &str) -> Option<String> {
+    if !options::get().plan_cache {
+        return None;
+    }
+    let path = plan_path(key)?;
+    let age = fs::metadata(&path)
+        .and_then(|m| m.modified())
+        .ok()?
+        .elapsed()
+        .ok()?;
+    if age > MAX_AGE {
+        let _ = fs::remove_file(&path);
+        return None;
+    }
+    fs::read_to_string(path).ok()
+}
+
+/// Save a plan that parsed.
+pub fn save(key: &str, plan: &str) -> Result<(), String> {
+    if !options::get().plan_cache {
+        return Ok(());
+    }
+    let path = plan_path(key).ok_or("No data directory")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    config::write_atomic(&path, plan).map_err(|e| e.to_string())
+}
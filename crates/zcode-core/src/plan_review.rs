@@ -0,0 +1,77 @@
+//! Review the planner's todos before anything runs: approve, edit, reorder, delete, or add
+//! steps, and add files to read for context.
+
+use crate::ui;
+
+const ACTIONS: &[&str] = &[
+    "Run this plan",
+    "Edit a step",
+    "Add a step",
+    "Delete a step",
+    "Move a step",
+    "Add a file to read",
+    "Cancel",
+];
+
+/// Let the user change `todos` and `paths_to_read` until they run or cancel the plan.
+/// Returns false if the plan was cancelled.
+pub fn review(todos: &mut Vec<String>, paths_to_read: &mut Vec<String>) -> Result<bool, String> {
+    loop {
+        let action = ui::select("Plan", ACTIONS, 0)?;
+        match ACTIONS[action] {
+            "Run this plan" if todos.is_empty() => {
+                ui::note("The plan has no steps; add one or cancel.")
+            }
+            "Run this plan" => return Ok(true),
+            "Edit a step" => {
+                if let Some(i) = pick_step(todos, "Edit which step?")? {
+                    todos[i] = ui::input("Step", &todos[i])?;
+                }
+            }
+            "Add a step" => {
+                let todo = ui::input("New step", "")?;
+                let mut positions: Vec<String> = (1..=todos.len())
+                    .map(|n| format!("Before step {}", n))
+                    .collect();
+                positions.push("At the end".into());
+                let at = ui::select("Where?", &positions, todos.len())?;
+                todos.insert(at, todo);
+            }
+            "Delete a step" => {
+                if let Some(i) = pick_step(todos, "Delete which step?")? {
+                    todos.remove(i);
+                }
+            }
+            "Move a step" => {
+                if let Some(from) = pick_step(todos, "Move which step?")? {
+                    let todo = todos.remove(from);
+                    let positions: Vec<String> = (1..=todos.len() + 1)
+                        .map(|n| format!("To position {}", n))
+                        .collect();
+                    let to = ui::select("Where?", &positions, from)?;
+                    todos.insert(to, todo);
+                }
+            }
+            "Add a file to read" => {
+                let path = ui::input("Path", "")?;
+                if !paths_to_read.contains(&path) {
+                    paths_to_read.push(path);
+                }
+            }
+            _ => return Ok(false),
+        }
+        ui::todo_list(todos, &vec![ui::TodoStatus::Pending; todos.len()]);
+        if !paths_to_read.is_empty() {
+            ui::note(&format!("Files to read: {}", paths_to_read.join(", ")));
+        }
+    }
+}
+
+/// Index of a step chosen by the user, or None if there are no steps.
+fn pick_step(todos: &[String], prompt: &str) -> Result<Option<usize>, String> {
+    if todos.is_empty() {
+        ui::note("The plan has no steps.");
+        return Ok(None);
+    }
+    ui::select(prompt, todos, 0).map(Some)
+}
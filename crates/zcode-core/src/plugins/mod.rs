@@ -0,0 +1,207 @@
+//! Tools from executables in `<config dir>/tools/` (`~/.config/zcode/tools/` on Linux). Each
+//! one describes itself when run with `--schema`:
+//!
+//! ```json
+//! {"name": "jira_issue", "description": "Fetch a Jira issue", "parameters": {"type": "object", ...}}
+//! ```
+//!
+//! and is then offered to the model like a built-in tool. A call runs the executable in the
+//! workspace with the arguments as JSON on stdin; its stdout is the result, and a non-zero exit
+//! turns its stderr into the error.
+//!
+//! `.wasm` files in the same directory are sandboxed plugins (see `wasm`), which list their
+//! own tools.
+
+mod wasm;
+
+use crate::config;
+use crate::tools::Executor;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// How long an executable gets to print its schema.
+const SCHEMA_TIMEOUT: Duration = Duration::from_secs(5);
+
+static PLUGINS: OnceLock<Vec<Plugin>> = OnceLock::new();
+
+#[derive(Debug, Clone)]
+pub struct Plugin {
+    pub name: String,
+    pub description: String,
+    /// JSON schema of the arguments.
+    pub parameters: serde_json::Value,
+    pub source: Source,
+}
+
+#[derive(Debug, Clone)]
+pub enum Source {
+    Executable(PathBuf),
+    Wasm(Arc<wasm::Module>),
+}
+
+#[derive(Deserialize)]
+struct Schema {
+    name: Option<String>,
+    #[serde(default)]
+    description: String,
+    parameters: Option<serde_json::Value>,
+}
+
+/// Where plugin executables live.
+pub fn dir() -> Option<PathBuf> {
+    config::config_dir().map(|d| d.join("tools"))
+}
+
+/// Every plugin found, discovered on first use.
+pub fn all() -> &'static [Plugin] {
+    PLUGINS.get_or_init(|| dir().map(|d| discover(&d)).unwrap_or_default())
+}
+
+pub fn find(name: &str) -> Option<&'static Plugin> {
+    all().iter().find(|p| p.name == name)
+}
+
+fn discover(dir: &Path) -> Vec<Plugin> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| is_wasm(p) || is_executable(p))
+        .collect();
+    paths.sort();
+    let mut plugins: Vec<Plugin> = Vec::new();
+    for path in paths {
+        let found = if is_wasm(&path) {
+            load_wasm(&path)
+        } else {
+            describe(&path).map(|p| vec![p])
+        };
+        match found {
+            Ok(found) => {
+                for plugin in found {
+                    if plugins.iter().any(|p| p.name == plugin.name) {
+                        warn!(path = %path.display(), name = %plugin.name, "duplicate plugin tool name; skipped");
+                    } else {
+                        plugins.push(plugin);
+                    }
+                }
+            }
+            Err(e) => warn!(path = %path.display(), error = %e, "plugin skipped"),
+        }
+    }
+    plugins
+}
+
+fn is_wasm(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|e| e == "wasm")
+}
+
+/// Compile a WebAssembly plugin and list its tools.
+fn load_wasm(path: &Path) -> Result<Vec<Plugin>, String> {
+    let (module, tools) = wasm::Module::load(path)?;
+    tools
+        .into_iter()
+        .map(|tool| {
+            check_name(&tool.name)?;
+            let parameters = serde_json::from_str(&tool.parameters)
+                .map_err(|e| format!("bad parameters schema for {}: {}", tool.name, e))?;
+            Ok(Plugin {
+                name: tool.name,
+                description: tool.description,
+                parameters,
+                source: Source::Wasm(module.clone()),
+            })
+        })
+        .collect()
+}
+
+fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(format!("invalid tool name {:?}", name));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Ask an executable for its schema.
+fn describe(path: &Path) -> Result<Plugin, String> {
+    let mut child = Command::new(path)
+        .arg("--schema")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    let started = Instant::now();
+    while child.try_wait().map_err(|e| e.to_string())?.is_none() {
+        if started.elapsed() > SCHEMA_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err("--schema timed out".into());
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!("--schema exited with {}", output.status));
+    }
+    let schema: Schema = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("bad --schema output: {}", e))?;
+    let name = schema
+        .name
+        .or_else(|| path.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .unwrap_or_default();
+    check_name(&name)?;
+    Ok(Plugin {
+        name,
+        description: schema.description,
+        parameters: schema
+            .parameters
+            .unwrap_or_else(|| serde_json::json!({ "type": "object", "properties": {} })),
+        source: Source::Executable(path.to_path_buf()),
+    })
+}
+
+impl Plugin {
+    /// Run the tool with `arguments` (JSON).
+    pub fn run(&self, executor: &Executor, arguments: &str) -> Result<String, String> {
+        let path = match &self.source {
+            Source::Executable(path) => path,
+            Source::Wasm(module) => {
+                return module.execute(executor.workspace(), &self.name, arguments)
+            }
+        };
+        let (status, stdout, stderr) = executor.run_process(Command::new(path), Some(arguments))?;
+        match status {
+            Some(status) if status.success() => Ok(stdout),
+            Some(status) => Err(format!(
+                "{} exited with {}: {}",
+                self.name,
+                status,
+                stderr.trim()
+            )),
+            None => Err(format!("{} interrupted by user", self.name)),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Host for WebAssembly tool plugins: components implementing the `plugin` world in
+//! `wit/plugin.wit`. Each call runs in a fresh instance with a memory cap and a fuel budget,
+//! and the only capabilities are logging and reading files inside the workspace.
+
+use std::path::{Component as PathComponent, Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use tracing::info;
+use wasmtime::component::{Component, Linker};
+use wasmtime::{Config, Engine, Store, StoreLimits, StoreLimitsBuilder};
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        path: "wit/plugin.wit",
+        world: "plugin",
+    });
+}
+
+use bindings::zcode::plugin::host;
+use bindings::Plugin;
+
+/// Linear memory one instance may grow to.
+const MAX_MEMORY: usize = 64 * 1024 * 1024;
+/// Instructions (roughly) one call may execute before it is stopped.
+const FUEL: u64 = 5_000_000_000;
+/// Largest file `read-file` hands to a plugin.
+const MAX_READ: u64 = 4 * 1024 * 1024;
+
+static ENGINE: OnceLock<Result<Engine, String>> = OnceLock::new();
+
+fn engine() -> Result<&'static Engine, String> {
+    ENGINE
+        .get_or_init(|| {
+            let mut config = Config::new();
+            config.consume_fuel(true);
+            Engine::new(&config).map_err(|e| e.to_string())
+        })
+        .as_ref()
+        .map_err(|e| e.clone())
+}
+
+/// What a running plugin can reach.
+struct HostState {
+    /// Workspace `read-file` is confined to; None while listing tools.
+    workspace: Option<PathBuf>,
+    plugin: String,
+    limits: StoreLimits,
+}
+
+impl host::Host for HostState {
+    fn log(&mut self, message: String) {
+        info!(plugin = %self.plugin, "{}", message);
+    }
+
+    fn read_file(&mut self, path: String) -> Result<String, String> {
+        let workspace = self
+            .workspace
+            .as_ref()
+            .ok_or("No workspace while listing tools")?;
+        let relative = Path::new(&path);
+        if !relative
+            .components()
+            .all(|c| matches!(c, PathComponent::Normal(_) | PathComponent::CurDir))
+        {
+            return Err(format!(
+                "{}: only relative paths inside the workspace can be read",
+                path
+            ));
+        }
+        // Resolve symlinks so a link can't lead outside the workspace.
+        let root = workspace.canonicalize().map_err(|e| e.to_string())?;
+        let full = root
+            .join(relative)
+            .canonicalize()
+            .map_err(|e| format!("{}: {}", path, e))?;
+        if !full.starts_with(&root) {
+            return Err(format!("{}: outside the workspace", path));
+        }
+        if std::fs::metadata(&full).map_err(|e| e.to_string())?.len() > MAX_READ {
+            return Err(format!("{}: larger than {} bytes", path, MAX_READ));
+        }
+        std::fs::read_to_string(&full).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+/// A compiled plugin component.
+pub struct Module {
+    name: String,
+    component: Component,
+    linker: Linker<HostState>,
+}
+
+impl std::fmt::Debug for Module {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Module").field("name", &self.name).finish()
+    }
+}
+
+/// A tool a component provides.
+pub struct ToolDef {
+    pub name: String,
+    pub description: String,
+    pub parameters: String,
+}
+
+impl Module {
+    /// Compile the component at `path` and ask it for its tools.
+    pub fn load(path: &Path) -> Result<(Arc<Module>, Vec<ToolDef>), String> {
+        let engine = engine()?;
+        let component = Component::from_file(engine, path).map_err(|e| e.to_string())?;
+        let mut linker = Linker::new(engine);
+        Plugin::add_to_linker(&mut linker, |state: &mut HostState| state)
+            .map_err(|e| e.to_string())?;
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let module = Arc::new(Module {
+            name,
+            component,
+            linker,
+        });
+        let (mut store, plugin) = module.instantiate(None)?;
+        let tools = plugin
+            .call_tools(&mut store)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|t| ToolDef {
+                name: t.name,
+                description: t.description,
+                parameters: t.parameters,
+            })
+            .collect();
+        Ok((module, tools))
+    }
+
+    fn instantiate(&self, workspace: Option<&Path>) -> Result<(Store<HostState>, Plugin), String> {
+        let state = HostState {
+            workspace: workspace.map(Path::to_path_buf),
+            plugin: self.name.clone(),
+            limits: StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build(),
+        };
+        let mut store = Store::new(self.component.engine(), state);
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(FUEL).map_err(|e| e.to_string())?;
+        let plugin = Plugin::instantiate(&mut store, &self.component, &self.linker)
+            .map_err(|e| e.to_string())?;
+        Ok((store, plugin))
+    }
+
+    /// Run `tool` in a fresh instance.
+    pub fn execute(&self, workspace: &Path, tool: &str, arguments: &str) -> Result<String, String> {
+        let (mut store, plugin) = self.instantiate(Some(workspace))?;
+        plugin
+            .call_execute(&mut store, tool, arguments)
+            .map_err(|e| format!("{} trapped: {}", self.name, e))?
+    }
+}
@@ -0,0 +1,123 @@
+//! `--create-pr`: commit a successful run's changes on a new branch, push it, and open a PR.
+
+use crate::agent;
+use crate::context;
+use crate::git::{self, git};
+use crate::github::{self, Client};
+use crate::run::{self, RunSummary};
+use crate::tools::Executor;
+use crate::ui;
+use std::process::Command;
+
+/// The diff beyond this many bytes is cut before it goes into the commit message prompt.
+const DIFF_CAP: usize = 24 * 1024;
+/// Each todo's note is cut to this many characters in the PR body.
+const NOTE_CAP: usize = 400;
+
+const COMMIT_SYSTEM: &str = "Write a git commit message for the diff: an imperative subject line under 72 characters, a blank line, then a short body explaining what changed and why. Output only the message.";
+
+/// Lowercase words of `text` joined by dashes, for branch names.
+fn slug(text: &str) -> String {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .take(6)
+        .map(|w| w.to_lowercase())
+        .collect();
+    words.join("-")
+}
+
+fn pr_body(summary: &RunSummary, message_body: &str) -> String {
+    let mut body = format!("## Plan\n\n{}\n\n", summary.summary);
+    for (i, t) in summary.todos.iter().enumerate() {
+        let mark = if t.status == ui::TodoStatus::Done {
+            "x"
+        } else {
+            " "
+        };
+        body.push_str(&format!("- [{}] {}. {}\n", mark, i + 1, t.todo));
+        if let Some(note) = t.note.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+            let note: String = note.chars().take(NOTE_CAP).collect();
+            body.push_str(&format!("  > {}\n", note.replace('\n', "\n  > ")));
+        }
+    }
+    if !message_body.trim().is_empty() {
+        body.push_str(&format!("\n## Changes\n\n{}\n", message_body.trim()));
+    }
+    body.push_str("\n---\nCreated with zcode.\n");
+    body
+}
+
+/// Commit the run's changes on a new branch, push it, and open a PR. Returns the PR URL.
+pub async fn create(
+    api_key: &str,
+    executor: &Executor,
+    summary: &RunSummary,
+) -> Result<String, String> {
+    let workspace = executor.workspace();
+    let changes = executor.changes();
+    if changes.is_empty() {
+        return Err("No changes to put in a pull request".into());
+    }
+    let base = git(workspace, &["rev-parse", "--abbrev-ref", "HEAD"])?
+        .trim()
+        .to_string();
+
+    let diff: String = changes
+        .iter()
+        .map(ui::plain_diff)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let planner = agent::from_spec(&run::planner_model(), api_key)?;
+    let user = format!(
+        "Task: {}\n\nDiff:\n{}",
+        summary.summary,
+        context::truncate(&diff, DIFF_CAP)
+    );
+    let message = ui::with_spinner(
+        "Writing commit message",
+        planner.completion(COMMIT_SYSTEM, &user),
+    )
+    .await
+    .unwrap_or_else(|_| summary.summary.clone());
+    let message = message.trim().trim_matches('`').trim().to_string();
+    let (subject, message_body) = message.split_once('\n').unwrap_or((&message, ""));
+
+    let branch = format!(
+        "zcode/{}-{}",
+        slug(&summary.summary),
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    );
+    git(workspace, &["checkout", "-b", &branch])?;
+    let paths: Vec<&str> = changes.iter().map(|c| c.path.as_str()).collect();
+    git::commit_paths(workspace, &paths, &message)?;
+    ui::phase_done(&format!("Committed on {}", branch));
+    ui::with_spinner("Pushing", async {
+        git(workspace, &["push", "-u", "origin", &branch])
+    })
+    .await?;
+
+    let body = pr_body(summary, message_body);
+    let gh = Command::new("gh")
+        .args([
+            "pr", "create", "--base", &base, "--head", &branch, "--title", subject, "--body", &body,
+        ])
+        .current_dir(workspace)
+        .output()
+        .ok()
+        .filter(|o| o.status.success());
+    if let Some(out) = gh {
+        return Ok(String::from_utf8_lossy(&out.stdout).trim().to_string());
+    }
+    let repo = github::origin_repo(workspace)?;
+    let pr = Client::new()?
+        .post(
+            &format!("/repos/{}/pulls", repo),
+            &serde_json::json!({ "title": subject, "head": branch, "base": base, "body": body }),
+        )
+        .await?;
+    Ok(pr["html_url"].as_str().unwrap_or_default().to_string())
+}
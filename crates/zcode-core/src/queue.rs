@@ -0,0 +1,273 @@
+//! `zcode queue`: line up prompts with `queue add` and work through them with `queue run`,
+//! one at a time. Each task starts from the branch the queue was run on and is committed to
+//! a fresh `zcode/queue-<id>` branch, so tasks don't build on each other's changes.
+
+use crate::agent::Agent;
+use crate::batch::FileReport;
+use crate::config;
+use crate::git::{self, git};
+use crate::run::{self, Agents};
+use crate::tools::Executor;
+use crate::ui;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Pending,
+    Completed,
+    /// Finished without changing any files.
+    Unchanged,
+    Failed,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Task {
+    pub id: usize,
+    pub prompt: String,
+    pub status: Status,
+    /// Branch holding the task's commit.
+    pub branch: Option<String>,
+    pub reply: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Queue {
+    pub tasks: Vec<Task>,
+}
+
+#[derive(Serialize)]
+struct TaskReport<'a> {
+    id: usize,
+    prompt: &'a str,
+    status: Status,
+    branch: Option<&'a str>,
+    reply: Option<&'a str>,
+    duration_secs: f64,
+    files: Vec<FileReport>,
+}
+
+fn queue_path(workspace: &Path) -> Option<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    workspace.hash(&mut hasher);
+    let name = workspace
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    config::data_dir().map(|d| {
+        d.join("queue")
+            .join(format!("{}-{:016x}.json", name, hasher.finish()))
+    })
+}
+
+impl Queue {
+    pub fn load(workspace: &Path) -> Self {
+        queue_path(workspace)
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|c| serde_json::from_str(&c).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, workspace: &Path) -> Result<(), String> {
+        let path = queue_path(workspace).ok_or("No data directory")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        config::write_atomic(&path, content).map_err(|e| e.to_string())
+    }
+}
+
+/// Queue a prompt; returns its id.
+pub fn add(workspace: &Path, prompt: &str) -> Result<usize, String> {
+    if prompt.trim().is_empty() {
+        return Err("Empty prompt".into());
+    }
+    let mut queue = Queue::load(workspace);
+    let id = queue.tasks.iter().map(|t| t.id).max().unwrap_or(0) + 1;
+    queue.tasks.push(Task {
+        id,
+        prompt: prompt.trim().to_string(),
+        status: Status::Pending,
+        branch: None,
+        reply: None,
+    });
+    queue.save(workspace)?;
+    Ok(id)
+}
+
+/// Print the queue.
+pub fn list(workspace: &Path) {
+    let queue = Queue::load(workspace);
+    if queue.tasks.is_empty() {
+        ui::info("The queue is empty; add tasks with `zcode queue add \"...\"`.");
+        return;
+    }
+    for task in &queue.tasks {
+        let first_line = task.prompt.lines().next().unwrap_or_default();
+        let branch = task
+            .branch
+            .as_deref()
+            .map(|b| format!(" → {}", b))
+            .unwrap_or_default();
+        outln!(
+            "{:>3}  {:<9}  {}{}",
+            task.id,
+            status_label(task.status),
+            first_line,
+            branch
+        );
+    }
+}
+
+/// Remove one task, or with `None` every task that is no longer pending.
+pub fn remove(workspace: &Path, id: Option<usize>) -> Result<usize, String> {
+    let mut queue = Queue::load(workspace);
+    let before = queue.tasks.len();
+    match id {
+        Some(id) => queue.tasks.retain(|t| t.id != id),
+        None => queue.tasks.retain(|t| t.status == Status::Pending),
+    }
+    let removed = before - queue.tasks.len();
+    if id.is_some() && removed == 0 {
+        return Err(format!("No queued task {}", id.unwrap_or_default()));
+    }
+    queue.save(workspace)?;
+    Ok(removed)
+}
+
+fn status_label(status: Status) -> &'static str {
+    match status {
+        Status::Pending => "pending",
+        Status::Completed => "completed",
+        Status::Unchanged => "unchanged",
+        Status::Failed => "failed",
+    }
+}
+
+/// Run every pending task in order and write a JSON report to the queue dir. Returns the
+/// report's path.
+pub async fn run<P: Agent, E: Agent>(
+    agents: &Agents<P, E>,
+    executor: &Executor,
+) -> Result<PathBuf, String> {
+    let workspace = executor.workspace();
+    if git::is_dirty(workspace)? {
+        return Err("The working tree has uncommitted changes; commit or stash them first".into());
+    }
+    let base = match git(workspace, &["rev-parse", "--abbrev-ref", "HEAD"])?.trim() {
+        "HEAD" => git(workspace, &["rev-parse", "HEAD"])?.trim().to_string(),
+        branch => branch.to_string(),
+    };
+    let mut queue = Queue::load(workspace);
+    let pending: Vec<usize> = (0..queue.tasks.len())
+        .filter(|&i| queue.tasks[i].status == Status::Pending)
+        .collect();
+    if pending.is_empty() {
+        return Err("No pending tasks; add some with `zcode queue add \"...\"`".into());
+    }
+
+    let mut reports = Vec::new();
+    for (n, &i) in pending.iter().enumerate() {
+        let (id, prompt) = (queue.tasks[i].id, queue.tasks[i].prompt.clone());
+        ui::phase(&format!(
+            "Queued task {}/{} (#{}): {}",
+            n + 1,
+            pending.len(),
+            id,
+            prompt.lines().next().unwrap_or_default()
+        ));
+        let branch = format!("zcode/queue-{}", id);
+        git(workspace, &["checkout", "-B", &branch, &base])?;
+        executor.checkpoint();
+        let started = Instant::now();
+        let reply = run::run_once(agents, executor, &prompt).await;
+        let changes = executor.changes();
+        // Commit everything, including files written by commands, so the next task starts clean.
+        let committed = git::is_dirty(workspace)?;
+        if committed {
+            git(workspace, &["add", "-A"])?;
+            git(
+                workspace,
+                &[
+                    "commit",
+                    "-q",
+                    "-m",
+                    &format!("zcode: {}", prompt.lines().next().unwrap_or_default()),
+                ],
+            )?;
+        }
+        let status = match (&reply, changes.is_empty() && !committed) {
+            (None, _) => Status::Failed,
+            (Some(_), true) => Status::Unchanged,
+            (Some(_), false) => Status::Completed,
+        };
+        git(workspace, &["checkout", "-q", &base])?;
+        let kept = status != Status::Unchanged;
+        if !kept {
+            let _ = git(workspace, &["branch", "-D", &branch]);
+        }
+        let task = &mut queue.tasks[i];
+        task.status = status;
+        task.branch = kept.then_some(branch);
+        task.reply = reply;
+        queue.save(workspace)?;
+        reports.push((i, started.elapsed().as_secs_f64(), changes));
+        outln!();
+    }
+
+    let report: Vec<TaskReport> = reports
+        .iter()
+        .map(|(i, duration_secs, changes)| {
+            let task = &queue.tasks[*i];
+            TaskReport {
+                id: task.id,
+                prompt: &task.prompt,
+                status: task.status,
+                branch: task.branch.as_deref(),
+                reply: task.reply.as_deref(),
+                duration_secs: *duration_secs,
+                files: changes
+                    .iter()
+                    .map(|c| {
+                        let (added, removed) =
+                            ui::line_counts(c.before.as_deref(), c.after.as_deref());
+                        FileReport {
+                            path: c.path.clone(),
+                            added,
+                            removed,
+                        }
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    ui::phase("Queue summary");
+    for task in &report {
+        let branch = task.branch.map(|b| format!(" → {}", b)).unwrap_or_default();
+        let first_line = task.prompt.lines().next().unwrap_or_default();
+        outln!(
+            "{:>3}  {:<9}  {} ({} files){}",
+            task.id,
+            status_label(task.status),
+            first_line,
+            task.files.len(),
+            branch
+        );
+    }
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let path = queue_path(workspace)
+        .and_then(|p| p.parent().map(|d| d.join(format!("report-{}.json", secs))))
+        .ok_or("No data directory")?;
+    let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(path)
+}
@@ -0,0 +1,422 @@
+//! Ranked context selection: candidate files are scored by the planner's hints, uncommitted
+//! and recent edits, imports of hinted files, and semantic similarity to the prompt, then
+//! packed into the context token budget, slicing files that would not fit. Excluded and
+//! generated files (see `exclude`) only get in when the plan names them.
+
+use crate::config;
+use crate::context;
+use crate::events::{self, Event};
+use crate::exclude::{self, Filter};
+use crate::git;
+use crate::run;
+use crate::slicing;
+use crate::tokens;
+use futures_util::StreamExt;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Context budget in tokens unless `context_tokens` is set in the config.
+const DEFAULT_BUDGET: usize = 16_000;
+/// Most of the budget one file may take.
+const FILE_SHARE: usize = 3;
+/// Below this many tokens left, stop adding files.
+const MIN_BLOCK_TOKENS: usize = 200;
+/// Files taken from a hinted directory.
+const DIR_FILES: usize = 20;
+/// Top candidates read before packing.
+const MAX_READS: usize = 40;
+/// Files read at the same time.
+const READ_CONCURRENCY: usize = 8;
+
+const HINT_READ: f32 = 3.0;
+const HINT_CHANGE: f32 = 2.5;
+const UNCOMMITTED: f32 = 1.0;
+const IMPORTED: f32 = 1.0;
+const RECENT: f32 = 0.5;
+const SEMANTIC: f32 = 2.0;
+/// Score multiplier for excluded files the plan names anyway.
+const EXCLUDED: f32 = 0.25;
+/// Files modified within this long count as recent.
+const RECENT_WINDOW: Duration = Duration::from_secs(24 * 3600);
+
+/// Token count of `text` for the executor model, which reads the context (see `tokens`).
+pub fn estimate_tokens(text: &str) -> usize {
+    tokens::count(&run::executor_model(), text)
+}
+
+/// Token budget for gathered context: `context_tokens` in the config, else the default.
+pub fn budget() -> usize {
+    config::load().context_tokens.unwrap_or(DEFAULT_BUDGET)
+}
+
+/// A file worth reading, with why.
+#[derive(Debug, Default)]
+pub struct Candidate {
+    pub score: f32,
+    /// 1-based line range most relevant to the prompt (from semantic search), kept when the
+    /// file has to be truncated.
+    pub focus: Option<(usize, usize)>,
+    /// Named by the plan, so kept even if it looks generated.
+    pub hinted: bool,
+}
+
+/// A file packed into the context.
+#[derive(Debug)]
+pub struct Selected {
+    pub path: String,
+    pub block: String,
+    pub truncated: bool,
+}
+
+/// Score candidate files. `read_hints` and `change_hints` come from the plan; `semantic`
+/// holds `(path, start, end, similarity)` hits from the index.
+pub fn rank(
+    workspace: &Path,
+    read_hints: &[String],
+    change_hints: &[String],
+    semantic: &[(String, usize, usize, f32)],
+) -> Vec<(String, Candidate)> {
+    let filter = Filter::load(workspace);
+    let mut candidates: BTreeMap<String, Candidate> = BTreeMap::new();
+    let add = |candidates: &mut BTreeMap<String, Candidate>, path: &str, score: f32| {
+        if workspace.join(path).is_file() && !filter.excludes(&normalize(path)) {
+            candidates.entry(normalize(path)).or_default().score += score;
+        }
+    };
+    for (hints, score) in [(read_hints, HINT_READ), (change_hints, HINT_CHANGE)] {
+        for hint in hints {
+            if workspace.join(hint).is_dir() {
+                for file in dir_files(workspace, hint) {
+                    add(&mut candidates, &file, score / 2.0);
+                }
+            } else if workspace.join(hint).is_file() {
+                let path = normalize(hint);
+                let score = if filter.excludes(&path) {
+                    score * EXCLUDED
+                } else {
+                    score
+                };
+                let entry = candidates.entry(path).or_default();
+                entry.score += score;
+                entry.hinted = true;
+            }
+        }
+    }
+    let hinted: Vec<String> = candidates.keys().cloned().collect();
+    for path in &hinted {
+        for import in imports(workspace, path) {
+            add(&mut candidates, &import, IMPORTED);
+        }
+    }
+    for path in uncommitted(workspace) {
+        add(&mut candidates, &path, UNCOMMITTED);
+    }
+    for (path, start, end, similarity) in semantic {
+        if filter.excludes(path) && !candidates.contains_key(path) {
+            continue;
+        }
+        let entry = candidates.entry(path.clone()).or_default();
+        if entry.focus.is_none() {
+            entry.score += SEMANTIC * similarity.max(0.0);
+            entry.focus = Some((*start, *end));
+        }
+    }
+    let now = SystemTime::now();
+    for (path, candidate) in candidates.iter_mut() {
+        let modified = fs::metadata(workspace.join(path)).and_then(|m| m.modified());
+        if modified.is_ok_and(|m| now.duration_since(m).is_ok_and(|age| age < RECENT_WINDOW)) {
+            candidate.score += RECENT;
+        }
+    }
+    let mut ranked: Vec<(String, Candidate)> = candidates.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.score.total_cmp(&a.1.score));
+    ranked
+}
+
+/// Hints that name neither a file nor a directory in the workspace.
+pub fn missing(workspace: &Path, hints: &[String]) -> Vec<String> {
+    hints
+        .iter()
+        .filter(|h| !workspace.join(h).exists())
+        .cloned()
+        .collect()
+}
+
+/// Read the top candidates concurrently (bounded), showing each file as it finishes.
+/// Results are in `ranked` order.
+pub async fn read(workspace: &Path, ranked: &[(String, Candidate)]) -> Vec<Result<String, String>> {
+    // Owned paths keep the futures `Send` for callers that spawn the pipeline.
+    let paths: Vec<(usize, String, PathBuf)> = ranked
+        .iter()
+        .take(MAX_READS)
+        .enumerate()
+        .map(|(i, (path, _))| (i, path.clone(), workspace.join(path)))
+        .collect();
+    let reads = paths.into_iter().map(|(i, path, full)| async move {
+        let result = tokio::fs::read_to_string(full)
+            .await
+            .map_err(|e| e.to_string());
+        events::emit(Event::FileRead {
+            path: &path,
+            error: result.as_ref().err().map(String::as_str),
+        });
+        (i, result)
+    });
+    let mut results: Vec<(usize, Result<String, String>)> = futures_util::stream::iter(reads)
+        .buffer_unordered(READ_CONCURRENCY)
+        .collect()
+        .await;
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, r)| r).collect()
+}
+
+/// Pack the read candidates, best first, until `budget` tokens are used. Each file gets at
+/// most a third of the budget; longer files keep their definitions relevant to `terms` (see
+/// `slicing`), else are cut around their focus lines, or from the top.
+pub fn pack(
+    ranked: &[(String, Candidate)],
+    contents: Vec<Result<String, String>>,
+    terms: &[String],
+    budget: usize,
+) -> Vec<Selected> {
+    let mut left = budget;
+    let mut selected = Vec::new();
+    for ((path, candidate), content) in ranked.iter().zip(contents) {
+        if left < MIN_BLOCK_TOKENS {
+            break;
+        }
+        let Ok(content) = content else {
+            continue;
+        };
+        if !candidate.hinted && exclude::looks_generated(&content) {
+            continue;
+        }
+        let cap = left.min(budget / FILE_SHARE).max(MIN_BLOCK_TOKENS);
+        let truncated = estimate_tokens(&content) > cap;
+        let sliced = truncated
+            .then(|| slicing::slice(path, &content, terms, candidate.focus, cap * 4))
+            .flatten();
+        let body = match (truncated, sliced, candidate.focus) {
+            (false, ..) => content,
+            (true, Some(sliced), _) => sliced,
+            (true, None, Some(focus)) => around(&content, focus, cap * 4),
+            (true, None, None) => context::truncate(&content, cap * 4),
+        };
+        let block = format!("--- {} ---\n{}", path, body);
+        left = left.saturating_sub(estimate_tokens(&block));
+        selected.push(Selected {
+            path: path.clone(),
+            block,
+            truncated,
+        });
+    }
+    selected
+}
+
+/// Lines of `content` around the 1-based `focus` range, within `cap` bytes.
+fn around(content: &str, (start, end): (usize, usize), cap: usize) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut from = start.saturating_sub(1).min(lines.len());
+    let mut to = end.min(lines.len()).max(from);
+    let mut size: usize = lines[from..to].iter().map(|l| l.len() + 1).sum();
+    // Grow the window a line at a time in both directions while it fits.
+    loop {
+        let mut grew = false;
+        if from > 0 && size + lines[from - 1].len() < cap {
+            from -= 1;
+            size += lines[from].len() + 1;
+            grew = true;
+        }
+        if to < lines.len() && size + lines[to].len() < cap {
+            size += lines[to].len() + 1;
+            to += 1;
+            grew = true;
+        }
+        if !grew {
+            break;
+        }
+    }
+    let window = context::truncate(&lines[from..to].join("\n"), cap);
+    format!(
+        "… (lines {}-{} of {})\n{}",
+        from + 1,
+        to,
+        lines.len(),
+        window
+    )
+}
+
+fn normalize(path: &str) -> String {
+    path.trim_start_matches("./").to_string()
+}
+
+/// Files directly in `dir` (sorted, capped).
+fn dir_files(workspace: &Path, dir: &str) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(workspace.join(dir)) else {
+        return Vec::new();
+    };
+    let mut files: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+        .map(|e| {
+            Path::new(dir)
+                .join(e.file_name())
+                .to_string_lossy()
+                .to_string()
+        })
+        .collect();
+    files.sort();
+    files.truncate(DIR_FILES);
+    files
+}
+
+/// Paths with uncommitted changes (empty outside a git repository).
+fn uncommitted(workspace: &Path) -> Vec<String> {
+    git::git(
+        workspace,
+        &["status", "--porcelain", "--untracked-files=all"],
+    )
+    .map(|out| {
+        out.lines()
+            .filter_map(|l| l.get(3..))
+            .map(|p| {
+                p.rsplit(" -> ")
+                    .next()
+                    .unwrap_or(p)
+                    .trim_matches('"')
+                    .to_string()
+            })
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Workspace files that `path` imports, for Rust (`mod`, `use crate::`), JavaScript and
+/// TypeScript (relative `import`/`require`), and Python (relative and top-level imports).
+fn imports(workspace: &Path, path: &str) -> Vec<String> {
+    let Ok(content) = fs::read_to_string(workspace.join(path)) else {
+        return Vec::new();
+    };
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    let mut found = Vec::new();
+    for line in content.lines().map(str::trim) {
+        let candidates: Vec<String> = match ext {
+            "rs" => rust_import(path, dir, line),
+            "js" | "jsx" | "ts" | "tsx" | "mjs" => js_import(dir, line),
+            "py" => python_import(dir, line),
+            _ => Vec::new(),
+        };
+        if let Some(hit) = candidates.into_iter().find(|c| workspace.join(c).is_file()) {
+            if !found.contains(&hit) {
+                found.push(hit);
+            }
+        }
+    }
+    found
+}
+
+fn join(dir: &Path, rel: &str) -> String {
+    dir.join(rel).to_string_lossy().to_string()
+}
+
+fn rust_import(path: &str, dir: &Path, line: &str) -> Vec<String> {
+    let line = line.strip_prefix("pub ").unwrap_or(line);
+    if let Some(name) = line.strip_prefix("mod ").and_then(|l| l.strip_suffix(';')) {
+        // `mod x;` in main.rs, lib.rs, or mod.rs lives next to it; elsewhere in a subdirectory.
+        let file = Path::new(path)
+            .file_name()
+            .and_then(|f| f.to_str())
+            .unwrap_or_default();
+        let base = if matches!(file, "main.rs" | "lib.rs" | "mod.rs") {
+            dir.to_path_buf()
+        } else {
+            dir.join(Path::new(path).file_stem().unwrap_or_default())
+        };
+        return vec![
+            join(&base, &format!("{}.rs", name)),
+            join(&base, &format!("{}/mod.rs", name)),
+        ];
+    }
+    let Some(rest) = line.strip_prefix("use crate::") else {
+        return Vec::new();
+    };
+    let module: String = rest
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    if module.is_empty() {
+        return Vec::new();
+    }
+    let src = path
+        .split_once("src/")
+        .map(|(root, _)| format!("{}src", root))
+        .unwrap_or_else(|| "src".into());
+    vec![
+        format!("{}/{}.rs", src, module),
+        format!("{}/{}/mod.rs", src, module),
+    ]
+}
+
+fn js_import(dir: &Path, line: &str) -> Vec<String> {
+    if !(line.starts_with("import ") || line.starts_with("export ") || line.contains("require(")) {
+        return Vec::new();
+    }
+    let Some(spec) = line
+        .split(['\'', '"'])
+        .nth(1)
+        .filter(|s| s.starts_with('.'))
+    else {
+        return Vec::new();
+    };
+    let mut out = vec![join(dir, spec)];
+    for ext in ["ts", "tsx", "js", "jsx", "mjs"] {
+        out.push(join(dir, &format!("{}.{}", spec, ext)));
+        out.push(join(dir, &format!("{}/index.{}", spec, ext)));
+    }
+    out.into_iter().map(|p| clean(&p)).collect()
+}
+
+fn python_import(dir: &Path, line: &str) -> Vec<String> {
+    let module = if let Some(rest) = line.strip_prefix("from ") {
+        rest.split_whitespace().next().unwrap_or_default()
+    } else if let Some(rest) = line.strip_prefix("import ") {
+        rest.split([' ', ',']).next().unwrap_or_default()
+    } else {
+        return Vec::new();
+    };
+    if module.is_empty() {
+        return Vec::new();
+    }
+    let relative = module.starts_with('.');
+    let file = module.trim_start_matches('.').replace('.', "/");
+    let base = if relative {
+        dir.to_path_buf()
+    } else {
+        Path::new("").to_path_buf()
+    };
+    vec![
+        join(&base, &format!("{}.py", file)),
+        join(&base, &format!("{}/__init__.py", file)),
+    ]
+}
+
+/// Resolve `.` and `..` components of a relative path.
+fn clean(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
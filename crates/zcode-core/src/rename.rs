@@ -0,0 +1,158 @@
+//! `zcode rename <old> <new>`: project-wide symbol rename from the syntax tree, with a
+//! preview diff and a compile check. No model is involved.
+
+use crate::context;
+use crate::fix;
+use crate::tools::{Executor, FileChange};
+use crate::ui;
+use std::fs;
+use tree_sitter::{Node, Parser};
+
+/// Syntax node kinds that name a symbol (variables, functions, types, fields, modules).
+const IDENTIFIER_KINDS: &[&str] = &[
+    "identifier",
+    "type_identifier",
+    "field_identifier",
+    "shorthand_field_identifier",
+];
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Byte ranges of identifier nodes spelled `name`, skipping strings and comments.
+fn occurrences(parser: &mut Parser, source: &str, name: &str) -> Vec<(usize, usize)> {
+    let Some(tree) = parser.parse(source, None) else {
+        return Vec::new();
+    };
+    let mut found = Vec::new();
+    let mut stack: Vec<Node> = vec![tree.root_node()];
+    while let Some(node) = stack.pop() {
+        if IDENTIFIER_KINDS.contains(&node.kind()) && &source[node.byte_range()] == name {
+            found.push((node.start_byte(), node.end_byte()));
+            continue;
+        }
+        let mut cursor = node.walk();
+        stack.extend(node.children(&mut cursor));
+    }
+    found.sort_unstable();
+    found
+}
+
+/// Whole-word mentions of `name` in a file we can't parse, for the report.
+fn mentions(content: &str, name: &str) -> usize {
+    content
+        .match_indices(name)
+        .filter(|(at, _)| {
+            let before = content[..*at].chars().next_back();
+            let after = content[at + name.len()..].chars().next();
+            !before.is_some_and(|c| c.is_alphanumeric() || c == '_')
+                && !after.is_some_and(|c| c.is_alphanumeric() || c == '_')
+        })
+        .count()
+}
+
+/// Rename `old` to `new` in every Rust file. Returns whether the rename was applied
+/// and, if a check command is given, passes it.
+pub fn run(
+    executor: &Executor,
+    old: &str,
+    new: &str,
+    check: Option<&str>,
+    yes: bool,
+) -> Result<bool, String> {
+    if !is_identifier(old) || !is_identifier(new) {
+        return Err("Both names must be identifiers".into());
+    }
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_rust::LANGUAGE.into())
+        .map_err(|e| e.to_string())?;
+
+    let mut changes: Vec<FileChange> = Vec::new();
+    let mut other_files: Vec<String> = Vec::new();
+    for path in context::workspace_files(executor.workspace()) {
+        let Ok(content) = fs::read_to_string(executor.workspace().join(&path)) else {
+            continue;
+        };
+        if !path.ends_with(".rs") {
+            if mentions(&content, old) > 0 {
+                other_files.push(path);
+            }
+            continue;
+        }
+        let ranges = occurrences(&mut parser, &content, old);
+        if ranges.is_empty() {
+            continue;
+        }
+        let mut renamed = String::with_capacity(content.len());
+        let mut last = 0;
+        for (start, end) in ranges {
+            renamed.push_str(&content[last..start]);
+            renamed.push_str(new);
+            last = end;
+        }
+        renamed.push_str(&content[last..]);
+        changes.push(FileChange {
+            path,
+            before: Some(content),
+            after: Some(renamed),
+        });
+    }
+
+    if changes.is_empty() {
+        ui::info(&format!("No Rust identifiers named {} found.", old));
+        return Ok(false);
+    }
+    let opts = ui::DiffOptions {
+        context: 1,
+        ..Default::default()
+    };
+    let preview: Vec<String> = changes.iter().map(|c| ui::file_diff(c, &opts)).collect();
+    ui::page(&preview.join("\n"));
+    ui::changes_summary("Rename", &changes);
+    if !other_files.is_empty() {
+        ui::note(&format!(
+            "Also mentioned in non-Rust files (not changed): {}",
+            other_files.join(", ")
+        ));
+    }
+
+    if !yes {
+        let apply = ui::confirm(&format!("Apply rename in {} files?", changes.len()), false)?;
+        if !apply {
+            return Ok(false);
+        }
+    }
+    write_all(executor, &changes, |c| c.after.as_deref())?;
+
+    let Some(command) = check else {
+        return Ok(true);
+    };
+    let (passed, output) = fix::check(executor, command)?;
+    if !passed {
+        write_all(executor, &changes, |c| c.before.as_deref())?;
+        ui::info(&context::truncate(&output, 4 * 1024));
+        ui::note(&format!(
+            "`{}` failed after the rename, so it was reverted.",
+            command
+        ));
+    }
+    Ok(passed)
+}
+
+fn write_all(
+    executor: &Executor,
+    changes: &[FileChange],
+    content: impl Fn(&FileChange) -> Option<&str>,
+) -> Result<(), String> {
+    for change in changes {
+        executor.track(&change.path);
+        let text = content(change).unwrap_or_default();
+        fs::write(executor.workspace().join(&change.path), text)
+            .map_err(|e| format!("{}: {}", change.path, e))?;
+    }
+    Ok(())
+}
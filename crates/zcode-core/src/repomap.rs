@@ -0,0 +1,180 @@
+//! Repository map for the planner: every workspace file with its top-level symbols (parsed
+//! with tree-sitter for Rust, Python, JavaScript, TypeScript, and Go), shrunk to fit a token
+//! budget so plans name files that exist. Excluded and generated files (see `exclude`) are
+//! left out.
+
+use crate::config;
+use crate::context;
+use crate::exclude::{self, Filter};
+use crate::ranking;
+use std::fs;
+use std::path::Path;
+use tree_sitter::{Language, Node, Parser};
+
+/// Map budget in tokens unless `repo_map_tokens` is set in the config.
+const DEFAULT_BUDGET: usize = 4_000;
+/// Files bigger than this are listed without symbols.
+const MAX_FILE_BYTES: u64 = 256 * 1024;
+/// Files parsed for symbols; the rest are listed by path only.
+const MAX_PARSED_FILES: usize = 2_000;
+/// Longest symbol line kept.
+const SIGNATURE_CHARS: usize = 100;
+/// Symbols per file tried in turn until the map fits the budget.
+const SYMBOL_LIMITS: [usize; 6] = [usize::MAX, 12, 6, 3, 1, 0];
+
+pub fn budget() -> usize {
+    config::load().repo_map_tokens.unwrap_or(DEFAULT_BUDGET)
+}
+
+/// The map for `workspace` within `budget` tokens: one line per file, its symbols indented
+/// below it.
+pub fn build(workspace: &Path, budget: usize) -> String {
+    let filter = Filter::load(workspace);
+    let files: Vec<String> = context::workspace_files(workspace)
+        .into_iter()
+        .filter(|path| !filter.excludes(path))
+        .collect();
+    let mut parser = Parser::new();
+    let entries: Vec<(String, Vec<String>)> = files
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, path)| {
+            if i >= MAX_PARSED_FILES {
+                return Some((path, Vec::new()));
+            }
+            let symbols = symbols(&mut parser, workspace, &path)?;
+            Some((path, symbols))
+        })
+        .collect();
+    for limit in SYMBOL_LIMITS {
+        let map = render(&entries, limit);
+        if ranking::estimate_tokens(&map) <= budget {
+            return map;
+        }
+    }
+    context::truncate(&render(&entries, 0), budget * 4)
+}
+
+fn render(entries: &[(String, Vec<String>)], limit: usize) -> String {
+    let mut out = String::new();
+    for (path, symbols) in entries {
+        out.push_str(path);
+        out.push('\n');
+        for symbol in symbols.iter().take(limit) {
+            out.push_str("  ");
+            out.push_str(symbol);
+            out.push('\n');
+        }
+        if symbols.len() > limit && limit > 0 {
+            out.push_str(&format!("  … {} more\n", symbols.len() - limit));
+        }
+    }
+    out
+}
+
+/// Grammar and top-level node kinds worth listing for a file extension.
+pub(crate) fn language(ext: &str) -> Option<(Language, &'static [&'static str])> {
+    const RUST: &[&str] = &[
+        "function_item",
+        "struct_item",
+        "enum_item",
+        "trait_item",
+        "impl_item",
+        "mod_item",
+        "type_item",
+        "const_item",
+        "static_item",
+        "macro_definition",
+    ];
+    const PYTHON: &[&str] = &[
+        "function_definition",
+        "class_definition",
+        "decorated_definition",
+    ];
+    const JS: &[&str] = &[
+        "function_declaration",
+        "class_declaration",
+        "lexical_declaration",
+        "export_statement",
+        "interface_declaration",
+        "type_alias_declaration",
+        "enum_declaration",
+    ];
+    const GO: &[&str] = &[
+        "function_declaration",
+        "method_declaration",
+        "type_declaration",
+    ];
+    Some(match ext {
+        "rs" => (tree_sitter_rust::LANGUAGE.into(), RUST),
+        "py" => (tree_sitter_python::LANGUAGE.into(), PYTHON),
+        "js" | "jsx" | "mjs" | "cjs" => (tree_sitter_javascript::LANGUAGE.into(), JS),
+        "ts" => (tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(), JS),
+        "tsx" => (tree_sitter_typescript::LANGUAGE_TSX.into(), JS),
+        "go" => (tree_sitter_go::LANGUAGE.into(), GO),
+        _ => return None,
+    })
+}
+
+/// Signature lines of the top-level symbols in one file; None if it looks generated.
+fn symbols(parser: &mut Parser, workspace: &Path, path: &str) -> Option<Vec<String>> {
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    let Some((language, kinds)) = language(ext) else {
+        return Some(Vec::new());
+    };
+    let full = workspace.join(path);
+    if fs::metadata(&full)
+        .map(|m| m.len() > MAX_FILE_BYTES)
+        .unwrap_or(true)
+    {
+        return Some(Vec::new());
+    }
+    let Ok(source) = fs::read_to_string(&full) else {
+        return Some(Vec::new());
+    };
+    if exclude::looks_generated(&source) {
+        return None;
+    }
+    if parser.set_language(&language).is_err() {
+        return Some(Vec::new());
+    }
+    let Some(tree) = parser.parse(&source, None) else {
+        return Some(Vec::new());
+    };
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    Some(
+        root.children(&mut cursor)
+            .filter(|node| kinds.contains(&node.kind()))
+            .filter_map(|node| signature(node, &source))
+            .collect(),
+    )
+}
+
+/// First line of a declaration, without its body or initializer.
+fn signature(node: Node, source: &str) -> Option<String> {
+    // Decorators come first; the definition line is what identifies the symbol.
+    let text = match node.kind() {
+        "decorated_definition" => node
+            .child_by_field_name("definition")?
+            .utf8_text(source.as_bytes())
+            .ok()?,
+        _ => node.utf8_text(source.as_bytes()).ok()?,
+    };
+    let mut line = text.lines().next()?.trim();
+    if let Some((head, _)) = line.split_once(" = ") {
+        line = head;
+    }
+    let line = line.trim_end_matches(['{', ':', ';']).trim_end();
+    if line.is_empty() {
+        return None;
+    }
+    let mut signature: String = line.chars().take(SIGNATURE_CHARS).collect();
+    if line.chars().count() > SIGNATURE_CHARS {
+        signature.push('…');
+    }
+    Some(signature)
+}
@@ -0,0 +1,154 @@
+//! The report that closes a pipeline run: how each todo went, the files changed, commands
+//! run, verification results, and tokens and cost. Printed after every run, and written to
+//! `.zcode/reports/<run id>.md` with `--save-report` (or `save_report = true`).
+
+use crate::events::{self, Event};
+use crate::options;
+use crate::run::TodoResult;
+use crate::tools::{CommandRun, FileChange};
+use crate::ui::{self, TodoStatus};
+use crate::usage::{self, Usage};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Where reports are written, relative to the workspace.
+pub const REPORTS_DIR: &str = ".zcode/reports";
+
+/// Where the last report was saved, for notifications to link to.
+static LAST_SAVED: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+pub fn last_saved() -> Option<PathBuf> {
+    LAST_SAVED.lock().unwrap().clone()
+}
+
+pub struct Report {
+    pub id: String,
+    pub summary: String,
+    pub prompt: String,
+    pub todos: Vec<TodoResult>,
+    pub files: Vec<FileChange>,
+    pub commands: Vec<CommandRun>,
+    /// Verification commands and whether each passed in the end.
+    pub checks: Vec<(String, bool)>,
+    pub usage: Vec<Usage>,
+}
+
+impl Report {
+    /// Show in the front end and, if enabled, save to the workspace.
+    pub fn finish(&self, workspace: &Path) {
+        events::emit(Event::Report { report: self });
+        if options::get().save_report {
+            match self.save(workspace) {
+                Ok(path) => {
+                    events::note(&format!("Report saved to {}", path.display()));
+                    *LAST_SAVED.lock().unwrap() = Some(path);
+                }
+                Err(e) => events::error(&format!("Could not save the report: {}", e)),
+            }
+        }
+    }
+
+    /// Print to the terminal.
+    pub fn print(&self) {
+        let todos: Vec<String> = self.todos.iter().map(|t| t.todo.clone()).collect();
+        let statuses: Vec<TodoStatus> = self.todos.iter().map(|t| t.status).collect();
+        let done = statuses.iter().filter(|s| **s == TodoStatus::Done).count();
+        ui::note(&format!(
+            "{} — {} of {} steps done",
+            self.summary,
+            done,
+            todos.len()
+        ));
+        ui::todo_list(&todos, &statuses);
+        ui::changes_summary("Files changed", &self.files);
+        if !self.commands.is_empty() {
+            ui::note(&format!("Commands run ({}):", self.commands.len()));
+            for run in &self.commands {
+                ui::note(&format!("  {} {}", command_mark(run.ok), run.command));
+            }
+        }
+        for (command, passed) in &self.checks {
+            ui::note(&format!(
+                "Check {}: {}",
+                if *passed { "passed" } else { "FAILED" },
+                command
+            ));
+        }
+        ui::note(&self.usage_line());
+    }
+
+    fn usage_line(&self) -> String {
+        let input: u64 = self.usage.iter().map(|u| u.prompt_tokens).sum();
+        let output: u64 = self.usage.iter().map(|u| u.completion_tokens).sum();
+        format!(
+            "{} model calls, {} in / {} out tokens, ${:.4}",
+            self.usage.len(),
+            input,
+            output,
+            usage::total_cost(&self.usage)
+        )
+    }
+
+    /// The report as markdown.
+    pub fn markdown(&self) -> String {
+        let mut out = format!(
+            "# {}\n\n> {}\n\n## Steps\n\n",
+            self.summary,
+            self.prompt.replace('\n', "\n> ")
+        );
+        for todo in &self.todos {
+            let mark = match todo.status {
+                TodoStatus::Done => "[x]",
+                _ => "[ ]",
+            };
+            out.push_str(&format!("- {} {}", mark, todo.todo));
+            if todo.status == TodoStatus::Failed {
+                out.push_str(" (failed)");
+            }
+            out.push('\n');
+        }
+        out.push_str("\n## Files changed\n\n");
+        if self.files.is_empty() {
+            out.push_str("None.\n");
+        }
+        for file in &self.files {
+            let (added, removed) = ui::line_counts(file.before.as_deref(), file.after.as_deref());
+            out.push_str(&format!("- `{}` +{} -{}\n", file.path, added, removed));
+        }
+        if !self.commands.is_empty() {
+            out.push_str("\n## Commands run\n\n");
+            for run in &self.commands {
+                out.push_str(&format!("- {} `{}`\n", command_mark(run.ok), run.command));
+            }
+        }
+        if !self.checks.is_empty() {
+            out.push_str("\n## Verification\n\n");
+            for (command, passed) in &self.checks {
+                out.push_str(&format!(
+                    "- {} `{}`\n",
+                    if *passed { "passed" } else { "failed" },
+                    command
+                ));
+            }
+        }
+        out.push_str(&format!("\n## Usage\n\n{}\n", self.usage_line()));
+        out
+    }
+
+    fn save(&self, workspace: &Path) -> Result<PathBuf, String> {
+        let dir = workspace.join(REPORTS_DIR);
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let path = dir.join(format!("{}.md", self.id));
+        fs::write(&path, self.markdown()).map_err(|e| e.to_string())?;
+        Ok(path)
+    }
+}
+
+fn command_mark(ok: Option<bool>) -> &'static str {
+    match ok {
+        Some(true) => "ok",
+        Some(false) => "failed",
+        None => "interrupted",
+    }
+}
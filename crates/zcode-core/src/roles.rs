@@ -0,0 +1,75 @@
+//! Named agent roles for `--strategy multi-agent`: an architect plans, an implementer works
+//! through the steps, a tester covers the changes with tests, and a reviewer checks the diff.
+//! Each role's model and instructions can be set under `[roles.<name>]` in the config.
+
+use crate::agent::{self, OpenAiAgent};
+use crate::config;
+use crate::run;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Architect,
+    Implementer,
+    Tester,
+    Reviewer,
+}
+
+impl Role {
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Architect => "architect",
+            Role::Implementer => "implementer",
+            Role::Tester => "tester",
+            Role::Reviewer => "reviewer",
+        }
+    }
+
+    fn default_prompt(self) -> &'static str {
+        match self {
+            Role::Architect => "You are the architect. Break the work into steps that follow the project's existing structure and keep each step small enough to check.",
+            Role::Implementer => "You are the implementer. Carry out each step exactly as planned, matching the style of the surrounding code.",
+            Role::Tester => "You are the tester. Write or update tests that cover the changes, following the project's test layout, and run them.",
+            Role::Reviewer => "You are the reviewer. Be strict about correctness and missed requirements; ignore matters of taste.",
+        }
+    }
+
+    /// Model spec: `roles.<name>.model`, else the planner model for the reviewer and the
+    /// executor model for the rest.
+    pub fn model(self) -> String {
+        self.configured().model.unwrap_or_else(|| match self {
+            Role::Reviewer => run::planner_model(),
+            _ => run::executor_model(),
+        })
+    }
+
+    /// Instructions: `roles.<name>.prompt`, else a built-in one.
+    pub fn prompt(self) -> String {
+        self.configured()
+            .prompt
+            .unwrap_or_else(|| self.default_prompt().to_string())
+    }
+
+    fn configured(self) -> config::RoleConfig {
+        config::load().roles.remove(self.name()).unwrap_or_default()
+    }
+
+    pub fn agent(self, api_key: &str) -> Result<OpenAiAgent, String> {
+        Ok(agent::from_spec(&self.model(), api_key)?.with_instructions(&self.prompt()))
+    }
+}
+
+/// The roles the pipeline adds after execution (the architect and implementer take the
+/// planner and executor slots).
+pub struct Team {
+    pub tester: OpenAiAgent,
+    pub reviewer: OpenAiAgent,
+}
+
+impl Team {
+    pub fn from_config(api_key: &str) -> Result<Self, String> {
+        Ok(Self {
+            tester: Role::Tester.agent(api_key)?,
+            reviewer: Role::Reviewer.agent(api_key)?,
+        })
+    }
+}
@@ -0,0 +1,86 @@
+//! Per-phase model routing: `[routing]` in the config sends a pipeline phase, or turns that
+//! continue a tool loop vs. ones that start from a prompt, to a different model than the
+//! planner or executor would use.
+//!
+//! ```toml
+//! [routing]
+//! planning = "gpt-4o-mini"
+//! tracking = "gpt-4o-mini"
+//!
+//! [routing.execution]
+//! tools = "gpt-4o"
+//! prose = "gpt-4.1"
+//! ```
+
+use crate::config::{self, Route};
+use std::future::Future;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Planning,
+    Execution,
+    /// Checking which todos a step finished.
+    Tracking,
+    Replanning,
+    Testing,
+    Review,
+    Verification,
+}
+
+impl Phase {
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Planning => "planning",
+            Phase::Execution => "execution",
+            Phase::Tracking => "tracking",
+            Phase::Replanning => "replanning",
+            Phase::Testing => "testing",
+            Phase::Review => "review",
+            Phase::Verification => "verification",
+        }
+    }
+}
+
+/// What a model call is doing: continuing a tool loop, or writing from a prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
+    Tools,
+    Prose,
+}
+
+impl Turn {
+    pub fn name(self) -> &'static str {
+        match self {
+            Turn::Tools => "tools",
+            Turn::Prose => "prose",
+        }
+    }
+}
+
+tokio::task_local! {
+    static PHASE: Phase;
+}
+
+/// Run `future` with model calls inside it routed as `phase`. Scopes nest; the innermost wins.
+pub async fn scoped<F: Future>(phase: Phase, future: F) -> F::Output {
+    PHASE.scope(phase, future).await
+}
+
+/// The phase of the calling task, if it runs inside `scoped`.
+pub fn current() -> Option<Phase> {
+    PHASE.try_with(|p| *p).ok()
+}
+
+/// Configured model for a call: `routing.<phase>.<turn>`, then `routing.<phase>`, then
+/// `routing.<turn>`. None keeps the agent's own model.
+pub fn model_for(phase: Option<Phase>, turn: Turn) -> Option<String> {
+    let routing = config::load().routing;
+    let by_phase = phase.and_then(|p| match routing.get(p.name())? {
+        Route::Model(model) => Some(model.clone()),
+        Route::ByTurn(turns) => turns.get(turn.name()).cloned(),
+    });
+    by_phase.or_else(|| match routing.get(turn.name())? {
+        Route::Model(model) => Some(model.clone()),
+        Route::ByTurn(_) => None,
+    })
+}
@@ -0,0 +1,511 @@
+//! `zcode --stdio`: JSON-RPC 2.0 on stdin and stdout, one message per line, for editor
+//! plugins. Requests start sessions, run prompts, answer approvals, and steer or cancel the
+//! running prompt; while a prompt runs, its events (the same JSON as `--output-format json`)
+//! arrive as `event` notifications. `edit` works on editor buffers instead: nothing is written,
+//! and the changes come back as text edits for the editor to apply. The protocol is described
+//! in the README.
+
+use crate::agent::Message;
+use crate::approval;
+use crate::context;
+use crate::events::{self, ChangedFile};
+use crate::hooks;
+use crate::interrupt;
+use crate::run::{self, execute_step, Agents, StepOutcome};
+use crate::session::Session;
+use crate::steering;
+use crate::tools::{Executor, FileChange};
+use crate::usage;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use similar::TextDiff;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{broadcast, mpsc};
+
+/// Events buffered for the writer.
+const EVENT_BUFFER: usize = 1024;
+
+/// Version of the protocol, reported by `initialize`.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// A prompt is already running.
+const BUSY: i64 = -32000;
+/// No such session, or no such pending tool call.
+const NOT_FOUND: i64 = -32001;
+/// Steering or cancelling with no prompt running.
+const IDLE: i64 = -32002;
+/// An `edit` stopped before the model finished (a failed request, or repeated tool errors).
+const FAILED: i64 = -32003;
+
+type RpcError = (i64, String);
+
+#[derive(Deserialize)]
+struct Request {
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct InitializeParams {
+    auto_approve: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct StartParams {
+    /// Id of a saved session to continue.
+    resume: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PromptParams {
+    session_id: String,
+    prompt: String,
+}
+
+#[derive(Deserialize)]
+struct ApprovalParams {
+    id: String,
+    approve: bool,
+}
+
+#[derive(Deserialize)]
+struct SteerParams {
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct EditParams {
+    prompt: String,
+    /// Open buffers, which may differ from the files on disk.
+    #[serde(default)]
+    buffers: Vec<Buffer>,
+    selection: Option<Selection>,
+}
+
+#[derive(Deserialize)]
+struct Buffer {
+    path: String,
+    text: String,
+}
+
+/// Selected lines, 1-based and inclusive.
+#[derive(Deserialize)]
+struct Selection {
+    path: String,
+    start_line: usize,
+    end_line: usize,
+}
+
+#[derive(Clone)]
+struct Rpc {
+    agents: Arc<Agents>,
+    executor: Arc<Executor>,
+    /// Lines for stdout; one writer keeps messages whole.
+    out: mpsc::UnboundedSender<String>,
+    sessions: Arc<Mutex<BTreeMap<String, Arc<Mutex<Session>>>>>,
+    running: Arc<AtomicBool>,
+}
+
+/// The claim on the one prompt slot; dropping it, even while unwinding from a panic, frees the
+/// slot.
+struct Running(Arc<AtomicBool>);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Serve requests from stdin until it closes or a `shutdown` request arrives.
+pub async fn run(agents: Agents, executor: Arc<Executor>) -> Result<(), String> {
+    approval::set_required(true);
+    let (out, mut lines) = mpsc::unbounded_channel::<String>();
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(line) = lines.recv().await {
+            if stdout
+                .write_all(format!("{}\n", line).as_bytes())
+                .await
+                .is_err()
+                || stdout.flush().await.is_err()
+            {
+                break;
+            }
+        }
+    });
+    let rpc = Rpc {
+        agents: Arc::new(agents),
+        executor,
+        out,
+        sessions: Arc::default(),
+        running: Arc::default(),
+    };
+    hooks::session_start(&rpc.executor);
+
+    let mut stdin = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = stdin.next_line().await.map_err(|e| e.to_string())? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: Request = match serde_json::from_str::<Value>(&line) {
+            Err(e) => {
+                rpc.respond(Value::Null, Err((PARSE_ERROR, e.to_string())));
+                continue;
+            }
+            Ok(value) => match serde_json::from_value(value) {
+                Ok(request) => request,
+                Err(e) => {
+                    rpc.respond(Value::Null, Err((INVALID_REQUEST, e.to_string())));
+                    continue;
+                }
+            },
+        };
+        if request.method == "shutdown" {
+            rpc.respond(request.id.unwrap_or_default(), Ok(Value::Null));
+            break;
+        }
+        rpc.handle(request);
+    }
+
+    interrupt::trigger();
+    rpc.executor.kill_children();
+    hooks::session_end(&rpc.executor);
+    drop(rpc);
+    // A prompt still winding down holds a sender; don't wait for it past a flush.
+    let _ = tokio::time::timeout(Duration::from_secs(2), writer).await;
+    Ok(())
+}
+
+/// Wrap a prompt's events as notifications for its session (None for `edit`), until the sink
+/// is dropped.
+async fn forward_events(
+    rpc: Rpc,
+    session_id: Option<String>,
+    mut events: broadcast::Receiver<String>,
+) {
+    loop {
+        match events.recv().await {
+            Ok(line) => {
+                let event: Value = serde_json::from_str(&line).unwrap_or(Value::String(line));
+                rpc.notify("event", json!({ "session_id": session_id, "event": event }));
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    // Methods without required params may be called with none.
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string()))
+}
+
+impl Rpc {
+    fn send(&self, message: Value) {
+        let _ = self.out.send(message.to_string());
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn respond(&self, id: Value, result: Result<Value, RpcError>) {
+        self.send(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => {
+                json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+            }
+        });
+    }
+
+    /// Answer a request; `session/prompt` answers when its prompt finishes. Notifications
+    /// (requests without an id) get no response, not even for errors.
+    fn handle(&self, request: Request) {
+        let result = match request.method.as_str() {
+            "initialize" => self.initialize(request.params),
+            "session/start" => self.start_session(request.params),
+            "session/prompt" => match self.start_prompt(request.params, request.id.clone()) {
+                Ok(()) => return,
+                Err(e) => Err(e),
+            },
+            "edit" => match self.start_edit(request.params, request.id.clone()) {
+                Ok(()) => return,
+                Err(e) => Err(e),
+            },
+            "approval/respond" => params(request.params).and_then(|p: ApprovalParams| {
+                if self.running.load(Ordering::Relaxed) && approval::decide(&p.id, p.approve) {
+                    Ok(Value::Null)
+                } else {
+                    Err((
+                        NOT_FOUND,
+                        format!("No tool call {} is waiting for approval", p.id),
+                    ))
+                }
+            }),
+            "steer" => params(request.params).and_then(|p: SteerParams| {
+                self.require_running()?;
+                steering::push(&p.text);
+                Ok(Value::Null)
+            }),
+            "cancel" => self.require_running().map(|()| {
+                interrupt::trigger();
+                Value::Null
+            }),
+            method => Err((METHOD_NOT_FOUND, format!("Unknown method {}", method))),
+        };
+        if let Some(id) = request.id {
+            self.respond(id, result);
+        }
+    }
+
+    /// Claim the prompt slot, or BUSY while a prompt or edit holds it.
+    fn claim(&self) -> Result<Running, RpcError> {
+        if self.running.swap(true, Ordering::Relaxed) {
+            return Err((BUSY, "A prompt is already running".into()));
+        }
+        Ok(Running(self.running.clone()))
+    }
+
+    fn require_running(&self) -> Result<(), RpcError> {
+        if self.running.load(Ordering::Relaxed) {
+            Ok(())
+        } else {
+            Err((IDLE, "No prompt is running".into()))
+        }
+    }
+
+    fn initialize(&self, raw: Value) -> Result<Value, RpcError> {
+        let p: InitializeParams = params(raw)?;
+        approval::set_required(!p.auto_approve);
+        Ok(json!({
+            "name": "zcode",
+            "version": env!("CARGO_PKG_VERSION"),
+            "protocol_version": PROTOCOL_VERSION,
+            "workspace": self.executor.workspace(),
+        }))
+    }
+
+    fn start_session(&self, raw: Value) -> Result<Value, RpcError> {
+        let p: StartParams = params(raw)?;
+        let mut session = match &p.resume {
+            Some(id) => Session::load(id).map_err(|e| (NOT_FOUND, e))?,
+            None => Session::default(),
+        };
+        let mut sessions = self.sessions.lock().unwrap();
+        if p.resume.is_none() {
+            // Ids are timestamps in seconds; keep sessions started in the same second apart.
+            let base = session.id.clone();
+            let mut n = 1;
+            while sessions.contains_key(&session.id) {
+                n += 1;
+                session.id = format!("{}-{}", base, n);
+            }
+        }
+        let result = json!({ "session_id": session.id, "turns": session.turns });
+        sessions.insert(session.id.clone(), Arc::new(Mutex::new(session)));
+        Ok(result)
+    }
+
+    fn start_prompt(&self, raw: Value, id: Option<Value>) -> Result<(), RpcError> {
+        let p: PromptParams = params(raw)?;
+        if p.prompt.trim().is_empty() {
+            return Err((INVALID_PARAMS, "Empty prompt".into()));
+        }
+        let session = self
+            .sessions
+            .lock()
+            .unwrap()
+            .get(&p.session_id)
+            .cloned()
+            .ok_or_else(|| (NOT_FOUND, format!("No session {}", p.session_id)))?;
+        let running = self.claim()?;
+        let rpc = self.clone();
+        tokio::spawn(async move {
+            let result = rpc.run_prompt(&p.session_id, &session, &p.prompt).await;
+            drop(running);
+            if let Some(id) = id {
+                rpc.respond(id, Ok(result));
+            }
+        });
+        Ok(())
+    }
+
+    /// Run one prompt with the session's history; returns the `session/prompt` result.
+    async fn run_prompt(&self, session_id: &str, session: &Mutex<Session>, prompt: &str) -> Value {
+        let full_prompt = {
+            let mut session = session.lock().unwrap();
+            // Its run, journal, and usage are recorded under this session.
+            session.activate();
+            session.begin_turn(prompt);
+            let full_prompt = context::expand_mentions(prompt, self.executor.workspace());
+            let history = session.history_block();
+            if history.is_empty() {
+                full_prompt
+            } else {
+                format!("Earlier in this session:\n{}\n\n{}", history, full_prompt)
+            }
+        };
+        let (sink, events) = broadcast::channel(EVENT_BUFFER);
+        let forwarder = tokio::spawn(forward_events(
+            self.clone(),
+            Some(session_id.to_string()),
+            events,
+        ));
+        events::set_sink(Some(sink));
+        self.executor.checkpoint();
+        let mark = usage::mark();
+        let reply = run::run_once(&self.agents, &self.executor, &full_prompt).await;
+        // Every event is out before the response.
+        events::set_sink(None);
+        let _ = forwarder.await;
+        let changes: Vec<ChangedFile> = self
+            .executor
+            .changes()
+            .iter()
+            .map(ChangedFile::from)
+            .collect();
+        let result = json!({
+            "reply": reply,
+            "changes": changes,
+            "cost": usage::total_cost(usage::all().get(mark..).unwrap_or_default()),
+        });
+        session.lock().unwrap().finish_turn(reply);
+        result
+    }
+
+    fn start_edit(&self, raw: Value, id: Option<Value>) -> Result<(), RpcError> {
+        let mut p: EditParams = params(raw)?;
+        if p.prompt.trim().is_empty() {
+            return Err((INVALID_PARAMS, "Empty prompt".into()));
+        }
+        // Editors know buffers by absolute path; tools use paths relative to the workspace.
+        let workspace = self.executor.workspace().to_path_buf();
+        let relative = |path: &str| {
+            std::path::Path::new(path)
+                .strip_prefix(&workspace)
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_else(|_| path.to_string())
+        };
+        for buffer in &mut p.buffers {
+            buffer.path = relative(&buffer.path);
+        }
+        if let Some(selection) = &mut p.selection {
+            selection.path = relative(&selection.path);
+        }
+        let running = self.claim()?;
+        let rpc = self.clone();
+        tokio::spawn(async move {
+            let result = rpc.run_edit(p).await;
+            drop(running);
+            if let Some(id) = id {
+                rpc.respond(id, result);
+            }
+        });
+        Ok(())
+    }
+
+    /// Run an `edit` request against an in-memory copy of its buffers; returns the edits.
+    async fn run_edit(&self, p: EditParams) -> Result<Value, RpcError> {
+        let buffers: BTreeMap<String, String> =
+            p.buffers.into_iter().map(|b| (b.path, b.text)).collect();
+        let executor =
+            Executor::in_memory(self.executor.workspace().to_path_buf(), buffers.clone());
+        let mut prompt = p.prompt;
+        if let Some(selection) = &p.selection {
+            let content = executor
+                .read(&selection.path)
+                .map_err(|e| (INVALID_PARAMS, format!("{}: {}", selection.path, e)))?;
+            let start = selection.start_line.max(1);
+            let lines: String = content
+                .lines()
+                .enumerate()
+                .skip(start - 1)
+                .take((selection.end_line + 1).saturating_sub(start))
+                .map(|(i, l)| format!("{:>5} {}\n", i + 1, l))
+                .collect();
+            prompt.push_str(&format!(
+                "\n\nSelected in the editor, {} lines {}-{}:\n{}",
+                selection.path, start, selection.end_line, lines
+            ));
+        }
+        if !buffers.is_empty() {
+            let blocks: Vec<String> = buffers
+                .iter()
+                .map(|(path, text)| {
+                    format!(
+                        "--- {} ---\n{}",
+                        path,
+                        context::truncate(text, context::MENTION_FILE_CAP)
+                    )
+                })
+                .collect();
+            prompt.push_str(&format!(
+                "\n\nOpen editor buffers (current contents, possibly unsaved):\n{}",
+                blocks.join("\n\n")
+            ));
+        }
+        prompt.push_str("\n\nMake the change with write_file, giving each file's complete new content. Your edits go to the user's editor, not to disk, so commands can't be run; read other files if you need them.");
+
+        let (sink, events) = broadcast::channel(EVENT_BUFFER);
+        let forwarder = tokio::spawn(forward_events(self.clone(), None, events));
+        events::set_sink(Some(sink));
+        // Nothing touches the disk, so there is nothing to approve.
+        let approvals = approval::required();
+        approval::set_required(false);
+        let mut messages = vec![Message::Role {
+            role: "user".into(),
+            content: prompt,
+        }];
+        let outcome = execute_step(&self.agents.exec_agent, &executor, &mut messages).await;
+        approval::set_required(approvals);
+        events::set_sink(None);
+        let _ = forwarder.await;
+
+        let reply = match outcome {
+            StepOutcome::Done(reply) | StepOutcome::Interrupted(reply) => reply,
+            StepOutcome::Failed(e) | StepOutcome::Stalled(e) => return Err((FAILED, e)),
+        };
+        let files: Vec<Value> = executor.changes().iter().map(file_edits).collect();
+        Ok(json!({ "reply": reply, "files": files }))
+    }
+}
+
+/// A changed file as LSP-style text edits (0-based lines, whole lines replaced), ready for
+/// e.g. `vim.lsp.util.apply_text_edits`.
+fn file_edits(change: &FileChange) -> Value {
+    let before = change.before.as_deref().unwrap_or_default();
+    let after = change.after.as_deref().unwrap_or_default();
+    let new_lines: Vec<&str> = after.split_inclusive('\n').collect();
+    let diff = TextDiff::from_lines(before, after);
+    let edits: Vec<Value> = diff
+        .grouped_ops(0)
+        .iter()
+        .filter_map(|group| {
+            let (first, last) = (group.first()?, group.last()?);
+            let old = first.old_range().start..last.old_range().end;
+            let new = first.new_range().start..last.new_range().end;
+            Some(json!({
+                "range": {
+                    "start": { "line": old.start, "character": 0 },
+                    "end": { "line": old.end, "character": 0 },
+                },
+                "newText": new_lines[new].concat(),
+            }))
+        })
+        .collect();
+    json!({ "path": change.path, "created": change.before.is_none(), "edits": edits })
+}
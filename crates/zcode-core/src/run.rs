@@ -0,0 +1,1967 @@
+//! Multi-step reasoning pipeline: plan → gather context → execute todos → review → verify →
+//! report.
+
+use crate::agent::{self, Agent, Message, OpenAiAgent, ToolCall, READ_ONLY_TOOLS};
+use crate::approval;
+use crate::ci;
+use crate::config;
+use crate::context;
+use crate::dataset;
+use crate::events::{self, Event};
+use crate::git;
+use crate::hooks;
+use crate::index;
+use crate::interrupt;
+use crate::memory;
+use crate::notify;
+use crate::options;
+use crate::plan_cache;
+use crate::plan_review;
+use crate::ranking;
+use crate::repomap;
+use crate::report::Report;
+use crate::roles::{Role, Team};
+use crate::routing::{self, Phase};
+use crate::run_state::{self, RunState};
+use crate::session;
+use crate::slicing;
+use crate::steering;
+use crate::tools::{Executor, FileChange};
+use crate::ui;
+use crate::usage;
+use crate::verify;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::info;
+
+pub(crate) const PLANNER_MODEL: &str = "gpt-4o-mini";
+pub(crate) const EXECUTOR_MODEL: &str = "gpt-4o";
+
+/// Model spec for planning and progress checks: `--planner-model`, else `planner_model` in
+/// the config (either may be a `[models]` alias), else `PLANNER_MODEL`.
+pub fn planner_model() -> String {
+    options::get()
+        .planner_model
+        .or_else(|| config::load().planner_model)
+        .map_or_else(
+            || PLANNER_MODEL.to_string(),
+            |spec| config::resolve_model(&spec),
+        )
+}
+
+/// Model spec for tool use: `--executor-model`, else `executor_model` in the config, else
+/// `EXECUTOR_MODEL`.
+pub fn executor_model() -> String {
+    options::get()
+        .executor_model
+        .or_else(|| config::load().executor_model)
+        .map_or_else(
+            || EXECUTOR_MODEL.to_string(),
+            |spec| config::resolve_model(&spec),
+        )
+}
+
+/// Check that the planner, executor, role, and routing models name OpenAI or a configured
+/// provider, and that they are in `allowed_models` when it is set.
+pub fn check_models() -> Result<(), String> {
+    let config = config::load();
+    let mut models = vec![planner_model(), executor_model()];
+    models.extend(config.roles.into_values().filter_map(|role| role.model));
+    for route in config.routing.into_values() {
+        match route {
+            config::Route::Model(model) => models.push(model),
+            config::Route::ByTurn(turns) => models.extend(turns.into_values()),
+        }
+    }
+    let models: Vec<String> = models.iter().map(|m| config::resolve_model(m)).collect();
+    for model in &models {
+        agent::check_spec(model)?;
+    }
+    let Some(allowed) = config.allowed_models else {
+        return Ok(());
+    };
+    match models.iter().find(|model| {
+        !allowed
+            .iter()
+            .any(|p| crate::tools::glob_match(model, p.trim()))
+    }) {
+        Some(model) => Err(format!("Model {} is not in allowed_models", model)),
+        None => Ok(()),
+    }
+}
+
+const PLANNER_SYSTEM: &str = r#"You are a coding task planner. Given a user request and a map of the repository's files and their top-level symbols, output a JSON object (and nothing else) with:
+- "summary": one-line summary of the task
+- "paths_to_read": array of file/dir paths to read for context (e.g. ["src/main.rs", "Cargo.toml"]). Use at most 8 paths. Omit if not needed.
+- "todos": array of 1–8 concrete step descriptions to complete the task (e.g. "Add a config module", "Update main to use config")
+- "files_to_change": array of paths you expect to create or modify
+- "scope": "small", "medium", or "large" — your estimate of the size of the change
+- "parallel": array of groups of step numbers (e.g. [[2, 3]]) whose steps are independent and touch disjoint files, so they can run at the same time. Omit if steps depend on each other.
+
+Output only valid JSON, no markdown or explanation."#;
+
+/// Max chars of the working tree's uncommitted diff given to the planner and executor.
+const WORKING_DIFF_CAP: usize = 16 * 1024;
+
+/// Hits from the semantic index that rank files for the context when an index exists.
+const RELEVANT_CHUNKS: usize = 10;
+
+const TRACK_SYSTEM: &str = r#"You track progress on a coding plan. Given the numbered plan and a transcript of the assistant's latest work, output a JSON object (and nothing else): {"done": [numbers of the steps this work completed]}. Count a step only if the transcript shows it finished; an empty list is fine."#;
+
+/// Times a step is handed back to the executor while the tracker does not see it finished.
+const MAX_NUDGES: usize = 2;
+/// Consecutive failed tool calls after which a step counts as stalled.
+const MAX_TOOL_ERRORS: usize = 3;
+/// Plan revisions allowed per run when steps stall.
+const MAX_REPLANS: usize = 2;
+
+const REPLAN_SYSTEM: &str = r#"You are a coding task planner revising a plan that stalled. Given the user request, the plan with each step's status, and what went wrong, output a JSON object (and nothing else): {"todos": [1–6 concrete steps that replace the stalled step and everything after it]}. Take a different approach from the one that failed; do not repeat completed steps."#;
+
+/// Max chars of each message in the transcript given to the tracker.
+const TRACK_MESSAGE_CAP: usize = 500;
+
+const CRITIC_SYSTEM: &str = r#"You are a senior engineer reviewing a change. Given the user request and the diff that was made for it, look for bugs, requirements the diff misses, and style that clashes with the surrounding code. Output a JSON object (and nothing else): {"findings": [short, specific problems to fix, each naming the file]}. Only list real problems; an empty list means the change is good."#;
+
+/// Model requests a sub-agent may make.
+const SUBAGENT_REQUESTS: usize = 15;
+/// Max chars of the diff a sub-agent reports back.
+const SUBAGENT_DIFF_CAP: usize = 8 * 1024;
+
+/// Max chars of the diff given to the reviewer.
+const CRITIC_DIFF_CAP: usize = 24 * 1024;
+
+/// The models a pipeline run talks to: a cheap planner (plan and progress checks) and a stronger
+/// executor (tool use). Any `Agent` can fill either role.
+pub struct Agents<P: Agent = OpenAiAgent, E: Agent = OpenAiAgent> {
+    pub planner: P,
+    pub exec_agent: E,
+    /// OpenAI key for searching the semantic index, for embedders that need it.
+    pub index_key: Option<String>,
+    /// Tester and reviewer for `--strategy multi-agent`.
+    pub team: Option<Team>,
+}
+
+impl Agents {
+    /// Planner and executor for the configured models (`planner_model`, `executor_model`), or
+    /// the architect, implementer, and the rest of the team with `--strategy multi-agent`.
+    pub fn from_config(api_key: &str) -> Result<Self, String> {
+        if options::get().multi_agent {
+            return Ok(Self {
+                planner: Role::Architect.agent(api_key)?.with_routing(api_key),
+                exec_agent: Role::Implementer.agent(api_key)?.with_routing(api_key),
+                index_key: Some(api_key.to_string()),
+                team: Some(Team::from_config(api_key)?),
+            });
+        }
+        Self::for_models(api_key, &planner_model(), &executor_model())
+    }
+
+    /// Planner and executor for the given model specs.
+    pub fn for_models(api_key: &str, planner: &str, executor: &str) -> Result<Self, String> {
+        Ok(Self {
+            planner: agent::from_spec(planner, api_key)?.with_routing(api_key),
+            exec_agent: agent::from_spec(executor, api_key)?.with_routing(api_key),
+            index_key: Some(api_key.to_string()),
+            team: None,
+        })
+    }
+}
+
+/// Plan from the planner model (JSON).
+#[derive(Debug, Deserialize)]
+struct Plan {
+    summary: Option<String>,
+    paths_to_read: Option<Vec<String>>,
+    todos: Option<Vec<String>>,
+    files_to_change: Option<Vec<String>>,
+    scope: Option<String>,
+    parallel: Option<Vec<Vec<usize>>>,
+}
+
+/// Revised remaining steps from the planner.
+#[derive(Debug, Deserialize)]
+struct Revision {
+    todos: Vec<String>,
+}
+
+/// Reviewer answer: problems to fix in the diff.
+#[derive(Debug, Deserialize)]
+struct Review {
+    findings: Vec<String>,
+}
+
+/// Tracker answer: 1-based numbers of the steps the latest work completed.
+#[derive(Debug, Deserialize)]
+struct Progress {
+    done: Vec<usize>,
+}
+
+/// The plan plus the context gathered for it: everything the execute phase needs.
+struct Prepared {
+    summary: String,
+    todos: Vec<String>,
+    /// Paths from the plan that were read into the context.
+    read: Vec<String>,
+    files_to_change: Vec<String>,
+    scope: Option<String>,
+    context_block: String,
+    /// Groups of independent todos (0-based) that may run concurrently.
+    parallel: Vec<Vec<usize>>,
+}
+
+fn extract_json(text: &str) -> Option<&str> {
+    let text = text.trim();
+    if let Some(s) = text.strip_prefix("```json") {
+        return s.trim_end().strip_suffix("```").map(|s| s.trim());
+    }
+    if let Some(s) = text.strip_prefix("```") {
+        return s.trim_end().strip_suffix("```").map(|s| s.trim());
+    }
+    Some(text)
+}
+
+fn read_file_call(path: &str) -> ToolCall {
+    ToolCall {
+        id: "ctx_read".into(),
+        type_: "function".into(),
+        function: crate::agent::FunctionCall {
+            name: "read_file".into(),
+            arguments: format!(r#"{{"path":"{}"}}"#, path),
+        },
+    }
+}
+
+/// The planner's 1-based parallel groups as 0-based indices, dropping out-of-range or repeated
+/// steps and groups left with fewer than two.
+fn parallel_groups(groups: Vec<Vec<usize>>, todo_count: usize) -> Vec<Vec<usize>> {
+    let mut seen = Vec::new();
+    let mut valid = Vec::new();
+    for group in groups {
+        let mut members = Vec::new();
+        for i in group.into_iter().filter_map(|n| n.checked_sub(1)) {
+            if i < todo_count && !seen.contains(&i) {
+                seen.push(i);
+                members.push(i);
+            }
+        }
+        if members.len() > 1 {
+            members.sort_unstable();
+            valid.push(members);
+        }
+    }
+    valid
+}
+
+/// Paths written by the file-writing tool calls in `messages`.
+fn written_paths(messages: &[Message]) -> Vec<String> {
+    let mut paths = Vec::new();
+    for message in messages {
+        if let Message::Assistant {
+            tool_calls: Some(calls),
+            ..
+        } = message
+        {
+            for path in calls.iter().filter_map(written_path) {
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+    }
+    paths
+}
+
+/// Path written by a file-writing tool call (for showing its diff).
+fn written_path(tc: &ToolCall) -> Option<String> {
+    if !matches!(tc.function.name.as_str(), "create_file" | "write_file") {
+        return None;
+    }
+    let v: serde_json::Value = serde_json::from_str(&tc.function.arguments).ok()?;
+    v["path"].as_str().map(|s| s.to_string())
+}
+
+/// Add a write to the batch, merging repeated writes to the same path.
+fn record_change(
+    changes: &mut Vec<FileChange>,
+    path: &str,
+    before: Option<String>,
+    after: Option<String>,
+) {
+    match changes.iter_mut().find(|c| c.path == path) {
+        Some(existing) => existing.after = after,
+        None => changes.push(FileChange {
+            path: path.to_string(),
+            before,
+            after,
+        }),
+    }
+}
+
+/// Assistant turns and tool results in `messages`, condensed for the tracker.
+fn work_transcript(messages: &[Message]) -> String {
+    let cap = |s: &str| context::truncate(s, TRACK_MESSAGE_CAP);
+    let mut lines = Vec::new();
+    for message in messages {
+        match message {
+            Message::Assistant {
+                content,
+                tool_calls,
+                ..
+            } => {
+                if let Some(content) = content.as_deref().filter(|c| !c.is_empty()) {
+                    lines.push(format!("Assistant: {}", cap(content)));
+                }
+                for tc in tool_calls.iter().flatten() {
+                    lines.push(format!(
+                        "Tool call {}: {}",
+                        tc.function.name,
+                        cap(&tc.function.arguments)
+                    ));
+                }
+            }
+            Message::ToolResult {
+                function_name,
+                content,
+                ..
+            } => {
+                lines.push(format!("Result of {}: {}", function_name, cap(content)));
+            }
+            Message::Role { .. } => {}
+        }
+    }
+    lines.join("\n")
+}
+
+/// Ask the planner which todos (0-based) the work in `messages` finished. None if it couldn't tell.
+async fn finished_todos<P: Agent>(
+    planner: &P,
+    plan_list: &[String],
+    messages: &[Message],
+) -> Option<Vec<usize>> {
+    let user = format!(
+        "Plan:\n{}\n\nLatest work:\n{}",
+        plan_list.join("\n"),
+        work_transcript(messages)
+    );
+    let checking = events::progress(
+        "Checking progress",
+        routing::scoped(Phase::Tracking, planner.completion(TRACK_SYSTEM, &user)),
+    );
+    let text = match interrupt::cancellable(checking).await? {
+        Ok(text) => text,
+        Err(e) => {
+            info!(error = %e, "progress check failed");
+            return None;
+        }
+    };
+    let progress: Progress = serde_json::from_str(extract_json(&text).unwrap_or(&text)).ok()?;
+    Some(
+        progress
+            .done
+            .into_iter()
+            .filter_map(|n| n.checked_sub(1))
+            .filter(|i| *i < plan_list.len())
+            .collect(),
+    )
+}
+
+/// Todos as a numbered list ("1. ...").
+fn numbered(todos: &[String]) -> Vec<String> {
+    todos
+        .iter()
+        .enumerate()
+        .map(|(i, t)| format!("{}. {}", i + 1, t))
+        .collect()
+}
+
+/// Ask the planner for new steps replacing todo `from` onwards after it stalled with `failure`.
+async fn replan<P: Agent>(
+    planner: &P,
+    user_prompt: &str,
+    todos: &[String],
+    statuses: &[ui::TodoStatus],
+    from: usize,
+    failure: &str,
+) -> Option<Vec<String>> {
+    let plan: Vec<String> = numbered(todos)
+        .into_iter()
+        .zip(statuses)
+        .enumerate()
+        .map(|(n, (line, status))| {
+            let status = match status {
+                ui::TodoStatus::Done => "done",
+                _ if n == from => "stalled",
+                _ => "not started",
+            };
+            format!("{} [{}]", line, status)
+        })
+        .collect();
+    let user = format!(
+        "User request:\n{}\n\nPlan:\n{}\n\nWhat went wrong:\n{}",
+        user_prompt,
+        plan.join("\n"),
+        failure
+    );
+    let planning = events::progress(
+        "Revising plan",
+        routing::scoped(Phase::Replanning, planner.completion(REPLAN_SYSTEM, &user)),
+    );
+    let text = match interrupt::cancellable(planning).await? {
+        Ok(text) => text,
+        Err(e) => {
+            events::error(&e);
+            return None;
+        }
+    };
+    let revision: Revision = serde_json::from_str(extract_json(&text).unwrap_or(&text)).ok()?;
+    (!revision.todos.is_empty()).then_some(revision.todos)
+}
+
+/// Everything the run changed so far as a unified diff, capped at `CRITIC_DIFF_CAP`.
+fn run_diff(executor: &Executor) -> String {
+    let diff: String = executor
+        .changes()
+        .iter()
+        .map(ui::plain_diff)
+        .collect::<Vec<_>>()
+        .join("\n");
+    context::truncate(&diff, CRITIC_DIFF_CAP)
+}
+
+/// Ask `reviewer` to review the run's diff against the request. None if it couldn't tell.
+async fn critique<R: Agent>(
+    reviewer: &R,
+    user_prompt: &str,
+    executor: &Executor,
+) -> Option<Vec<String>> {
+    let user = format!(
+        "User request:\n{}\n\nDiff:\n{}",
+        user_prompt,
+        run_diff(executor)
+    );
+    let reviewing = events::progress(
+        "Reviewing changes",
+        routing::scoped(Phase::Review, reviewer.completion(CRITIC_SYSTEM, &user)),
+    );
+    let text = match interrupt::cancellable(reviewing).await? {
+        Ok(text) => text,
+        Err(e) => {
+            events::error(&e);
+            return None;
+        }
+    };
+    let review: Review = serde_json::from_str(extract_json(&text).unwrap_or(&text)).ok()?;
+    Some(review.findings)
+}
+
+pub(crate) enum StepOutcome {
+    /// The model finished the step; carries its final text, if any.
+    Done(Option<String>),
+    /// The model request failed.
+    Failed(String),
+    /// Tool calls kept failing; carries the last error.
+    Stalled(String),
+    /// Ctrl-C; carries any text streamed before the interrupt.
+    Interrupted(Option<String>),
+}
+
+/// How far a conversation may go: the main one is unbounded and may spawn sub-agents; a
+/// sub-agent has a request budget and cannot spawn more.
+#[derive(Clone, Copy)]
+struct Scope {
+    max_requests: Option<usize>,
+    can_spawn: bool,
+    /// The conversation is a saved run's, appended to its journal as it grows.
+    journal: bool,
+}
+
+impl Scope {
+    fn journal(&self, messages: &[Message]) {
+        if self.journal {
+            run_state::journal(messages);
+        }
+    }
+}
+
+const MAIN_SCOPE: Scope = Scope {
+    max_requests: None,
+    can_spawn: true,
+    journal: false,
+};
+
+const RUN_SCOPE: Scope = Scope {
+    journal: true,
+    ..MAIN_SCOPE
+};
+
+const SUBAGENT_SCOPE: Scope = Scope {
+    max_requests: Some(SUBAGENT_REQUESTS),
+    can_spawn: false,
+    journal: false,
+};
+
+/// Stream assistant turns and run their tool calls until the model answers without tools.
+pub(crate) async fn execute_step<A: Agent>(
+    exec_agent: &A,
+    executor: &Executor,
+    messages: &mut Vec<Message>,
+) -> StepOutcome {
+    execute_turns(exec_agent, executor, messages, MAIN_SCOPE).await
+}
+
+/// `execute_step` on a run's own conversation, journaling every message as it is added.
+async fn execute_run_step<A: Agent>(
+    exec_agent: &A,
+    executor: &Executor,
+    messages: &mut Vec<Message>,
+) -> StepOutcome {
+    execute_turns(exec_agent, executor, messages, RUN_SCOPE).await
+}
+
+/// Run a `spawn_subagent` call: a fresh conversation with only the task and the files it
+/// names, run quietly within `SUBAGENT_SCOPE`. Returns its summary and diff for the parent.
+fn spawn_subagent<'a, A: Agent>(
+    exec_agent: &'a A,
+    executor: &'a Executor,
+    arguments: &'a str,
+) -> Pin<Box<dyn Future<Output = Result<String, String>> + Send + 'a>> {
+    Box::pin(async move {
+        let args: serde_json::Value = serde_json::from_str(arguments).map_err(|e| e.to_string())?;
+        let task = args["task"].as_str().ok_or("Missing task")?;
+        let mut context = Vec::new();
+        for path in args["files"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|p| p.as_str())
+        {
+            if let Ok(content) = executor.execute(&read_file_call(path)) {
+                context.push(format!("--- {} ---\n{}", path, content));
+            }
+        }
+        let mut messages = vec![Message::Role {
+            role: "user".into(),
+            content: format!(
+                "You are a sub-agent doing one scoped subtask of a larger task.\n\nSubtask: {}\n\n{}\nRead anything else you need, do the subtask, then reply without tool calls with a short summary of what you did.",
+                task,
+                context.join("\n\n")
+            ),
+        }];
+        info!(task, "sub-agent started");
+        let outcome = events::hidden(execute_turns(
+            exec_agent,
+            executor,
+            &mut messages,
+            SUBAGENT_SCOPE,
+        ))
+        .await;
+        let written = written_paths(&messages);
+        let diff: String = executor
+            .changes()
+            .iter()
+            .filter(|c| written.contains(&c.path))
+            .map(ui::plain_diff)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let summary = match outcome {
+            StepOutcome::Done(content) => content.unwrap_or_else(|| "Done.".into()),
+            StepOutcome::Failed(e) | StepOutcome::Stalled(e) => {
+                format!("The sub-agent stopped: {}", e)
+            }
+            StepOutcome::Interrupted(_) => "The sub-agent was interrupted.".into(),
+        };
+        Ok(format!(
+            "Sub-agent summary: {}\n\nDiff:\n{}",
+            summary,
+            if diff.is_empty() {
+                "(no changes)".into()
+            } else {
+                context::truncate(&diff, SUBAGENT_DIFF_CAP)
+            }
+        ))
+    })
+}
+
+async fn execute_turns<A: Agent>(
+    exec_agent: &A,
+    executor: &Executor,
+    messages: &mut Vec<Message>,
+    scope: Scope,
+) -> StepOutcome {
+    let mut failed_calls = 0;
+    let mut last_error = String::new();
+    let mut requests = 0;
+    loop {
+        requests += 1;
+        if scope.max_requests.is_some_and(|max| requests > max) {
+            return StepOutcome::Stalled(format!(
+                "used its budget of {} model requests",
+                requests - 1
+            ));
+        }
+        let generation = interrupt::generation();
+        let mut partial = String::new();
+        let mut on_chunk = |chunk: &str| {
+            events::emit(Event::AssistantDelta { text: chunk });
+            partial.push_str(chunk);
+        };
+
+        if let Err(e) = ci::before_request(usage::total_cost(&usage::all())) {
+            return StepOutcome::Failed(e);
+        }
+        // Sub-agents finish their own task; guidance goes to the main conversation.
+        if scope.can_spawn {
+            let guidance = steering::take();
+            if !guidance.is_empty() {
+                let text = guidance.join("\n");
+                events::emit(Event::Steering { text: &text });
+                messages.push(Message::Role {
+                    role: "user".into(),
+                    content: format!("Guidance from the user, sent while you were working. Follow it from here on:\n{}", text),
+                });
+            }
+        }
+        scope.journal(messages);
+        events::emit(Event::Thinking {
+            tokens: &exec_agent.stream_counter(),
+        });
+        let stream = exec_agent.chat_stream(messages, None, &mut on_chunk);
+        let resp = match interrupt::cancellable(stream).await {
+            Some(Ok(r)) => r,
+            Some(Err(e)) => {
+                events::emit(Event::TurnEnded {
+                    text: true,
+                    usage: None,
+                });
+                ci::record(ci::Exit::ApiFailure);
+                return StepOutcome::Failed(e);
+            }
+            None => {
+                // Keep whatever streamed before the interrupt.
+                events::emit(Event::TurnEnded {
+                    text: true,
+                    usage: None,
+                });
+                events::emit(Event::Interrupted);
+                if partial.is_empty() {
+                    return StepOutcome::Interrupted(None);
+                }
+                messages.push(Message::Assistant {
+                    role: "assistant".into(),
+                    content: Some(partial.clone()),
+                    tool_calls: None,
+                });
+                scope.journal(messages);
+                return StepOutcome::Interrupted(Some(partial));
+            }
+        };
+        scope.journal(messages);
+
+        if let Some(tool_calls) = resp.tool_calls {
+            events::emit(Event::TurnEnded {
+                text: true,
+                usage: resp.usage.as_ref(),
+            });
+            let mut batch_changes: Vec<FileChange> = Vec::new();
+            for tc in &tool_calls {
+                events::emit(Event::ToolStarted {
+                    id: &tc.id,
+                    name: &tc.function.name,
+                    arguments: &tc.function.arguments,
+                });
+                let written_path = written_path(tc);
+                let before = written_path.as_deref().and_then(|p| executor.read(p).ok());
+                let allowed = !exec_agent.is_read_only()
+                    || READ_ONLY_TOOLS.contains(&tc.function.name.as_str());
+                let outcome = if !allowed {
+                    Err(format!(
+                        "{} is not available in read-only mode",
+                        tc.function.name
+                    ))
+                } else if !ci::tool_allowed(&tc.function.name) {
+                    let e = format!("{} is not allowed by --allow-tools", tc.function.name);
+                    events::emit(Event::ToolFinished {
+                        id: &tc.id,
+                        name: &tc.function.name,
+                        ok: false,
+                        output: &e,
+                        change: None,
+                    });
+                    return StepOutcome::Failed(e);
+                } else if tc.function.name == "spawn_subagent" {
+                    if scope.can_spawn {
+                        usage::record_tool(&tc.function.name);
+                        spawn_subagent(exec_agent, executor, &tc.function.arguments).await
+                    } else {
+                        Err("sub-agents cannot spawn sub-agents; do the work yourself".into())
+                    }
+                } else if !approval::request(&tc.id, &tc.function.name, &tc.function.arguments)
+                    .await
+                {
+                    Err(format!("{} was rejected by the user", tc.function.name))
+                } else {
+                    usage::record_tool(&tc.function.name);
+                    executor.execute_blocking(tc).await
+                };
+                let change = match (&outcome, written_path) {
+                    (Ok(_), Some(path)) => Some(FileChange {
+                        after: executor.read(&path).ok(),
+                        path,
+                        before,
+                    }),
+                    _ => None,
+                };
+                events::emit(Event::ToolFinished {
+                    id: &tc.id,
+                    name: &tc.function.name,
+                    ok: outcome.is_ok(),
+                    output: outcome.as_ref().unwrap_or_else(|e| e),
+                    change: change.as_ref(),
+                });
+                if let Some(change) = change {
+                    record_change(
+                        &mut batch_changes,
+                        &change.path,
+                        change.before,
+                        change.after,
+                    );
+                }
+                let result = match outcome {
+                    Ok(r) => {
+                        failed_calls = 0;
+                        r
+                    }
+                    Err(e) => {
+                        failed_calls += 1;
+                        last_error = e.clone();
+                        format!("Error: {}", e)
+                    }
+                };
+                messages.push(Message::ToolResult {
+                    role: "tool".into(),
+                    tool_call_id: tc.id.clone(),
+                    function_name: tc.function.name.clone(),
+                    content: result,
+                });
+                scope.journal(messages);
+                if interrupt::generation() != generation {
+                    events::emit(Event::Changes {
+                        title: "Changed",
+                        changes: &batch_changes,
+                    });
+                    events::emit(Event::Interrupted);
+                    return StepOutcome::Interrupted(None);
+                }
+            }
+            events::emit(Event::Changes {
+                title: "Changed",
+                changes: &batch_changes,
+            });
+            if failed_calls >= MAX_TOOL_ERRORS {
+                return StepOutcome::Stalled(format!(
+                    "{} tool calls failed in a row; last: {}",
+                    failed_calls, last_error
+                ));
+            }
+            continue;
+        }
+
+        events::emit(Event::TurnEnded {
+            text: resp.content.as_ref().is_some_and(|s| !s.is_empty()),
+            usage: resp.usage.as_ref(),
+        });
+        return StepOutcome::Done(resp.content.filter(|s| !s.is_empty()));
+    }
+}
+
+/// One todo run in its own conversation alongside others.
+struct Branch {
+    index: usize,
+    outcome: StepOutcome,
+    /// Files the branch wrote.
+    written: Vec<String>,
+}
+
+/// Run the todos in `group` concurrently, each in a copy of the conversation so far. Their
+/// output is hidden while they run; only the todo list is shown afterwards.
+async fn run_parallel<A: Agent>(
+    exec_agent: &A,
+    executor: &Executor,
+    messages: &[Message],
+    todos: &[String],
+    group: &[usize],
+) -> Vec<Branch> {
+    let branches = group.iter().map(|&i| async move {
+        let mut branch = messages.to_vec();
+        branch.push(Message::Role {
+            role: "user".into(),
+            content: format!(
+                "Step {}/{}: {}\n\nOther steps are being done at the same time; change only the files this step needs.",
+                i + 1,
+                todos.len(),
+                todos[i]
+            ),
+        });
+        let start = branch.len();
+        let outcome = execute_step(exec_agent, executor, &mut branch).await;
+        Branch {
+            index: i,
+            outcome,
+            written: written_paths(&branch[start..]),
+        }
+    });
+    events::hidden(futures_util::future::join_all(branches)).await
+}
+
+/// Paths written by more than one branch, with the (1-based) steps that wrote each.
+fn conflicts(branches: &[Branch]) -> Vec<(String, Vec<usize>)> {
+    let mut conflicts: Vec<(String, Vec<usize>)> = Vec::new();
+    for (n, branch) in branches.iter().enumerate() {
+        for path in &branch.written {
+            if conflicts.iter().any(|(p, _)| p == path) {
+                continue;
+            }
+            let steps: Vec<usize> = branches[n..]
+                .iter()
+                .filter(|b| b.written.contains(path))
+                .map(|b| b.index + 1)
+                .collect();
+            if steps.len() > 1 {
+                conflicts.push((path.clone(), steps));
+            }
+        }
+    }
+    conflicts
+}
+
+/// Outcome of one todo in the last run.
+#[derive(Debug, Clone)]
+pub struct TodoResult {
+    pub todo: String,
+    pub status: ui::TodoStatus,
+    /// The model's closing text for the step, or the error.
+    pub note: Option<String>,
+}
+
+/// What the last pipeline run planned and how each todo went (for PR bodies and reports).
+#[derive(Debug, Clone)]
+pub struct RunSummary {
+    pub summary: String,
+    pub todos: Vec<TodoResult>,
+    /// Verification commands that still failed at the end of the run.
+    pub failing_checks: Vec<String>,
+}
+
+impl RunSummary {
+    pub fn succeeded(&self) -> bool {
+        self.todos.iter().all(|t| t.status == ui::TodoStatus::Done)
+            && self.failing_checks.is_empty()
+    }
+}
+
+static LAST_RUN: Mutex<Option<RunSummary>> = Mutex::new(None);
+/// Commit the files changed so far as a `zcode:` checkpoint for a finished todo.
+fn commit_checkpoint(executor: &Executor, todo: &str) {
+    if !options::get().auto_commit {
+        return;
+    }
+    let changes = executor.changes();
+    let paths: Vec<&str> = changes.iter().map(|c| c.path.as_str()).collect();
+    let subject: String = todo
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .take(72)
+        .collect();
+    match git::commit_paths(executor.workspace(), &paths, &format!("zcode: {}", subject)) {
+        Ok(true) => events::note(&format!("Committed checkpoint: zcode: {}", subject)),
+        Ok(false) => {}
+        Err(e) => {
+            events::error(&format!("Auto-commit failed, turning it off: {}", e));
+            options::update(|o| o.auto_commit = false);
+        }
+    }
+}
+
+/// Summary of the most recent pipeline run in this process.
+pub fn last_run() -> Option<RunSummary> {
+    LAST_RUN.lock().unwrap().clone()
+}
+
+fn store_summary(
+    summary: &str,
+    todos: &[String],
+    statuses: &[ui::TodoStatus],
+    notes: &[Option<String>],
+    failing_checks: Vec<String>,
+) {
+    let todos = todos
+        .iter()
+        .zip(statuses)
+        .zip(notes)
+        .map(|((todo, status), note)| TodoResult {
+            todo: todo.clone(),
+            status: *status,
+            note: note.clone(),
+        })
+        .collect();
+    *LAST_RUN.lock().unwrap() = Some(RunSummary {
+        summary: summary.to_string(),
+        todos,
+        failing_checks,
+    });
+}
+
+/// Run the full pipeline for one prompt. Returns the final assistant text, if any.
+pub async fn run_once<P: Agent, E: Agent>(
+    agents: &Agents<P, E>,
+    executor: &Executor,
+    user_prompt: &str,
+) -> Option<String> {
+    if let Err(e) = hooks::prompt_submit(executor, user_prompt) {
+        events::error(&e);
+        return None;
+    }
+    let started = Instant::now();
+    let mark = usage::mark();
+    let reply = run_pipeline(agents, executor, user_prompt).await;
+    usage::log_run(executor.workspace(), mark);
+    let first_line = user_prompt.lines().next().unwrap_or_default();
+    notify::run_finished("zcode finished", first_line, started.elapsed());
+    hooks::prompt_finish(
+        executor,
+        user_prompt,
+        reply.as_deref(),
+        started.elapsed(),
+        mark,
+    );
+    reply
+}
+
+/// Phases 1–3: project layout, plan, and context.
+async fn prepare<P: Agent>(
+    planner: &P,
+    index_key: Option<&str>,
+    executor: &Executor,
+    user_prompt: &str,
+) -> Option<Prepared> {
+    // --- Phase 1: Map the repository for the planner ---
+    events::phase("Gathering project layout");
+    let repo_map = repomap::build(executor.workspace(), repomap::budget());
+    let working_diff = git::working_diff(executor.workspace()).map(|diff| {
+        format!(
+            "Uncommitted changes in the working tree (the user's work in progress: build on it, don't undo it):\n{}",
+            context::truncate(&diff, WORKING_DIFF_CAP)
+        )
+    });
+    events::phase_done("Project layout");
+
+    // --- Phase 2: Plan (cheap model) ---
+    events::phase("Planning");
+    let mut plan_user = format!(
+        "User request:\n{}\n\nRepository map (files and their top-level symbols):\n{}",
+        user_prompt, repo_map
+    );
+    if let Some(diff) = &working_diff {
+        plan_user.push_str(&format!("\n\n{}", diff));
+    }
+    let cache_key = plan_cache::key(&planner_model(), user_prompt, &repo_map);
+    let cached = plan_cache::load(&cache_key);
+    let plan_text = match cached.clone() {
+        Some(t) => {
+            events::phase_done("Plan reused from cache (--no-plan-cache to plan again)");
+            t
+        }
+        None => {
+            let planning = events::progress(
+                "Planning",
+                routing::scoped(
+                    Phase::Planning,
+                    planner.completion(PLANNER_SYSTEM, &plan_user),
+                ),
+            );
+            match interrupt::cancellable(planning).await {
+                Some(Ok(t)) => t,
+                Some(Err(e)) => {
+                    ci::record(ci::Exit::ApiFailure);
+                    events::error(&e);
+                    return None;
+                }
+                None => {
+                    events::emit(Event::Interrupted);
+                    return None;
+                }
+            }
+        }
+    };
+    let plan_json = extract_json(&plan_text).unwrap_or(&plan_text);
+    let plan: Plan = match serde_json::from_str(plan_json) {
+        Ok(p) => p,
+        Err(e) => {
+            ci::record(ci::Exit::ApiFailure);
+            events::error(&format!("Failed to parse plan: {}. Raw: {}", e, plan_text));
+            return None;
+        }
+    };
+    if cached.is_none() {
+        if let Err(e) = plan_cache::save(&cache_key, plan_json) {
+            info!(error = %e, "could not cache plan");
+        }
+    }
+    let mut todos = plan
+        .todos
+        .unwrap_or_else(|| vec!["Complete the user request.".into()]);
+    let mut parallel = if options::get().parallel {
+        parallel_groups(plan.parallel.unwrap_or_default(), todos.len())
+    } else {
+        Vec::new()
+    };
+    let mut paths_to_read = plan.paths_to_read.unwrap_or_default();
+    info!(
+        todos = todos.len(),
+        paths = paths_to_read.len(),
+        "plan ready"
+    );
+    events::emit(Event::Plan {
+        summary: plan.summary.as_deref().unwrap_or("Task"),
+        todos: &todos,
+        paths_to_read: &paths_to_read,
+    });
+    events::todos(&todos, &vec![ui::TodoStatus::Pending; todos.len()]);
+    if options::get().review_plan {
+        let planned = todos.clone();
+        match plan_review::review(&mut todos, &mut paths_to_read) {
+            // Edited steps no longer match the planner's groups.
+            Ok(true) if todos != planned => parallel.clear(),
+            Ok(true) => {}
+            Ok(false) => {
+                events::note("Plan cancelled; nothing was changed.");
+                return None;
+            }
+            Err(e) => {
+                events::error(&e);
+                return None;
+            }
+        }
+    }
+
+    // --- Phase 3: Gather context (read paths_from_plan) ---
+    events::phase("Gathering context");
+    let mut context_parts = vec![format!("Repository map:\n{}", repo_map)];
+    context_parts.extend(working_diff);
+    let memories = memory::load(executor.workspace());
+    if !memories.is_empty() {
+        let keys: Vec<&str> = memories.keys().map(String::as_str).collect();
+        context_parts.push(format!(
+            "Saved project memories (read them with recall_memory): {}",
+            keys.join(", ")
+        ));
+    }
+    if let Some(instructions) = context::project_instructions(executor.workspace()) {
+        context_parts.insert(
+            0,
+            format!(
+                "Project instructions ({}):\n{}",
+                context::INSTRUCTIONS_FILE,
+                instructions
+            ),
+        );
+    }
+    let mut semantic = Vec::new();
+    if let Some(key) = index_key {
+        // Pick up edits made since the index was last brought up to date.
+        index::refresh(key, executor.workspace()).await;
+    }
+    if let Some((key, index)) = index_key.zip(index::load(executor.workspace())) {
+        match index::search(key, &index, user_prompt, RELEVANT_CHUNKS).await {
+            Ok(hits) => {
+                semantic = hits
+                    .iter()
+                    .map(|(c, score)| (c.path.clone(), c.start, c.end, *score))
+                    .collect()
+            }
+            Err(e) => info!(error = %e, "semantic search failed"),
+        }
+    }
+    let files_to_change = plan.files_to_change.unwrap_or_default();
+    let ranked = ranking::rank(
+        executor.workspace(),
+        &paths_to_read,
+        &files_to_change,
+        &semantic,
+    );
+    let contents = ranking::read(executor.workspace(), &ranked).await;
+    // Tell the model about planned paths it won't see, rather than leaving them out silently.
+    let mut unreadable: Vec<String> = ranking::missing(executor.workspace(), &paths_to_read)
+        .into_iter()
+        .map(|path| {
+            events::emit(Event::FileRead {
+                path: &path,
+                error: Some("not found"),
+            });
+            format!("{}: not found", path)
+        })
+        .collect();
+    for ((path, _), content) in ranked.iter().zip(&contents) {
+        if let Err(e) = content {
+            unreadable.push(format!("{}: {}", path, e));
+        }
+    }
+    let terms = slicing::terms(&format!(
+        "{}\n{}\n{}",
+        user_prompt,
+        plan.summary.as_deref().unwrap_or_default(),
+        todos.join("\n")
+    ));
+    let selected = ranking::pack(&ranked, contents, &terms, ranking::budget());
+    if !unreadable.is_empty() {
+        context_parts.push(format!("Could not read:\n{}", unreadable.join("\n")));
+    }
+    let mut read = Vec::new();
+    for file in selected {
+        if file.truncated {
+            info!(path = %file.path, "truncated to fit the context budget");
+        }
+        context_parts.push(file.block);
+        read.push(file.path);
+    }
+    info!(
+        candidates = ranked.len(),
+        selected = read.len(),
+        "context selected"
+    );
+    events::phase_done(&format!("Context gathered ({} files)", read.len()));
+    Some(Prepared {
+        summary: plan.summary.unwrap_or_else(|| "Task".into()),
+        todos,
+        read,
+        files_to_change,
+        scope: plan.scope,
+        context_block: context_parts.join("\n\n"),
+        parallel,
+    })
+}
+
+/// Run only the planning and context phases and render the plan as markdown.
+pub async fn plan_only<P: Agent, E: Agent>(
+    agents: &Agents<P, E>,
+    executor: &Executor,
+    user_prompt: &str,
+) -> Option<String> {
+    let prepared = prepare(
+        &agents.planner,
+        agents.index_key.as_deref(),
+        executor,
+        user_prompt,
+    )
+    .await?;
+    let list = |items: &[String]| -> String {
+        if items.is_empty() {
+            "- (none)\n".to_string()
+        } else {
+            items.iter().map(|i| format!("- {}\n", i)).collect()
+        }
+    };
+    let steps: String = prepared
+        .todos
+        .iter()
+        .enumerate()
+        .map(|(i, t)| format!("{}. {}\n", i + 1, t))
+        .collect();
+    Some(format!(
+        "# Plan: {}\n\n## Steps\n{}\n## Files to change\n{}\n## Context read\n{}\n## Estimated scope\n{}: {} steps, {} files, ~{} context tokens per request\n",
+        prepared.summary,
+        steps,
+        list(&prepared.files_to_change),
+        list(&prepared.read),
+        prepared.scope.as_deref().unwrap_or("unknown"),
+        prepared.todos.len(),
+        prepared.files_to_change.len(),
+        ranking::estimate_tokens(&prepared.context_block),
+    ))
+}
+
+async fn run_pipeline<P: Agent, E: Agent>(
+    agents: &Agents<P, E>,
+    executor: &Executor,
+    user_prompt: &str,
+) -> Option<String> {
+    let usage_mark = usage::mark();
+    let Prepared {
+        summary,
+        todos,
+        context_block,
+        parallel,
+        ..
+    } = prepare(
+        &agents.planner,
+        agents.index_key.as_deref(),
+        executor,
+        user_prompt,
+    )
+    .await?;
+    let initial_user = format!(
+        "Context:\n{}\n\nTask: {}\n\nUser request: {}\n\nPlan:\n{}\n\nWork through the plan one step at a time; each step will be given to you in turn.",
+        context_block,
+        summary,
+        user_prompt,
+        numbered(&todos).join("\n")
+    );
+    let state = RunState {
+        id: run_state::new_id(),
+        workspace: executor.workspace().to_path_buf(),
+        prompt: user_prompt.to_string(),
+        summary,
+        statuses: vec![ui::TodoStatus::Pending; todos.len()],
+        notes: vec![None; todos.len()],
+        todos,
+        parallel,
+        replans: 0,
+        messages: vec![Message::Role {
+            role: "user".into(),
+            content: initial_user,
+        }],
+        baseline: Default::default(),
+        snapshot: None,
+        session: session::active_id(),
+        journal_from: 0,
+        usage_mark,
+    };
+    execute_run(agents, executor, state).await
+}
+
+/// Pick up a saved run where it stopped: steps that were running or failed are tried again.
+pub async fn resume_run<P: Agent, E: Agent>(
+    agents: &Agents<P, E>,
+    executor: &Executor,
+    mut state: RunState,
+) -> Option<String> {
+    if state.workspace != executor.workspace() {
+        events::error(&format!(
+            "Run {} was in {}",
+            state.id,
+            state.workspace.display()
+        ));
+        return None;
+    }
+    if !state.matches_workspace(executor.workspace()) {
+        events::note("The workspace changed since this run stopped; continuing with the files as they are now.");
+    }
+    if let Err(e) = hooks::prompt_submit(executor, &state.prompt) {
+        events::error(&e);
+        return None;
+    }
+    executor.restore_baseline(state.baseline.clone());
+    for status in &mut state.statuses {
+        if *status != ui::TodoStatus::Done {
+            *status = ui::TodoStatus::Pending;
+        }
+    }
+    let done = state
+        .statuses
+        .iter()
+        .filter(|s| **s == ui::TodoStatus::Done)
+        .count();
+    events::note(&format!(
+        "Resuming run {}: {} ({} of {} steps done)",
+        state.id,
+        state.summary,
+        done,
+        state.todos.len()
+    ));
+    let started = Instant::now();
+    let mark = usage::mark();
+    state.usage_mark = mark;
+    let prompt = state.prompt.clone();
+    let reply = execute_run(agents, executor, state).await;
+    usage::log_run(executor.workspace(), mark);
+    notify::run_finished(
+        "zcode finished",
+        prompt.lines().next().unwrap_or_default(),
+        started.elapsed(),
+    );
+    hooks::prompt_finish(executor, &prompt, reply.as_deref(), started.elapsed(), mark);
+    reply
+}
+
+/// Phases 4–7 for a planned run, saving its state as it goes. The saved state is removed
+/// once the run gets through to the report.
+async fn execute_run<P: Agent, E: Agent>(
+    agents: &Agents<P, E>,
+    executor: &Executor,
+    mut state: RunState,
+) -> Option<String> {
+    let reply = routing::scoped(
+        Phase::Execution,
+        execute_phases(agents, executor, &mut state),
+    )
+    .await;
+    if let Some(key) = agents
+        .index_key
+        .as_deref()
+        .filter(|_| !executor.changes().is_empty())
+    {
+        index::refresh(key, executor.workspace()).await;
+    }
+    if state.is_saved() {
+        events::note(&format!(
+            "Run saved; continue it with `zcode --resume-run {}`",
+            state.id
+        ));
+    }
+    reply
+}
+
+async fn execute_phases<P: Agent, E: Agent>(
+    agents: &Agents<P, E>,
+    executor: &Executor,
+    state: &mut RunState,
+) -> Option<String> {
+    let Agents {
+        planner,
+        exec_agent,
+        team,
+        ..
+    } = agents;
+    let summary = state.summary.clone();
+    let summary = summary.as_str();
+    let user_prompt = state.prompt.clone();
+    let user_prompt = user_prompt.as_str();
+
+    // What the session had changed before, so the report covers only this run.
+    let files_before: BTreeMap<String, Option<String>> = executor
+        .changes()
+        .into_iter()
+        .map(|c| (c.path, c.after))
+        .collect();
+    let commands_before = executor.commands().len();
+
+    // --- Phase 4: Execute todos one at a time with strong model (tools + stream) ---
+    events::phase("Executing");
+    steering::take();
+    let listener = steering::listen();
+    let mut reply: Option<String> = None;
+    // A failed model request (e.g. a network outage) keeps the run resumable.
+    let mut request_failed = false;
+    let mut i = 0;
+    while i < state.todos.len() {
+        save_state(state, executor);
+        if state.statuses[i] != ui::TodoStatus::Pending {
+            // Finished along the way, or already run in a parallel group.
+            i += 1;
+            continue;
+        }
+        let group = state
+            .parallel
+            .iter()
+            .find(|g| g[0] == i)
+            .map(|g| {
+                g.iter()
+                    .copied()
+                    .filter(|&n| state.statuses[n] == ui::TodoStatus::Pending)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|g| g.len() > 1);
+        if let Some(group) = group {
+            for &n in &group {
+                state.statuses[n] = ui::TodoStatus::Running;
+                events::emit(Event::TodoStarted {
+                    index: n,
+                    todo: &state.todos[n],
+                });
+            }
+            events::todos(&state.todos, &state.statuses);
+            ci::reset_turns();
+            let branches =
+                run_parallel(exec_agent, executor, &state.messages, &state.todos, &group).await;
+            let mut merged = Vec::new();
+            let mut interrupted = None;
+            for branch in &branches {
+                let n = branch.index;
+                let (ok, note) = match &branch.outcome {
+                    StepOutcome::Done(content) => (true, content.clone()),
+                    StepOutcome::Failed(e) | StepOutcome::Stalled(e) => {
+                        (false, Some(format!("Failed: {}", e)))
+                    }
+                    StepOutcome::Interrupted(partial) => {
+                        interrupted = Some(partial.clone());
+                        (false, partial.clone())
+                    }
+                };
+                state.statuses[n] = if ok {
+                    ui::TodoStatus::Done
+                } else {
+                    ui::TodoStatus::Failed
+                };
+                events::emit(Event::TodoFinished { index: n, ok });
+                merged.push(format!(
+                    "Step {} ({}): {}; changed: {}. {}",
+                    n + 1,
+                    state.todos[n],
+                    if ok { "done" } else { "failed" },
+                    if branch.written.is_empty() {
+                        "nothing".to_string()
+                    } else {
+                        branch.written.join(", ")
+                    },
+                    note.as_deref().unwrap_or_default()
+                ));
+                if ok {
+                    reply = note.clone().or(reply.take());
+                } else if let Some(e) = note.as_deref().filter(|_| interrupted.is_none()) {
+                    events::error(e);
+                }
+                state.notes[n] = note;
+            }
+            events::todos(&state.todos, &state.statuses);
+            if let Some(partial) = interrupted {
+                ci::record(ci::Exit::Partial);
+                store_summary(
+                    summary,
+                    &state.todos,
+                    &state.statuses,
+                    &state.notes,
+                    Vec::new(),
+                );
+                return partial.or(reply);
+            }
+            state.messages.push(Message::Role {
+                role: "user".into(),
+                content: format!(
+                    "These steps were done in parallel by other workers:\n{}",
+                    merged.join("\n")
+                ),
+            });
+
+            // Steps that wrote the same file may have overwritten each other's edits.
+            let conflicts = conflicts(&branches);
+            if !conflicts.is_empty() {
+                let list: Vec<String> = conflicts
+                    .iter()
+                    .map(|(path, steps)| {
+                        let steps: Vec<String> = steps.iter().map(|n| n.to_string()).collect();
+                        format!("{} (steps {})", path, steps.join(", "))
+                    })
+                    .collect();
+                events::error(&format!(
+                    "Parallel steps changed the same files: {}",
+                    list.join("; ")
+                ));
+                state.messages.push(Message::Role {
+                    role: "user".into(),
+                    content: format!(
+                        "These files were written by more than one of those steps, so later writes may have lost earlier edits: {}. Read each file and make sure it has the changes of every step, then reply without tool calls.",
+                        list.join("; ")
+                    ),
+                });
+                ci::reset_turns();
+                if let StepOutcome::Failed(e) | StepOutcome::Stalled(e) =
+                    execute_run_step(exec_agent, executor, &mut state.messages).await
+                {
+                    events::error(&e);
+                    for (_, steps) in &conflicts {
+                        for n in steps {
+                            state.statuses[n - 1] = ui::TodoStatus::Failed;
+                            state.notes[n - 1] = Some(format!(
+                                "Conflicting parallel edits were not reconciled: {}",
+                                e
+                            ));
+                        }
+                    }
+                }
+            }
+            let done: Vec<&str> = group
+                .iter()
+                .filter(|&&n| state.statuses[n] == ui::TodoStatus::Done)
+                .map(|&n| state.todos[n].as_str())
+                .collect();
+            if !done.is_empty() {
+                commit_checkpoint(executor, &done.join("; "));
+            }
+            i += 1;
+            continue;
+        }
+        let todo = state.todos[i].clone();
+        state.statuses[i] = ui::TodoStatus::Running;
+        events::todos(&state.todos, &state.statuses);
+        events::emit(Event::TodoStarted {
+            index: i,
+            todo: &todo,
+        });
+        ci::reset_turns();
+        state.messages.push(Message::Role {
+            role: "user".into(),
+            content: format!("Step {}/{}: {}", i + 1, state.todos.len(), todo),
+        });
+        // The tracker must see the step finished; until then the model is sent back to it.
+        let mut nudges = 0;
+        let (outcome, confirmed) = loop {
+            let step_start = state.messages.len();
+            let outcome = execute_run_step(exec_agent, executor, &mut state.messages).await;
+            if !matches!(outcome, StepOutcome::Done(_)) {
+                break (outcome, false);
+            }
+            // Without an answer from the tracker, take the model's word for it.
+            let finished = finished_todos(
+                planner,
+                &numbered(&state.todos),
+                &state.messages[step_start..],
+            )
+            .await
+            .unwrap_or_else(|| vec![i]);
+            for &n in finished.iter().filter(|n| **n != i) {
+                if state.statuses[n] == ui::TodoStatus::Pending {
+                    state.statuses[n] = ui::TodoStatus::Done;
+                    events::emit(Event::TodoFinished { index: n, ok: true });
+                    state.notes[n] = Some(format!("Done as part of step {}", i + 1));
+                }
+            }
+            events::todos(&state.todos, &state.statuses);
+            if finished.contains(&i) || nudges == MAX_NUDGES {
+                break (outcome, finished.contains(&i));
+            }
+            nudges += 1;
+            state.messages.push(Message::Role {
+                role: "user".into(),
+                content: format!(
+                    "Step {} ({}) does not look finished yet. Keep working on it, and reply without tool calls once it is done.",
+                    i + 1,
+                    todo
+                ),
+            });
+        };
+        let stall = match outcome {
+            StepOutcome::Done(content) if !confirmed => format!(
+                "Step {} ({}) was not finished after {} reminders. Last reply: {}",
+                i + 1,
+                todo,
+                MAX_NUDGES,
+                content.unwrap_or_default()
+            ),
+            StepOutcome::Stalled(e) => format!("Step {} ({}) stalled: {}", i + 1, todo, e),
+            StepOutcome::Done(content) => {
+                state.statuses[i] = ui::TodoStatus::Done;
+                events::emit(Event::TodoFinished { index: i, ok: true });
+                state.notes[i] = content.clone();
+                reply = content.or(reply);
+                commit_checkpoint(executor, &todo);
+                i += 1;
+                continue;
+            }
+            StepOutcome::Failed(e) => {
+                state.statuses[i] = ui::TodoStatus::Failed;
+                events::emit(Event::TodoFinished {
+                    index: i,
+                    ok: false,
+                });
+                events::error(&e);
+                state.notes[i] = Some(format!("Failed: {}", e));
+                request_failed = true;
+                break;
+            }
+            StepOutcome::Interrupted(partial) => {
+                state.statuses[i] = ui::TodoStatus::Failed;
+                events::emit(Event::TodoFinished {
+                    index: i,
+                    ok: false,
+                });
+                events::todos(&state.todos, &state.statuses);
+                ci::record(ci::Exit::Partial);
+                store_summary(
+                    summary,
+                    &state.todos,
+                    &state.statuses,
+                    &state.notes,
+                    Vec::new(),
+                );
+                return partial.or(reply);
+            }
+        };
+
+        // Stalled: revise the rest of the plan, or give up on the step.
+        events::error(&stall);
+        let revised = if state.replans < MAX_REPLANS {
+            replan(
+                planner,
+                user_prompt,
+                &state.todos,
+                &state.statuses,
+                i,
+                &stall,
+            )
+            .await
+        } else {
+            None
+        };
+        let Some(revised) = revised else {
+            state.statuses[i] = ui::TodoStatus::Failed;
+            events::emit(Event::TodoFinished {
+                index: i,
+                ok: false,
+            });
+            state.notes[i] = Some(stall);
+            i += 1;
+            continue;
+        };
+        state.replans += 1;
+        info!(state.replans, steps = revised.len(), "plan revised");
+        // The groups were for the old steps.
+        state.parallel.clear();
+        state.todos.truncate(i);
+        state.todos.extend(revised);
+        state.statuses.truncate(i);
+        state
+            .statuses
+            .resize(state.todos.len(), ui::TodoStatus::Pending);
+        state.notes.truncate(i);
+        state.notes.resize(state.todos.len(), None);
+        events::emit(Event::Plan {
+            summary,
+            todos: &state.todos,
+            paths_to_read: &[],
+        });
+        events::phase_done("Plan revised");
+        state.messages.push(Message::Role {
+            role: "user".into(),
+            content: format!(
+                "That approach stalled ({}). The plan has been revised; the remaining steps are now:\n{}",
+                stall,
+                numbered(&state.todos)[i..].join("\n")
+            ),
+        });
+    }
+    if !request_failed {
+        save_state(state, executor);
+    }
+    events::todos(&state.todos, &state.statuses);
+
+    // --- Tester (multi-agent): cover the changes with tests ---
+    if let Some(team) = team.as_ref().filter(|_| !executor.changes().is_empty()) {
+        events::phase("Testing");
+        let mut tester_messages = vec![Message::Role {
+            role: "user".into(),
+            content: format!(
+                "User request: {}\n\nTask: {}\n\nThese changes were just made:\n{}\n\nWrite or update tests that cover them, run the tests, and reply without tool calls with a short summary.",
+                user_prompt,
+                summary,
+                run_diff(executor)
+            ),
+        }];
+        ci::reset_turns();
+        match routing::scoped(
+            Phase::Testing,
+            execute_step(&team.tester, executor, &mut tester_messages),
+        )
+        .await
+        {
+            StepOutcome::Done(_) => commit_checkpoint(executor, "Add tests"),
+            StepOutcome::Failed(e) | StepOutcome::Stalled(e) => events::error(&e),
+            StepOutcome::Interrupted(partial) => {
+                ci::record(ci::Exit::Partial);
+                store_summary(
+                    summary,
+                    &state.todos,
+                    &state.statuses,
+                    &state.notes,
+                    Vec::new(),
+                );
+                return partial.or(reply);
+            }
+        }
+    }
+
+    // --- Phase 5: Review the diff against the request, then one fix round for the findings ---
+    if (options::get().critic || team.is_some()) && !executor.changes().is_empty() {
+        events::phase("Reviewing");
+        let findings = match team {
+            Some(team) => critique(&team.reviewer, user_prompt, executor).await,
+            None => critique(planner, user_prompt, executor).await,
+        };
+        if let Some(findings) = findings {
+            events::emit(Event::Review {
+                findings: &findings,
+            });
+            if findings.is_empty() {
+                events::phase_done("No review findings");
+            } else {
+                let list: Vec<String> = findings.iter().map(|f| format!("- {}", f)).collect();
+                events::note(&format!("Review findings:\n{}", list.join("\n")));
+                state.messages.push(Message::Role {
+                    role: "user".into(),
+                    content: format!(
+                        "A reviewer checked your changes against the request and found:\n{}\n\nFix these issues, then reply without tool calls.",
+                        list.join("\n")
+                    ),
+                });
+                ci::reset_turns();
+                match execute_run_step(exec_agent, executor, &mut state.messages).await {
+                    StepOutcome::Done(content) => {
+                        reply = content.or(reply);
+                        commit_checkpoint(executor, "Address review findings");
+                    }
+                    StepOutcome::Failed(e) | StepOutcome::Stalled(e) => events::error(&e),
+                    StepOutcome::Interrupted(partial) => {
+                        ci::record(ci::Exit::Partial);
+                        store_summary(
+                            summary,
+                            &state.todos,
+                            &state.statuses,
+                            &state.notes,
+                            Vec::new(),
+                        );
+                        return partial.or(reply);
+                    }
+                }
+            }
+        }
+    }
+
+    // --- Phase 6: Verify (the project's build and test commands, with fix rounds) ---
+    let mut failing_checks = Vec::new();
+    let mut check_results = Vec::new();
+    let checks = verify::commands(executor.workspace());
+    if options::get().verify && !checks.is_empty() && !executor.changes().is_empty() {
+        events::phase("Verifying");
+        match routing::scoped(
+            Phase::Verification,
+            verify::run(exec_agent, executor, &checks, summary),
+        )
+        .await
+        {
+            Ok(failing) => failing_checks = failing,
+            Err(e) => {
+                events::error(&e);
+                failing_checks = checks.clone();
+            }
+        }
+        if failing_checks.is_empty() {
+            commit_checkpoint(executor, "Fix verification failures");
+        }
+        check_results = checks
+            .iter()
+            .map(|c| (c.clone(), !failing_checks.contains(c)))
+            .collect();
+    }
+    if state.statuses.iter().any(|s| *s != ui::TodoStatus::Done) || !failing_checks.is_empty() {
+        ci::record(ci::Exit::Partial);
+    }
+    store_summary(
+        summary,
+        &state.todos,
+        &state.statuses,
+        &state.notes,
+        failing_checks,
+    );
+    drop(listener);
+    let undelivered = steering::take();
+    if !undelivered.is_empty() {
+        events::note(&format!(
+            "Not passed on (the model had finished): {}",
+            undelivered.join("; ")
+        ));
+    }
+
+    // --- Phase 7: Report what the run did ---
+    events::phase("Report");
+    let files = executor
+        .changes()
+        .into_iter()
+        .filter_map(|mut change| match files_before.get(&change.path) {
+            // Changed earlier in the session: report only what this run did to it.
+            Some(before) if *before == change.after => None,
+            Some(before) => {
+                change.before = before.clone();
+                Some(change)
+            }
+            None => Some(change),
+        })
+        .collect();
+    let report = Report {
+        id: state.id.clone(),
+        summary: summary.to_string(),
+        prompt: user_prompt.to_string(),
+        todos: last_run().map(|r| r.todos).unwrap_or_default(),
+        files,
+        commands: executor.commands().split_off(commands_before),
+        checks: check_results,
+        usage: usage::all().split_off(state.usage_mark.min(usage::mark())),
+    };
+    report.finish(executor.workspace());
+    dataset::record(
+        &report,
+        executor.workspace(),
+        &state.messages,
+        reply.as_deref(),
+    );
+    if !request_failed {
+        state.remove();
+    }
+    reply
+}
+
+/// Save the run so far, noting (not failing) when it can't be saved.
+fn save_state(state: &mut RunState, executor: &Executor) {
+    if let Err(e) = state.save(executor.baseline()) {
+        info!(error = %e, "could not save run state");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{cassette, FakeAgent};
+    use std::fs;
+
+    fn task(text: &str) -> Vec<Message> {
+        vec![Message::Role {
+            role: "user".into(),
+            content: text.into(),
+        }]
+    }
+
+    fn tool_results(messages: &[Message]) -> Vec<String> {
+        messages
+            .iter()
+            .filter_map(|m| match m {
+                Message::ToolResult { content, .. } => Some(content.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn tool_calls_run_until_the_model_answers_without_tools() {
+        let workspace = tempfile::tempdir().unwrap();
+        let executor = Executor::new(workspace.path().to_path_buf());
+        let agent = FakeAgent::from_yaml(
+            r#"
+- tool_calls:
+    - name: write_file
+      arguments: { path: hello.txt, content: "Hello\n" }
+- tool_calls:
+    - name: read_file
+      arguments: { path: hello.txt }
+- content: Wrote hello.txt.
+"#,
+        )
+        .unwrap();
+
+        let mut messages = task("Write hello.txt");
+        let outcome = execute_step(&agent, &executor, &mut messages).await;
+        assert!(matches!(outcome, StepOutcome::Done(Some(ref text)) if text == "Wrote hello.txt."));
+        assert_eq!(
+            fs::read_to_string(workspace.path().join("hello.txt")).unwrap(),
+            "Hello\n"
+        );
+        assert_eq!(tool_results(&messages), ["Created hello.txt", "Hello\n"]);
+        assert_eq!(
+            agent.inputs(),
+            ["Write hello.txt", "Created hello.txt", "Hello\n"]
+        );
+        assert_eq!(messages.len(), 6);
+        assert_eq!(executor.changes().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn repeated_tool_failures_stall_the_step() {
+        let workspace = tempfile::tempdir().unwrap();
+        let executor = Executor::new(workspace.path().to_path_buf());
+        let failing = "- tool_calls: [{ name: read_file, arguments: { path: missing.txt } }]\n";
+        let agent = FakeAgent::from_yaml(&failing.repeat(MAX_TOOL_ERRORS + 1)).unwrap();
+
+        let mut messages = task("Read missing.txt");
+        let outcome = execute_step(&agent, &executor, &mut messages).await;
+        assert!(
+            matches!(outcome, StepOutcome::Stalled(ref e) if e.starts_with("3 tool calls failed in a row"))
+        );
+        assert_eq!(agent.remaining(), 1);
+        assert!(tool_results(&messages)
+            .iter()
+            .all(|r| r.starts_with("Error: ")));
+    }
+
+    #[tokio::test]
+    async fn a_failed_request_fails_the_step() {
+        let executor = Executor::new(tempfile::tempdir().unwrap().path().to_path_buf());
+        let agent = FakeAgent::from_yaml("- error: rate limited\n").unwrap();
+        let outcome = execute_step(&agent, &executor, &mut task("go")).await;
+        assert!(matches!(outcome, StepOutcome::Failed(ref e) if e == "rate limited"));
+    }
+
+    /// A recorded streamed reply: server-sent events ending in `[DONE]`.
+    fn sse(deltas: &[serde_json::Value]) -> String {
+        let events: String = deltas
+            .iter()
+            .map(|delta| {
+                format!(
+                    "data: {}\n\n",
+                    serde_json::json!({"choices": [{"index": 0, "delta": delta}]})
+                )
+            })
+            .collect();
+        events + "data: [DONE]\n\n"
+    }
+
+    #[tokio::test]
+    async fn replayed_recordings_drive_the_tool_loop() {
+        let fixtures = tempfile::tempdir().unwrap();
+        let replies = [
+            sse(&[
+                serde_json::json!({"tool_calls": [{"index": 0, "id": "call_a", "function": {"name": "write_file", "arguments": "{\"path\": \"notes.md\","}}]}),
+                serde_json::json!({"tool_calls": [{"index": 0, "function": {"arguments": " \"content\": \"# Notes\\n\"}"}}]}),
+            ]),
+            sse(&[
+                serde_json::json!({"content": "Added "}),
+                serde_json::json!({"content": "notes.md."}),
+            ]),
+        ];
+        for (i, body) in replies.into_iter().enumerate() {
+            let fixture = serde_json::json!({
+                "endpoint": "chat/completions",
+                "hash": "",
+                "request": {},
+                "status": 200,
+                "body": body,
+            });
+            fs::write(
+                fixtures.path().join(format!("{:04}.json", i + 1)),
+                fixture.to_string(),
+            )
+            .unwrap();
+        }
+        cassette::replay(fixtures.path()).unwrap();
+        // Other tests in this process call the API (or the response cache) as usual.
+        struct Stop;
+        impl Drop for Stop {
+            fn drop(&mut self) {
+                cassette::stop();
+            }
+        }
+        let _stop = Stop;
+
+        let workspace = tempfile::tempdir().unwrap();
+        let executor = Executor::new(workspace.path().to_path_buf());
+        let agent = OpenAiAgent::new("test-key".into());
+        let mut messages = task("Add notes.md");
+        let outcome = execute_step(&agent, &executor, &mut messages).await;
+        assert!(matches!(outcome, StepOutcome::Done(Some(ref text)) if text == "Added notes.md."));
+        assert_eq!(
+            fs::read_to_string(workspace.path().join("notes.md")).unwrap(),
+            "# Notes\n"
+        );
+        assert!(
+            matches!(&messages[2], Message::ToolResult { tool_call_id, .. } if tool_call_id == "call_a")
+        );
+
+        // The recordings are used up.
+        let outcome = execute_step(&agent, &executor, &mut task("again")).await;
+        assert!(
+            matches!(outcome, StepOutcome::Failed(ref e) if e == "No recorded response left for chat/completions")
+        );
+    }
+}
@@ -0,0 +1,485 @@
+//! Pipeline runs saved to the data dir after every phase and step, so a run stopped by
+//! Ctrl-C, a crash, or a failed request can be picked up with `--resume-run <id>`.
+//!
+//! Between saves, each message added to the executor conversation is appended to a journal,
+//! so a run that dies mid-step (a panic, OOM, or closed terminal) resumes with every message up
+//! to that point rather than the last step's. The journal is the REPL session's
+//! (`sessions/<session>.jsonl`, shared by its runs) or, outside a session, the run's own
+//! (`runs/<id>.jsonl`). It is only ever appended to; a save records how long it was, and
+//! loading replays what came after.
+
+use crate::agent::Message;
+use crate::config;
+use crate::git;
+use crate::session;
+use crate::ui::TodoStatus;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// The journal of the run being saved in this process.
+static JOURNAL: Mutex<Option<Journal>> = Mutex::new(None);
+
+struct Journal {
+    path: PathBuf,
+    run: String,
+    /// Messages of the conversation already in the saved state or the journal.
+    written: usize,
+}
+
+/// A journal line: `message` is run `run`'s message at `index`, replacing any from there.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    /// Empty in journals written before they were shared by a session's runs.
+    #[serde(default)]
+    run: String,
+    index: usize,
+    message: Message,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunState {
+    pub id: String,
+    pub workspace: PathBuf,
+    /// The prompt as given to the pipeline.
+    pub prompt: String,
+    pub summary: String,
+    pub todos: Vec<String>,
+    pub statuses: Vec<TodoStatus>,
+    pub notes: Vec<Option<String>>,
+    /// Groups of independent todos (0-based) for `--parallel`.
+    pub parallel: Vec<Vec<usize>>,
+    pub replans: usize,
+    /// The executor conversation, starting with the context and plan.
+    pub messages: Vec<Message>,
+    /// Content of each touched file before the run, so resumed runs report the whole diff.
+    pub baseline: BTreeMap<String, Option<String>>,
+    /// Git tree of the workspace when the state was saved (see `git::snapshot`).
+    pub snapshot: Option<String>,
+    /// The REPL session the run belongs to, whose journal it appends to.
+    #[serde(default)]
+    pub session: Option<String>,
+    /// Length of the journal in bytes when the state was saved; later entries are replayed.
+    #[serde(default)]
+    pub journal_from: u64,
+    /// Start of this process's model calls for the run, for its report.
+    #[serde(skip)]
+    pub usage_mark: usize,
+}
+
+pub fn runs_dir() -> Option<PathBuf> {
+    config::data_dir().map(|d| d.join("runs"))
+}
+
+/// Append the messages added to the saved run's conversation since the last call. Does
+/// nothing when no run has been saved.
+pub fn journal(messages: &[Message]) {
+    let mut active = JOURNAL.lock().unwrap();
+    let Some(journal) = active.as_mut() else {
+        return;
+    };
+    // A shortened conversation is picked up by the index of the next message.
+    journal.written = journal.written.min(messages.len());
+    if journal.written == messages.len() {
+        return;
+    }
+    let lines: String = messages[journal.written..]
+        .iter()
+        .enumerate()
+        .filter_map(|(i, message)| {
+            let entry = Entry {
+                run: journal.run.clone(),
+                index: journal.written + i,
+                message: message.clone(),
+            };
+            serde_json::to_string(&entry).ok().map(|line| line + "\n")
+        })
+        .collect();
+    let written = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&journal.path)
+        .and_then(|mut f| f.write_all(lines.as_bytes()));
+    match written {
+        Ok(()) => journal.written = messages.len(),
+        Err(e) => {
+            warn!(path = %journal.path.display(), error = %e, "could not write the run journal")
+        }
+    }
+}
+
+/// Apply run `run`'s entries in the journal at `path`, from byte `from` on, to `messages`.
+/// Lines cut short by a crash are skipped.
+fn replay(path: &Path, from: u64, run: &str, messages: &mut Vec<Message>) {
+    let Ok(content) = fs::read(path) else {
+        return;
+    };
+    let Some(content) = content.get(from as usize..) else {
+        return;
+    };
+    for line in String::from_utf8_lossy(content).lines() {
+        let Ok(entry) = serde_json::from_str::<Entry>(line) else {
+            continue;
+        };
+        if !entry.run.is_empty() && entry.run != run {
+            continue;
+        }
+        if entry.index > messages.len() {
+            break;
+        }
+        messages.truncate(entry.index);
+        messages.push(entry.message);
+    }
+    answer_pending_calls(messages);
+}
+
+/// End the journal's last line if a crash cut it short, so new entries start on their own line.
+fn end_line(path: &Path) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().read(true).append(true).open(path)?;
+    let len = file.metadata()?.len();
+    if len == 0 {
+        return Ok(());
+    }
+    file.seek(SeekFrom::Start(len - 1))?;
+    let mut last = [0u8];
+    file.read_exact(&mut last)?;
+    if last[0] != b'\n' {
+        file.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Give every tool call that has no result (the run stopped while running it) one saying so,
+/// since APIs reject a conversation with unanswered tool calls.
+fn answer_pending_calls(messages: &mut Vec<Message>) {
+    let mut i = 0;
+    while i < messages.len() {
+        let calls = match &messages[i] {
+            Message::Assistant {
+                tool_calls: Some(calls),
+                ..
+            } => calls.clone(),
+            _ => Vec::new(),
+        };
+        i += 1;
+        let mut answered = Vec::new();
+        while let Some(Message::ToolResult { tool_call_id, .. }) = messages.get(i) {
+            answered.push(tool_call_id.clone());
+            i += 1;
+        }
+        for call in calls.iter().filter(|c| !answered.contains(&c.id)) {
+            messages.insert(
+                i,
+                Message::ToolResult {
+                    role: "tool".into(),
+                    tool_call_id: call.id.clone(),
+                    function_name: call.function.name.clone(),
+                    content: "Error: not run; the run stopped before this call".into(),
+                },
+            );
+            i += 1;
+        }
+    }
+}
+
+/// What `--continue` picks up.
+pub enum Latest {
+    Run(String),
+    Session(String),
+}
+
+/// The most recent REPL session, or the latest unfinished run in `workspace` when it was saved
+/// after that session or belongs to it (a session is saved again after its run stops).
+pub fn latest(workspace: &Path) -> Option<Latest> {
+    #[derive(Deserialize)]
+    struct Saved {
+        id: String,
+        workspace: PathBuf,
+        #[serde(default)]
+        session: Option<String>,
+    }
+    let run = runs_dir()
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|e| {
+            let saved = e.metadata().and_then(|m| m.modified()).ok()?;
+            let state: Saved = serde_json::from_str(&fs::read_to_string(e.path()).ok()?).ok()?;
+            (state.workspace == workspace).then_some((state, saved))
+        })
+        .max_by_key(|(_, saved)| *saved);
+    match (run, session::latest()) {
+        (Some((run, saved)), session)
+            if session
+                .as_ref()
+                .is_none_or(|(id, at)| saved >= *at || run.session.as_ref() == Some(id)) =>
+        {
+            Some(Latest::Run(run.id))
+        }
+        (_, Some((id, _))) => Some(Latest::Session(id)),
+        _ => None,
+    }
+}
+
+/// A fresh run id (seconds since the epoch).
+pub fn new_id() -> String {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+        .to_string()
+}
+
+impl RunState {
+    /// Load a saved run by id.
+    pub fn load(id: &str) -> Result<Self, String> {
+        let path = runs_dir()
+            .ok_or("No data directory")?
+            .join(format!("{}.json", id));
+        let content =
+            fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut state: Self = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+        if let Some(journal) = state.journal_path() {
+            replay(&journal, state.journal_from, &state.id, &mut state.messages);
+        }
+        Ok(state)
+    }
+
+    fn journal_path(&self) -> Option<PathBuf> {
+        match &self.session {
+            Some(session) => session::sessions_dir().map(|d| d.join(format!("{}.jsonl", session))),
+            None => runs_dir().map(|d| d.join(format!("{}.jsonl", self.id))),
+        }
+    }
+
+    /// Save with a fresh workspace snapshot.
+    pub fn save(&mut self, baseline: BTreeMap<String, Option<String>>) -> Result<(), String> {
+        self.baseline = baseline;
+        self.snapshot = git::snapshot(&self.workspace).ok();
+        let dir = runs_dir().ok_or("No data directory")?;
+        let path = self.journal_path().ok_or("No data directory")?;
+        for dir in [Some(dir.as_path()), path.parent()].into_iter().flatten() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let _ = end_line(&path);
+        // The saved state holds the whole conversation; only later entries are replayed.
+        self.journal_from = fs::metadata(&path).map_or(0, |m| m.len());
+        let content = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        config::write_atomic(&dir.join(format!("{}.json", self.id)), content)
+            .map_err(|e| e.to_string())?;
+        *JOURNAL.lock().unwrap() = Some(Journal {
+            path,
+            run: self.id.clone(),
+            written: self.messages.len(),
+        });
+        Ok(())
+    }
+
+    /// Forget the run once it has finished. A session's journal stays with the session.
+    pub fn remove(&self) {
+        if let Some(dir) = runs_dir() {
+            let _ = fs::remove_file(dir.join(format!("{}.json", self.id)));
+            if self.session.is_none() {
+                let _ = fs::remove_file(dir.join(format!("{}.jsonl", self.id)));
+            }
+        }
+        *JOURNAL.lock().unwrap() = None;
+    }
+
+    pub fn is_saved(&self) -> bool {
+        runs_dir().is_some_and(|d| d.join(format!("{}.json", self.id)).is_file())
+    }
+
+    /// Whether `workspace` still looks the way it did when the state was saved. True when
+    /// either snapshot is unavailable (e.g. not a git repository).
+    pub fn matches_workspace(&self, workspace: &Path) -> bool {
+        match (&self.snapshot, git::snapshot(workspace)) {
+            (Some(saved), Ok(now)) => *saved == now,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{FunctionCall, ToolCall};
+
+    fn user(text: &str) -> Message {
+        Message::Role {
+            role: "user".into(),
+            content: text.into(),
+        }
+    }
+
+    fn calls(ids: &[&str]) -> Message {
+        Message::Assistant {
+            role: "assistant".into(),
+            content: None,
+            tool_calls: Some(
+                ids.iter()
+                    .map(|id| ToolCall {
+                        id: id.to_string(),
+                        type_: "function".into(),
+                        function: FunctionCall {
+                            name: "read_file".into(),
+                            arguments: "{}".into(),
+                        },
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    fn result(id: &str) -> Message {
+        Message::ToolResult {
+            role: "tool".into(),
+            tool_call_id: id.into(),
+            function_name: "read_file".into(),
+            content: "ok".into(),
+        }
+    }
+
+    fn entry(run: &str, index: usize, message: Message) -> String {
+        serde_json::to_string(&Entry {
+            run: run.into(),
+            index,
+            message,
+        })
+        .unwrap()
+            + "\n"
+    }
+
+    fn contents(messages: &[Message]) -> Vec<String> {
+        messages
+            .iter()
+            .map(|m| match m {
+                Message::Role { content, .. } => content.clone(),
+                Message::Assistant { tool_calls, .. } => {
+                    let ids: Vec<String> =
+                        tool_calls.iter().flatten().map(|c| c.id.clone()).collect();
+                    format!("calls {}", ids.join(","))
+                }
+                Message::ToolResult {
+                    tool_call_id,
+                    content,
+                    ..
+                } => format!("{}: {}", tool_call_id, content),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn replay_applies_entries_after_the_offset() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.jsonl");
+        let saved = entry("r1", 0, user("task"));
+        let journal = saved.clone() + &entry("r1", 1, user("more")) + &entry("r1", 2, user("last"));
+        fs::write(&path, journal).unwrap();
+
+        let mut messages = vec![user("task")];
+        replay(&path, saved.len() as u64, "r1", &mut messages);
+        assert_eq!(contents(&messages), ["task", "more", "last"]);
+    }
+
+    #[test]
+    fn replay_rewrites_from_an_entry_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.jsonl");
+        let journal = entry("r1", 1, user("first try")) + &entry("r1", 1, user("second try"));
+        fs::write(&path, journal).unwrap();
+
+        let mut messages = vec![user("task"), user("old")];
+        replay(&path, 0, "r1", &mut messages);
+        assert_eq!(contents(&messages), ["task", "second try"]);
+    }
+
+    #[test]
+    fn replay_skips_other_runs_and_torn_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        let torn = entry("r1", 2, user("cut off"));
+        let journal = entry("r1", 1, user("mine"))
+            + &entry("r2", 1, user("another run's"))
+            + &entry("", 2, user("older journal"))
+            + &torn[..torn.len() / 2];
+        fs::write(&path, journal).unwrap();
+
+        let mut messages = vec![user("task")];
+        replay(&path, 0, "r1", &mut messages);
+        assert_eq!(contents(&messages), ["task", "mine", "older journal"]);
+    }
+
+    #[test]
+    fn replay_stops_at_a_gap() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.jsonl");
+        fs::write(
+            &path,
+            entry("r1", 3, user("too far")) + &entry("r1", 1, user("next")),
+        )
+        .unwrap();
+
+        let mut messages = vec![user("task")];
+        replay(&path, 0, "r1", &mut messages);
+        assert_eq!(contents(&messages), ["task"]);
+    }
+
+    #[test]
+    fn replay_without_a_journal_changes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut messages = vec![user("task")];
+        replay(&dir.path().join("missing.jsonl"), 0, "r1", &mut messages);
+        assert_eq!(contents(&messages), ["task"]);
+    }
+
+    #[test]
+    fn pending_calls_get_a_result() {
+        let mut messages = vec![user("task"), calls(&["a", "b"]), result("a"), calls(&["c"])];
+        answer_pending_calls(&mut messages);
+        assert_eq!(
+            contents(&messages),
+            [
+                "task",
+                "calls a,b",
+                "a: ok",
+                "b: Error: not run; the run stopped before this call",
+                "calls c",
+                "c: Error: not run; the run stopped before this call",
+            ]
+        );
+    }
+
+    #[test]
+    fn answered_calls_are_left_alone() {
+        let mut messages = vec![
+            user("task"),
+            calls(&["a", "b"]),
+            result("b"),
+            result("a"),
+            user("next"),
+        ];
+        answer_pending_calls(&mut messages);
+        assert_eq!(
+            contents(&messages),
+            ["task", "calls a,b", "b: ok", "a: ok", "next"]
+        );
+    }
+
+    #[test]
+    fn end_line_finishes_a_torn_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.jsonl");
+        fs::write(&path, "{\"torn").unwrap();
+        end_line(&path).unwrap();
+        end_line(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"torn\n");
+    }
+}
@@ -0,0 +1,193 @@
+//! State carried across prompts in one interactive session, saved to the data dir after
+//! every change so it can be resumed with `--resume <id>`.
+
+use crate::config;
+use crate::usage::{self, Usage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Turns of earlier conversation included as context with each new prompt.
+const HISTORY_TURNS: usize = 5;
+/// Max chars of each earlier reply included as context.
+const HISTORY_REPLY_CAP: usize = 2000;
+
+static ACTIVE_ID: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Turn {
+    pub prompt: String,
+    /// Final assistant text; None while the turn is running or if it produced none.
+    pub reply: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub turns: Vec<Turn>,
+    /// Files attached to every prompt (via `/add` or an `@path` mention).
+    pub pinned: Vec<String>,
+    /// Hash of each pinned file's contents as last sent, to flag files changed since.
+    #[serde(skip)]
+    pub pinned_seen: HashMap<String, u64>,
+    /// Every model call made in this session, for cost tracking and `zcode usage`.
+    #[serde(default)]
+    pub usage: Vec<Usage>,
+    /// Clipboard text staged by `/paste`, attached to the next prompt.
+    #[serde(skip)]
+    pub pasted: Option<String>,
+    /// Length of the usage ledger when the current turn began.
+    #[serde(skip)]
+    turn_started: usize,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let session = Self {
+            id: secs.to_string(),
+            turns: Vec::new(),
+            pinned: Vec::new(),
+            pinned_seen: HashMap::new(),
+            usage: Vec::new(),
+            pasted: None,
+            turn_started: 0,
+        };
+        session.activate();
+        session
+    }
+}
+
+pub fn sessions_dir() -> Option<PathBuf> {
+    config::data_dir().map(|d| d.join("sessions"))
+}
+
+/// Id of the session currently running in this process, if any.
+pub fn active_id() -> Option<String> {
+    ACTIVE_ID.lock().unwrap().clone()
+}
+
+/// The most recently saved session, with when it was saved.
+pub fn latest() -> Option<(String, SystemTime)> {
+    fs::read_dir(sessions_dir()?)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let id = e
+                .file_name()
+                .to_string_lossy()
+                .strip_suffix(".json")?
+                .to_string();
+            Some((id, e.metadata().and_then(|m| m.modified()).ok()?))
+        })
+        .max_by_key(|(_, saved)| *saved)
+}
+
+impl Session {
+    /// A new session with the given id instead of the time.
+    pub fn with_id(id: String) -> Self {
+        let session = Self {
+            id,
+            ..Self::default()
+        };
+        session.activate();
+        session
+    }
+
+    /// Load a saved session by id.
+    pub fn load(id: &str) -> Result<Self, String> {
+        let path = sessions_dir()
+            .ok_or("No data directory")?
+            .join(format!("{}.json", id));
+        let content =
+            fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let session: Self = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+        usage::restore(session.usage.clone());
+        session.activate();
+        Ok(session)
+    }
+
+    /// Record runs, journals, and usage under this session from now on. Creating or loading a
+    /// session activates it; a frontend holding several activates each before its turn.
+    pub fn activate(&self) {
+        *ACTIVE_ID.lock().unwrap() = Some(self.id.clone());
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let dir = sessions_dir().ok_or("No data directory")?;
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        config::write_atomic(&dir.join(format!("{}.json", self.id)), content)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Pin a file into context for the following prompts; false if it already was.
+    pub fn pin(&mut self, path: &str) -> bool {
+        if self.pinned.iter().any(|p| p == path) {
+            return false;
+        }
+        self.pinned.push(path.to_string());
+        let _ = self.save();
+        true
+    }
+
+    pub fn last_reply(&self) -> Option<&str> {
+        self.turns.iter().rev().find_map(|t| t.reply.as_deref())
+    }
+
+    /// Record a new prompt (saved immediately so an interrupted turn is not lost).
+    pub fn begin_turn(&mut self, prompt: &str) {
+        self.turns.push(Turn {
+            prompt: prompt.to_string(),
+            reply: None,
+        });
+        self.turn_started = usage::mark();
+        let _ = self.save();
+    }
+
+    pub fn finish_turn(&mut self, reply: Option<String>) {
+        if let Some(turn) = self.turns.last_mut() {
+            turn.reply = reply;
+        }
+        // Only this turn's calls: the ledger also holds other sessions' in a server.
+        self.usage
+            .extend_from_slice(usage::all().get(self.turn_started..).unwrap_or_default());
+        let _ = self.save();
+    }
+
+    /// The whole conversation as markdown.
+    pub fn transcript(&self) -> String {
+        self.turns
+            .iter()
+            .map(|t| {
+                format!(
+                    "## > {}\n\n{}\n",
+                    t.prompt,
+                    t.reply.as_deref().unwrap_or("(no reply)")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Earlier turns formatted as a context block (empty if this is the first turn).
+    pub fn history_block(&self) -> String {
+        let done = self.turns.len().saturating_sub(1);
+        let earlier = &self.turns[done.saturating_sub(HISTORY_TURNS)..done];
+        earlier
+            .iter()
+            .map(|t| {
+                let reply = t.reply.as_deref().unwrap_or("(no reply)");
+                let reply: String = reply.chars().take(HISTORY_REPLY_CAP).collect();
+                format!("User: {}\nAssistant: {}", t.prompt, reply)
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
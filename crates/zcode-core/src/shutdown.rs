@@ -0,0 +1,43 @@
+//! Graceful shutdown on SIGTERM/SIGHUP and when the REPL ends.
+
+use crate::hooks;
+use crate::session;
+use crate::tools::Executor;
+use crate::ui;
+use crate::usage;
+use std::sync::Arc;
+
+/// Clean up on SIGTERM/SIGHUP (e.g. a dropped SSH connection), then call `exit` with the
+/// conventional status (143 or 129).
+pub fn install(executor: Arc<Executor>, exit: fn(i32)) {
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        use tokio::signal::unix::{signal, SignalKind};
+        let (Ok(mut term), Ok(mut hup)) = (
+            signal(SignalKind::terminate()),
+            signal(SignalKind::hangup()),
+        ) else {
+            return;
+        };
+        let code = tokio::select! {
+            _ = term.recv() => 143,
+            _ = hup.recv() => 129,
+        };
+        finish(&executor);
+        exit(code);
+    });
+    #[cfg(not(unix))]
+    let _ = (executor, exit);
+}
+
+/// Kill spawned commands, run the `session_end` hooks, and print the session cost and how to
+/// resume the session (saved after every turn).
+pub fn finish(executor: &Executor) {
+    executor.kill_children();
+    hooks::session_end(executor);
+    ui::changes_summary("Changed this session", &executor.changes());
+    ui::cost_summary(&usage::all());
+    if let Some(id) = session::active_id() {
+        ui::resume_hint(&id);
+    }
+}
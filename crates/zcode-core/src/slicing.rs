@@ -0,0 +1,243 @@
+//! Function-level slices of files too large for the context budget: the definitions most
+//! relevant to the task are kept whole and the rest of the file is reduced to an outline, so
+//! no function is cut in half.
+
+use crate::context;
+use crate::repomap;
+use std::collections::BTreeSet;
+use std::path::Path;
+use tree_sitter::{Node, Parser};
+
+/// Definitions that hold other definitions, and the field naming their body.
+const CONTAINERS: &[&str] = &[
+    "impl_item",
+    "trait_item",
+    "mod_item",
+    "class_definition",
+    "class_declaration",
+];
+/// Definitions listed inside a container.
+const MEMBERS: &[&str] = &[
+    "function_item",
+    "function_signature_item",
+    "const_item",
+    "type_item",
+    "function_definition",
+    "decorated_definition",
+    "method_definition",
+    "public_field_definition",
+];
+/// Terms shorter than this are ignored.
+const MIN_TERM: usize = 3;
+
+const NAME_MATCH: f32 = 3.0;
+const NAME_CONTAINS: f32 = 1.0;
+const BODY_TERM: f32 = 0.2;
+const BODY_TERMS_MAX: f32 = 1.0;
+const FOCUS: f32 = 2.0;
+
+/// Lowercased identifiers in `text` (the prompt and plan), used to find relevant definitions.
+pub fn terms(text: &str) -> Vec<String> {
+    let words: BTreeSet<String> = text
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|w| w.len() >= MIN_TERM && !w.chars().all(|c| c.is_ascii_digit()))
+        .map(|w| w.to_lowercase())
+        .collect();
+    words.into_iter().collect()
+}
+
+struct Unit {
+    /// 0-based, inclusive.
+    start: usize,
+    end: usize,
+    depth: usize,
+    name: String,
+    signature: String,
+    score: f32,
+}
+
+/// `content` cut down to `cap` bytes: whole definitions, most relevant to `terms` (or
+/// overlapping the 1-based `focus` lines) first, and an outline of the others. None when the
+/// file's language isn't supported or it has no definitions.
+pub fn slice(
+    path: &str,
+    content: &str,
+    terms: &[String],
+    focus: Option<(usize, usize)>,
+    cap: usize,
+) -> Option<String> {
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    let (language, kinds) = repomap::language(ext)?;
+    let mut parser = Parser::new();
+    parser.set_language(&language).ok()?;
+    let tree = parser.parse(content, None)?;
+    let mut units = Vec::new();
+    collect(tree.root_node(), content, kinds, 0, &mut units);
+    if units.is_empty() {
+        return None;
+    }
+    let lines: Vec<&str> = content.lines().collect();
+    for unit in &mut units {
+        unit.score = score(unit, &lines, terms, focus);
+    }
+
+    // Everything starts as an outline line; whole definitions replace lines while they fit.
+    let outline_size: usize = units.iter().map(|u| outline_line(u).len() + 1).sum();
+    let mut left = cap.saturating_sub(outline_size + 100);
+    let mut order: Vec<usize> = (0..units.len()).collect();
+    order.sort_by(|&a, &b| {
+        units[b]
+            .score
+            .total_cmp(&units[a].score)
+            .then(units[a].start.cmp(&units[b].start))
+    });
+    let mut shown: Vec<usize> = Vec::new();
+    for i in order {
+        let unit = &units[i];
+        let inside_shown = shown
+            .iter()
+            .any(|&s| units[s].start <= unit.start && unit.end <= units[s].end);
+        if inside_shown {
+            continue;
+        }
+        let size = section(unit, &lines).len() + 1;
+        if size <= left {
+            left -= size;
+            // A container shown whole makes its shown members redundant.
+            shown.retain(|&s| !(unit.start <= units[s].start && units[s].end <= unit.end));
+            shown.push(i);
+        }
+    }
+    if shown.is_empty() {
+        return None;
+    }
+
+    let mut out = format!(
+        "… ({} lines; {} relevant definitions in full, the rest as an outline)\n",
+        lines.len(),
+        shown.len()
+    );
+    for (i, unit) in units.iter().enumerate() {
+        if shown.contains(&i) {
+            out.push_str(&section(unit, &lines));
+            out.push('\n');
+        } else if !shown
+            .iter()
+            .any(|&s| units[s].start <= unit.start && unit.end <= units[s].end)
+        {
+            out.push_str(&outline_line(unit));
+            out.push('\n');
+        }
+    }
+    Some(context::truncate(&out, cap))
+}
+
+fn collect(node: Node, source: &str, kinds: &[&str], depth: usize, out: &mut Vec<Unit>) {
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        let wanted = if depth == 0 { kinds } else { MEMBERS };
+        if !wanted.contains(&child.kind()) {
+            continue;
+        }
+        let text = child.utf8_text(source.as_bytes()).unwrap_or_default();
+        out.push(Unit {
+            start: child.start_position().row,
+            end: child.end_position().row,
+            depth,
+            name: name(child, source).unwrap_or_default().to_lowercase(),
+            signature: text
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .trim_end_matches('{')
+                .trim_end()
+                .to_string(),
+            score: 0.0,
+        });
+        if depth == 0 {
+            if let Some(body) = body(child) {
+                collect(body, source, kinds, depth + 1, out);
+            }
+        }
+    }
+}
+
+/// The body of a container definition, looking through `export` and decorators.
+fn body(node: Node) -> Option<Node> {
+    match node.kind() {
+        "export_statement" => body(node.child_by_field_name("declaration")?),
+        "decorated_definition" => body(node.child_by_field_name("definition")?),
+        kind if CONTAINERS.contains(&kind) => node.child_by_field_name("body"),
+        _ => None,
+    }
+}
+
+/// The identifier a definition introduces (for an `impl`, the type it's for).
+fn name(node: Node, source: &str) -> Option<String> {
+    for field in ["name", "type"] {
+        if let Some(n) = node.child_by_field_name(field) {
+            return n.utf8_text(source.as_bytes()).ok().map(str::to_string);
+        }
+    }
+    for field in ["declaration", "definition"] {
+        if let Some(inner) = node.child_by_field_name(field) {
+            return name(inner, source);
+        }
+    }
+    // `const x = …` (JS) and `type X …` (Go) name their inner declarator or spec.
+    let mut cursor = node.walk();
+    let inner = node
+        .named_children(&mut cursor)
+        .find(|c| matches!(c.kind(), "variable_declarator" | "type_spec"));
+    inner.and_then(|c| name(c, source))
+}
+
+fn score(unit: &Unit, lines: &[&str], terms: &[String], focus: Option<(usize, usize)>) -> f32 {
+    let mut score = 0.0;
+    if !unit.name.is_empty() {
+        for term in terms {
+            if unit.name == *term {
+                score += NAME_MATCH;
+            } else if term.len() > MIN_TERM && unit.name.contains(term.as_str()) {
+                score += NAME_CONTAINS;
+            }
+        }
+    }
+    let body = lines[unit.start..=unit.end.min(lines.len().saturating_sub(1))]
+        .join("\n")
+        .to_lowercase();
+    let in_body = terms
+        .iter()
+        .filter(|t| t.len() > MIN_TERM && body.contains(t.as_str()))
+        .count();
+    score += (in_body as f32 * BODY_TERM).min(BODY_TERMS_MAX);
+    if let Some((from, to)) = focus {
+        if unit.start < to && from <= unit.end + 1 {
+            score += FOCUS;
+        }
+    }
+    score
+}
+
+fn outline_line(unit: &Unit) -> String {
+    format!(
+        "{}[line {}] {}",
+        "  ".repeat(unit.depth),
+        unit.start + 1,
+        unit.signature
+    )
+}
+
+fn section(unit: &Unit, lines: &[&str]) -> String {
+    let end = unit.end.min(lines.len().saturating_sub(1));
+    format!(
+        "[lines {}-{}]\n{}",
+        unit.start + 1,
+        end + 1,
+        lines[unit.start..=end].join("\n")
+    )
+}
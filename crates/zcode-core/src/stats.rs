@@ -0,0 +1,140 @@
+//! `zcode usage`: spend and activity from the usage history, per project, model, and tool.
+
+use crate::ui;
+use crate::usage::{self, RunRecord};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Parse `30m`, `12h`, `7d`, or `4w` into seconds.
+fn parse_since(spec: &str) -> Result<u64, String> {
+    let spec = spec.trim();
+    let (num, unit) = spec.split_at(spec.len().saturating_sub(1));
+    let n: u64 = num
+        .parse()
+        .map_err(|_| format!("Bad duration: {} (e.g. 7d, 12h)", spec))?;
+    let secs = match unit {
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        "w" => 7 * 86_400,
+        _ => return Err(format!("Bad duration unit in {} (use m, h, d, or w)", spec)),
+    };
+    Ok(n * secs)
+}
+
+#[derive(Default)]
+struct Totals {
+    runs: usize,
+    calls: usize,
+    input: u64,
+    cached: u64,
+    output: u64,
+    cost: f64,
+}
+
+impl Totals {
+    fn add_run(&mut self, run: &RunRecord) {
+        self.runs += 1;
+        for call in &run.calls {
+            self.calls += 1;
+            self.input += call.prompt_tokens;
+            self.cached += call.cached_tokens;
+            self.output += call.completion_tokens;
+            self.cost += call.cost();
+        }
+    }
+
+    fn row(&self, label: &str) -> Vec<String> {
+        vec![
+            label.to_string(),
+            self.runs.to_string(),
+            self.calls.to_string(),
+            self.input.to_string(),
+            self.cached.to_string(),
+            self.output.to_string(),
+            format!("${:.4}", self.cost),
+        ]
+    }
+}
+
+const HEADERS: &[&str] = &["", "runs", "calls", "in", "cached", "out", "cost"];
+
+pub fn run(since: Option<&str>, project: Option<&str>) -> Result<(), String> {
+    let cutoff = match since {
+        Some(spec) => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            now.saturating_sub(parse_since(spec)?)
+        }
+        None => 0,
+    };
+    let runs: Vec<RunRecord> = usage::history()
+        .into_iter()
+        .filter(|r| r.time >= cutoff)
+        .filter(|r| project.is_none_or(|p| r.workspace == p))
+        .collect();
+    if runs.is_empty() {
+        ui::info("No usage recorded for this period.");
+        return Ok(());
+    }
+
+    let mut total = Totals::default();
+    let mut by_project: BTreeMap<&str, Totals> = BTreeMap::new();
+    let mut by_model: BTreeMap<&str, Totals> = BTreeMap::new();
+    let mut tools: BTreeMap<&str, u64> = BTreeMap::new();
+    for run in &runs {
+        total.add_run(run);
+        by_project.entry(&run.workspace).or_default().add_run(run);
+        for call in &run.calls {
+            let model = by_model.entry(&call.model).or_default();
+            model.calls += 1;
+            model.input += call.prompt_tokens;
+            model.cached += call.cached_tokens;
+            model.output += call.completion_tokens;
+            model.cost += call.cost();
+        }
+        for (tool, n) in &run.tools {
+            *tools.entry(tool).or_default() += n;
+        }
+    }
+
+    let mut projects: Vec<(&str, Totals)> = by_project.into_iter().collect();
+    projects.sort_by(|a, b| b.1.cost.total_cmp(&a.1.cost));
+    let mut rows: Vec<Vec<String>> = projects.iter().map(|(p, t)| t.row(p)).collect();
+    rows.push(total.row("total"));
+    ui::phase_done("By project");
+    ui::table(HEADERS, &rows);
+
+    outln!();
+    ui::phase_done("By model");
+    let rows: Vec<Vec<String>> = by_model
+        .iter()
+        .map(|(m, t)| {
+            let mut row = t.row(m);
+            row.remove(1);
+            row
+        })
+        .collect();
+    let model_headers: Vec<&str> = HEADERS
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != 1)
+        .map(|(_, h)| *h)
+        .collect();
+    ui::table(&model_headers, &rows);
+
+    if !tools.is_empty() {
+        outln!();
+        ui::phase_done("Tool calls");
+        let mut tools: Vec<(&str, u64)> = tools.into_iter().collect();
+        tools.sort_by_key(|t| std::cmp::Reverse(t.1));
+        let rows: Vec<Vec<String>> = tools
+            .iter()
+            .map(|(t, n)| vec![t.to_string(), n.to_string()])
+            .collect();
+        ui::table(&["", "calls"], &rows);
+    }
+    Ok(())
+}
@@ -0,0 +1,87 @@
+//! Guidance typed while a run executes ("don't touch the DB schema"): lines are queued and
+//! handed to the model at the next turn boundary, so a run can be corrected without
+//! stopping it. `zcode serve` clients queue guidance with a `steer` message.
+
+use crate::ui;
+use std::io::{BufRead, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// How often the stdin listener checks whether it should stop.
+const POLL_MS: i32 = 200;
+
+static QUEUE: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Queue guidance for the next turn.
+pub fn push(text: &str) {
+    let text = text.trim();
+    if !text.is_empty() {
+        QUEUE.lock().unwrap().push(text.to_string());
+    }
+}
+
+/// Take all queued guidance.
+pub fn take() -> Vec<String> {
+    std::mem::take(&mut *QUEUE.lock().unwrap())
+}
+
+/// Reads stdin lines into the queue until dropped.
+pub struct Listener {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Listen for typed guidance when stdin is a terminal. The listener polls, so dropping it
+/// returns stdin to the REPL within `POLL_MS`.
+pub fn listen() -> Listener {
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = std::io::stdin().is_terminal().then(|| {
+        let stop = stop.clone();
+        std::thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                if !stdin_ready() {
+                    continue;
+                }
+                let mut line = String::new();
+                match std::io::stdin().lock().read_line(&mut line) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) if line.trim().is_empty() => {}
+                    Ok(_) => {
+                        push(&line);
+                        ui::info(&format!("Queued for the next turn: {}", line.trim()));
+                    }
+                }
+            }
+        })
+    });
+    Listener { stop, thread }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Wait up to `POLL_MS` for a line on stdin (the terminal is line-buffered).
+#[cfg(unix)]
+fn stdin_ready() -> bool {
+    let mut fd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // SAFETY: one valid pollfd for the duration of the call.
+    let ready = unsafe { libc::poll(&mut fd, 1, POLL_MS) };
+    ready > 0 && fd.revents & libc::POLLIN != 0
+}
+
+#[cfg(not(unix))]
+fn stdin_ready() -> bool {
+    std::thread::sleep(std::time::Duration::from_millis(POLL_MS as u64));
+    false
+}
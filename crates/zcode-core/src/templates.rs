@@ -0,0 +1,118 @@
+//! Reusable prompt templates stored as `<config dir>/templates/<name>.md`.
+//!
+//! Templates may use `{{file}}` (the path given with `--file`), `{{selection}}` (the lines
+//! picked with `--lines`, or the whole file), and `{{args}}` (remaining arguments). The first
+//! line, if it starts with `#`, is the description shown by `/templates`.
+
+use crate::config;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub struct Template {
+    pub name: String,
+    pub description: String,
+    pub body: String,
+}
+
+/// Values substituted into a template.
+#[derive(Default)]
+pub struct TemplateVars {
+    pub file: Option<String>,
+    /// 1-based inclusive line range within `file`.
+    pub lines: Option<(usize, usize)>,
+    pub args: Vec<String>,
+}
+
+pub fn templates_dir() -> Option<PathBuf> {
+    config::config_dir().map(|d| d.join("templates"))
+}
+
+fn parse(name: &str, content: &str) -> Template {
+    let (description, body) = match content.split_once('\n') {
+        Some((first, rest)) if first.starts_with('#') => (
+            first.trim_start_matches('#').trim().to_string(),
+            rest.trim_start(),
+        ),
+        _ => (String::new(), content),
+    };
+    Template {
+        name: name.to_string(),
+        description,
+        body: body.to_string(),
+    }
+}
+
+pub fn load(name: &str) -> Result<Template, String> {
+    let dir = templates_dir().ok_or("No config directory")?;
+    let path = dir.join(format!("{}.md", name));
+    let content = fs::read_to_string(&path).map_err(|_| {
+        format!(
+            "No template named {:?} (looked for {})",
+            name,
+            path.display()
+        )
+    })?;
+    Ok(parse(name, &content))
+}
+
+/// All templates, sorted by name.
+pub fn list() -> Vec<Template> {
+    let Some(dir) = templates_dir() else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut templates: Vec<Template> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let path = e.path();
+            if path.extension()? != "md" {
+                return None;
+            }
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            let content = fs::read_to_string(&path).ok()?;
+            Some(parse(&name, &content))
+        })
+        .collect();
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    templates
+}
+
+/// Parse a `START-END` line range.
+pub fn parse_lines(spec: &str) -> Result<(usize, usize), String> {
+    let (a, b) = spec.split_once('-').ok_or("Expected START-END")?;
+    let start: usize = a.trim().parse().map_err(|_| "Bad start line")?;
+    let end: usize = b.trim().parse().map_err(|_| "Bad end line")?;
+    if start == 0 || end < start {
+        return Err("Invalid line range".into());
+    }
+    Ok((start, end))
+}
+
+impl Template {
+    /// Substitute variables; the selection is read from the workspace.
+    pub fn render(&self, workspace: &Path, vars: &TemplateVars) -> Result<String, String> {
+        let selection = match &vars.file {
+            Some(file) => {
+                let content = fs::read_to_string(workspace.join(file))
+                    .map_err(|e| format!("{}: {}", file, e))?;
+                match vars.lines {
+                    Some((start, end)) => content
+                        .lines()
+                        .skip(start - 1)
+                        .take(end + 1 - start)
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    None => content,
+                }
+            }
+            None => String::new(),
+        };
+        Ok(self
+            .body
+            .replace("{{file}}", vars.file.as_deref().unwrap_or(""))
+            .replace("{{selection}}", &selection)
+            .replace("{{args}}", &vars.args.join(" ")))
+    }
+}
@@ -0,0 +1,174 @@
+//! `zcode test-gen <path>`: write tests for a module following the project's conventions,
+//! run them, and iterate on failures.
+
+use crate::agent::{self, Message};
+use crate::context;
+use crate::fix;
+use crate::run::{self, execute_step, StepOutcome};
+use crate::tools::Executor;
+use crate::ui;
+use std::fs;
+use std::path::Path;
+
+/// Test output beyond this many bytes is cut before it goes into the prompt.
+const OUTPUT_CAP: usize = 16 * 1024;
+/// An existing test file longer than this is cut when shown as a style example.
+const EXAMPLE_CAP: usize = 4 * 1024;
+
+/// How tests are written and run in this project.
+struct Conventions {
+    /// Human-readable description for the prompt.
+    description: String,
+    /// Command that runs the test suite.
+    command: String,
+    /// An existing test file, as a style example.
+    example: Option<String>,
+}
+
+fn detect(workspace: &Path, target: &str) -> Conventions {
+    let files = context::workspace_files(workspace);
+    let ext = Path::new(target)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("");
+    let read = |p: &String| fs::read_to_string(workspace.join(p)).ok();
+
+    let (description, command, example) = match ext {
+        "rs" => {
+            let inline: Vec<&String> = files
+                .iter()
+                .filter(|f| {
+                    f.ends_with(".rs") && read(f).is_some_and(|c| c.contains("#[cfg(test)]"))
+                })
+                .collect();
+            let integration: Vec<&String> = files
+                .iter()
+                .filter(|f| f.starts_with("tests/") && f.ends_with(".rs"))
+                .collect();
+            let description = match (inline.len(), integration.len()) {
+                (0, 0) => "Rust project with no tests yet: add a `#[cfg(test)] mod tests` at the bottom of the target file.".to_string(),
+                (i, t) if t > i => format!("Rust project using integration tests in tests/ ({} files); add a new file there.", t),
+                (i, _) => format!("Rust project using inline `#[cfg(test)] mod tests` modules ({} files); add one to the target file.", i),
+            };
+            let example = inline.first().or(integration.first()).copied().cloned();
+            (description, "cargo test".to_string(), example)
+        }
+        "py" => {
+            let tests: Vec<&String> = files
+                .iter()
+                .filter(|f| {
+                    f.ends_with(".py")
+                        && Path::new(f)
+                            .file_name()
+                            .is_some_and(|n| n.to_string_lossy().starts_with("test_"))
+                })
+                .collect();
+            let description = match tests.first().and_then(|t| Path::new(t).parent()) {
+                Some(dir) if !dir.as_os_str().is_empty() => format!(
+                    "Python project with pytest files named test_*.py in {}/.",
+                    dir.display()
+                ),
+                _ => "Python project: add a pytest file named test_<module>.py next to the target."
+                    .to_string(),
+            };
+            (
+                description,
+                "python -m pytest -q".to_string(),
+                tests.first().copied().cloned(),
+            )
+        }
+        "go" => {
+            let example = files.iter().find(|f| f.ends_with("_test.go")).cloned();
+            (
+                "Go project: add <file>_test.go in the same package as the target.".to_string(),
+                "go test ./...".to_string(),
+                example,
+            )
+        }
+        "js" | "ts" | "jsx" | "tsx" | "mjs" => {
+            let example = files
+                .iter()
+                .find(|f| {
+                    f.contains(".test.") || f.contains(".spec.") || f.starts_with("__tests__/")
+                })
+                .cloned();
+            let description = match &example {
+                Some(e) => format!("JavaScript/TypeScript project; follow the naming and framework of {}.", e),
+                None => "JavaScript/TypeScript project: add <name>.test.<ext> next to the target using the framework in package.json.".to_string(),
+            };
+            (description, "npm test".to_string(), example)
+        }
+        _ => (
+            "Follow the project's existing test layout.".to_string(),
+            "make test".to_string(),
+            None,
+        ),
+    };
+    let example = example.and_then(|p| {
+        read(&p).map(|c| format!("--- {} ---\n{}", p, context::truncate(&c, EXAMPLE_CAP)))
+    });
+    Conventions {
+        description,
+        command,
+        example,
+    }
+}
+
+/// Generate tests for `target`. Returns whether the test command passes at the end.
+pub async fn run(
+    api_key: &str,
+    executor: &Executor,
+    target: &str,
+    command: Option<&str>,
+    max_iterations: usize,
+) -> Result<bool, String> {
+    let source = context::file_block(executor.workspace(), target)
+        .map_err(|e| format!("{}: {}", target, e))?;
+    let conventions = detect(executor.workspace(), target);
+    let command = command.unwrap_or(&conventions.command);
+    ui::phase_done(&conventions.description);
+
+    let mut prompt = format!(
+        "Write tests for {}. Cover its public behavior, edge cases, and error paths; don't change non-test code unless it cannot be tested otherwise.\n\nConventions: {}\nThe tests are run with `{}`.\n\n{}",
+        target, conventions.description, command, source
+    );
+    if let Some(example) = &conventions.example {
+        prompt.push_str(&format!(
+            "\n\nExisting test for style reference:\n{}",
+            example
+        ));
+    }
+
+    let agent = agent::from_spec(&run::executor_model(), api_key)?;
+    let mut messages = vec![Message::Role {
+        role: "user".into(),
+        content: prompt,
+    }];
+    for iteration in 1..=max_iterations {
+        match execute_step(&agent, executor, &mut messages).await {
+            // The check command decides whether the round worked.
+            StepOutcome::Done(_) | StepOutcome::Stalled(_) => {}
+            StepOutcome::Interrupted(_) => return Err("Interrupted".into()),
+            StepOutcome::Failed(e) => return Err(e),
+        }
+        let (passed, output) = fix::check(executor, command)?;
+        if passed {
+            ui::info(&format!("Tests pass after {} round(s).", iteration));
+            return Ok(true);
+        }
+        messages.push(Message::Role {
+            role: "user".into(),
+            content: format!(
+                "`{}` fails:\n{}\n\nFix the new tests. If a failure reveals a real bug in {}, keep the test, fix the bug, and say so.",
+                command,
+                context::truncate(&output, OUTPUT_CAP),
+                target
+            ),
+        });
+    }
+    ui::note(&format!(
+        "Tests still fail after {} rounds.",
+        max_iterations
+    ));
+    Ok(false)
+}
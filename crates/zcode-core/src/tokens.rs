@@ -0,0 +1,288 @@
+//! Local token counts, so context budgets and request sizes are checked before sending rather
+//! than found out from an API error. OpenAI models are counted with their own BPE tokenizers
+//! (tiktoken's `o200k_base` and `cl100k_base`), loaded on first use.
+//!
+//! Models map to a tokenizer by prefix; `[tokenizers]` in the config maps model or provider
+//! patterns (`"ollama:*" = "cl100k"`) to `o200k`, `cl100k`, or `bytes` (four bytes a token).
+
+use crate::config;
+use crate::tools::glob_match;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, OnceLock};
+use tiktoken_rs::CoreBPE;
+use tracing::warn;
+
+static TOKENIZER_CACHE: Mutex<Option<HashMap<String, Tokenizer>>> = Mutex::new(None);
+static O200K: OnceLock<Option<CoreBPE>> = OnceLock::new();
+static CL100K: OnceLock<Option<CoreBPE>> = OnceLock::new();
+
+/// Tokens added per chat message for its role and framing.
+const MESSAGE_OVERHEAD: usize = 4;
+/// Room left for the reply when fitting a request into the context window, at most a quarter
+/// of the window.
+const REPLY_RESERVE: usize = 8_192;
+/// Context window for models not in `WINDOWS`.
+const DEFAULT_WINDOW: usize = 128_000;
+/// Latest messages never shortened by `fit`.
+const KEEP_RECENT: usize = 6;
+const ELIDED: &str =
+    "[output elided to fit the context window; run the tool again if it is still needed]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tokenizer {
+    /// GPT-4o, GPT-4.1, and the o-series.
+    O200k,
+    /// GPT-4, GPT-3.5, and the text-embedding-3 models.
+    Cl100k,
+    /// Four bytes a token, for models whose tokenizer is unknown.
+    Bytes,
+}
+
+/// Built-in tokenizers by model prefix; longer prefixes win.
+const TOKENIZERS: &[(&str, Tokenizer)] = &[
+    ("gpt-4o", Tokenizer::O200k),
+    ("gpt-4.1", Tokenizer::O200k),
+    ("gpt-4.5", Tokenizer::O200k),
+    ("gpt-5", Tokenizer::O200k),
+    ("o1", Tokenizer::O200k),
+    ("o3", Tokenizer::O200k),
+    ("o4", Tokenizer::O200k),
+    ("gpt-4", Tokenizer::Cl100k),
+    ("gpt-3.5", Tokenizer::Cl100k),
+    ("text-embedding-3", Tokenizer::Cl100k),
+];
+
+/// Context windows by model prefix; longer prefixes win.
+const WINDOWS: &[(&str, usize)] = &[
+    ("gpt-4o", 128_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-5", 400_000),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("o4", 200_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4", 8_192),
+    ("gpt-3.5", 16_385),
+];
+
+fn longest_prefix<T: Copy>(table: &[(&str, T)], model: &str) -> Option<T> {
+    table
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, value)| *value)
+}
+
+/// Tokenizer for a model spec: `[tokenizers]` in the config, else by model name (without the
+/// provider), else `Bytes`. Looked up once per spec.
+pub fn tokenizer(spec: &str) -> Tokenizer {
+    let mut cache = TOKENIZER_CACHE.lock().unwrap();
+    *cache
+        .get_or_insert_with(HashMap::new)
+        .entry(spec.to_string())
+        .or_insert_with(|| lookup(spec))
+}
+
+fn lookup(spec: &str) -> Tokenizer {
+    if let Some(tokenizer) = configured(&config::load().tokenizers, spec) {
+        return tokenizer;
+    }
+    let model = spec.strip_prefix("openai:").unwrap_or(spec);
+    longest_prefix(TOKENIZERS, model).unwrap_or(Tokenizer::Bytes)
+}
+
+/// The tokenizer of the longest `[tokenizers]` pattern matching `spec`.
+fn configured(tokenizers: &BTreeMap<String, Tokenizer>, spec: &str) -> Option<Tokenizer> {
+    tokenizers
+        .iter()
+        .filter(|(pattern, _)| glob_match(spec, pattern))
+        .max_by_key(|(pattern, _)| pattern.len())
+        .map(|(_, tokenizer)| *tokenizer)
+}
+
+/// Context window of a model spec, in tokens.
+pub fn context_window(spec: &str) -> usize {
+    let model = spec.rsplit_once(':').map_or(spec, |(_, model)| model);
+    longest_prefix(WINDOWS, model).unwrap_or(DEFAULT_WINDOW)
+}
+
+/// Tokens `text` takes for the model `spec`.
+pub fn count(spec: &str, text: &str) -> usize {
+    count_with(tokenizer(spec), text)
+}
+
+pub fn count_with(tokenizer: Tokenizer, text: &str) -> usize {
+    match bpe(tokenizer) {
+        Some(bpe) => bpe.encode_ordinary(text).len(),
+        None => text.len().div_ceil(4),
+    }
+}
+
+/// The BPE for `tokenizer`, None for `Bytes` or when it fails to load.
+fn bpe(tokenizer: Tokenizer) -> Option<&'static CoreBPE> {
+    let (cell, load): (_, fn() -> Result<CoreBPE, String>) = match tokenizer {
+        Tokenizer::O200k => (&O200K, || {
+            tiktoken_rs::o200k_base().map_err(|e| e.to_string())
+        }),
+        Tokenizer::Cl100k => (&CL100K, || {
+            tiktoken_rs::cl100k_base().map_err(|e| e.to_string())
+        }),
+        Tokenizer::Bytes => return None,
+    };
+    cell.get_or_init(|| {
+        load()
+            .inspect_err(
+                |e| warn!(?tokenizer, error = %e, "tokenizer failed to load; counting bytes"),
+            )
+            .ok()
+    })
+    .as_ref()
+}
+
+/// Tokens a chat request's messages take.
+pub fn count_messages(spec: &str, messages: &[serde_json::Value]) -> usize {
+    let tokenizer = tokenizer(spec);
+    messages
+        .iter()
+        .map(|m| MESSAGE_OVERHEAD + count_with(tokenizer, &message_text(m)))
+        .sum()
+}
+
+fn message_text(message: &serde_json::Value) -> String {
+    let mut text = message["content"].as_str().unwrap_or_default().to_string();
+    if let Some(calls) = message.get("tool_calls") {
+        text.push_str(&calls.to_string());
+    }
+    text
+}
+
+/// Shorten a chat request that would not fit the model's context window: the oldest tool
+/// outputs are replaced with a note until it fits, keeping the system prompt, the first user
+/// message, and the latest messages. `extra` is what the rest of the request (tool
+/// definitions) takes. Returns how many outputs were elided.
+pub fn fit(spec: &str, messages: &mut [serde_json::Value], extra: usize) -> usize {
+    let window = context_window(spec);
+    let limit = window - REPLY_RESERVE.min(window / 4);
+    let tokenizer = tokenizer(spec);
+    let mut total = extra + count_messages(spec, messages);
+    let mut elided = 0;
+    let end = messages.len().saturating_sub(KEEP_RECENT);
+    for message in messages.iter_mut().take(end).skip(2) {
+        if total <= limit {
+            break;
+        }
+        if message["role"] != "tool" || message["content"] == ELIDED {
+            continue;
+        }
+        let before = count_with(tokenizer, message["content"].as_str().unwrap_or_default());
+        message["content"] = ELIDED.into();
+        total = total.saturating_sub(before) + count_with(tokenizer, ELIDED);
+        elided += 1;
+    }
+    elided
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// gpt-4's 8K window leaves 6,144 tokens for the request.
+    const MODEL: &str = "gpt-4";
+
+    fn tool(words: usize) -> serde_json::Value {
+        json!({"role": "tool", "content": "word ".repeat(words)})
+    }
+
+    fn conversation(tools: usize, words: usize) -> Vec<serde_json::Value> {
+        let mut messages = vec![
+            json!({"role": "system", "content": "You are a coding agent."}),
+            json!({"role": "user", "content": "Fix the build."}),
+        ];
+        for _ in 0..tools {
+            messages.push(
+                json!({"role": "assistant", "content": "", "tool_calls": [{"id": "call_0"}]}),
+            );
+            messages.push(tool(words));
+        }
+        messages
+    }
+
+    #[test]
+    fn fit_leaves_a_request_that_fits() {
+        let mut messages = conversation(4, 100);
+        let before = messages.clone();
+        assert_eq!(fit(MODEL, &mut messages, 0), 0);
+        assert_eq!(messages, before);
+    }
+
+    #[test]
+    fn fit_elides_the_oldest_tool_outputs_first() {
+        let mut messages = conversation(6, 1_500);
+        let elided = fit(MODEL, &mut messages, 0);
+        assert!(elided > 0);
+        assert!(
+            count_messages(MODEL, &messages)
+                <= context_window(MODEL) - REPLY_RESERVE.min(context_window(MODEL) / 4)
+        );
+        let outputs: Vec<bool> = messages
+            .iter()
+            .filter(|m| m["role"] == "tool")
+            .map(|m| m["content"] == ELIDED)
+            .collect();
+        assert_eq!(outputs.iter().filter(|e| **e).count(), elided);
+        assert!(
+            outputs.windows(2).all(|w| w[0] || !w[1]),
+            "only the oldest are elided: {:?}",
+            outputs
+        );
+        assert_eq!(messages[1]["content"], "Fix the build.");
+    }
+
+    #[test]
+    fn fit_keeps_the_latest_messages() {
+        let mut messages = conversation(3, 3_000);
+        fit(MODEL, &mut messages, 0);
+        let recent = messages.len() - KEEP_RECENT;
+        assert!(messages[recent..].iter().all(|m| m["content"] != ELIDED));
+    }
+
+    #[test]
+    fn fit_counts_the_rest_of_the_request() {
+        let mut messages = conversation(4, 800);
+        assert_eq!(fit(MODEL, &mut messages.clone(), 0), 0);
+        assert!(fit(MODEL, &mut messages, 5_000) > 0);
+    }
+
+    #[test]
+    fn models_map_to_their_tokenizer_and_window() {
+        assert_eq!(lookup("gpt-4o-mini"), Tokenizer::O200k);
+        assert_eq!(lookup("openai:gpt-4-turbo"), Tokenizer::Cl100k);
+        assert_eq!(lookup("ollama:llama3"), Tokenizer::Bytes);
+        assert_eq!(context_window("gpt-4"), 8_192);
+        assert_eq!(context_window("gpt-4-turbo"), 128_000);
+        assert_eq!(context_window("ollama:llama3"), DEFAULT_WINDOW);
+        assert_eq!(count_with(Tokenizer::Bytes, "12345678"), 2);
+    }
+
+    #[test]
+    fn the_most_specific_configured_pattern_wins() {
+        let tokenizers = BTreeMap::from([
+            ("*".to_string(), Tokenizer::Bytes),
+            ("groq:*".to_string(), Tokenizer::Cl100k),
+            ("groq:llama*".to_string(), Tokenizer::O200k),
+        ]);
+        assert_eq!(
+            configured(&tokenizers, "groq:llama3-70b"),
+            Some(Tokenizer::O200k)
+        );
+        assert_eq!(
+            configured(&tokenizers, "groq:mixtral"),
+            Some(Tokenizer::Cl100k)
+        );
+        assert_eq!(configured(&tokenizers, "gpt-4o"), Some(Tokenizer::Bytes));
+        assert_eq!(configured(&BTreeMap::new(), "gpt-4o"), None);
+    }
+}
@@ -0,0 +1,520 @@
+use crate::agent::ToolCall;
+use crate::config;
+use crate::git;
+use crate::hooks;
+use crate::interrupt;
+use crate::memory;
+use crate::plugins;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// A file touched by a mutating tool, with its content at the baseline and now (None if absent).
+#[derive(Debug, Clone)]
+pub struct FileChange {
+    pub path: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// A `run_command` call and how it exited (None if it was interrupted).
+#[derive(Debug, Clone)]
+pub struct CommandRun {
+    pub command: String,
+    pub ok: Option<bool>,
+}
+
+/// Runs tool calls in a workspace. Clones share their state, so a clone can run a call on
+/// another thread (see `execute_blocking`).
+#[derive(Clone)]
+pub struct Executor {
+    workspace: std::path::PathBuf,
+    /// Content of each touched file as of session start or the last checkpoint.
+    baseline: Arc<Mutex<BTreeMap<String, Option<String>>>>,
+    /// Process groups of every command spawned this session (killed on shutdown).
+    process_groups: Arc<Mutex<Vec<u32>>>,
+    /// Every `run_command` call this session, in order.
+    commands: Arc<Mutex<Vec<CommandRun>>>,
+    /// File contents held in memory instead of on disk (see `in_memory`), by path.
+    overlay: Option<Arc<Mutex<BTreeMap<String, String>>>>,
+}
+
+impl Executor {
+    pub fn new(workspace: std::path::PathBuf) -> Self {
+        Self {
+            workspace,
+            baseline: Arc::default(),
+            process_groups: Arc::default(),
+            commands: Arc::default(),
+            overlay: None,
+        }
+    }
+
+    /// An executor that never writes to the workspace: `buffers` (e.g. unsaved editor buffers)
+    /// stand in for those files, writes only change the in-memory copy, and `changes` reports
+    /// edits against `buffers` or the files on disk. Commands and plugins are unavailable.
+    pub fn in_memory(workspace: std::path::PathBuf, buffers: BTreeMap<String, String>) -> Self {
+        Self {
+            baseline: Arc::new(Mutex::new(
+                buffers
+                    .iter()
+                    .map(|(p, t)| (p.clone(), Some(t.clone())))
+                    .collect(),
+            )),
+            overlay: Some(Arc::new(Mutex::new(buffers))),
+            ..Self::new(workspace)
+        }
+    }
+
+    /// Current content of a workspace file, as tools see it.
+    pub fn read(&self, path: &str) -> Result<String, String> {
+        if let Some(content) = self
+            .overlay
+            .as_ref()
+            .and_then(|o| o.lock().unwrap().get(path).cloned())
+        {
+            return Ok(content);
+        }
+        fs::read_to_string(self.workspace.join(path)).map_err(|e| e.to_string())
+    }
+
+    pub fn workspace(&self) -> &std::path::Path {
+        &self.workspace
+    }
+
+    /// Remember a file's current content before the first mutation since the last checkpoint.
+    pub(crate) fn track(&self, path: &str) {
+        self.track_as(path, || self.read(path).ok());
+    }
+
+    /// `track` with the content the file had before it was changed.
+    fn track_as(&self, path: &str, before: impl FnOnce() -> Option<String>) {
+        self.baseline
+            .lock()
+            .unwrap()
+            .entry(path.to_string())
+            .or_insert_with(before);
+    }
+
+    /// Files whose content differs from the baseline.
+    pub fn changes(&self) -> Vec<FileChange> {
+        self.baseline
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(path, before)| {
+                let after = self.read(path).ok();
+                (*before != after).then(|| FileChange {
+                    path: path.clone(),
+                    before: before.clone(),
+                    after,
+                })
+            })
+            .collect()
+    }
+
+    /// Content of each touched file at the baseline (None if it did not exist).
+    pub fn baseline(&self) -> BTreeMap<String, Option<String>> {
+        self.baseline.lock().unwrap().clone()
+    }
+
+    /// Pick up a baseline saved by an earlier process, keeping files tracked since.
+    pub fn restore_baseline(&self, baseline: BTreeMap<String, Option<String>>) {
+        let mut current = self.baseline.lock().unwrap();
+        for (path, before) in baseline {
+            current.insert(path, before);
+        }
+    }
+
+    /// Commands the model has run this session, in order.
+    pub fn commands(&self) -> Vec<CommandRun> {
+        self.commands.lock().unwrap().clone()
+    }
+
+    /// Make the current state the new baseline for `changes`.
+    pub fn checkpoint(&self) {
+        self.baseline.lock().unwrap().clear();
+    }
+
+    /// Remember a spawned command's process group, forgetting groups that have since emptied.
+    fn track_group(&self, pgid: u32) {
+        let mut groups = self.process_groups.lock().unwrap();
+        groups.retain(|&g| group_alive(g));
+        groups.push(pgid);
+    }
+
+    /// Forget a command's process group once it has been waited on, unless something it
+    /// started in the background (a dev server) is still running in it. An id is not reused
+    /// while its group has members, so a kept one still names that group.
+    fn untrack_group(&self, pgid: u32) {
+        if !group_alive(pgid) {
+            self.process_groups.lock().unwrap().retain(|&g| g != pgid);
+        }
+    }
+
+    /// Kill every process group spawned by `run_command`, including backgrounded servers.
+    pub fn kill_children(&self) {
+        for pgid in self.process_groups.lock().unwrap().drain(..) {
+            #[cfg(unix)]
+            unsafe {
+                libc::kill(-(pgid as i32), libc::SIGTERM);
+            }
+            #[cfg(not(unix))]
+            let _ = pgid;
+        }
+    }
+
+    pub fn execute(&self, tool_call: &ToolCall) -> Result<String, String> {
+        let started = Instant::now();
+        let result = match hooks::pre_tool(self, tool_call) {
+            Ok(()) => hooks::post_tool(self, tool_call, self.execute_inner(tool_call)),
+            Err(reason) => Err(reason),
+        };
+        info!(
+            tool = %tool_call.function.name,
+            duration_ms = started.elapsed().as_millis() as u64,
+            ok = result.is_ok(),
+            "tool executed"
+        );
+        if let Err(e) = &result {
+            debug!(tool = %tool_call.function.name, error = %e, "tool failed");
+        }
+        result
+    }
+
+    /// `execute` on a blocking thread, so a long command holds up neither the runtime nor
+    /// other conversations running at the same time (`--parallel`).
+    pub async fn execute_blocking(&self, tool_call: &ToolCall) -> Result<String, String> {
+        let executor = self.clone();
+        let call = tool_call.clone();
+        tokio::task::spawn_blocking(move || executor.execute(&call))
+            .await
+            .unwrap_or_else(|e| Err(format!("{} failed: {}", tool_call.function.name, e)))
+    }
+
+    fn execute_inner(&self, tool_call: &ToolCall) -> Result<String, String> {
+        let args: serde_json::Value =
+            serde_json::from_str(&tool_call.function.arguments).map_err(|e| e.to_string())?;
+
+        if let Some(overlay) = &self.overlay {
+            match tool_call.function.name.as_str() {
+                "create_file" | "write_file" => {
+                    let path = args["path"].as_str().ok_or("Missing path")?;
+                    let content = args["content"].as_str().ok_or("Missing content")?;
+                    self.track(path);
+                    overlay
+                        .lock()
+                        .unwrap()
+                        .insert(path.to_string(), content.to_string());
+                    return Ok(format!("Created {}", path));
+                }
+                "read_file" | "list_dir" | "save_memory" | "recall_memory" => {}
+                name => {
+                    return Err(format!(
+                        "{} is not available here: edits go to the editor, not to disk",
+                        name
+                    ))
+                }
+            }
+        }
+
+        match tool_call.function.name.as_str() {
+            "create_file" | "write_file" => {
+                let path = args["path"].as_str().ok_or("Missing path")?;
+                let content = args["content"].as_str().ok_or("Missing content")?;
+                let full_path = self.workspace.join(path);
+                if let Some(parent) = full_path.parent() {
+                    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                self.track(path);
+                let mut f = fs::File::create(&full_path).map_err(|e| e.to_string())?;
+                f.write_all(content.as_bytes()).map_err(|e| e.to_string())?;
+                Ok(format!("Created {}", path))
+            }
+            "read_file" => {
+                let path = args["path"].as_str().ok_or("Missing path")?;
+                self.read(path)
+            }
+            "list_dir" => {
+                let path = args["path"].as_str().unwrap_or(".");
+                let full_path = self.workspace.join(path);
+                let entries = fs::read_dir(&full_path).map_err(|e| e.to_string())?;
+                let mut names: Vec<String> = entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.file_name().to_string_lossy().to_string())
+                    .collect();
+                names.sort();
+                Ok(names.join("\n"))
+            }
+            "run_command" => {
+                let cmd = args["command"].as_str().ok_or("Missing command")?;
+                if let Some(allowed) = config::load().tools.allow_commands {
+                    if !command_allowed(cmd, &allowed) {
+                        return Err(format!(
+                            "`{}` is not in allow_commands ({})",
+                            cmd,
+                            allowed.join(", ")
+                        ));
+                    }
+                }
+                self.run_command(cmd)
+            }
+            "save_memory" => {
+                let key = args["key"].as_str().ok_or("Missing key")?;
+                let text = args["text"].as_str().ok_or("Missing text")?;
+                memory::save(&self.workspace, key, text)?;
+                Ok(format!("Saved memory: {}", key))
+            }
+            "recall_memory" => {
+                let query = args["query"].as_str().unwrap_or_default();
+                Ok(memory::recall_text(&self.workspace, query))
+            }
+            "create_directory" => {
+                let path = args["path"].as_str().ok_or("Missing path")?;
+                let full_path = self.workspace.join(path);
+                fs::create_dir_all(&full_path).map_err(|e| e.to_string())?;
+                Ok(format!("Created directory {}", path))
+            }
+            name => match plugins::find(name) {
+                Some(plugin) => plugin.run(self, &tool_call.function.arguments),
+                None => Err(format!("Unknown tool: {}", name)),
+            },
+        }
+    }
+
+    /// Run a shell command with no input, killing its process group if Ctrl-C is pressed
+    /// meanwhile. Returns its exit status (None if interrupted), stdout, and stderr.
+    pub fn run_shell(&self, cmd: &str) -> Result<(Option<ExitStatus>, String, String), String> {
+        let mut command = Command::new("sh");
+        command.args(["-c", cmd]);
+        self.run_process(command, None)
+    }
+
+    /// Run a program in the workspace like `run_shell`, writing `input` to its stdin.
+    pub fn run_process(
+        &self,
+        mut command: Command,
+        input: Option<&str>,
+    ) -> Result<(Option<ExitStatus>, String, String), String> {
+        let started = interrupt::generation();
+        command
+            .current_dir(&self.workspace)
+            // Never the terminal: the steering reader owns it while a run is going.
+            .stdin(if input.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let mut child = command.spawn().map_err(|e| e.to_string())?;
+        self.track_group(child.id());
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            // A program that exits without reading its input is not an error here.
+            let _ = stdin.write_all(input.as_bytes());
+        }
+        let stdout = read_pipe(child.stdout.take());
+        let stderr = read_pipe(child.stderr.take());
+
+        let status = loop {
+            if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+                break Some(status);
+            }
+            if interrupt::generation() != started {
+                kill_group(&mut child);
+                break None;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        self.untrack_group(child.id());
+
+        Ok((
+            status,
+            String::from_utf8_lossy(&stdout.join().unwrap_or_default()).to_string(),
+            String::from_utf8_lossy(&stderr.join().unwrap_or_default()).to_string(),
+        ))
+    }
+
+    fn run_command(&self, cmd: &str) -> Result<String, String> {
+        // Files the command changes (formatters, generators, `sed -i`) are found by comparing
+        // snapshots, so `changes` covers them too. Outside a git repository they are not seen.
+        let before = git::snapshot(&self.workspace).ok();
+        let (status, mut result, stderr) = self.run_shell(cmd)?;
+        if let Some(before) = before {
+            self.track_changed_since(&before);
+        }
+        self.commands.lock().unwrap().push(CommandRun {
+            command: cmd.to_string(),
+            ok: status.map(|s| s.success()),
+        });
+        if !stderr.is_empty() {
+            result.push_str(&format!("\nstderr: {}", stderr));
+        }
+        match status {
+            Some(status) if !status.success() => {
+                result.push_str(&format!("\nexit code: {}", status));
+            }
+            Some(_) => {}
+            None => result.push_str("\ninterrupted by user"),
+        }
+        Ok(result)
+    }
+
+    /// Track every file that differs from the snapshot `before`, with its content there.
+    fn track_changed_since(&self, before: &str) {
+        let changed = git::snapshot(&self.workspace)
+            .and_then(|after| git::changed_paths(&self.workspace, before, &after));
+        match changed {
+            Ok(paths) => {
+                for path in paths {
+                    self.track_as(&path, || git::file_in_tree(&self.workspace, before, &path));
+                }
+            }
+            Err(e) => debug!(error = %e, "could not find files changed by the command"),
+        }
+    }
+}
+
+/// Whether every command in a shell line (split at `;`, `&`, `&&`, `|`, `||`, and newlines)
+/// matches one of `patterns`. Substitutions (`$(...)`, backticks) are never allowed.
+fn command_allowed(line: &str, patterns: &[String]) -> bool {
+    if line.contains("$(") || line.contains('`') {
+        return false;
+    }
+    let bytes = line.as_bytes();
+    let mut commands = Vec::new();
+    let mut start = 0;
+    for (i, &b) in bytes.iter().enumerate() {
+        // `&` in a redirection (`2>&1`, `&>file`) does not end a command.
+        let redirect = b == b'&'
+            && (i > 0 && matches!(bytes[i - 1], b'>' | b'<') || bytes.get(i + 1) == Some(&b'>'));
+        if matches!(b, b'\n' | b';' | b'&' | b'|') && !redirect {
+            commands.push(&line[start..i]);
+            start = i + 1;
+        }
+    }
+    commands.push(&line[start..]);
+    commands
+        .into_iter()
+        .map(str::trim)
+        .filter(|cmd| !cmd.is_empty())
+        .all(|cmd| patterns.iter().any(|p| glob_match(cmd, p.trim())))
+}
+
+/// Whether `pattern` matches all of `text`, `*` matching any text.
+pub(crate) fn glob_match(text: &str, pattern: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, rest) = parts.split_first().expect("split yields at least one part");
+    let Some(mut remaining) = text.strip_prefix(first) else {
+        return false;
+    };
+    let Some((last, middle)) = rest.split_last() else {
+        return remaining.is_empty();
+    };
+    for part in middle {
+        match remaining.find(part) {
+            Some(at) => remaining = &remaining[at + part.len()..],
+            None => return false,
+        }
+    }
+    remaining.ends_with(last)
+}
+
+fn read_pipe<R: std::io::Read + Send + 'static>(
+    pipe: Option<R>,
+) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+/// Whether any process is left in the process group `pgid`.
+fn group_alive(pgid: u32) -> bool {
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(-(pgid as i32), 0) == 0
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pgid;
+        false
+    }
+}
+
+fn kill_group(child: &mut std::process::Child) {
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(-(child.id() as i32), libc::SIGKILL);
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(line: &str, patterns: &[&str]) -> bool {
+        command_allowed(
+            line,
+            &patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn glob_matches_whole_text() {
+        assert!(glob_match("cargo test", "cargo test"));
+        assert!(!glob_match("cargo test --all", "cargo test"));
+        assert!(glob_match("cargo test --all", "cargo *"));
+        assert!(glob_match("cargo", "cargo*"));
+        assert!(!glob_match("cargo", "cargo *"));
+        assert!(glob_match("git log --oneline", "git * --oneline"));
+        assert!(!glob_match("git log --stat", "git * --oneline"));
+        assert!(glob_match("anything", "*"));
+        assert!(glob_match("a-b-c", "a*b*c"));
+        assert!(!glob_match("a-c-b", "a*b*c"));
+    }
+
+    #[test]
+    fn every_command_in_a_line_must_match() {
+        let patterns = ["cargo *", "echo *"];
+        assert!(allowed("cargo build", &patterns));
+        assert!(allowed("cargo build && echo done", &patterns));
+        assert!(allowed("cargo build; echo done\necho again", &patterns));
+        assert!(!allowed("cargo build && rm -rf target", &patterns));
+        assert!(!allowed("cargo build | sh", &patterns));
+        assert!(!allowed("echo ok || curl example.com", &patterns));
+        assert!(!allowed("cargo build & rm x", &patterns));
+    }
+
+    #[test]
+    fn redirections_do_not_split_commands() {
+        let patterns = ["cargo *"];
+        assert!(allowed("cargo test 2>&1", &patterns));
+        assert!(allowed("cargo test &>log.txt", &patterns));
+        assert!(allowed("cargo test <&0", &patterns));
+    }
+
+    #[test]
+    fn substitutions_are_never_allowed() {
+        let patterns = ["echo *"];
+        assert!(!allowed("echo $(rm -rf /)", &patterns));
+        assert!(!allowed("echo `whoami`", &patterns));
+    }
+
+    #[test]
+    fn blank_commands_are_ignored() {
+        assert!(allowed("  cargo check ;  ", &["cargo check"]));
+        assert!(allowed("", &[]));
+    }
+}
@@ -0,0 +1,4 @@
+mod executor;
+
+pub(crate) use executor::glob_match;
+pub use executor::{CommandRun, Executor, FileChange};
@@ -0,0 +1,189 @@
+//! Diff rendering: unified (with intra-line highlights) or side-by-side, with unchanged
+//! stretches collapsed to a marker line.
+
+use super::style::Colorize;
+use super::{glyph, link, theme};
+use similar::{ChangeTag, DiffOp, TextDiff};
+
+pub struct DiffOptions {
+    /// Unchanged lines shown around each change.
+    pub context: usize,
+    pub side_by_side: bool,
+    /// Truncate the rendered diff to this many lines.
+    pub max_lines: Option<usize>,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            context: 3,
+            side_by_side: false,
+            max_lines: None,
+        }
+    }
+}
+
+/// Render the diff between two versions of `path` (None = file absent).
+pub fn render(path: &str, before: Option<&str>, after: Option<&str>, opts: &DiffOptions) -> String {
+    let linked = link::file(path, None);
+    let header = match (before, after) {
+        (None, _) => format!("+++ {} (new)", linked),
+        (_, None) => format!("--- {} (deleted)", linked),
+        _ => format!("~~~ {}", linked),
+    };
+    let diff = TextDiff::from_lines(before.unwrap_or(""), after.unwrap_or(""));
+    let mut lines = vec![header.bold().to_string()];
+    let groups = diff.grouped_ops(opts.context);
+    let mut prev_end = 0;
+    for group in &groups {
+        let start = group[0].old_range().start;
+        if start > prev_end {
+            lines.push(collapsed(start - prev_end));
+        }
+        prev_end = group[group.len() - 1].old_range().end;
+        if opts.side_by_side {
+            side_by_side(&diff, path, group, &mut lines);
+        } else {
+            unified(&diff, path, group, &mut lines);
+        }
+    }
+    let total_old = diff.old_slices().len();
+    if !groups.is_empty() && total_old > prev_end {
+        lines.push(collapsed(total_old - prev_end));
+    }
+    if let Some(max) = opts.max_lines {
+        if lines.len() > max {
+            let hidden = lines.len() - max;
+            lines.truncate(max);
+            lines.push(
+                format!("{} {} more lines (see /diff)", glyph("…", "..."), hidden)
+                    .color(theme().dim)
+                    .to_string(),
+            );
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Uncolored unified diff (for reports and files).
+pub fn plain(path: &str, before: Option<&str>, after: Option<&str>) -> String {
+    TextDiff::from_lines(before.unwrap_or(""), after.unwrap_or(""))
+        .unified_diff()
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string()
+}
+
+/// Lines (added, removed) between two versions.
+pub fn line_counts(before: Option<&str>, after: Option<&str>) -> (usize, usize) {
+    let diff = TextDiff::from_lines(before.unwrap_or(""), after.unwrap_or(""));
+    diff.iter_all_changes()
+        .fold((0, 0), |(add, del), c| match c.tag() {
+            ChangeTag::Insert => (add + 1, del),
+            ChangeTag::Delete => (add, del + 1),
+            ChangeTag::Equal => (add, del),
+        })
+}
+
+fn collapsed(n: usize) -> String {
+    format!(
+        "  {} {} unchanged line{}",
+        glyph("⋯", "..."),
+        n,
+        if n == 1 { "" } else { "s" }
+    )
+    .color(theme().dim)
+    .to_string()
+}
+
+/// `@@ -a,b +c,d @@` followed by a link to the hunk's first line in the new file.
+fn hunk_header(path: &str, group: &[DiffOp]) -> String {
+    let (first, last) = (&group[0], &group[group.len() - 1]);
+    let line = first.new_range().start + 1;
+    let range = format!(
+        "@@ -{},{} +{},{} @@",
+        first.old_range().start + 1,
+        last.old_range().end - first.old_range().start,
+        line,
+        last.new_range().end - first.new_range().start
+    );
+    format!(
+        "{} {}",
+        range.color(theme().diff_hunk),
+        link::file(path, Some(line)).color(theme().dim)
+    )
+}
+
+fn unified<'a>(
+    diff: &'a TextDiff<'a, 'a, 'a, str>,
+    path: &str,
+    group: &[DiffOp],
+    out: &mut Vec<String>,
+) {
+    out.push(hunk_header(path, group));
+    for op in group {
+        for change in diff.iter_inline_changes(op) {
+            let mut line = String::new();
+            let sign = match change.tag() {
+                ChangeTag::Delete => "-",
+                ChangeTag::Insert => "+",
+                ChangeTag::Equal => " ",
+            };
+            line.push_str(&paint(change.tag(), sign, false));
+            for (emphasized, value) in change.iter_strings_lossy() {
+                let value = value.trim_end_matches('\n');
+                line.push_str(&paint(change.tag(), value, emphasized));
+            }
+            out.push(line);
+        }
+    }
+}
+
+fn paint(tag: ChangeTag, s: &str, emphasized: bool) -> String {
+    match (tag, emphasized) {
+        (ChangeTag::Delete, false) => s.color(theme().diff_remove).to_string(),
+        (ChangeTag::Delete, true) => s.color(theme().diff_remove).bold().underline().to_string(),
+        (ChangeTag::Insert, false) => s.color(theme().diff_add).to_string(),
+        (ChangeTag::Insert, true) => s.color(theme().diff_add).bold().underline().to_string(),
+        (ChangeTag::Equal, _) => s.color(theme().dim).to_string(),
+    }
+}
+
+fn side_by_side<'a>(
+    diff: &TextDiff<'a, 'a, 'a, str>,
+    path: &str,
+    group: &[DiffOp],
+    out: &mut Vec<String>,
+) {
+    let col = super::term_width().saturating_sub(3) / 2;
+    let text = col.saturating_sub(6).max(10);
+    out.push(hunk_header(path, group));
+    let old = diff.old_slices();
+    let new = diff.new_slices();
+    for op in group {
+        let (old_range, new_range) = (op.old_range(), op.new_range());
+        let rows = old_range.len().max(new_range.len());
+        let changed = !matches!(op, DiffOp::Equal { .. });
+        for i in 0..rows {
+            let cell = |lines: &[&str], range: &std::ops::Range<usize>, tag: ChangeTag| {
+                let idx = range.start + i;
+                if i < range.len() {
+                    let line: String = lines[idx]
+                        .trim_end_matches('\n')
+                        .chars()
+                        .take(text)
+                        .collect();
+                    let padded = format!("{:>4} {:<width$}", idx + 1, line, width = text);
+                    paint(if changed { tag } else { ChangeTag::Equal }, &padded, false)
+                } else {
+                    " ".repeat(text + 5)
+                }
+            };
+            out.push(format!(
+                "{} {} {}",
+                cell(old, &old_range, ChangeTag::Delete),
+                glyph("│", "|"),
+                cell(new, &new_range, ChangeTag::Insert)
+            ));
+        }
+    }
+}
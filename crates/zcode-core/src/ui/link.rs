@@ -0,0 +1,33 @@
+//! OSC 8 terminal hyperlinks for file paths (file:// or an editor URL scheme).
+
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+static ROOT: OnceLock<PathBuf> = OnceLock::new();
+/// URL template with `{path}` and `{line}` placeholders, e.g. `vscode://file/{path}:{line}`.
+static EDITOR_URL: OnceLock<Option<String>> = OnceLock::new();
+
+/// Enable hyperlinks for paths relative to `workspace` (only when stdout is a terminal).
+pub fn init(workspace: &Path, editor_url: Option<String>) {
+    if std::io::stdout().is_terminal() && !super::is_plain() {
+        let _ = ROOT.set(workspace.to_path_buf());
+        let _ = EDITOR_URL.set(editor_url);
+    }
+}
+
+/// `path` wrapped in a hyperlink to the file (and line, if the URL scheme supports it).
+pub fn file(path: &str, line: Option<usize>) -> String {
+    let Some(root) = ROOT.get() else {
+        return path.to_string();
+    };
+    let abs = root.join(path);
+    let abs = abs.to_string_lossy();
+    let url = match EDITOR_URL.get().and_then(|u| u.as_deref()) {
+        Some(template) => template
+            .replace("{path}", &abs)
+            .replace("{line}", &line.unwrap_or(1).to_string()),
+        None => format!("file://{}", abs),
+    };
+    format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, path)
+}
@@ -0,0 +1,747 @@
+//! Terminal UI with colors for phases, tools, errors, and output.
+
+mod diff;
+pub mod link;
+mod prompt;
+mod style;
+mod terminal;
+mod theme;
+
+pub use diff::DiffOptions;
+pub use prompt::{confirm, fuzzy_pick, input, password, select};
+pub use terminal::Terminal;
+pub use theme::{current as theme, init as init_theme, Theme};
+
+use crate::tools::FileChange;
+use crate::usage::Usage;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use style::Colorize;
+
+/// Whether a frontend owns the terminal; embedders that never attach get no output.
+static ATTACHED: AtomicBool = AtomicBool::new(false);
+/// Whether stderr may be written, which a frontend speaking a protocol on stdout allows alone.
+static ATTACHED_ERR: AtomicBool = AtomicBool::new(false);
+
+/// Let the core draw progress, diffs, and prompts on stdout/stderr.
+pub fn attach_terminal() {
+    ATTACHED.store(true, Ordering::Relaxed);
+    ATTACHED_ERR.store(true, Ordering::Relaxed);
+}
+
+/// Let the core write errors to stderr but keep stdout to the frontend (`--stdio`).
+pub fn attach_stderr() {
+    ATTACHED_ERR.store(true, Ordering::Relaxed);
+}
+
+pub fn is_attached() -> bool {
+    ATTACHED.load(Ordering::Relaxed)
+}
+
+pub fn write_out(args: std::fmt::Arguments) {
+    if is_attached() {
+        let _ = std::io::Write::write_fmt(&mut std::io::stdout().lock(), args);
+    }
+}
+
+pub fn write_err(args: std::fmt::Arguments) {
+    if ATTACHED_ERR.load(Ordering::Relaxed) {
+        let _ = std::io::Write::write_fmt(&mut std::io::stderr().lock(), args);
+    }
+}
+
+/// Plain output: no colors, spinners, or unicode glyphs (for CI logs and pipes).
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// Enable plain output when asked to, when NO_COLOR is set, or when stdout is not a terminal.
+pub fn init_plain(no_color: bool) {
+    let no_color_env = std::env::var("NO_COLOR").is_ok_and(|v| !v.is_empty());
+    let plain = no_color || no_color_env || !std::io::stdout().is_terminal();
+    PLAIN.store(plain, Ordering::Relaxed);
+    if plain {
+        style::set_override(false);
+    }
+}
+
+pub fn is_plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+/// Only the final answer (printed by the caller) and errors: for `-q` in pipelines.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// `unicode` normally, `ascii` in plain mode.
+pub(crate) fn glyph(unicode: &'static str, ascii: &'static str) -> &'static str {
+    if is_plain() {
+        ascii
+    } else {
+        unicode
+    }
+}
+
+pub fn phase(label: &str) {
+    if is_quiet() {
+        return;
+    }
+    outln!(
+        "{}",
+        format!("{} {} ", glyph("▸", ">"), label)
+            .color(theme().accent)
+            .bold()
+    );
+}
+
+pub fn phase_done(label: &str) {
+    if is_quiet() {
+        return;
+    }
+    outln!(
+        "{}",
+        format!("  {} {} ", glyph("✓", "+"), label).color(theme().success)
+    );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TodoStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+/// Print the plan's todos with their current status.
+pub fn todo_list(todos: &[String], statuses: &[TodoStatus]) {
+    if is_quiet() {
+        return;
+    }
+    for (i, (todo, status)) in todos.iter().zip(statuses).enumerate() {
+        let line = format!("[{}/{}] {}", i + 1, todos.len(), todo);
+        let styled = match status {
+            TodoStatus::Pending => format!("{} {}", glyph("○", "-"), line).color(theme().dim),
+            TodoStatus::Running => format!("{} {}", glyph("●", ">"), line)
+                .color(theme().text)
+                .bold(),
+            TodoStatus::Done => format!("{} {}", glyph("✓", "+"), line).color(theme().success),
+            TodoStatus::Failed => format!("{} {}", glyph("✗", "x"), line).color(theme().error),
+        };
+        outln!("  {}", styled);
+    }
+}
+
+/// Mark a file read for context.
+pub fn reading_file_done(path: &str) {
+    if is_quiet() {
+        return;
+    }
+    outln!(
+        "{}",
+        format!("  {} {} ", glyph("✓", "+"), link::file(path, None)).color(theme().success)
+    );
+}
+
+/// Mark a file that could not be read.
+pub fn reading_file_failed(path: &str, error: &str) {
+    if is_quiet() {
+        return;
+    }
+    outln!(
+        "{}",
+        format!(
+            "  {} {}: {}",
+            glyph("✗", "x"),
+            link::file(path, None),
+            error
+        )
+        .color(theme().error)
+    );
+}
+
+pub fn tool_call(name: &str) {
+    if is_quiet() {
+        return;
+    }
+    outln!(
+        "{}",
+        format!("  {} {} ", glyph("→", "->"), name).color(theme().tool)
+    );
+}
+
+/// Show tool call with optional arguments preview (e.g. "run_command" with "cargo build"),
+/// wrapped under the tool name and capped at a few lines.
+pub fn tool_call_with_args(name: &str, args_preview: Option<&str>) {
+    if is_quiet() {
+        return;
+    }
+    if let Some(preview) = args_preview {
+        if let Some(path) = preview.strip_prefix("path: ") {
+            let line = format!(
+                "  {} {} path: {}",
+                glyph("→", "->"),
+                name,
+                link::file(path, None)
+            );
+            outln!("{}", line.color(theme().tool));
+            return;
+        }
+        let prefix = format!("  {} {} ", glyph("→", "->"), name);
+        for line in wrap_capped(preview, &prefix, 3) {
+            outln!("{}", line.color(theme().tool));
+        }
+    } else {
+        tool_call(name);
+    }
+}
+
+/// Wrap `text` to the terminal width with `prefix` on the first line and a matching hanging
+/// indent on the rest, keeping at most `max_lines` lines.
+fn wrap_capped(text: &str, prefix: &str, max_lines: usize) -> Vec<String> {
+    let indent = " ".repeat(textwrap::core::display_width(prefix));
+    let width = wrap_width().unwrap_or(usize::MAX);
+    let opts = textwrap::Options::new(width)
+        .initial_indent(prefix)
+        .subsequent_indent(&indent);
+    let mut lines: Vec<String> = textwrap::wrap(text, opts)
+        .into_iter()
+        .map(|l| l.into_owned())
+        .collect();
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        if let Some(last) = lines.last_mut() {
+            last.push_str(glyph(" …", " ..."));
+        }
+    }
+    lines
+}
+
+pub fn tool_running() {
+    if is_quiet() {
+        return;
+    }
+    out!(
+        "{}",
+        format!("    {} ", glyph("…", "...")).color(theme().dim)
+    );
+    let _ = std::io::Write::flush(&mut std::io::stdout());
+}
+
+pub fn tool_result(s: &str) {
+    if is_quiet() {
+        return;
+    }
+    // Keep result muted so assistant output stands out
+    let text = s.split_whitespace().collect::<Vec<_>>().join(" ");
+    for line in wrap_capped(&text, "    ", 4) {
+        outln!("{}", line.color(theme().dim));
+    }
+}
+
+pub fn tool_error(e: &str) {
+    if is_quiet() {
+        return;
+    }
+    eoutln!(
+        "{}",
+        format!("    {} {}", glyph("✗", "x"), e).color(theme().error)
+    );
+}
+
+/// File mirroring streamed assistant text (`--output`).
+static TEE: Mutex<Option<std::fs::File>> = Mutex::new(None);
+
+/// Mirror all streamed assistant text to `path` (truncated first).
+pub fn tee_to(path: &std::path::Path) -> Result<(), String> {
+    let file = std::fs::File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    *TEE.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+fn tee(text: &str) {
+    if let Some(file) = TEE.lock().unwrap().as_mut() {
+        let _ = std::io::Write::write_all(file, text.as_bytes());
+    }
+}
+
+/// Streaming word-wrap state for assistant text.
+struct WrapState {
+    col: usize,
+    /// Word being accumulated until whitespace arrives.
+    word: String,
+    /// Current line so far (to detect ``` fences, inside which lines are not wrapped).
+    line: String,
+    in_code: bool,
+}
+
+static WRAP: Mutex<WrapState> = Mutex::new(WrapState {
+    col: 0,
+    word: String::new(),
+    line: String::new(),
+    in_code: false,
+});
+
+impl WrapState {
+    fn flush_word(&mut self, out: &mut String, width: usize) {
+        if self.word.is_empty() {
+            return;
+        }
+        let w = textwrap::core::display_width(&self.word);
+        if self.col > 0 && self.col + w > width {
+            out.push('\n');
+            self.col = 0;
+        }
+        out.push_str(&self.word);
+        self.col += w;
+        self.word.clear();
+    }
+
+    /// Feed streamed text; returns what can be printed now.
+    fn push(&mut self, chunk: &str, width: usize) -> String {
+        let mut out = String::new();
+        for ch in chunk.chars() {
+            if ch == '\n' {
+                self.flush_word(&mut out, width);
+                out.push('\n');
+                if self.line.trim_start().starts_with("```") {
+                    self.in_code = !self.in_code;
+                }
+                self.line.clear();
+                self.col = 0;
+                continue;
+            }
+            self.line.push(ch);
+            if self.in_code {
+                out.push(ch);
+                self.col += 1;
+            } else if ch == ' ' {
+                self.flush_word(&mut out, width);
+                // Keep indentation, but don't start a wrapped continuation line with a space.
+                let continuation = self.col == 0 && !self.line.trim().is_empty();
+                if !continuation && self.col < width {
+                    out.push(' ');
+                    self.col += 1;
+                }
+            } else {
+                self.word.push(ch);
+            }
+        }
+        out
+    }
+
+    fn finish(&mut self, width: usize) -> String {
+        let mut out = String::new();
+        self.flush_word(&mut out, width);
+        self.col = 0;
+        self.line.clear();
+        self.in_code = false;
+        out
+    }
+}
+
+pub fn assistant_chunk(chunk: &str) {
+    tee(chunk);
+    if is_quiet() {
+        return;
+    }
+    let text = match wrap_width() {
+        Some(width) => WRAP.lock().unwrap().push(chunk, width),
+        None => chunk.to_string(),
+    };
+    out!("{}", text.color(theme().text));
+}
+
+pub fn assistant_line() {
+    tee("\n");
+    if is_quiet() {
+        return;
+    }
+    if let Some(width) = wrap_width() {
+        let rest = WRAP.lock().unwrap().finish(width);
+        out!("{}", rest.color(theme().text));
+    }
+    outln!();
+}
+
+const SPINNER_FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// Generation of the current thinking indicator and whether it is still shown.
+static THINKING: Mutex<(u64, bool)> = Mutex::new((0, false));
+
+/// Animate a thinking indicator with elapsed time and the count of streamed tokens (`tokens`)
+/// until the first streamed chunk or tool call (call before chat_stream, then clear_thinking).
+pub fn thinking(tokens: Arc<AtomicUsize>) {
+    if is_plain() || is_quiet() {
+        return;
+    }
+    let generation = {
+        let mut state = THINKING.lock().unwrap();
+        state.0 += 1;
+        state.1 = true;
+        state.0
+    };
+    let started = Instant::now();
+    tokio::spawn(async move {
+        let mut i = 0usize;
+        loop {
+            {
+                let state = THINKING.lock().unwrap();
+                if *state != (generation, true) {
+                    break;
+                }
+                let n = tokens.load(Ordering::Relaxed);
+                let counter = if n > 0 {
+                    format!(" · {} tokens", n)
+                } else {
+                    String::new()
+                };
+                let line = format!(
+                    "{} {}s{}",
+                    SPINNER_FRAMES[i],
+                    started.elapsed().as_secs(),
+                    counter
+                );
+                out!("\r  {} ", line.color(theme().dim));
+                let _ = std::io::Write::flush(&mut std::io::stdout());
+            }
+            i = (i + 1) % SPINNER_FRAMES.len();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    });
+}
+
+/// Clear the thinking indicator so streamed output starts clean.
+pub fn clear_thinking() {
+    if is_plain() {
+        return;
+    }
+    let mut state = THINKING.lock().unwrap();
+    if state.1 {
+        state.1 = false;
+        out!("\r\x1b[2K");
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+    }
+}
+
+/// Print an error, even when quiet; the core reports its errors with `events::error`.
+pub fn error_msg(e: &str) {
+    eoutln!("{}", format!("Error: {}", e).color(theme().error).bold());
+}
+
+/// Render a colored diff for one changed file.
+pub fn file_diff(change: &FileChange, opts: &DiffOptions) -> String {
+    diff::render(
+        &change.path,
+        change.before.as_deref(),
+        change.after.as_deref(),
+        opts,
+    )
+}
+
+/// Uncolored unified diff for one changed file.
+pub fn plain_diff(change: &FileChange) -> String {
+    diff::plain(
+        &change.path,
+        change.before.as_deref(),
+        change.after.as_deref(),
+    )
+}
+
+/// Lines (added, removed) between two versions of a file.
+pub fn line_counts(before: Option<&str>, after: Option<&str>) -> (usize, usize) {
+    diff::line_counts(before, after)
+}
+
+/// Show the diff produced by a write tool, capped so long rewrites stay readable.
+pub fn tool_diff(path: &str, before: Option<&str>, after: Option<&str>) {
+    if is_quiet() {
+        return;
+    }
+    let opts = DiffOptions {
+        max_lines: Some(40),
+        ..Default::default()
+    };
+    for line in diff::render(path, before, after, &opts).lines() {
+        outln!("    {}", line);
+    }
+}
+
+/// Consolidated list of created/modified/deleted files with +/- line counts.
+pub fn changes_summary(title: &str, changes: &[FileChange]) {
+    if is_quiet() || changes.is_empty() {
+        return;
+    }
+    outln!(
+        "{}",
+        format!("  {} ({} files)", title, changes.len()).color(theme().accent)
+    );
+    for c in changes {
+        let (kind, color) = match (&c.before, &c.after) {
+            (None, Some(_)) => ("created ", theme().diff_add),
+            (Some(_), None) => ("deleted ", theme().diff_remove),
+            _ => ("modified", theme().tool),
+        };
+        let (added, removed) = diff::line_counts(c.before.as_deref(), c.after.as_deref());
+        outln!(
+            "    {} {} {} {}",
+            kind.color(color),
+            link::file(&c.path, None),
+            format!("+{}", added).color(theme().diff_add),
+            format!("-{}", removed).color(theme().diff_remove)
+        );
+    }
+}
+
+/// Detected terminal width in columns (80 if unknown).
+pub fn term_width() -> usize {
+    wrap_width().unwrap_or(80)
+}
+
+/// Width to wrap output at; None when stdout is not a terminal (leave lines unwrapped).
+pub fn wrap_width() -> Option<usize> {
+    #[cfg(feature = "tui")]
+    return terminal_size::terminal_size_of(std::io::stdout()).map(|(w, _)| w.0 as usize);
+    #[cfg(not(feature = "tui"))]
+    None
+}
+
+/// Print text through `$PAGER` (default `less -FRX`) when stdout is a terminal.
+pub fn page(text: &str) {
+    if !is_attached() || !std::io::stdout().is_terminal() {
+        out!("{}", text);
+        return;
+    }
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less -FRX".into());
+    let child = std::process::Command::new("sh")
+        .args(["-c", &pager])
+        .stdin(std::process::Stdio::piped())
+        .spawn();
+    match child {
+        Ok(mut child) => {
+            if let Some(mut stdin) = child.stdin.take() {
+                let _ = std::io::Write::write_all(&mut stdin, text.as_bytes());
+            }
+            let _ = child.wait();
+        }
+        Err(_) => out!("{}", text),
+    }
+}
+
+/// Dim one-line summary of a model call's token usage and speed.
+pub fn usage_line(u: &Usage) {
+    if is_quiet() {
+        return;
+    }
+    let cached = if u.cached_tokens > 0 {
+        format!(" ({} cached)", u.cached_tokens)
+    } else {
+        String::new()
+    };
+    outln!(
+        "{}",
+        format!(
+            "    {} · {} in{} / {} out · {:.1}s · {:.0} tok/s",
+            u.model,
+            u.prompt_tokens,
+            cached,
+            u.completion_tokens,
+            u.latency.as_secs_f64(),
+            u.tokens_per_sec()
+        )
+        .color(theme().dim)
+    );
+}
+
+/// Session totals: tokens and estimated cost.
+pub fn cost_summary(calls: &[Usage]) {
+    if is_quiet() {
+        return;
+    }
+    if calls.is_empty() {
+        return;
+    }
+    let prompt: u64 = calls.iter().map(|u| u.prompt_tokens).sum();
+    let completion: u64 = calls.iter().map(|u| u.completion_tokens).sum();
+    outln!(
+        "{}",
+        format!(
+            "Cost: ${:.4} ({} calls, {} in / {} out tokens)",
+            crate::usage::total_cost(calls),
+            calls.len(),
+            prompt,
+            completion
+        )
+        .color(theme().dim)
+    );
+}
+
+/// Print rows under a header, padding each column to its widest cell. Columns after the
+/// first are right-aligned (they hold numbers).
+pub fn table(headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<&str>| -> String {
+        cells
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(i, (c, w))| {
+                if i == 0 {
+                    format!("{:<w$}", c, w = *w)
+                } else {
+                    format!("{:>w$}", c, w = *w)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    };
+    outln!("  {}", line(headers.to_vec()).color(theme().dim));
+    for row in rows {
+        outln!("  {}", line(row.iter().map(String::as_str).collect()));
+    }
+}
+
+/// A short closing remark under the last phase.
+pub fn note(msg: &str) {
+    if is_quiet() {
+        return;
+    }
+    outln!("  {}", msg);
+}
+
+pub fn interrupted() {
+    if is_quiet() {
+        return;
+    }
+    outln!(
+        "{}",
+        format!(
+            "\n  {} Interrupted (press Ctrl-C again to exit)",
+            glyph("⏹", "!")
+        )
+        .color(theme().tool)
+    );
+}
+
+pub fn resume_hint(session_id: &str) {
+    outln!(
+        "{}",
+        format!("Session saved. Resume with: zcode --resume {}", session_id).color(theme().dim)
+    );
+}
+
+/// One line of a `zcode doctor` report, with an indented fix when the check failed.
+pub fn check(passed: bool, label: &str, fix: Option<&str>) {
+    if passed {
+        outln!("  {} {}", glyph("✓", "ok").color(theme().success), label);
+    } else {
+        outln!("  {} {}", glyph("✗", "FAIL").color(theme().error), label);
+    }
+    if let Some(fix) = fix {
+        outln!("      {}", format!("fix: {}", fix).color(theme().dim));
+    }
+}
+
+pub fn info(msg: &str) {
+    if is_quiet() {
+        return;
+    }
+    outln!("{}", msg.color(theme().dim));
+}
+
+pub fn welcome() {
+    outln!(
+        "{}",
+        "zcode — multi-step coding agent (OpenAI). Type a prompt or Ctrl-D to exit."
+            .color(theme().dim)
+    );
+    outln!();
+}
+
+/// Generation of the current spinner and whether it is still shown.
+static SPINNER: Mutex<(u64, bool)> = Mutex::new((0, false));
+
+/// Show an animated spinner with `msg` and the elapsed seconds until `spinner_done`.
+pub fn spinner_start(msg: &str) {
+    if is_quiet() {
+        return;
+    }
+    if is_plain() {
+        outln!("  {} ...", msg);
+        return;
+    }
+    let generation = {
+        let mut state = SPINNER.lock().unwrap();
+        state.0 += 1;
+        state.1 = true;
+        state.0
+    };
+    let started = Instant::now();
+    let msg = msg.to_string();
+    tokio::spawn(async move {
+        let mut i = 0usize;
+        loop {
+            tokio::time::sleep(Duration::from_millis(80)).await;
+            let state = SPINNER.lock().unwrap();
+            if *state != (generation, true) {
+                break;
+            }
+            let elapsed = format!("{}s", started.elapsed().as_secs());
+            out!(
+                "\r  {} {} {} ",
+                SPINNER_FRAMES[i],
+                msg,
+                elapsed.color(theme().dim)
+            );
+            let _ = std::io::Write::flush(&mut std::io::stdout());
+            i = (i + 1) % SPINNER_FRAMES.len();
+        }
+    });
+}
+
+/// Replace the spinner with a checkmark, `msg`, and the total time.
+pub fn spinner_done(msg: &str, elapsed: Duration) {
+    if is_quiet() {
+        return;
+    }
+    let elapsed = format!("({:.1}s)", elapsed.as_secs_f64());
+    if is_plain() {
+        outln!("  + {} {}", msg, elapsed);
+        return;
+    }
+    let mut state = SPINNER.lock().unwrap();
+    state.1 = false;
+    out!(
+        "\r\x1b[2K  {} {} {}\n",
+        glyph("✓", "+"),
+        msg,
+        elapsed.color(theme().dim)
+    );
+    let _ = std::io::Write::flush(&mut std::io::stdout());
+}
+
+/// Run a future while showing an animated spinner, message, and elapsed seconds. When the
+/// future completes, the spinner is replaced with a checkmark and the total time.
+pub async fn with_spinner<F, T>(msg: &str, future: F) -> T
+where
+    F: Future<Output = T>,
+{
+    let started = Instant::now();
+    spinner_start(msg);
+    let result = future.await;
+    spinner_done(msg, started.elapsed());
+    result
+}
@@ -0,0 +1,88 @@
+//! Questions asked on the terminal. Without the `tui` feature there is no terminal to ask on,
+//! and each returns an error instead.
+
+#[cfg(feature = "tui")]
+use dialoguer::theme::ColorfulTheme;
+#[cfg(feature = "tui")]
+use dialoguer::{Confirm, FuzzySelect, Input, Password, Select};
+
+#[cfg(not(feature = "tui"))]
+const NO_TERMINAL: &str = "Cannot ask: zcode-core was built without the `tui` feature";
+
+/// Yes or no, `default` on Enter.
+pub fn confirm(prompt: &str, default: bool) -> Result<bool, String> {
+    #[cfg(feature = "tui")]
+    return Confirm::new()
+        .with_prompt(prompt)
+        .default(default)
+        .interact()
+        .map_err(|e| e.to_string());
+    #[cfg(not(feature = "tui"))]
+    {
+        let _ = (prompt, default);
+        Err(NO_TERMINAL.into())
+    }
+}
+
+/// The index of one of `items`, `default` preselected.
+pub fn select<T: ToString>(prompt: &str, items: &[T], default: usize) -> Result<usize, String> {
+    #[cfg(feature = "tui")]
+    return Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .items(items)
+        .default(default)
+        .interact()
+        .map_err(|e| e.to_string());
+    #[cfg(not(feature = "tui"))]
+    {
+        let _ = (prompt, items.len(), default);
+        Err(NO_TERMINAL.into())
+    }
+}
+
+/// A line of text, starting from `initial`, trimmed.
+pub fn input(prompt: &str, initial: &str) -> Result<String, String> {
+    #[cfg(feature = "tui")]
+    return Input::<String>::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .with_initial_text(initial)
+        .interact_text()
+        .map(|s| s.trim().to_string())
+        .map_err(|e| e.to_string());
+    #[cfg(not(feature = "tui"))]
+    {
+        let _ = (prompt, initial);
+        Err(NO_TERMINAL.into())
+    }
+}
+
+/// A secret, not echoed.
+pub fn password(prompt: &str) -> Result<String, String> {
+    #[cfg(feature = "tui")]
+    return Password::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .interact()
+        .map_err(|e| e.to_string());
+    #[cfg(not(feature = "tui"))]
+    {
+        let _ = prompt;
+        Err(NO_TERMINAL.into())
+    }
+}
+
+/// Inline fuzzy finder over `items`; returns the chosen index (None if cancelled).
+pub fn fuzzy_pick(prompt: &str, items: &[String]) -> Option<usize> {
+    #[cfg(feature = "tui")]
+    return FuzzySelect::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .items(items)
+        .max_length(12)
+        .interact_opt()
+        .ok()
+        .flatten();
+    #[cfg(not(feature = "tui"))]
+    {
+        let _ = (prompt, items);
+        None
+    }
+}
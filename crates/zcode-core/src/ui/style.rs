@@ -0,0 +1,69 @@
+//! Text colors: `colored` with the `tui` feature, else the same calls returning plain text, so
+//! builds that embed the core without a terminal don't pull in terminal crates.
+
+#[cfg(feature = "tui")]
+pub use colored::control::set_override;
+#[cfg(feature = "tui")]
+pub use colored::{Color, Colorize};
+
+#[cfg(not(feature = "tui"))]
+pub use plain::*;
+
+#[cfg(not(feature = "tui"))]
+mod plain {
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    /// The theme colors; ignored when printing.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Color {
+        Black,
+        Red,
+        Green,
+        Yellow,
+        Blue,
+        Magenta,
+        Cyan,
+        White,
+        BrightBlack,
+        BrightRed,
+        BrightGreen,
+        BrightYellow,
+        BrightBlue,
+        BrightMagenta,
+        BrightCyan,
+        BrightWhite,
+    }
+
+    impl FromStr for Color {
+        type Err = ();
+
+        /// Any name is accepted, since no color is shown.
+        fn from_str(_: &str) -> Result<Self, ()> {
+            Ok(Color::White)
+        }
+    }
+
+    pub trait Colorize: Display {
+        fn color(&self, _: Color) -> String {
+            self.to_string()
+        }
+
+        fn bold(&self) -> String {
+            self.to_string()
+        }
+
+        fn underline(&self) -> String {
+            self.to_string()
+        }
+
+        fn clear(&self) -> String {
+            self.to_string()
+        }
+    }
+
+    impl Colorize for str {}
+    impl Colorize for String {}
+
+    pub fn set_override(_: bool) {}
+}
@@ -0,0 +1,111 @@
+//! The terminal front end for the core's events.
+
+use crate::events::{self, Event, Observer};
+use crate::options;
+use std::sync::Arc;
+
+/// Renders events in the terminal; each renderer is silent when the UI is quiet, and only
+/// errors are shown from hidden work.
+pub struct Terminal;
+
+impl Observer for Terminal {
+    fn on_event(&self, event: &Event) {
+        if let Event::Error { message } = event {
+            return super::error_msg(message);
+        }
+        if events::is_hidden() {
+            return;
+        }
+        match event {
+            Event::PhaseChanged { phase, done: false } => super::phase(phase),
+            Event::PhaseChanged { phase, done: true } => super::phase_done(phase),
+            Event::AssistantDelta { text } => {
+                super::clear_thinking();
+                super::assistant_chunk(text);
+                let _ = std::io::Write::flush(&mut std::io::stdout());
+            }
+            Event::ToolStarted {
+                name, arguments, ..
+            } => super::tool_call_with_args(name, args_preview(arguments, name).as_deref()),
+            Event::ToolFinished {
+                ok: true,
+                change: Some(change),
+                ..
+            } => super::tool_diff(
+                &change.path,
+                change.before.as_deref(),
+                change.after.as_deref(),
+            ),
+            Event::ToolFinished {
+                ok: true, output, ..
+            } => super::tool_result(output),
+            Event::ToolFinished {
+                ok: false, output, ..
+            } => super::tool_error(output),
+            Event::Steering { text } => super::note(&format!("Passing on your guidance: {}", text)),
+            Event::Note { text } => super::note(text),
+            Event::Todos { todos, statuses } => super::todo_list(todos, statuses),
+            Event::Progress {
+                label,
+                elapsed: None,
+            } => super::spinner_start(label),
+            Event::Progress {
+                label,
+                elapsed: Some(elapsed),
+            } => super::spinner_done(label, *elapsed),
+            Event::Thinking { tokens } => super::thinking(Arc::clone(tokens)),
+            Event::TurnEnded { text, usage } => {
+                super::clear_thinking();
+                if *text {
+                    super::assistant_line();
+                }
+                if let Some(usage) = usage.filter(|_| options::get().show_usage) {
+                    super::usage_line(usage);
+                }
+            }
+            Event::Interrupted => super::interrupted(),
+            Event::FileRead { path, error: None } => super::reading_file_done(path),
+            Event::FileRead {
+                path,
+                error: Some(error),
+            } => super::reading_file_failed(path, error),
+            Event::Changes { title, changes } => super::changes_summary(title, changes),
+            Event::Report { report } => report.print(),
+            _ => {}
+        }
+    }
+}
+
+/// Produce a short preview of tool arguments for the UI (e.g. "path: src/..." or "command: cargo build").
+fn args_preview(args_json: &str, tool_name: &str) -> Option<String> {
+    let v: serde_json::Value = serde_json::from_str(args_json).ok()?;
+    let obj = v.as_object()?;
+    let preview = match tool_name {
+        "run_command" => obj
+            .get("command")
+            .and_then(|c| c.as_str())
+            .map(|s| s.to_string()),
+        "read_file" | "write_file" | "create_file" => obj
+            .get("path")
+            .and_then(|p| p.as_str())
+            .map(|s| format!("path: {}", s)),
+        "spawn_subagent" => obj
+            .get("task")
+            .and_then(|t| t.as_str())
+            .map(|s| s.to_string()),
+        "save_memory" => obj
+            .get("key")
+            .and_then(|k| k.as_str())
+            .map(|s| format!("key: {}", s)),
+        "recall_memory" => obj
+            .get("query")
+            .and_then(|q| q.as_str())
+            .map(|s| format!("query: {}", s)),
+        "list_dir" => obj
+            .get("path")
+            .and_then(|p| p.as_str())
+            .map(|s| format!("path: {}", s)),
+        _ => None,
+    };
+    preview
+}
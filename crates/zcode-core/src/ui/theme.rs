@@ -0,0 +1,101 @@
+//! Color theme for terminal output, selected with `theme = "dark" | "light"` under `[ui]` in
+//! config and tweakable per color (e.g. `accent = "magenta"` under `[ui.colors]`).
+
+use super::style::Color;
+use crate::config;
+use std::sync::OnceLock;
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// Phase headers and the prompt.
+    pub accent: Color,
+    pub success: Color,
+    /// Tool calls and warnings.
+    pub tool: Color,
+    pub error: Color,
+    /// Assistant text and plan steps.
+    pub text: Color,
+    /// Muted output: tool results, hints, unchanged diff lines.
+    pub dim: Color,
+    pub diff_add: Color,
+    pub diff_remove: Color,
+    pub diff_hunk: Color,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            accent: Color::BrightCyan,
+            success: Color::Green,
+            tool: Color::Yellow,
+            error: Color::Red,
+            text: Color::BrightWhite,
+            dim: Color::BrightBlack,
+            diff_add: Color::Green,
+            diff_remove: Color::Red,
+            diff_hunk: Color::Cyan,
+        }
+    }
+
+    /// For light backgrounds, where bright_white/yellow text is unreadable.
+    pub fn light() -> Self {
+        Self {
+            accent: Color::Blue,
+            success: Color::Green,
+            tool: Color::Magenta,
+            error: Color::Red,
+            text: Color::Black,
+            dim: Color::BrightBlack,
+            diff_add: Color::Green,
+            diff_remove: Color::Red,
+            diff_hunk: Color::Blue,
+        }
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            _ => None,
+        }
+    }
+
+    /// Theme from config: the named preset with any `[ui.colors]` overrides applied.
+    pub fn from_config() -> Self {
+        let ui = config::load().ui;
+        let mut theme = ui
+            .theme
+            .as_deref()
+            .and_then(Self::preset)
+            .unwrap_or_else(Self::dark);
+        let colors = ui.colors;
+        let slots: [(Option<String>, &mut Color); 9] = [
+            (colors.accent, &mut theme.accent),
+            (colors.success, &mut theme.success),
+            (colors.tool, &mut theme.tool),
+            (colors.error, &mut theme.error),
+            (colors.text, &mut theme.text),
+            (colors.dim, &mut theme.dim),
+            (colors.diff_add, &mut theme.diff_add),
+            (colors.diff_remove, &mut theme.diff_remove),
+            (colors.diff_hunk, &mut theme.diff_hunk),
+        ];
+        for (color, slot) in slots {
+            if let Some(color) = color.and_then(|c| c.parse().ok()) {
+                *slot = color;
+            }
+        }
+        theme
+    }
+}
+
+/// Set the theme used by all ui output (call once at startup).
+pub fn init(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+pub fn current() -> &'static Theme {
+    THEME.get_or_init(Theme::dark)
+}
@@ -0,0 +1,191 @@
+//! `zcode self-update`: install the latest GitHub release over the running binary, once its
+//! checksums verify against the pinned release key.
+
+use crate::http;
+use crate::ui;
+use minisign_verify::{PublicKey, Signature};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::time::Duration;
+
+const RELEASES_URL: &str = "https://api.github.com/repos/Shavkatjon-O/zcode/releases/latest";
+/// Release file listing `<sha256>  <asset name>` for every binary.
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+/// Detached minisign signature of `CHECKSUMS_ASSET` (`minisign -Sm SHA256SUMS`).
+const SIGNATURE_ASSET: &str = "SHA256SUMS.minisig";
+/// Minisign public key the release checksums are signed with. A release whose checksums it
+/// did not sign is not installed, whatever the GitHub release holds.
+const RELEASE_KEY: &str = "RWSra+YTMSp9FgsmXCUCQWsc2ehEQxPo5xC/A42cyMFQubTeFtz2b8b8";
+
+/// Release binary for this platform, e.g. `zcode-x86_64-linux`.
+fn asset_name() -> String {
+    let ext = if cfg!(windows) { ".exe" } else { "" };
+    format!(
+        "zcode-{}-{}{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        ext
+    )
+}
+
+/// `v1.2.3` or `1.2.3` as comparable numbers; pre-release suffixes are ignored.
+fn parse_version(v: &str) -> Vec<u64> {
+    v.trim_start_matches('v')
+        .split(['.', '-', '+'])
+        .map_while(|p| p.parse().ok())
+        .collect()
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    http::builder()
+        .timeout(Duration::from_secs(60))
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Check that `signature` (a `.minisig` file) signs `sums` with `key`.
+fn verify_signature(key: &str, sums: &[u8], signature: &str) -> Result<(), String> {
+    let key = PublicKey::from_base64(key).map_err(|e| format!("Release key: {}", e))?;
+    let signature =
+        Signature::decode(signature).map_err(|e| format!("{}: {}", SIGNATURE_ASSET, e))?;
+    key.verify(sums, &signature, false).map_err(|e| {
+        format!(
+            "{} is not signed by the release key ({}); not installed",
+            CHECKSUMS_ASSET, e
+        )
+    })
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, String> {
+    let res = client.get(url).send().await.map_err(|e| e.to_string())?;
+    if !res.status().is_success() {
+        return Err(format!("{}: {}", url, res.status()));
+    }
+    res.bytes()
+        .await
+        .map(|b| b.to_vec())
+        .map_err(|e| e.to_string())
+}
+
+/// Check for a newer release and, unless `check_only`, install it.
+pub async fn run(check_only: bool) -> Result<(), String> {
+    let client = client()?;
+    let res = client
+        .get(RELEASES_URL)
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await
+        .map_err(|e| format!("Cannot reach GitHub: {}", e))?;
+    if !res.status().is_success() {
+        return Err(format!("GitHub releases: {}", res.status()));
+    }
+    let release: serde_json::Value = res.json().await.map_err(|e| e.to_string())?;
+    let tag = release["tag_name"].as_str().ok_or("Release has no tag")?;
+    let current = env!("CARGO_PKG_VERSION");
+    if parse_version(tag) <= parse_version(current) {
+        ui::info(&format!(
+            "zcode {} is up to date (latest release: {}).",
+            current, tag
+        ));
+        return Ok(());
+    }
+    ui::info(&format!("Update available: {} -> {}", current, tag));
+    if check_only {
+        return Ok(());
+    }
+
+    let assets = release["assets"].as_array().cloned().unwrap_or_default();
+    let url_of = |name: &str| {
+        assets
+            .iter()
+            .find(|a| a["name"].as_str() == Some(name))
+            .and_then(|a| a["browser_download_url"].as_str())
+            .map(String::from)
+    };
+    let name = asset_name();
+    let binary_url = url_of(&name)
+        .ok_or_else(|| format!("Release {} has no build for this platform ({})", tag, name))?;
+    let sums_url = url_of(CHECKSUMS_ASSET).ok_or_else(|| {
+        format!(
+            "Release {} has no {}; refusing to install an unverified binary",
+            tag, CHECKSUMS_ASSET
+        )
+    })?;
+
+    let signature_url = url_of(SIGNATURE_ASSET).ok_or_else(|| {
+        format!(
+            "Release {} has no {}; refusing to install an unverified binary",
+            tag, SIGNATURE_ASSET
+        )
+    })?;
+
+    let sums = download(&client, &sums_url).await?;
+    let signature = String::from_utf8(download(&client, &signature_url).await?)
+        .map_err(|e| format!("{}: {}", SIGNATURE_ASSET, e))?;
+    verify_signature(RELEASE_KEY, &sums, &signature)?;
+    let sums = String::from_utf8(sums).map_err(|e| e.to_string())?;
+    let expected = sums
+        .lines()
+        .filter_map(|l| l.split_once(char::is_whitespace))
+        .find(|(_, file)| file.trim().trim_start_matches('*') == name)
+        .map(|(sum, _)| sum.to_lowercase())
+        .ok_or_else(|| format!("{} has no entry for {}", CHECKSUMS_ASSET, name))?;
+    let binary = ui::with_spinner(
+        &format!("Downloading {}", name),
+        download(&client, &binary_url),
+    )
+    .await?;
+    let actual: String = Sha256::digest(&binary)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if actual != expected {
+        return Err(format!(
+            "Checksum mismatch for {} (expected {}, got {}); not installed",
+            name, expected, actual
+        ));
+    }
+
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let staged = exe.with_extension("new");
+    fs::write(&staged, &binary).map_err(|e| format!("{}: {}", staged.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))
+            .map_err(|e| e.to_string())?;
+    }
+    // Renaming over the running binary is atomic on Unix; Windows needs the old one moved aside.
+    #[cfg(windows)]
+    fs::rename(&exe, exe.with_extension("old")).map_err(|e| e.to_string())?;
+    fs::rename(&staged, &exe).map_err(|e| format!("Cannot replace {}: {}", exe.display(), e))?;
+    ui::info(&format!("Updated to {} ({})", tag, exe.display()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "RWQZA9wV9synGylJqzN6JbRxtwpoex7A3jnIGWrbbN0qBlMkHGzan6WM";
+    const SUMS: &str =
+        "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef  zcode-x86_64-linux\n";
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQZA9wV9synGxIO7KmGafXHICVWxQNDHI61p4Rwyxsu3nw/gVfeBiLgwZ1L9UWdV1g5aJY6P+IkoC3oAsFQyL7w2bIbqOpdEwI=
+trusted comment: timestamp:1760000000\tfile:SHA256SUMS\thashed
+KHROJPaSMYzCRyYLrgEu4n8jWAZfcxLDk62OY8FdcJCCJ74hzEB4Dx3sqUQVdV92q3F060Kdf+n6ctwwoky0Aw==
+";
+
+    #[test]
+    fn signed_checksums_verify() {
+        verify_signature(KEY, SUMS.as_bytes(), SIGNATURE).unwrap();
+    }
+
+    #[test]
+    fn tampered_checksums_or_another_key_are_refused() {
+        let tampered = SUMS.replace("0123", "fedc");
+        assert!(verify_signature(KEY, tampered.as_bytes(), SIGNATURE).is_err());
+        assert!(verify_signature(RELEASE_KEY, SUMS.as_bytes(), SIGNATURE).is_err());
+        assert!(verify_signature(KEY, SUMS.as_bytes(), "not a signature").is_err());
+    }
+}
@@ -0,0 +1,134 @@
+//! `zcode upgrade-dep <crate> <version>`: bump a dependency in Cargo.toml, fix the
+//! breakage, and summarize the API changes that were adapted to.
+
+use crate::agent;
+use crate::context;
+use crate::fix;
+use crate::interrupt;
+use crate::run;
+use crate::tools::Executor;
+use crate::ui;
+use std::fs;
+use toml_edit::DocumentMut;
+
+const MANIFEST: &str = "Cargo.toml";
+const DEPENDENCY_TABLES: &[&str] = &["dependencies", "dev-dependencies", "build-dependencies"];
+/// The diff beyond this many bytes is cut before it goes into the summary prompt.
+const DIFF_CAP: usize = 24 * 1024;
+
+const SUMMARY_SYSTEM: &str = "You review a dependency upgrade. Given the diff of the code changes made to compile against the new version, list the API changes that were adapted to as short markdown bullets (old API -> new API, and where). Mention anything that may behave differently at runtime. No preamble.";
+
+/// Paths of every dependency table: `[dependencies]` and friends, including
+/// `[target.'cfg(...)'.dependencies]`.
+fn dependency_tables(doc: &DocumentMut) -> Vec<Vec<String>> {
+    let mut paths: Vec<Vec<String>> = DEPENDENCY_TABLES
+        .iter()
+        .filter(|name| doc.contains_key(name))
+        .map(|name| vec![name.to_string()])
+        .collect();
+    if let Some(targets) = doc.get("target").and_then(|t| t.as_table_like()) {
+        for (cfg, item) in targets.iter() {
+            let Some(cfg_table) = item.as_table_like() else {
+                continue;
+            };
+            for name in DEPENDENCY_TABLES {
+                if cfg_table.contains_key(name) {
+                    paths.push(vec!["target".into(), cfg.to_string(), name.to_string()]);
+                }
+            }
+        }
+    }
+    paths
+}
+
+/// Set the version requirement of `krate` wherever it is declared. Returns the old requirement.
+fn bump(doc: &mut DocumentMut, krate: &str, version: &str) -> Option<String> {
+    let mut old = None;
+    let mut found = false;
+    for path in dependency_tables(doc) {
+        let mut item = doc.as_item_mut();
+        for key in &path {
+            item = &mut item[key.as_str()];
+        }
+        let Some(dep) = item.get_mut(krate) else {
+            continue;
+        };
+        if let Some(v) = dep.as_str() {
+            old.get_or_insert(v.to_string());
+            *dep = toml_edit::value(version);
+            found = true;
+        } else if let Some(spec) = dep.as_table_like_mut() {
+            if let Some(v) = spec.get("version").and_then(|v| v.as_str()) {
+                old.get_or_insert(v.to_string());
+            }
+            spec.insert("version", toml_edit::value(version));
+            found = true;
+        }
+    }
+    found.then(|| old.unwrap_or_else(|| "(none)".into()))
+}
+
+/// Bump `krate` to `version`, fix what breaks, and print a summary of the adaptations.
+/// Returns whether the check command passes at the end.
+pub async fn run(
+    api_key: &str,
+    executor: &Executor,
+    krate: &str,
+    version: &str,
+    command: &str,
+    max_iterations: usize,
+) -> Result<bool, String> {
+    let path = executor.workspace().join(MANIFEST);
+    let content = fs::read_to_string(&path).map_err(|e| format!("{}: {}", MANIFEST, e))?;
+    let mut doc: DocumentMut = content
+        .parse()
+        .map_err(|e| format!("{}: {}", MANIFEST, e))?;
+    let old = bump(&mut doc, krate, version)
+        .ok_or_else(|| format!("{} is not a dependency in {}", krate, MANIFEST))?;
+    executor.track(MANIFEST);
+    fs::write(&path, doc.to_string()).map_err(|e| format!("{}: {}", MANIFEST, e))?;
+    ui::phase_done(&format!("{} {} -> {}", krate, old, version));
+
+    let background = format!(
+        "The dependency `{}` was just upgraded from {} to {} in {}. The errors below come from API changes in that crate: adapt the code to the new API (check its changelog or docs if needed) instead of pinning the old version.",
+        krate, old, version, MANIFEST
+    );
+    let passed = fix::run(
+        api_key,
+        executor,
+        command,
+        None,
+        Some(&background),
+        max_iterations,
+    )
+    .await?;
+
+    let diff: String = executor
+        .changes()
+        .iter()
+        .filter(|c| !c.path.starts_with("Cargo."))
+        .map(ui::plain_diff)
+        .collect::<Vec<_>>()
+        .join("\n");
+    if diff.is_empty() {
+        ui::info("No code changes were needed.");
+        return Ok(passed);
+    }
+    let agent = agent::from_spec(&run::executor_model(), api_key)?;
+    let user = format!(
+        "Upgrade: {} {} -> {}\n\nDiff:\n{}",
+        krate,
+        old,
+        version,
+        context::truncate(&diff, DIFF_CAP)
+    );
+    let summarizing = ui::with_spinner(
+        "Summarizing API changes",
+        agent.completion(SUMMARY_SYSTEM, &user),
+    );
+    if let Some(Ok(summary)) = interrupt::cancellable(summarizing).await {
+        ui::phase_done("API changes adapted to");
+        outln!("{}", summary.trim());
+    }
+    Ok(passed)
+}
@@ -0,0 +1,277 @@
+//! Token usage reported by the provider for each model call, collected for the process, and
+//! cost estimates from a per-model price table.
+
+use crate::config;
+use crate::events;
+use crate::session;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+static LEDGER: Mutex<Vec<Usage>> = Mutex::new(Vec::new());
+/// Tool calls since the last `log_run`, by tool name.
+static TOOL_CALLS: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Usage {
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Prompt tokens served from the provider's prompt cache.
+    pub cached_tokens: u64,
+    pub latency: Duration,
+}
+
+impl Usage {
+    /// Parse an OpenAI-style `usage` object.
+    pub fn from_json(model: &str, v: &serde_json::Value, latency: Duration) -> Self {
+        Self {
+            model: model.to_string(),
+            prompt_tokens: v["prompt_tokens"].as_u64().unwrap_or(0),
+            completion_tokens: v["completion_tokens"].as_u64().unwrap_or(0),
+            cached_tokens: v["prompt_tokens_details"]["cached_tokens"]
+                .as_u64()
+                .unwrap_or(0),
+            latency,
+        }
+    }
+
+    pub fn tokens_per_sec(&self) -> f64 {
+        let secs = self.latency.as_secs_f64();
+        if secs > 0.0 {
+            self.completion_tokens as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// USD per 1M tokens: (input, cached input, output).
+#[derive(Debug, Clone, Copy)]
+pub struct Price {
+    pub input: f64,
+    pub cached_input: f64,
+    pub output: f64,
+}
+
+/// Built-in prices; longer prefixes win (so `gpt-4o-mini` is not priced as `gpt-4o`).
+const PRICES: &[(&str, Price)] = &[
+    (
+        "gpt-4o-mini",
+        Price {
+            input: 0.15,
+            cached_input: 0.075,
+            output: 0.60,
+        },
+    ),
+    (
+        "gpt-4o",
+        Price {
+            input: 2.50,
+            cached_input: 1.25,
+            output: 10.00,
+        },
+    ),
+    (
+        "gpt-4.1-nano",
+        Price {
+            input: 0.10,
+            cached_input: 0.025,
+            output: 0.40,
+        },
+    ),
+    (
+        "gpt-4.1-mini",
+        Price {
+            input: 0.40,
+            cached_input: 0.10,
+            output: 1.60,
+        },
+    ),
+    (
+        "gpt-4.1",
+        Price {
+            input: 2.00,
+            cached_input: 0.50,
+            output: 8.00,
+        },
+    ),
+    (
+        "o3-mini",
+        Price {
+            input: 1.10,
+            cached_input: 0.55,
+            output: 4.40,
+        },
+    ),
+    (
+        "o4-mini",
+        Price {
+            input: 1.10,
+            cached_input: 0.275,
+            output: 4.40,
+        },
+    ),
+    (
+        "text-embedding-3-small",
+        Price {
+            input: 0.02,
+            cached_input: 0.02,
+            output: 0.0,
+        },
+    ),
+    (
+        "text-embedding-3-large",
+        Price {
+            input: 0.13,
+            cached_input: 0.13,
+            output: 0.0,
+        },
+    ),
+];
+
+/// Models with a price: the built-in table and `price` in the config.
+pub fn priced_models() -> Vec<String> {
+    let mut models: Vec<String> = PRICES.iter().map(|(model, _)| model.to_string()).collect();
+    models.extend(config::load().price.into_keys());
+    models.sort();
+    models.dedup();
+    models
+}
+
+/// Price for `model`: `price.<model> = "input,cached,output"` in config, else the built-in table.
+pub fn price(model: &str) -> Option<Price> {
+    if let Some(v) = config::load().price.get(model) {
+        let parts: Vec<f64> = v.split(',').filter_map(|p| p.trim().parse().ok()).collect();
+        if let [input, cached_input, output] = parts[..] {
+            return Some(Price {
+                input,
+                cached_input,
+                output,
+            });
+        }
+    }
+    PRICES
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, p)| *p)
+}
+
+impl Usage {
+    /// Estimated cost in USD (0 for models without a known price).
+    pub fn cost(&self) -> f64 {
+        let Some(p) = price(&self.model) else {
+            return 0.0;
+        };
+        let uncached = self.prompt_tokens.saturating_sub(self.cached_tokens) as f64;
+        (uncached * p.input
+            + self.cached_tokens as f64 * p.cached_input
+            + self.completion_tokens as f64 * p.output)
+            / 1_000_000.0
+    }
+}
+
+/// Total estimated cost of `calls` in USD.
+pub fn total_cost(calls: &[Usage]) -> f64 {
+    calls.iter().map(Usage::cost).sum()
+}
+
+/// Seed the ledger with calls from a resumed session.
+pub fn restore(calls: Vec<Usage>) {
+    LEDGER.lock().unwrap().extend(calls);
+}
+
+pub fn record(usage: Usage) {
+    events::emit(events::Event::Usage {
+        usage: &usage,
+        cost: usage.cost(),
+    });
+    LEDGER.lock().unwrap().push(usage);
+}
+
+/// Every call recorded so far in this process.
+pub fn all() -> Vec<Usage> {
+    LEDGER.lock().unwrap().clone()
+}
+
+/// Count a tool call for the usage history.
+pub fn record_tool(name: &str) {
+    *TOOL_CALLS
+        .lock()
+        .unwrap()
+        .entry(name.to_string())
+        .or_default() += 1;
+}
+
+/// Position in the ledger; pass to `log_run` to persist only the calls made after it.
+pub fn mark() -> usize {
+    LEDGER.lock().unwrap().len()
+}
+
+/// One run in the usage history (`usage.jsonl` in the data dir), read by `zcode usage`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunRecord {
+    /// Unix seconds when the run ended.
+    pub time: u64,
+    pub workspace: String,
+    #[serde(default)]
+    pub session: Option<String>,
+    pub calls: Vec<Usage>,
+    #[serde(default)]
+    pub tools: BTreeMap<String, u64>,
+}
+
+fn history_path() -> Option<PathBuf> {
+    config::data_dir().map(|d| d.join("usage.jsonl"))
+}
+
+/// Append the calls made since `since` (see `mark`) and the tool calls counted since the
+/// last log to the usage history.
+pub fn log_run(workspace: &Path, since: usize) {
+    let calls: Vec<Usage> = LEDGER
+        .lock()
+        .unwrap()
+        .get(since..)
+        .unwrap_or_default()
+        .to_vec();
+    let tools = std::mem::take(&mut *TOOL_CALLS.lock().unwrap());
+    if calls.is_empty() && tools.is_empty() {
+        return;
+    }
+    let record = RunRecord {
+        time: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        workspace: workspace.display().to_string(),
+        session: session::active_id(),
+        calls,
+        tools,
+    };
+    let Some(path) = history_path() else {
+        return;
+    };
+    let _ = path.parent().map(fs::create_dir_all);
+    if let (Ok(line), Ok(mut file)) = (
+        serde_json::to_string(&record),
+        OpenOptions::new().create(true).append(true).open(&path),
+    ) {
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+/// Every run in the usage history; unreadable lines are skipped.
+pub fn history() -> Vec<RunRecord> {
+    let Some(content) = history_path().and_then(|p| fs::read_to_string(p).ok()) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect()
+}
@@ -0,0 +1,90 @@
+//! Verification phase: run the project's build and test commands after a task and send
+//! failures through the `zcode fix` loop before the run reports completion.
+
+use crate::agent::Agent;
+use crate::config;
+use crate::events::{self, Event};
+use crate::fix;
+use crate::tools::Executor;
+use std::fs;
+use std::path::Path;
+
+/// Edit-and-check rounds per failing command.
+const FIX_ROUNDS: usize = 3;
+
+/// Commands that verify the workspace: `verify` in `.zcode.toml` or the config (a command or
+/// a list; an empty list turns verification off), else detected from the build files.
+pub fn commands(workspace: &Path) -> Vec<String> {
+    if let Some(configured) = config::load().verify {
+        return configured.to_vec();
+    }
+    if workspace.join("Cargo.toml").is_file() {
+        return vec!["cargo test".into()];
+    }
+    if workspace.join("go.mod").is_file() {
+        return vec!["go build ./...".into(), "go test ./...".into()];
+    }
+    if let Ok(package) = fs::read_to_string(workspace.join("package.json")) {
+        let package: serde_json::Value = serde_json::from_str(&package).unwrap_or_default();
+        let scripts = &package["scripts"];
+        let mut found = Vec::new();
+        if scripts["build"].is_string() {
+            found.push("npm run build".into());
+        }
+        // `npm init` adds a test script that always fails.
+        if scripts["test"]
+            .as_str()
+            .is_some_and(|t| !t.contains("no test specified"))
+        {
+            found.push("npm test".into());
+        }
+        return found;
+    }
+    if ["pyproject.toml", "pytest.ini", "setup.cfg"]
+        .iter()
+        .any(|f| workspace.join(f).is_file())
+    {
+        return vec!["python -m pytest -q".into()];
+    }
+    if fs::read_to_string(workspace.join("Makefile"))
+        .is_ok_and(|m| m.lines().any(|l| l.starts_with("test:")))
+    {
+        return vec!["make test".into()];
+    }
+    Vec::new()
+}
+
+/// Run each command, fixing failures with `agent`. `task` tells the fixer what the changes
+/// were for. Returns the commands that still fail.
+pub async fn run<A: Agent>(
+    agent: &A,
+    executor: &Executor,
+    commands: &[String],
+    task: &str,
+) -> Result<Vec<String>, String> {
+    let background = format!("These changes were just made for the task: {}", task);
+    let mut failing = Vec::new();
+    for command in commands {
+        let (mut passed, output) = fix::check(executor, command)?;
+        if !passed {
+            passed = fix::repair(
+                agent,
+                executor,
+                command,
+                output,
+                Some(&background),
+                FIX_ROUNDS,
+            )
+            .await?;
+        }
+        events::emit(Event::Verification { command, passed });
+        if !passed {
+            events::error(&format!(
+                "`{}` still fails after {} fix rounds",
+                command, FIX_ROUNDS
+            ));
+            failing.push(command.clone());
+        }
+    }
+    Ok(failing)
+}
@@ -0,0 +1,166 @@
+//! Completion webhooks: when a one-shot or `--ci` run finishes, POST a JSON summary (task,
+//! status, diffstat, cost) to `webhook_url`, for dashboards and chat bots, and a readable
+//! message with a truncated diff to `slack_webhook_url` and `discord_webhook_url`.
+
+use crate::events::ChangedFile;
+use crate::tools::Executor;
+use crate::{ci, config, context, http, report, run, ui, usage};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tracing::warn;
+
+/// Longest task line in a chat message.
+const TASK_CAP: usize = 200;
+
+#[derive(Serialize)]
+pub struct Payload {
+    pub task: String,
+    /// `success`, `partial` (some todos or checks failed), or `failed` (no reply).
+    pub status: &'static str,
+    pub diffstat: Diffstat,
+    /// Estimated cost in USD.
+    pub cost: f64,
+    pub duration_secs: f64,
+    pub workspace: String,
+    /// The saved run report, with `--save-report`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report: Option<String>,
+    /// The `--ci` exit code, for headless runs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct Diffstat {
+    pub added: usize,
+    pub removed: usize,
+    pub files: Vec<ChangedFile>,
+}
+
+impl Payload {
+    /// Summarize the run that just ended in `executor`'s workspace.
+    pub fn new(task: &str, reply: Option<&str>, executor: &Executor, elapsed: Duration) -> Self {
+        let status = match (reply, run::last_run()) {
+            (None, _) => "failed",
+            (Some(_), Some(summary)) if !summary.succeeded() => "partial",
+            _ => "success",
+        };
+        let files: Vec<ChangedFile> = executor.changes().iter().map(ChangedFile::from).collect();
+        Self {
+            task: task.to_string(),
+            status,
+            diffstat: Diffstat {
+                added: files.iter().map(|f| f.added).sum(),
+                removed: files.iter().map(|f| f.removed).sum(),
+                files,
+            },
+            cost: usage::total_cost(&usage::all()),
+            duration_secs: elapsed.as_secs_f64(),
+            workspace: executor.workspace().display().to_string(),
+            report: report::last_saved().map(|p| p.display().to_string()),
+            exit_code: ci::enabled().then(ci::exit_code),
+        }
+    }
+}
+
+/// Chat services with incoming webhooks.
+#[derive(Clone, Copy)]
+enum Chat {
+    Slack,
+    Discord,
+}
+
+impl Chat {
+    fn url(self) -> Option<String> {
+        match self {
+            Chat::Slack => config::load().slack_webhook_url,
+            Chat::Discord => config::load().discord_webhook_url,
+        }
+    }
+
+    /// Most of the diff one message carries; Discord messages are capped at 2000 characters.
+    fn diff_cap(self) -> usize {
+        match self {
+            Chat::Slack => 2500,
+            Chat::Discord => 1200,
+        }
+    }
+
+    fn body(self, payload: &Payload, diff: &str) -> Value {
+        let task = context::truncate(payload.task.lines().next().unwrap_or_default(), TASK_CAP);
+        let mut text = format!(
+            "zcode run {}: {}\n{} files changed (+{} -{}), ${:.4}, {:.0}s in {}",
+            payload.status,
+            task,
+            payload.diffstat.files.len(),
+            payload.diffstat.added,
+            payload.diffstat.removed,
+            payload.cost,
+            payload.duration_secs,
+            payload.workspace,
+        );
+        if let Some(report) = &payload.report {
+            text.push_str(&format!("\nReport: {}", report));
+        }
+        if !diff.is_empty() {
+            let fence = match self {
+                Chat::Slack => "```",
+                Chat::Discord => "```diff",
+            };
+            text.push_str(&format!(
+                "\n{}\n{}\n```",
+                fence,
+                context::truncate(diff.trim_end(), self.diff_cap())
+            ));
+        }
+        match self {
+            Chat::Slack => json!({ "text": text }),
+            Chat::Discord => json!({ "content": text }),
+        }
+    }
+}
+
+/// Send the run summary to every configured webhook. Failures are logged, not fatal.
+pub async fn run_finished(task: &str, reply: Option<&str>, executor: &Executor, elapsed: Duration) {
+    let chats: Vec<(Chat, String)> = [Chat::Slack, Chat::Discord]
+        .into_iter()
+        .filter_map(|chat| chat.url().map(|url| (chat, url)))
+        .collect();
+    let generic = config::load().webhook_url;
+    if generic.is_none() && chats.is_empty() {
+        return;
+    }
+    let payload = Payload::new(task, reply, executor, elapsed);
+    let mut deliveries = Vec::new();
+    if let Some(url) = generic {
+        deliveries.push((url, json!(payload)));
+    }
+    if !chats.is_empty() {
+        let diff: String = executor.changes().iter().map(ui::plain_diff).collect();
+        deliveries.extend(
+            chats
+                .into_iter()
+                .map(|(chat, url)| (url, chat.body(&payload, &diff))),
+        );
+    }
+    for (url, body) in deliveries {
+        if let Err(e) = post(&url, &body).await {
+            warn!(%url, error = %e, "webhook failed");
+        }
+    }
+}
+
+async fn post(url: &str, body: &Value) -> Result<(), String> {
+    let res = http::client()
+        .post(url)
+        .timeout(Duration::from_secs(10))
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !res.status().is_success() {
+        return Err(res.status().to_string());
+    }
+    Ok(())
+}
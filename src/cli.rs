@@ -1,9 +1,367 @@
-use clap::Parser;
+use crate::completions;
+use clap::{Parser, Subcommand};
+use clap_complete::ArgValueCompleter;
 
 #[derive(Parser)]
 #[command(name = "zcode")]
 #[command(about = "CLI coding agent powered by OpenAI (multi-step reasoning)")]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     #[arg(short, long)]
     pub prompt: Option<String>,
+
+    /// Read the prompt from a file (e.g. a task description checked into the repo)
+    #[arg(long, value_name = "PATH", conflicts_with = "prompt")]
+    pub prompt_file: Option<std::path::PathBuf>,
+
+    /// Run against this directory instead of the current one (like `make -C`)
+    #[arg(short = 'C', long, value_name = "DIR")]
+    pub workspace: Option<std::path::PathBuf>,
+
+    /// Resume a saved REPL session by id
+    #[arg(long, value_name = "ID", add = ArgValueCompleter::new(completions::sessions))]
+    pub resume: Option<String>,
+
+    /// Continue a pipeline run that was interrupted or hit a failed request, by id
+    #[arg(long, value_name = "ID", conflicts_with_all = ["prompt", "prompt_file", "plan", "resume"], add = ArgValueCompleter::new(completions::runs))]
+    pub resume_run: Option<String>,
+
+    /// Continue the most recent REPL session, or this workspace's latest unfinished run if newer
+    #[arg(long = "continue", conflicts_with_all = ["prompt", "prompt_file", "plan", "resume", "resume_run"])]
+    pub continue_last: bool,
+
+    /// Disable colors, spinners, and unicode glyphs (also via NO_COLOR or when not a TTY)
+    #[arg(long)]
+    pub no_color: bool,
+
+    /// With -p: print only the final answer on stdout (no phases, spinners, or tool output)
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Log to stderr (-v info, -vv debug, -vvv trace); a log file is always kept in the data dir
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Mirror the assistant's output to a file as it streams
+    #[arg(long, value_name = "FILE")]
+    pub output: Option<std::path::PathBuf>,
+
+    /// Use the clipboard as the prompt, or attach it as context when -p is given
+    #[arg(long)]
+    pub paste: bool,
+
+    /// `json`: print one JSON event per line (plan, todo_started, tool_call, tool_result,
+    /// assistant_delta, usage, done) instead of the human UI
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+    pub output_format: OutputFormat,
+
+    /// Headless run for CI: needs a prompt, never asks questions, plain or JSON output, and
+    /// exits 0 success, 2 partial, 3 policy-blocked, 4 budget exceeded, 5 API failure
+    #[arg(long)]
+    pub ci: bool,
+
+    /// Stop once the estimated cost reaches this many USD
+    #[arg(long, value_name = "USD")]
+    pub max_cost: Option<f64>,
+
+    /// Stop a step after this many model requests
+    #[arg(long, value_name = "N")]
+    pub max_turns: Option<usize>,
+
+    /// Tools the model may use (comma-separated); any other tool call stops the run
+    #[arg(long, value_delimiter = ',', value_name = "TOOLS")]
+    pub allow_tools: Option<Vec<String>>,
+
+    /// Model for planning and progress checks (default gpt-4o-mini); `<provider>:<model>` for
+    /// an OpenAI-compatible provider, e.g. `ollama:llama3.1`
+    #[arg(long, value_name = "MODEL", add = ArgValueCompleter::new(completions::models))]
+    pub planner_model: Option<String>,
+
+    /// Model that executes the steps with tools (default gpt-4o); same format as --planner-model
+    #[arg(long, value_name = "MODEL", add = ArgValueCompleter::new(completions::models))]
+    pub executor_model: Option<String>,
+
+    /// Commit after each completed step (`zcode: <step>`) for fine-grained rollback points
+    #[arg(long)]
+    pub auto_commit: bool,
+
+    /// After a successful run: commit on a new branch, push, and open a pull request
+    #[arg(long, conflicts_with = "plan")]
+    pub create_pr: bool,
+
+    /// Have a second model review the diff against the request and fix its findings before finishing
+    #[arg(long)]
+    pub critic: bool,
+
+    /// How the work is organised: `single` (planner + executor) or `multi-agent` (architect,
+    /// implementer, tester, and reviewer roles)
+    #[arg(long, value_enum)]
+    pub strategy: Option<Strategy>,
+
+    /// Run steps the planner marks as independent at the same time
+    #[arg(long)]
+    pub parallel: bool,
+
+    /// Speak JSON-RPC on stdin/stdout for editor integrations (see the README for the protocol)
+    #[arg(long, conflicts_with_all = ["prompt", "prompt_file", "resume", "resume_run"])]
+    pub stdio: bool,
+
+    /// Also write the end-of-run report to .zcode/reports/<run id>.md
+    #[arg(long)]
+    pub save_report: bool,
+
+    /// Save every model API request and response as fixtures in DIR
+    #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+    pub record: Option<std::path::PathBuf>,
+
+    /// Answer model API requests from fixtures saved with --record (no network or key needed)
+    #[arg(long, value_name = "DIR")]
+    pub replay: Option<std::path::PathBuf>,
+
+    /// Answer with the scripted replies in a YAML file instead of a model (for demos and tests)
+    #[arg(long, value_name = "SCRIPT", conflicts_with_all = ["stdio", "record", "replay"])]
+    pub fake_agent: Option<std::path::PathBuf>,
+
+    /// Append each run to the interaction log for dataset export (redacted JSONL)
+    #[arg(long)]
+    pub dataset_log: bool,
+
+    /// Plan again even if an identical request has a cached plan
+    #[arg(long)]
+    pub no_plan_cache: bool,
+
+    /// Answer repeated identical model requests from an on-disk cache
+    #[arg(long)]
+    pub response_cache: bool,
+
+    /// Skip running the project's build and test commands after the task
+    #[arg(long)]
+    pub no_verify: bool,
+
+    /// Review and edit the plan (reorder, delete, or add steps and files to read) before it runs
+    #[arg(long, conflicts_with_all = ["plan", "ci"])]
+    pub review: bool,
+
+    /// Only plan: print the steps, files, and estimated scope without changing anything
+    #[arg(long)]
+    pub plan: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Human,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Strategy {
+    Single,
+    MultiAgent,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run a prompt template from the config dir's templates/ folder
+    RunTemplate {
+        /// Template name (file name without .md)
+        #[arg(add = ArgValueCompleter::new(completions::templates))]
+        name: String,
+        /// File for {{file}} and {{selection}}
+        #[arg(long)]
+        file: Option<String>,
+        /// Line range of --file used for {{selection}}, e.g. 10-40
+        #[arg(long, value_name = "START-END")]
+        lines: Option<String>,
+        /// Extra arguments for {{args}}
+        args: Vec<String>,
+    },
+    /// Create the global config, a project .zcode.toml, and a starter ZCODE.md
+    Init {
+        /// Overwrite existing files
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check config, API access, and tools, and suggest fixes
+    Doctor,
+    /// Trust the workspace's .zcode.toml and the team config as they are now, so their gated settings apply
+    Trust,
+    /// Prompt for an API key, check it against the provider, and save it
+    Auth {
+        #[arg(value_parser = clap::builder::PossibleValuesParser::new(crate::auth::PROVIDERS))]
+        provider: String,
+    },
+    /// Explain a file or a line range (path[:START-END]) without changing anything
+    Explain {
+        #[arg(value_name = "PATH[:START-END]")]
+        target: String,
+    },
+    /// Fix build or test errors: piped output (or the check command's) is fed to the agent,
+    /// then the check command is re-run until it passes
+    Fix {
+        /// Command that reproduces the errors
+        #[arg(long, default_value = "cargo check")]
+        command: String,
+        /// Give up after this many edit-and-check rounds
+        #[arg(long, default_value_t = 5)]
+        max_iterations: usize,
+    },
+    /// Write tests for a file following the project's conventions, run them, and fix failures
+    TestGen {
+        path: String,
+        /// Command that runs the tests (detected from the project by default)
+        #[arg(long)]
+        command: Option<String>,
+        /// Give up after this many write-and-run rounds
+        #[arg(long, default_value_t = 3)]
+        max_iterations: usize,
+    },
+    /// Rename a symbol across all Rust files, with a preview and a compile check
+    Rename {
+        old: String,
+        new: String,
+        /// Command run after the rename; the rename is reverted if it fails
+        #[arg(long, default_value = "cargo check")]
+        check: String,
+        /// Skip the compile check
+        #[arg(long)]
+        no_check: bool,
+        /// Apply without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Draft a CHANGELOG.md entry from git history and add it after confirmation
+    Changelog {
+        /// Tag or commit to start from (default: the latest tag)
+        #[arg(long)]
+        since: Option<String>,
+        /// Version for the entry heading
+        #[arg(long, default_value = "Unreleased")]
+        version: String,
+        /// Write without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Bump a dependency in Cargo.toml, fix the breakage, and summarize the API changes
+    UpgradeDep {
+        #[arg(value_name = "CRATE")]
+        krate: String,
+        version: String,
+        /// Command that must pass after the upgrade
+        #[arg(long, default_value = "cargo check --all-targets")]
+        command: String,
+        /// Give up after this many edit-and-check rounds
+        #[arg(long, default_value_t = 5)]
+        max_iterations: usize,
+    },
+    /// Install the latest release from GitHub (checksum-verified) over this binary
+    SelfUpdate {
+        /// Only report whether an update is available
+        #[arg(long)]
+        check: bool,
+    },
+    /// Show tokens, cost, and tool calls from past runs, per project and model
+    Usage {
+        /// Only runs within this period, e.g. 12h, 7d, 4w
+        #[arg(long)]
+        since: Option<String>,
+        /// Only this project (defaults to all; `.` for the current workspace)
+        #[arg(long)]
+        project: Option<std::path::PathBuf>,
+    },
+    /// Build the semantic index of this workspace (embeddings of source chunks)
+    Index {
+        /// Show what is indexed instead of rebuilding
+        #[arg(long, conflicts_with = "clear")]
+        status: bool,
+        /// Delete the index
+        #[arg(long)]
+        clear: bool,
+    },
+    /// Work on a GitHub issue on a new branch and comment the resulting diff on it
+    Issue {
+        /// Issue number (repository from the origin remote) or issue URL
+        #[arg(value_name = "NUMBER|URL")]
+        reference: String,
+        /// Don't post a comment on the issue
+        #[arg(long)]
+        no_comment: bool,
+    },
+    /// Serve an HTTP API for driving the agent: sessions, prompts, SSE events, and tool approvals
+    Serve {
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+        /// Address to bind; anyone who can reach it can run commands in the workspace
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        /// Run mutating tool calls without waiting for approval
+        #[arg(long)]
+        auto_approve: bool,
+        /// Let pages from this origin (e.g. https://dash.example.com) open the WebSocket; loopback origins always can
+        #[arg(long, value_name = "ORIGIN")]
+        allow_origin: Vec<String>,
+    },
+    /// Read or change settings in ~/.config/zcode/config.toml
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Print a shell completion script (e.g. `source <(zcode completions zsh)` in ~/.zshrc)
+    Completions { shell: clap_complete::Shell },
+    /// Run every prompt in a YAML task list sequentially and write a JSON report
+    Batch {
+        /// YAML file with a `tasks:` list (strings or {name, prompt})
+        file: std::path::PathBuf,
+        /// Where to write the report
+        #[arg(long, default_value = "zcode-batch-report.json")]
+        report: std::path::PathBuf,
+    },
+    /// Run a suite of tasks against copies of fixture repos, check the results, and report pass rates per model config
+    Eval {
+        /// YAML suite: `tasks` (prompt, repo, setup, checks), optional `configs` and `runs`
+        suite: std::path::PathBuf,
+        /// Where to write the JSON report
+        #[arg(long, default_value = "zcode-eval-report.json")]
+        report: std::path::PathBuf,
+        /// Keep each run's workspace copy (in the temp dir) for inspection
+        #[arg(long)]
+        keep: bool,
+    },
+    /// Measure time to first token, streaming throughput, and tool round trips per model
+    Bench {
+        /// Model specs to compare (default: the planner and executor models)
+        #[arg(add = ArgValueCompleter::new(completions::models))]
+        models: Vec<String>,
+        /// Runs per model; the table shows medians
+        #[arg(long, default_value_t = 3)]
+        runs: usize,
+    },
+    /// Line up tasks and run them one at a time, each on its own branch
+    Queue {
+        #[command(subcommand)]
+        action: QueueAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum QueueAction {
+    /// Add a prompt to the end of the queue
+    Add { prompt: String },
+    /// Show queued tasks and how finished ones went
+    List,
+    /// Remove a task (every finished task without an id)
+    Remove { id: Option<usize> },
+    /// Run the pending tasks in order and write a summary report
+    Run,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// Print one setting (dotted keys reach into tables, e.g. openai.model)
+    Get { key: String },
+    /// Set a setting, keeping the rest of the file intact
+    Set { key: String, value: String },
+    /// Print every setting (secrets are masked)
+    List,
 }
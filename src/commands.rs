@@ -0,0 +1,237 @@
+//! REPL slash commands (e.g. `/diff`).
+
+use zcode_core::session::Session;
+use zcode_core::tools::Executor;
+use zcode_core::{clipboard, context, options, run, templates, tokens, ui, usage};
+
+/// Handle a `/command` line typed at the REPL prompt.
+pub fn handle(line: &str, executor: &Executor, session: &mut Session) {
+    let mut parts = line.trim_start_matches('/').splitn(2, char::is_whitespace);
+    let name = parts.next().unwrap_or("");
+    let args = parts.next().unwrap_or("").trim();
+    match name {
+        "diff" => diff(executor, args),
+        "copy" => copy(session, args),
+        "paste" => paste(session),
+        "add" => add(executor, session, args),
+        "drop" => drop_pinned(session, args),
+        "checkpoint" => {
+            executor.checkpoint();
+            ui::info("Checkpoint set; /diff will show changes from here.");
+        }
+        "stats" => stats(args),
+        "cost" => cost(executor, session),
+        "save" => save(executor, session, args),
+        "templates" => list_templates(),
+        "help" => help(),
+        _ => ui::error_msg(&format!("Unknown command: /{} (try /help)", name)),
+    }
+}
+
+fn help() {
+    ui::info("/diff [side]  show changes made to files this session (or since /checkpoint)");
+    ui::info("/checkpoint   reset the /diff baseline to the current state");
+    ui::info("/copy [code]  copy the last reply (or its last code block) to the clipboard");
+    ui::info("/paste        attach the clipboard contents to the next prompt");
+    ui::info("/add [path]   pin a file into context (fuzzy picker without a path)");
+    ui::info("/drop [path]  unpin a file (all files without a path)");
+    ui::info("/stats [on|off] show session token usage; toggle per-response usage lines");
+    ui::info("/cost         show the estimated cost of this session, per model");
+    ui::info("/save <file> [all]  write the last reply (or the whole transcript) to a file");
+    ui::info("/templates    list prompt templates (run with `zcode run-template <name>`)");
+    ui::info("/help         list commands");
+}
+
+fn diff(executor: &Executor, args: &str) {
+    let changes = executor.changes();
+    if changes.is_empty() {
+        ui::info("No changes.");
+        return;
+    }
+    let opts = ui::DiffOptions {
+        side_by_side: args == "side",
+        ..Default::default()
+    };
+    let text: String = changes
+        .iter()
+        .map(|c| ui::file_diff(c, &opts))
+        .collect::<Vec<_>>()
+        .join("\n");
+    ui::page(&text);
+}
+
+fn copy(session: &Session, args: &str) {
+    let Some(reply) = session.last_reply() else {
+        ui::info("Nothing to copy yet.");
+        return;
+    };
+    let text = if args == "code" {
+        match clipboard::last_code_block(reply) {
+            Some(code) => code,
+            None => {
+                ui::info("No code block in the last reply.");
+                return;
+            }
+        }
+    } else {
+        reply.to_string()
+    };
+    match clipboard::copy(&text) {
+        Ok(()) => ui::info(&format!("Copied {} chars to clipboard.", text.len())),
+        Err(e) => ui::error_msg(&format!("Clipboard unavailable: {}", e)),
+    }
+}
+
+fn add(executor: &Executor, session: &mut Session, args: &str) {
+    let path = if args.is_empty() {
+        let files = context::workspace_files(executor.workspace());
+        match ui::fuzzy_pick("Add file", &files) {
+            Some(i) => files[i].clone(),
+            None => return,
+        }
+    } else {
+        args.to_string()
+    };
+    if !executor.workspace().join(&path).is_file() {
+        ui::error_msg(&format!("Not a file: {}", path));
+        return;
+    }
+    session.pin(&path);
+    ui::info(&format!(
+        "Pinned {} ({} in context)",
+        path,
+        session.pinned.len()
+    ));
+}
+
+fn drop_pinned(session: &mut Session, args: &str) {
+    if args.is_empty() {
+        session.pinned.clear();
+        session.pinned_seen.clear();
+        ui::info("Unpinned all files.");
+    } else if let Some(i) = session.pinned.iter().position(|p| p == args) {
+        session.pinned.remove(i);
+        session.pinned_seen.remove(args);
+        ui::info(&format!("Unpinned {}", args));
+    } else {
+        ui::error_msg(&format!("Not pinned: {}", args));
+    }
+}
+
+fn stats(args: &str) {
+    match args {
+        "on" => options::update(|o| o.show_usage = true),
+        "off" => options::update(|o| o.show_usage = false),
+        _ => {}
+    }
+    let calls = usage::all();
+    let prompt: u64 = calls.iter().map(|u| u.prompt_tokens).sum();
+    let cached: u64 = calls.iter().map(|u| u.cached_tokens).sum();
+    let completion: u64 = calls.iter().map(|u| u.completion_tokens).sum();
+    ui::info(&format!(
+        "{} calls · {} prompt tokens ({} cached) · {} completion tokens",
+        calls.len(),
+        prompt,
+        cached,
+        completion
+    ));
+    ui::info(&format!(
+        "Per-response usage lines: {}",
+        if options::get().show_usage {
+            "on"
+        } else {
+            "off"
+        }
+    ));
+}
+
+fn cost(executor: &Executor, session: &Session) {
+    let calls = usage::all();
+    let mut models: Vec<&str> = calls.iter().map(|u| u.model.as_str()).collect();
+    models.sort();
+    models.dedup();
+    for model in models {
+        let per_model: Vec<usage::Usage> =
+            calls.iter().filter(|u| u.model == model).cloned().collect();
+        let priced = if usage::price(model).is_some() {
+            ""
+        } else {
+            " (no price known)"
+        };
+        ui::info(&format!(
+            "{:<16} {:>4} calls  ${:.4}{}",
+            model,
+            per_model.len(),
+            usage::total_cost(&per_model),
+            priced
+        ));
+    }
+    ui::cost_summary(&calls);
+    if !session.pinned.is_empty() {
+        let model = run::executor_model();
+        let pinned: usize = session
+            .pinned
+            .iter()
+            .filter_map(|path| std::fs::read_to_string(executor.workspace().join(path)).ok())
+            .map(|content| tokens::count(&model, &content))
+            .sum();
+        ui::info(&format!(
+            "Pinned files add ~{} tokens to each prompt ({}% of {}'s context window)",
+            pinned,
+            pinned * 100 / tokens::context_window(&model),
+            model
+        ));
+    }
+}
+
+fn save(executor: &Executor, session: &Session, args: &str) {
+    let mut parts = args.split_whitespace();
+    let Some(file) = parts.next() else {
+        ui::error_msg("Usage: /save <file> [all]");
+        return;
+    };
+    let text = if parts.next() == Some("all") {
+        session.transcript()
+    } else {
+        match session.last_reply() {
+            Some(reply) => reply.to_string(),
+            None => {
+                ui::info("Nothing to save yet.");
+                return;
+            }
+        }
+    };
+    let path = executor.workspace().join(file);
+    match std::fs::write(&path, text) {
+        Ok(()) => ui::info(&format!("Saved to {}", path.display())),
+        Err(e) => ui::error_msg(&format!("{}: {}", path.display(), e)),
+    }
+}
+
+fn list_templates() {
+    let all = templates::list();
+    if all.is_empty() {
+        let dir = templates::templates_dir()
+            .map(|d| d.display().to_string())
+            .unwrap_or_default();
+        ui::info(&format!("No templates. Add <name>.md files to {}", dir));
+        return;
+    }
+    for t in all {
+        ui::info(&format!("{:<24} {}", t.name, t.description));
+    }
+}
+
+fn paste(session: &mut Session) {
+    match clipboard::paste() {
+        Ok(text) if !text.trim().is_empty() => {
+            ui::info(&format!(
+                "Attached {} lines from the clipboard to the next prompt.",
+                text.lines().count()
+            ));
+            session.pasted = Some(text);
+        }
+        Ok(_) => ui::info("Clipboard is empty."),
+        Err(e) => ui::error_msg(&format!("Clipboard unavailable: {}", e)),
+    }
+}
@@ -0,0 +1,126 @@
+//! Shell completion with clap_complete's dynamic engine. The script from `zcode completions
+//! <shell>` calls back into zcode as you type, so saved session and run ids, template names,
+//! providers, and `[models]` aliases are read when completing, not when the script was made.
+
+use crate::cli::Cli;
+use clap::CommandFactory;
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::env::Shells;
+use clap_complete::{CompleteEnv, Shell};
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use zcode_core::{config, run_state, session, templates, usage};
+
+/// Environment variable the registered script sets when it asks zcode for completions.
+const VAR: &str = "COMPLETE";
+
+/// Answer a completion request from the shell and exit; returns when this is a normal run.
+pub fn complete() {
+    if std::env::var_os(VAR).is_some_and(|v| !v.is_empty() && v != "0") {
+        // Project settings (aliases, providers) apply to completions in the workspace too.
+        if let Ok(dir) = std::env::current_dir() {
+            config::set_workspace(&dir);
+        }
+    }
+    CompleteEnv::with_factory(Cli::command)
+        .var(VAR)
+        .bin("zcode")
+        .complete();
+}
+
+/// Write the script that registers completion for `shell` to stdout.
+pub fn generate(shell: Shell) -> Result<(), String> {
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&shell.to_string())
+        .ok_or_else(|| format!("No completion support for {}", shell))?;
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    completer
+        .write_registration(
+            VAR,
+            "zcode",
+            "zcode",
+            &exe.to_string_lossy(),
+            &mut std::io::stdout(),
+        )
+        .map_err(|e| e.to_string())
+}
+
+/// `values` that start with what has been typed so far.
+fn matching(
+    current: &OsStr,
+    values: impl IntoIterator<Item = (String, Option<String>)>,
+) -> Vec<CompletionCandidate> {
+    let prefix = current.to_string_lossy();
+    values
+        .into_iter()
+        .filter(|(value, _)| value.starts_with(prefix.as_ref()))
+        .map(|(value, help)| CompletionCandidate::new(value).help(help.map(Into::into)))
+        .collect()
+}
+
+/// File stems with `ext` in `dir`, newest (largest id) first.
+fn ids_in(dir: Option<PathBuf>, ext: &str) -> Vec<String> {
+    let Some(entries) = dir.and_then(|d| std::fs::read_dir(d).ok()) else {
+        return Vec::new();
+    };
+    let mut ids: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            name.strip_suffix(ext).map(|s| s.to_string())
+        })
+        .collect();
+    ids.sort_by(|a, b| b.cmp(a));
+    ids
+}
+
+/// Saved REPL sessions for `--resume`.
+pub fn sessions(current: &OsStr) -> Vec<CompletionCandidate> {
+    matching(
+        current,
+        ids_in(session::sessions_dir(), ".json")
+            .into_iter()
+            .map(|id| (id, None)),
+    )
+}
+
+/// Saved pipeline runs for `--resume-run`.
+pub fn runs(current: &OsStr) -> Vec<CompletionCandidate> {
+    matching(
+        current,
+        ids_in(run_state::runs_dir(), ".json")
+            .into_iter()
+            .map(|id| (id, None)),
+    )
+}
+
+/// Prompt templates for `run-template`.
+pub fn templates(current: &OsStr) -> Vec<CompletionCandidate> {
+    matching(
+        current,
+        templates::list().into_iter().map(|t| (t.name, None)),
+    )
+}
+
+/// Model specs: `[models]` aliases, `<provider>:` prefixes, and models with a known price.
+pub fn models(current: &OsStr) -> Vec<CompletionCandidate> {
+    let config = config::load();
+    let aliases = config
+        .models
+        .into_iter()
+        .map(|(alias, spec)| (alias, Some(spec)));
+    let providers: BTreeSet<String> = config
+        .providers
+        .into_keys()
+        .chain(["ollama".to_string()])
+        .collect();
+    let providers = providers
+        .into_iter()
+        .map(|provider| (format!("{}:", provider), Some("provider".to_string())));
+    let priced = usage::priced_models()
+        .into_iter()
+        .map(|model| (model, None));
+    matching(current, aliases.chain(providers).chain(priced))
+}
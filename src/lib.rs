@@ -1,6 +0,0 @@
-pub mod agent;
-pub mod cli;
-pub mod config;
-pub mod run;
-pub mod tools;
-pub mod ui;
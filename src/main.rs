@@ -1,24 +1,764 @@
-use zcode::{cli::Cli, config, tools::Executor};
+mod cli;
+mod commands;
+mod completions;
+mod repl;
+mod server;
+
 use clap::Parser;
+use cli::{Cli, Command, ConfigAction, OutputFormat, QueueAction, Strategy};
 use std::env;
+use std::io::{IsTerminal, Read};
+use std::sync::Arc;
+use std::time::Instant;
+use zcode_core::agent::{cassette, Agent, FakeAgent};
+use zcode_core::events::{self, ChangedFile, Event};
+use zcode_core::options::{self, Options};
+use zcode_core::run::Agents;
+use zcode_core::run_state::{Latest, RunState};
+use zcode_core::session::Session;
+use zcode_core::tools::Executor;
+use zcode_core::{
+    auth, ci, clipboard, config, doctor, hooks, init, interrupt, logging, shutdown, templates, ui,
+    usage, webhook,
+};
 
 #[tokio::main]
 async fn main() {
-    let cli = Cli::parse();
+    completions::complete();
+    let mut cli = Cli::parse();
+    let _log_guard = logging::init(cli.verbose);
+    // `--stdio` speaks JSON-RPC on stdout, and `serve` sends each session's events to its
+    // client; everything else renders the core's events in the terminal.
+    let serving = cli.stdio || matches!(cli.command, Some(Command::Serve { .. }));
+    if cli.stdio {
+        ui::attach_stderr();
+    } else {
+        ui::attach_terminal();
+    }
+    if !serving {
+        events::subscribe(Arc::new(ui::Terminal));
+    }
+
+    if let Some(Command::Completions { shell }) = cli.command {
+        if let Err(e) = completions::generate(shell) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::Config { action }) = &cli.command {
+        let dir = cli
+            .workspace
+            .clone()
+            .unwrap_or_else(|| env::current_dir().expect("current dir"));
+        config::set_workspace(&dir.canonicalize().unwrap_or(dir));
+        config_command(action);
+        return;
+    }
+
+    if let Some(path) = &cli.output {
+        if let Err(e) = ui::tee_to(path) {
+            eprintln!("Cannot write output file {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let workspace = match &cli.workspace {
+        Some(dir) => dir.canonicalize().unwrap_or_else(|e| {
+            eprintln!("Cannot use workspace {}: {}", dir.display(), e);
+            std::process::exit(1);
+        }),
+        None => env::current_dir().expect("current dir"),
+    };
+    if !workspace.is_dir() {
+        eprintln!("Workspace is not a directory: {}", workspace.display());
+        std::process::exit(1);
+    }
+    config::set_workspace(&workspace);
+    if let Err(e) = config::remote::refresh().await {
+        eprintln!("Invalid config: {}", e);
+        std::process::exit(1);
+    }
+    match config::validate() {
+        Ok(warnings) => {
+            for warning in warnings {
+                eprintln!("Warning: {}", warning);
+            }
+        }
+        Err(e) => {
+            eprintln!("Invalid config: {}", e);
+            std::process::exit(1);
+        }
+    }
+    if let Err(e) = zcode_core::http::proxies().and_then(|_| zcode_core::agent::check_providers()) {
+        eprintln!("Invalid config: {}", e);
+        std::process::exit(1);
+    }
+    let settings = config::load();
+    ui::init_plain(cli.no_color || cli.ci);
+    let interactive =
+        !cli.ci && cli.output_format == OutputFormat::Human && std::io::stdin().is_terminal();
+    options::init(Options {
+        planner_model: cli.planner_model.clone(),
+        executor_model: cli.executor_model.clone(),
+        verify: !cli.no_verify,
+        review_plan: (cli.review || settings.review_plan.unwrap_or(false))
+            && interactive
+            && !cli.quiet,
+        critic: cli.critic || settings.critic.unwrap_or(false),
+        multi_agent: match cli.strategy {
+            Some(strategy) => strategy == Strategy::MultiAgent,
+            None => settings.strategy.as_deref() == Some("multi-agent"),
+        },
+        parallel: cli.parallel || settings.parallel.unwrap_or(false),
+        auto_commit: cli.auto_commit || settings.auto_commit.unwrap_or(false),
+        save_report: cli.save_report || settings.save_report.unwrap_or(false),
+        dataset_log: cli.dataset_log || settings.dataset_log.unwrap_or(false),
+        plan_cache: !cli.no_plan_cache && settings.plan_cache.unwrap_or(true),
+        response_cache: cli.response_cache || settings.response_cache.unwrap_or(false),
+        show_usage: settings.ui.show_usage.unwrap_or(false),
+        ci: cli.ci,
+        limits: ci::Limits {
+            max_cost: cli.max_cost.or(settings.max_cost),
+            max_turns: cli.max_turns.or(settings.max_turns),
+            allow_tools: cli.allow_tools.clone().or(settings.tools.allow),
+        },
+    });
+    ui::init_theme(ui::Theme::from_config());
+    if let Some(Command::Trust) = cli.command {
+        match config::trust::trust_all() {
+            Ok(files) => {
+                for file in files {
+                    ui::info(&format!("Trusted {}", file));
+                }
+            }
+            Err(e) => {
+                ui::error_msg(&e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Err(e) = config::trust::init(interactive) {
+        ui::error_msg(&e);
+        std::process::exit(1);
+    }
+    if let Err(e) = zcode_core::run::check_models() {
+        ui::error_msg(&e);
+        std::process::exit(1);
+    }
+    ui::link::init(&workspace, settings.ui.editor_url);
+    if let Some(Command::Usage { since, project }) = &cli.command {
+        let project = project
+            .as_ref()
+            .map(|p| p.canonicalize().unwrap_or_else(|_| p.clone()));
+        let project = project.map(|p| p.display().to_string());
+        if let Err(e) = zcode_core::stats::run(since.as_deref(), project.as_deref()) {
+            ui::error_msg(&e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    let api_key = config::load_api_key().unwrap_or_else(|| {
+    if let Some(Command::Index { status: true, .. }) = cli.command {
+        zcode_core::index::status(&workspace);
+        return;
+    }
+    if let Some(Command::Index { clear: true, .. }) = cli.command {
+        match zcode_core::index::clear(&workspace) {
+            Ok(true) => ui::info("Index deleted."),
+            Ok(false) => ui::info("No index for this workspace."),
+            Err(e) => {
+                ui::error_msg(&e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(Command::Init { force }) = cli.command {
+        if let Err(e) = init::run(&workspace, force) {
+            ui::error_msg(&e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::Auth { provider }) = &cli.command {
+        if let Err(e) = auth::run(provider).await {
+            ui::error_msg(&e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::SelfUpdate { check }) = cli.command {
+        if let Err(e) = zcode_core::update::run(check).await {
+            ui::error_msg(&e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::Doctor) = cli.command {
+        if !doctor::run(&workspace).await {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::Rename {
+        old,
+        new,
+        check,
+        no_check,
+        yes,
+    }) = &cli.command
+    {
+        let executor = Executor::new(workspace);
+        let check = (!no_check).then_some(check.as_str());
+        let result = zcode_core::rename::run(&executor, old, new, check, *yes);
+        finish_task(&executor, result);
+        return;
+    }
+
+    if let Some(Command::Queue {
+        action: QueueAction::Add { prompt },
+    }) = &cli.command
+    {
+        match zcode_core::queue::add(&workspace, prompt) {
+            Ok(id) => ui::info(&format!(
+                "Queued task {}; run the queue with `zcode queue run`.",
+                id
+            )),
+            Err(e) => {
+                ui::error_msg(&e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(Command::Queue {
+        action: QueueAction::List,
+    }) = &cli.command
+    {
+        zcode_core::queue::list(&workspace);
+        return;
+    }
+    if let Some(Command::Queue {
+        action: QueueAction::Remove { id },
+    }) = &cli.command
+    {
+        match zcode_core::queue::remove(&workspace, *id) {
+            Ok(n) => ui::info(&format!("Removed {} task(s).", n)),
+            Err(e) => {
+                ui::error_msg(&e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let cassette = match (&cli.record, &cli.replay) {
+        (Some(dir), _) => cassette::record(dir),
+        (_, Some(dir)) => cassette::replay(dir),
+        _ => Ok(()),
+    };
+    if let Err(e) = cassette {
+        ui::error_msg(&e);
+        std::process::exit(1);
+    }
+    // Replayed and scripted replies, and indexing with local embeddings, need no key.
+    let keyless = cassette::is_replaying()
+        || cli.fake_agent.is_some()
+        || matches!(cli.command, Some(Command::Index { .. }))
+            && settings
+                .index
+                .model
+                .as_deref()
+                .is_some_and(zcode_core::index::embedder::is_local);
+    let api_key = config::load_api_key().or_else(|| keyless.then(String::new)).unwrap_or_else(|| {
         eprintln!(
-            "Set OPENAI_API_KEY env var or add api_key in ~/.config/zcode/config.toml"
+            "Set OPENAI_API_KEY env var or add api_key in ~/.config/zcode/config.toml (or run `zcode auth openai`)"
         );
         std::process::exit(1);
     });
 
-    let workspace = env::current_dir().expect("current dir");
-    let executor = Executor::new(workspace);
+    let executor = Arc::new(Executor::new(workspace));
+    interrupt::install(|code| {
+        println!();
+        std::process::exit(code);
+    });
+    shutdown::install(executor.clone(), |code| std::process::exit(code));
+    let agents = || {
+        Agents::from_config(&api_key).unwrap_or_else(|e| {
+            ui::error_msg(&e);
+            std::process::exit(1);
+        })
+    };
+
+    if cli.stdio {
+        if let Err(e) = zcode_core::rpc::run(agents(), executor.clone()).await {
+            ui::error_msg(&e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::Explain { target }) = &cli.command {
+        let result = zcode_core::explain::run(&api_key, &executor, target).await;
+        usage::log_run(executor.workspace(), 0);
+        if let Err(e) = result {
+            ui::error_msg(&e);
+            std::process::exit(1);
+        }
+        ui::cost_summary(&usage::all());
+        return;
+    }
+
+    if let Some(Command::Fix {
+        command,
+        max_iterations,
+    }) = &cli.command
+    {
+        let piped = read_piped_stdin();
+        let result =
+            zcode_core::fix::run(&api_key, &executor, command, piped, None, *max_iterations).await;
+        finish_task(&executor, result);
+        return;
+    }
+
+    if let Some(Command::TestGen {
+        path,
+        command,
+        max_iterations,
+    }) = &cli.command
+    {
+        let result = zcode_core::testgen::run(
+            &api_key,
+            &executor,
+            path,
+            command.as_deref(),
+            *max_iterations,
+        )
+        .await;
+        finish_task(&executor, result);
+        return;
+    }
+
+    if let Some(Command::Changelog {
+        since,
+        version,
+        yes,
+    }) = &cli.command
+    {
+        let result =
+            zcode_core::changelog::run(&api_key, &executor, since.as_deref(), version, *yes).await;
+        finish_task(&executor, result.map(|()| true));
+        return;
+    }
+
+    if let Some(Command::UpgradeDep {
+        krate,
+        version,
+        command,
+        max_iterations,
+    }) = &cli.command
+    {
+        let result = zcode_core::upgrade::run(
+            &api_key,
+            &executor,
+            krate,
+            version,
+            command,
+            *max_iterations,
+        )
+        .await;
+        finish_task(&executor, result);
+        return;
+    }
+
+    if let Some(Command::Index { .. }) = &cli.command {
+        let result = zcode_core::index::build(&api_key, executor.workspace()).await;
+        usage::log_run(executor.workspace(), 0);
+        match result {
+            Ok((index, update)) => ui::info(&format!(
+                "Indexed {} chunks from {} files ({} embedded, {} unchanged, {} files removed).",
+                index.chunks.len(),
+                index.files,
+                update.embedded,
+                update.reused,
+                update.removed
+            )),
+            Err(e) => {
+                ui::error_msg(&e);
+                std::process::exit(1);
+            }
+        }
+        ui::cost_summary(&usage::all());
+        return;
+    }
+
+    if let Some(Command::Issue {
+        reference,
+        no_comment,
+    }) = &cli.command
+    {
+        let result = zcode_core::issue::run(&agents(), &executor, reference, !no_comment).await;
+        ui::cost_summary(&usage::all());
+        if let Err(e) = result {
+            ui::error_msg(&e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::Serve {
+        port,
+        host,
+        auto_approve,
+        allow_origin,
+    }) = &cli.command
+    {
+        if let Err(e) = server::run(
+            agents(),
+            executor.clone(),
+            host,
+            *port,
+            *auto_approve,
+            allow_origin.clone(),
+        )
+        .await
+        {
+            ui::error_msg(&e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::Queue { .. }) = &cli.command {
+        match zcode_core::queue::run(&agents(), &executor).await {
+            Ok(report) => ui::info(&format!("Report: {}", report.display())),
+            Err(e) => {
+                ui::error_msg(&e);
+                std::process::exit(1);
+            }
+        }
+        ui::cost_summary(&usage::all());
+        return;
+    }
+
+    if let Some(Command::Batch { file, report }) = &cli.command {
+        match zcode_core::batch::run_batch(&agents(), &executor, file, report).await {
+            Ok(r) => println!(
+                "Batch done: {} completed, {} failed. Report: {}",
+                r.completed,
+                r.failed,
+                report.display()
+            ),
+            Err(e) => {
+                ui::error_msg(&e);
+                std::process::exit(1);
+            }
+        }
+        ui::cost_summary(&usage::all());
+        return;
+    }
+
+    if let Some(Command::Bench { models, runs }) = &cli.command {
+        let mut models = models.clone();
+        if models.is_empty() {
+            models = vec![
+                zcode_core::run::planner_model(),
+                zcode_core::run::executor_model(),
+            ];
+            models.dedup();
+        }
+        let results = zcode_core::bench::run(&api_key, &executor, &models, (*runs).max(1)).await;
+        zcode_core::bench::print_table(&results);
+        ui::cost_summary(&usage::all());
+        return;
+    }
+
+    if let Some(Command::Eval {
+        suite,
+        report,
+        keep,
+    }) = &cli.command
+    {
+        match zcode_core::eval::run(&api_key, suite, report, *keep).await {
+            Ok(r) => {
+                zcode_core::eval::print_summary(&r);
+                println!("Report: {}", report.display());
+            }
+            Err(e) => {
+                ui::error_msg(&e);
+                std::process::exit(1);
+            }
+        }
+        ui::cost_summary(&usage::all());
+        return;
+    }
+
+    let prompt = match cli.command.take() {
+        Some(Command::RunTemplate {
+            name,
+            file,
+            lines,
+            args,
+        }) => Some(render_template(&executor, &name, file, lines, args)),
+        _ => match &cli.prompt_file {
+            Some(path) => Some(std::fs::read_to_string(path).unwrap_or_else(|e| {
+                eprintln!("Cannot read prompt file {}: {}", path.display(), e);
+                std::process::exit(1);
+            })),
+            None => cli.prompt.take(),
+        },
+    };
+    let prompt = if cli.paste {
+        let pasted = clipboard::paste().unwrap_or_else(|e| {
+            eprintln!("Clipboard unavailable: {}", e);
+            std::process::exit(1);
+        });
+        Some(match prompt {
+            Some(p) => clipboard::attach(&p, &pasted),
+            None => pasted,
+        })
+    } else {
+        prompt
+    };
+    let prompt = match read_piped_stdin() {
+        Some(piped) => Some(match prompt {
+            Some(p) => format!("{}\n\nInput from stdin:\n```\n{}\n```", p, piped.trim_end()),
+            None => piped,
+        }),
+        None => prompt,
+    };
+
+    if cli.continue_last {
+        match zcode_core::run_state::latest(executor.workspace()) {
+            Some(Latest::Run(id)) => cli.resume_run = Some(id),
+            Some(Latest::Session(id)) => cli.resume = Some(id),
+            None => {
+                eprintln!("Nothing to continue: no saved session or unfinished run");
+                std::process::exit(1);
+            }
+        }
+    }
+    let resumed = cli.resume_run.as_deref().map(|id| {
+        RunState::load(id).unwrap_or_else(|e| {
+            eprintln!("Cannot resume run {}: {}", id, e);
+            std::process::exit(1);
+        })
+    });
+    let prompt = match &resumed {
+        Some(state) => Some(state.prompt.clone()),
+        None => prompt,
+    };
+
+    match &cli.fake_agent {
+        Some(script) => {
+            let agent = FakeAgent::from_file(script).unwrap_or_else(|e| {
+                eprintln!("Cannot load the agent script: {}", e);
+                std::process::exit(1);
+            });
+            let agents = Agents {
+                planner: agent.clone(),
+                exec_agent: agent,
+                index_key: None,
+                team: None,
+            };
+            run_prompt_or_repl(&cli, &agents, &executor, &api_key, prompt, resumed).await;
+        }
+        None => run_prompt_or_repl(&cli, &agents(), &executor, &api_key, prompt, resumed).await,
+    }
+}
 
-    if let Some(prompt) = cli.prompt {
-        zcode::run::run_once(&api_key, &executor, &prompt).await;
+/// Run the prompt (one-shot), or start the REPL when there is none.
+async fn run_prompt_or_repl<P: Agent, E: Agent>(
+    cli: &Cli,
+    agents: &Agents<P, E>,
+    executor: &Executor,
+    api_key: &str,
+    prompt: Option<String>,
+    resumed: Option<RunState>,
+) {
+    if let Some(prompt) = prompt {
+        ui::set_quiet(cli.quiet);
+        hooks::session_start(executor);
+        let started = Instant::now();
+        if cli.output_format == OutputFormat::Json {
+            events::enable();
+            let reply = if cli.plan {
+                zcode_core::run::plan_only(agents, executor, &prompt).await
+            } else {
+                let reply = run_or_resume(agents, executor, &prompt, resumed).await;
+                webhook::run_finished(&prompt, reply.as_deref(), executor, started.elapsed()).await;
+                reply
+            };
+            let changes = executor.changes();
+            events::emit(Event::Done {
+                reply: reply.as_deref(),
+                changes: changes.iter().map(ChangedFile::from).collect(),
+                cost: usage::total_cost(&usage::all()),
+            });
+            hooks::session_end(executor);
+            if cli.ci {
+                std::process::exit(ci::exit_code());
+            }
+            return;
+        }
+        if cli.plan {
+            let plan = zcode_core::run::plan_only(agents, executor, &prompt).await;
+            usage::log_run(executor.workspace(), 0);
+            hooks::session_end(executor);
+            match plan {
+                Some(plan) => println!("\n{}", plan),
+                None => std::process::exit(1),
+            }
+            ui::cost_summary(&usage::all());
+            return;
+        }
+        let reply = run_or_resume(agents, executor, &prompt, resumed).await;
+        webhook::run_finished(&prompt, reply.as_deref(), executor, started.elapsed()).await;
+        if cli.quiet {
+            if let Some(reply) = reply {
+                println!("{}", reply);
+            }
+        }
+        ui::changes_summary("Changed", &executor.changes());
+        if cli.create_pr {
+            create_pr(api_key, executor).await;
+        }
+        hooks::session_end(executor);
+        ui::cost_summary(&usage::all());
+        if cli.ci {
+            std::process::exit(ci::exit_code());
+        }
     } else {
-        zcode::run::run_repl(&api_key, &executor).await;
+        if cli.ci {
+            eprintln!("--ci needs a prompt (-p, --prompt-file, or piped stdin)");
+            std::process::exit(1);
+        }
+        if cli.output_format == OutputFormat::Json {
+            eprintln!("--output-format json needs a prompt (-p, --prompt-file, or piped stdin)");
+            std::process::exit(2);
+        }
+        let session = match &cli.resume {
+            Some(id) => Session::load(id).unwrap_or_else(|e| {
+                eprintln!("Cannot resume session {}: {}", id, e);
+                std::process::exit(1);
+            }),
+            None => Session::default(),
+        };
+        hooks::session_start(executor);
+        repl::run(agents, executor, session).await;
+        shutdown::finish(executor);
+    }
+}
+
+/// Run `prompt`, or continue the saved run it came from.
+async fn run_or_resume<P: Agent, E: Agent>(
+    agents: &Agents<P, E>,
+    executor: &Executor,
+    prompt: &str,
+    resumed: Option<RunState>,
+) -> Option<String> {
+    match resumed {
+        Some(state) => zcode_core::run::resume_run(agents, executor, state).await,
+        None => zcode_core::run::run_once(agents, executor, prompt).await,
+    }
+}
+
+fn render_template(
+    executor: &Executor,
+    name: &str,
+    file: Option<String>,
+    lines: Option<String>,
+    args: Vec<String>,
+) -> String {
+    let rendered = templates::load(name).and_then(|t| {
+        let lines = lines.as_deref().map(templates::parse_lines).transpose()?;
+        let vars = templates::TemplateVars { file, lines, args };
+        t.render(executor.workspace(), &vars)
+    });
+    rendered.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
+
+/// All of stdin when it is piped (not a terminal) and non-empty.
+fn read_piped_stdin() -> Option<String> {
+    if std::io::stdin().is_terminal() {
+        return None;
+    }
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input).ok()?;
+    (!input.trim().is_empty()).then_some(input)
+}
+
+fn config_command(action: &ConfigAction) {
+    match action {
+        ConfigAction::Get { key } => match config::get(key) {
+            Some(v) => println!("{}", v),
+            None => std::process::exit(1),
+        },
+        ConfigAction::Set { key, value } => {
+            if let Err(e) = config::set(key, value) {
+                ui::error_msg(&e);
+                std::process::exit(1);
+            }
+            match config::validate() {
+                Ok(warnings) => warnings.iter().for_each(|w| eprintln!("Warning: {}", w)),
+                Err(e) => eprintln!("Warning: the config no longer loads: {}", e),
+            }
+        }
+        ConfigAction::List => match config::entries() {
+            Ok(entries) => {
+                for (key, value) in entries {
+                    let secret = key.contains("key") || key.contains("token");
+                    println!("{} = {}", key, if secret { mask(&value) } else { value });
+                }
+            }
+            Err(e) => {
+                ui::error_msg(&e);
+                std::process::exit(1);
+            }
+        },
+    }
+}
+
+/// Show only the last four characters of a secret.
+fn mask(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    let tail: String = chars[chars.len().saturating_sub(4)..].iter().collect();
+    format!("****{}", tail)
+}
+
+/// Summarize changes and cost of an edit-and-check subcommand; exit non-zero unless it succeeded.
+fn finish_task(executor: &Executor, result: Result<bool, String>) {
+    usage::log_run(executor.workspace(), 0);
+    ui::changes_summary("Changed", &executor.changes());
+    ui::cost_summary(&usage::all());
+    match result {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            ui::error_msg(&e);
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn create_pr(api_key: &str, executor: &Executor) {
+    let Some(summary) = zcode_core::run::last_run().filter(|s| s.succeeded()) else {
+        ui::note("Not creating a pull request: the run did not complete every step.");
+        return;
+    };
+    match zcode_core::pr::create(api_key, executor, &summary).await {
+        Ok(url) => ui::info(&format!("Pull request: {}", url)),
+        Err(e) => ui::error_msg(&e),
     }
 }
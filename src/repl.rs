@@ -0,0 +1,119 @@
+//! The interactive REPL: line editing with persistent history, slash commands (see
+//! `commands`), and each prompt run through the pipeline with the session's context.
+
+use crate::commands;
+use colored::Colorize;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::path::PathBuf;
+use zcode_core::agent::Agent;
+use zcode_core::run::{self, Agents};
+use zcode_core::session::Session;
+use zcode_core::tools::Executor;
+use zcode_core::{clipboard, config, context, ui};
+
+/// Read prompts until Ctrl-D, running each with the session's pinned files and history.
+pub async fn run<P: Agent, E: Agent>(
+    agents: &Agents<P, E>,
+    executor: &Executor,
+    mut session: Session,
+) {
+    ui::welcome();
+    let mut reader = match LineReader::new() {
+        Ok(r) => r,
+        Err(e) => {
+            ui::error_msg(&e);
+            return;
+        }
+    };
+    while let Some(line) = reader.read() {
+        let prompt = line.trim().to_string();
+        if prompt.is_empty() {
+            continue;
+        }
+        if prompt.starts_with('/') {
+            commands::handle(&prompt, executor, &mut session);
+            continue;
+        }
+        ui::write_out(format_args!("\n"));
+        session.begin_turn(&prompt);
+        let mut full_prompt = context::expand_mentions(&prompt, executor.workspace());
+        if let Some(pasted) = session.pasted.take() {
+            full_prompt = clipboard::attach(&full_prompt, &pasted);
+        }
+        // Files mentioned now are attached above; pinning keeps them for later prompts.
+        let mentioned = context::mentioned_files(&prompt, executor.workspace());
+        let earlier: Vec<String> = session
+            .pinned
+            .iter()
+            .filter(|p| !mentioned.contains(p))
+            .cloned()
+            .collect();
+        if !earlier.is_empty() {
+            let blocks =
+                context::pinned_blocks(executor.workspace(), &earlier, &mut session.pinned_seen);
+            full_prompt = format!(
+                "{}\n\nPinned files (current contents; no need to read them again):\n{}",
+                full_prompt,
+                blocks.join("\n\n")
+            );
+        }
+        context::mark_seen(executor.workspace(), &mentioned, &mut session.pinned_seen);
+        for path in mentioned {
+            if session.pin(&path) {
+                ui::info(&format!(
+                    "Pinned {} for later prompts (/drop to unpin)",
+                    path
+                ));
+            }
+        }
+        let history = session.history_block();
+        if !history.is_empty() {
+            full_prompt = format!("Earlier in this session:\n{}\n\n{}", history, full_prompt);
+        }
+        let reply = run::run_once(agents, executor, &full_prompt).await;
+        session.finish_turn(reply);
+        ui::write_out(format_args!("\n"));
+    }
+}
+
+struct LineReader {
+    editor: DefaultEditor,
+    history: Option<PathBuf>,
+}
+
+impl LineReader {
+    fn new() -> Result<Self, String> {
+        let mut editor = DefaultEditor::new().map_err(|e| e.to_string())?;
+        let history = config::data_dir().map(|d| d.join("history.txt"));
+        if let Some(path) = &history {
+            let _ = editor.load_history(path);
+        }
+        Ok(Self { editor, history })
+    }
+
+    /// Read one line. Ctrl-C clears the line and returns an empty string; Ctrl-D returns None.
+    fn read(&mut self) -> Option<String> {
+        let prompt = "> ".color(ui::theme().accent).bold().to_string();
+        match self.editor.readline(&prompt) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    let _ = self.editor.add_history_entry(line.as_str());
+                    self.save_history();
+                }
+                Some(line)
+            }
+            Err(ReadlineError::Interrupted) => Some(String::new()),
+            Err(_) => None,
+        }
+    }
+
+    fn save_history(&mut self) {
+        if let Some(path) = &self.history {
+            if let Some(parent) = path.parent() {
+                let _ = std::fs::create_dir_all(parent);
+            }
+            let _ = self.editor.save_history(path);
+        }
+    }
+}
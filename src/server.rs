@@ -0,0 +1,437 @@
+//! `zcode serve`: an HTTP API over the same pipeline as the CLI. Sessions keep the
+//! conversation, prompts run in the background, their events stream as SSE or over a
+//! WebSocket, and mutating tool calls wait for a client to approve them.
+//!
+//! Every request needs the server's token, printed at startup (or `ZCODE_SERVE_TOKEN`), as
+//! `Authorization: Bearer <token>`; streams, which browsers open without headers, may pass
+//! it as `?token=` instead.
+
+use axum::extract::ws::{self, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Request, State};
+use axum::http::{header, HeaderMap, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{self, KeepAlive, Sse};
+use axum::response::Response;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+use zcode_core::events::{self, ChangedFile, Event};
+use zcode_core::run::{self, Agents};
+use zcode_core::session::Session;
+use zcode_core::tools::Executor;
+use zcode_core::{approval, context, steering, ui, usage};
+
+/// Events buffered per session for slow stream readers.
+const EVENT_BUFFER: usize = 1024;
+/// Environment variable that fixes the server's token instead of a random one.
+const TOKEN_VAR: &str = "ZCODE_SERVE_TOKEN";
+
+struct ServerSession {
+    session: Mutex<Session>,
+    events: broadcast::Sender<String>,
+    running: AtomicBool,
+}
+
+#[derive(Clone)]
+struct AppState {
+    agents: Arc<Agents>,
+    executor: Arc<Executor>,
+    sessions: Arc<Mutex<BTreeMap<String, Arc<ServerSession>>>>,
+    /// Usage marks, run limits, and the event sink are per process, so prompts from all
+    /// sessions run one at a time.
+    run_lock: Arc<tokio::sync::Mutex<()>>,
+    /// Session whose prompt holds the run lock (owner of any pending approvals).
+    active: Arc<Mutex<Option<String>>>,
+    /// Bearer token every request must carry.
+    token: Arc<str>,
+    /// Origins besides loopback ones whose pages may open the WebSocket.
+    allowed_origins: Arc<[String]>,
+}
+
+type ApiError = (StatusCode, String);
+
+impl AppState {
+    fn session(&self, id: &str) -> Result<Arc<ServerSession>, ApiError> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| (StatusCode::NOT_FOUND, format!("No session {}", id)))
+    }
+
+    fn is_active(&self, id: &str) -> bool {
+        self.active.lock().unwrap().as_deref() == Some(id)
+    }
+}
+
+#[derive(Serialize)]
+struct SessionInfo {
+    id: String,
+    running: bool,
+    turns: Vec<zcode_core::session::Turn>,
+    pending_approvals: Vec<String>,
+}
+
+impl SessionInfo {
+    fn of(state: &AppState, entry: &ServerSession) -> Self {
+        let session = entry.session.lock().unwrap();
+        Self {
+            id: session.id.clone(),
+            running: entry.running.load(Ordering::Relaxed),
+            turns: session.turns.clone(),
+            pending_approvals: if state.is_active(&session.id) {
+                approval::pending()
+            } else {
+                Vec::new()
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PromptRequest {
+    prompt: String,
+}
+
+#[derive(Deserialize)]
+struct Decision {
+    approve: bool,
+}
+
+/// Serve the API on `host:port` until the process is stopped.
+/// `allowed_origins` (`https://dash.example.com`) may open the WebSocket from a browser, as
+/// well as pages served from loopback.
+pub async fn run(
+    agents: Agents,
+    executor: Arc<Executor>,
+    host: &str,
+    port: u16,
+    auto_approve: bool,
+    allowed_origins: Vec<String>,
+) -> Result<(), String> {
+    let listener = tokio::net::TcpListener::bind((host, port))
+        .await
+        .map_err(|e| format!("Cannot listen on {}:{}: {}", host, port, e))?;
+    let addr = listener.local_addr().map_err(|e| e.to_string())?;
+    let token = std::env::var(TOKEN_VAR)
+        .ok()
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| random_hex(32));
+    ui::info(&format!(
+        "Serving on http://{} (workspace {})",
+        addr,
+        executor.workspace().display()
+    ));
+    ui::info(&format!("Token: {}", token));
+    approval::set_required(!auto_approve);
+
+    let state = AppState {
+        agents: Arc::new(agents),
+        executor,
+        sessions: Arc::default(),
+        run_lock: Arc::default(),
+        active: Arc::default(),
+        token: token.into(),
+        allowed_origins: allowed_origins.into(),
+    };
+    let app = Router::new()
+        .route("/sessions", post(create_session).get(list_sessions))
+        .route("/sessions/{id}", get(get_session))
+        .route("/sessions/{id}/prompts", post(post_prompt))
+        .route("/sessions/{id}/events", get(stream_events))
+        .route("/sessions/{id}/ws", get(websocket))
+        .route("/sessions/{id}/approvals/{call_id}", post(decide))
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state);
+    axum::serve(listener, app).await.map_err(|e| e.to_string())
+}
+
+/// Reject requests without the server's token: `Authorization: Bearer`, or `?token=` on the
+/// streams (`GET`), which browsers' `EventSource` and `WebSocket` open without headers.
+async fn authorize(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let query = (request.method() == Method::GET)
+        .then(|| request.uri().query())
+        .flatten()
+        .and_then(|q| q.split('&').find_map(|pair| pair.strip_prefix("token=")));
+    match bearer.or(query) {
+        Some(token) if same(token.as_bytes(), state.token.as_bytes()) => {
+            Ok(next.run(request).await)
+        }
+        _ => Err((StatusCode::UNAUTHORIZED, "Missing or wrong token".into())),
+    }
+}
+
+/// Compare in time that does not depend on where the inputs differ.
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// `bytes` random bytes as hex, for tokens and session ids.
+fn random_hex(bytes: usize) -> String {
+    let mut buf = vec![0u8; bytes];
+    getrandom::fill(&mut buf).expect("the OS random number generator is available");
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+async fn create_session(State(state): State<AppState>) -> (StatusCode, Json<SessionInfo>) {
+    // Random rather than the default timestamp, so ids can't be guessed.
+    let session = Session::with_id(random_hex(16));
+    let mut sessions = state.sessions.lock().unwrap();
+    let entry = Arc::new(ServerSession {
+        session: Mutex::new(session),
+        events: broadcast::channel(EVENT_BUFFER).0,
+        running: AtomicBool::new(false),
+    });
+    let info = SessionInfo::of(&state, &entry);
+    sessions.insert(info.id.clone(), entry);
+    (StatusCode::CREATED, Json(info))
+}
+
+async fn list_sessions(State(state): State<AppState>) -> Json<Vec<SessionInfo>> {
+    let sessions = state.sessions.lock().unwrap();
+    Json(
+        sessions
+            .values()
+            .map(|s| SessionInfo::of(&state, s))
+            .collect(),
+    )
+}
+
+async fn get_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<SessionInfo>, ApiError> {
+    let entry = state.session(&id)?;
+    Ok(Json(SessionInfo::of(&state, &entry)))
+}
+
+async fn post_prompt(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(req): Json<PromptRequest>,
+) -> Result<StatusCode, ApiError> {
+    let entry = state.session(&id)?;
+    start_prompt(&state, &id, &entry, req.prompt)?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Start a prompt in the background unless the session is already running one.
+fn start_prompt(
+    state: &AppState,
+    id: &str,
+    entry: &Arc<ServerSession>,
+    prompt: String,
+) -> Result<(), ApiError> {
+    if prompt.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Empty prompt".into()));
+    }
+    if entry.running.swap(true, Ordering::Relaxed) {
+        return Err((
+            StatusCode::CONFLICT,
+            "A prompt is already running in this session".into(),
+        ));
+    }
+    tokio::spawn(run_prompt(
+        state.clone(),
+        id.to_string(),
+        entry.clone(),
+        prompt,
+    ));
+    Ok(())
+}
+
+/// Run one prompt with the session's history, streaming its events to the session.
+async fn run_prompt(state: AppState, id: String, entry: Arc<ServerSession>, prompt: String) {
+    let _turn = state.run_lock.lock().await;
+    *state.active.lock().unwrap() = Some(id);
+    let full_prompt = {
+        let mut session = entry.session.lock().unwrap();
+        session.activate();
+        session.begin_turn(&prompt);
+        let full_prompt = context::expand_mentions(&prompt, state.executor.workspace());
+        let history = session.history_block();
+        if history.is_empty() {
+            full_prompt
+        } else {
+            format!("Earlier in this session:\n{}\n\n{}", history, full_prompt)
+        }
+    };
+    events::set_sink(Some(entry.events.clone()));
+    state.executor.checkpoint();
+    let mark = usage::mark();
+    let reply = run::run_once(&state.agents, &state.executor, &full_prompt).await;
+    let changes = state.executor.changes();
+    events::emit(Event::Done {
+        reply: reply.as_deref(),
+        changes: changes.iter().map(ChangedFile::from).collect(),
+        cost: usage::total_cost(usage::all().get(mark..).unwrap_or_default()),
+    });
+    events::set_sink(None);
+    *state.active.lock().unwrap() = None;
+    entry.session.lock().unwrap().finish_turn(reply);
+    entry.running.store(false, Ordering::Relaxed);
+}
+
+async fn stream_events(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<sse::Event, Infallible>>>, ApiError> {
+    let entry = state.session(&id)?;
+    // Lagged readers skip what they missed rather than ending the stream.
+    let stream = BroadcastStream::new(entry.events.subscribe())
+        .filter_map(|line| line.ok().map(|line| Ok(sse::Event::default().data(line))));
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+async fn decide(
+    State(state): State<AppState>,
+    Path((id, call_id)): Path<(String, String)>,
+    Json(decision): Json<Decision>,
+) -> Result<StatusCode, ApiError> {
+    state.session(&id)?;
+    decide_call(&state, &id, &call_id, decision.approve)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Decide a tool call waiting in session `id`.
+fn decide_call(state: &AppState, id: &str, call_id: &str, approve: bool) -> Result<(), ApiError> {
+    if state.is_active(id) && approval::decide(call_id, approve) {
+        Ok(())
+    } else {
+        Err((
+            StatusCode::NOT_FOUND,
+            format!("No tool call {} is waiting for approval", call_id),
+        ))
+    }
+}
+
+/// Queue guidance for session `id`'s running prompt.
+fn steer(state: &AppState, id: &str, text: &str) -> Result<(), ApiError> {
+    if !state.is_active(id) {
+        return Err((
+            StatusCode::CONFLICT,
+            "No prompt is running in this session".into(),
+        ));
+    }
+    steering::push(text);
+    Ok(())
+}
+
+/// Messages a WebSocket client sends.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Prompt {
+        prompt: String,
+    },
+    Approval {
+        id: String,
+        approve: bool,
+    },
+    /// Guidance for the running prompt, delivered at its next turn.
+    Steer {
+        text: String,
+    },
+}
+
+async fn websocket(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    // Browsers send any page's WebSockets without CORS checks; only trusted pages may connect.
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        let origin = origin.to_str().unwrap_or_default();
+        if !origin_allowed(origin, &state.allowed_origins) {
+            return Err((
+                StatusCode::FORBIDDEN,
+                format!("Origin {} is not allowed", origin),
+            ));
+        }
+    }
+    let entry = state.session(&id)?;
+    Ok(ws.on_upgrade(move |socket| websocket_session(state, id, entry, socket)))
+}
+
+/// Whether a page from `origin` (`scheme://host[:port]`) may use the API: loopback hosts, and
+/// the `allowed` origins.
+fn origin_allowed(origin: &str, allowed: &[String]) -> bool {
+    if allowed
+        .iter()
+        .any(|a| a.trim_end_matches('/').eq_ignore_ascii_case(origin))
+    {
+        return true;
+    }
+    let Some((_, authority)) = origin.split_once("://") else {
+        return false;
+    };
+    let host = match authority.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or_default(),
+        None => authority.split(':').next().unwrap_or_default(),
+    };
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Forward the session's events to the socket and act on prompts and approvals from it.
+/// Problems with a client message come back as an `error` event.
+async fn websocket_session(
+    state: AppState,
+    id: String,
+    entry: Arc<ServerSession>,
+    mut socket: WebSocket,
+) {
+    let mut events = entry.events.subscribe();
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(line) => {
+                    if socket.send(ws::Message::Text(line.into())).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => {
+                let text = match message {
+                    Some(Ok(ws::Message::Text(text))) => text,
+                    Some(Ok(ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+                let result = match serde_json::from_str::<ClientMessage>(&text) {
+                    Ok(ClientMessage::Prompt { prompt }) => start_prompt(&state, &id, &entry, prompt),
+                    Ok(ClientMessage::Approval { id: call_id, approve }) => decide_call(&state, &id, &call_id, approve),
+                    Ok(ClientMessage::Steer { text }) => steer(&state, &id, &text),
+                    Err(e) => Err((StatusCode::BAD_REQUEST, format!("Invalid message: {}", e))),
+                };
+                if let Err((_, message)) = result {
+                    let error = serde_json::to_string(&Event::Error { message: &message }).unwrap_or_default();
+                    if socket.send(ws::Message::Text(error.into())).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
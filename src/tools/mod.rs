@@ -1,3 +0,0 @@
-mod executor;
-
-pub use executor::Executor;